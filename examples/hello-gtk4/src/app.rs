@@ -0,0 +1,45 @@
+use gtk4::glib;
+use gtk4::prelude::*;
+
+const APP_ID: &str = "io.github.nathaniel_daniel.HelloGtk4";
+
+/// Run the app.
+pub fn run() -> glib::ExitCode {
+    let app = gtk4::Application::builder().application_id(APP_ID).build();
+    app.connect_startup(|_| load_css());
+    app.connect_activate(build_ui);
+    app.run()
+}
+
+/// Load the packaged stylesheet, if it exists next to the exe.
+fn load_css() {
+    let path = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("share/hello-gtk4/hello.css")));
+    let path = match path {
+        Some(path) if path.exists() => path,
+        _ => return,
+    };
+
+    let provider = gtk4::CssProvider::new();
+    provider.load_from_path(path);
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    }
+}
+
+fn build_ui(app: &gtk4::Application) {
+    let label = gtk4::Label::new(Some("Hello, GTK4!"));
+    let window = gtk4::ApplicationWindow::builder()
+        .application(app)
+        .title("hello-gtk4")
+        .default_width(320)
+        .default_height(200)
+        .child(&label)
+        .build();
+    window.present();
+}
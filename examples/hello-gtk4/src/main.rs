@@ -0,0 +1,14 @@
+#[cfg(feature = "gtk")]
+mod app;
+
+#[cfg(feature = "gtk")]
+fn main() -> gtk4::glib::ExitCode {
+    app::run()
+}
+
+// Without GTK4, this still builds with the rest of the workspace.
+#[cfg(not(feature = "gtk"))]
+fn main() {
+    eprintln!("hello-gtk4 was built without the `gtk` feature, which needs GTK4 installed");
+    std::process::exit(1);
+}
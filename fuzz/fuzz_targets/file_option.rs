@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use msys2_packager::file_option::FileOption;
+
+fuzz_target!(|input: &str| {
+    let _ = input.parse::<FileOption>();
+});
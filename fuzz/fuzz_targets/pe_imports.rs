@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use msys2_packager::util::get_dll_imports_from_bytes;
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = get_dll_imports_from_bytes(bytes);
+});
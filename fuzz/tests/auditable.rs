@@ -0,0 +1,9 @@
+use msys2_packager::auditable::read_audit_data_from_bytes;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn read_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let _ = read_audit_data_from_bytes(&bytes);
+    }
+}
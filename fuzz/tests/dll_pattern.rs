@@ -0,0 +1,42 @@
+use msys2_packager::util::matches_dll_pattern;
+use proptest::prelude::*;
+
+/// Dll names, without glob characters.
+fn name() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9_.+-]{0,24}"
+}
+
+proptest! {
+    #[test]
+    fn never_panics(pattern in any::<String>(), name in any::<String>()) {
+        let _ = matches_dll_pattern(&pattern, &name);
+    }
+
+    #[test]
+    fn literal_matches_itself_in_any_case(name in name()) {
+        prop_assert!(matches_dll_pattern(&name, &name));
+        prop_assert!(matches_dll_pattern(&name.to_ascii_uppercase(), &name.to_ascii_lowercase()));
+    }
+
+    #[test]
+    fn star_matches_any_middle(prefix in name(), middle in name(), suffix in name()) {
+        let pattern = format!("{prefix}*{suffix}");
+        let input = format!("{prefix}{middle}{suffix}");
+        prop_assert!(matches_dll_pattern(&pattern, &input));
+    }
+
+    #[test]
+    fn question_mark_matches_one_char(prefix in name(), c in "[a-z0-9]", suffix in name()) {
+        let pattern = format!("{prefix}?{suffix}");
+        let with_char = format!("{prefix}{c}{suffix}");
+        let without_char = format!("{prefix}{suffix}");
+        prop_assert!(matches_dll_pattern(&pattern, &with_char));
+        prop_assert!(!matches_dll_pattern(&pattern, &without_char));
+    }
+
+    #[test]
+    fn literal_does_not_match_longer_name(name in name(), extra in "[a-z]{1,4}") {
+        let longer = format!("{name}{extra}");
+        prop_assert!(!matches_dll_pattern(&name, &longer));
+    }
+}
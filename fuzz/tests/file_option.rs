@@ -0,0 +1,77 @@
+use msys2::Msys2Environment;
+use msys2_packager::file_option::FileOption;
+use msys2_packager::packager::FileFlags;
+use proptest::prelude::*;
+
+/// Paths that survive the mini-language, as in no separators and no surrounding whitespace.
+fn path() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9_.:/\\\\-]{1,32}"
+}
+
+fn flags() -> impl Strategy<Value = FileFlags> {
+    (0..=FileFlags::all().bits()).prop_map(FileFlags::from_bits_truncate)
+}
+
+proptest! {
+    #[test]
+    fn parse_never_panics(input in any::<String>()) {
+        let _ = input.parse::<FileOption>();
+    }
+
+    #[test]
+    fn parse_never_panics_on_near_valid_input(input in "((src|dest|flags|[a-z]{0,4})=[a-z_,.|=/ ]{0,16}\\|?){0,4}") {
+        let _ = input.parse::<FileOption>();
+    }
+
+    #[test]
+    fn roundtrip(src in proptest::option::of(path()), dest in path(), flags in flags()) {
+        let mut parts = Vec::new();
+        if let Some(src) = src.as_ref() {
+            parts.push(format!("src={src}"));
+        }
+        parts.push(format!(" dest = {dest} "));
+        if !flags.is_empty() {
+            parts.push(format!("flags={}", flags.names().join(",")));
+        }
+        let input = parts.join("|");
+
+        let option: FileOption = input.parse().expect("failed to parse");
+        prop_assert_eq!(option.src.as_ref().map(|src| src.as_str()), src.as_deref());
+        prop_assert_eq!(option.dest.as_str(), dest.as_str());
+        prop_assert_eq!(option.flags, flags);
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected(key in prop_oneof!["src", "dest"], a in path(), b in path()) {
+        let input = format!("{key}={a}|{key}={b}|dest=x");
+        prop_assert!(input.parse::<FileOption>().is_err());
+    }
+
+    #[test]
+    fn when_selects_environment(dest in path()) {
+        let input = format!("dest={{env}}/{{arch}}/{dest}|when=env:ucrt64,env:clang64");
+        let option: FileOption = input.parse().expect("failed to parse");
+
+        let resolved = option.resolve(Msys2Environment::Ucrt64).expect("missing file for ucrt64");
+        prop_assert_eq!(resolved.dest.as_str(), format!("ucrt64/x86_64/{dest}"));
+        prop_assert!(resolved.when.is_empty());
+        prop_assert!(option.resolve(Msys2Environment::Clang64).is_some());
+        prop_assert!(option.resolve(Msys2Environment::Mingw64).is_none());
+    }
+
+    #[test]
+    fn missing_dest_is_rejected(src in path()) {
+        let input = format!("src={src}|flags=lib");
+        prop_assert!(input.parse::<FileOption>().is_err());
+    }
+
+    #[test]
+    fn flag_names_round_trip(bits in 0_u32..32) {
+        let flags = FileFlags::from_bits_truncate(bits);
+        let parsed = flags
+            .names()
+            .into_iter()
+            .fold(FileFlags::empty(), |parsed, name| parsed | FileFlags::from_name(name).unwrap());
+        prop_assert_eq!(parsed, flags);
+    }
+}
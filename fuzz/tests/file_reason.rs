@@ -0,0 +1,15 @@
+use msys2_packager::packager::FileReason;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn file_reason_never_panics(input in any::<String>()) {
+        let _ = input.parse::<FileReason>();
+    }
+
+    #[test]
+    fn file_reason_round_trip(input in "(explicit|profile:[a-z-]{1,16}|dep-of:([a-z]{1,8}/)?[a-z0-9-]{1,16}\\.dll|theme:[A-Za-z-]{1,16})") {
+        let reason: FileReason = input.parse().unwrap();
+        prop_assert_eq!(reason.to_string(), input);
+    }
+}
@@ -0,0 +1,25 @@
+use msys2_packager::util::get_dll_imports_from_bytes;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn parse_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let _ = get_dll_imports_from_bytes(&bytes);
+    }
+
+    #[test]
+    fn parse_never_panics_with_dos_header(
+        pe_offset in 0x40u32..0x200,
+        rest in proptest::collection::vec(any::<u8>(), 0..4096),
+    ) {
+        // A DOS header pointing at a PE header, so parsing gets past the first checks.
+        let mut bytes = vec![0; 0x40];
+        bytes[0..2].copy_from_slice(b"MZ");
+        bytes[0x3C..0x40].copy_from_slice(&pe_offset.to_le_bytes());
+        bytes.resize(pe_offset as usize, 0);
+        bytes.extend_from_slice(b"PE\0\0");
+        bytes.extend_from_slice(&rest);
+
+        let _ = get_dll_imports_from_bytes(&bytes);
+    }
+}
@@ -0,0 +1,35 @@
+use msys2_packager::util::normalize_pe_timestamps_in_bytes;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn normalize_never_panics(
+        bytes in proptest::collection::vec(any::<u8>(), 0..4096),
+        timestamp in any::<u32>(),
+    ) {
+        let mut bytes = bytes;
+        let _ = normalize_pe_timestamps_in_bytes(&mut bytes, timestamp);
+    }
+
+    #[test]
+    fn normalize_is_idempotent_with_dos_header(
+        pe_offset in 0x40u32..0x200,
+        rest in proptest::collection::vec(any::<u8>(), 0..4096),
+        timestamp in any::<u32>(),
+    ) {
+        // A DOS header pointing at a PE header, so parsing gets past the first checks.
+        let mut bytes = vec![0; 0x40];
+        bytes[0..2].copy_from_slice(b"MZ");
+        bytes[0x3C..0x40].copy_from_slice(&pe_offset.to_le_bytes());
+        bytes.resize(pe_offset as usize, 0);
+        bytes.extend_from_slice(b"PE\0\0");
+        bytes.extend_from_slice(&rest);
+
+        let mut once = bytes.clone();
+        if normalize_pe_timestamps_in_bytes(&mut once, timestamp).is_ok() {
+            let mut twice = once.clone();
+            prop_assert!(normalize_pe_timestamps_in_bytes(&mut twice, timestamp).is_ok());
+            prop_assert_eq!(once, twice);
+        }
+    }
+}
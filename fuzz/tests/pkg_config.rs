@@ -0,0 +1,26 @@
+use msys2_packager::pkg_config::get_prefix;
+use msys2_packager::pkg_config::relocate_pkg_config;
+use msys2_packager::pkg_config::RELOCATED_PREFIX;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn relocate_never_panics(contents in any::<String>(), prefix in any::<String>()) {
+        let _ = relocate_pkg_config(&contents, &[prefix]);
+    }
+
+    #[test]
+    fn relocate_is_idempotent(contents in any::<String>()) {
+        let prefixes = ["C:/msys64/ucrt64".to_string(), "/ucrt64".to_string()];
+        let relocated = relocate_pkg_config(&contents, &prefixes);
+        prop_assert_eq!(relocate_pkg_config(&relocated, &prefixes), relocated);
+    }
+
+    #[test]
+    fn relocated_prefix(lines in proptest::collection::vec("[a-z]{1,8}=/ucrt64(/[a-z]{1,8})?", 0..8)) {
+        let contents = format!("prefix=/ucrt64\n{}\n", lines.join("\n"));
+        let relocated = relocate_pkg_config(&contents, &["/ucrt64".to_string()]);
+        prop_assert_eq!(get_prefix(&relocated), Some(RELOCATED_PREFIX));
+        prop_assert!(!relocated.contains("/ucrt64"));
+    }
+}
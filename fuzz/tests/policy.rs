@@ -0,0 +1,59 @@
+use msys2_packager::policy::compare_versions;
+use msys2_packager::policy::Policy;
+use proptest::prelude::*;
+use std::cmp::Ordering;
+
+/// Make a policy that allows MIT and Apache-2.0, and denies GPL-3.0-only.
+fn create_policy() -> Policy {
+    let mut policy = Policy::default();
+    policy.allow_licenses.insert("MIT".into());
+    policy.allow_licenses.insert("Apache-2.0".into());
+    policy.deny_licenses.insert("GPL-3.0-only".into());
+    policy
+}
+
+proptest! {
+    #[test]
+    fn license_never_panics(expression in any::<String>()) {
+        let _ = create_policy().is_license_allowed(&expression);
+    }
+
+    #[test]
+    fn license_or(allowed in "(MIT|Apache-2\\.0)", other in "LicenseRef-[A-Za-z0-9.]{1,16}") {
+        let policy = create_policy();
+        let or = format!("{allowed} OR {other}");
+        let slash = format!("{other}/{allowed}");
+        prop_assert!(policy.is_license_allowed(&or).unwrap());
+        prop_assert!(policy.is_license_allowed(&slash).unwrap());
+    }
+
+    #[test]
+    fn license_and(allowed in "(MIT|Apache-2\\.0)") {
+        let policy = create_policy();
+        let and = format!("({allowed}) AND mit");
+        let denied = format!("{allowed} AND GPL-3.0-only");
+        prop_assert!(policy.is_license_allowed(&and).unwrap());
+        prop_assert!(!policy.is_license_allowed(&denied).unwrap());
+    }
+
+    #[test]
+    fn version_never_panics(a in any::<String>(), b in any::<String>()) {
+        let _ = compare_versions(&a, &b);
+    }
+
+    #[test]
+    fn version_is_antisymmetric(a in "([0-9]:)?[0-9a-z.+_]{1,12}(-[0-9.]{1,4})?", b in "([0-9]:)?[0-9a-z.+_]{1,12}(-[0-9.]{1,4})?") {
+        prop_assert_eq!(compare_versions(&a, &b), compare_versions(&b, &a).reverse());
+        prop_assert_eq!(compare_versions(&a, &a), Ordering::Equal);
+    }
+
+    #[test]
+    fn version_numeric(a in 0_u64..100_000, b in 0_u64..100_000, release in 1_u32..10) {
+        prop_assert_eq!(
+            compare_versions(&format!("1.{a}-{release}"), &format!("1.{b}-{release}")),
+            a.cmp(&b)
+        );
+        prop_assert_eq!(compare_versions(&format!("{a}.0rc1"), &format!("{a}.0")), Ordering::Less);
+        prop_assert_eq!(compare_versions(&format!("1:{a}"), &format!("{b}")), Ordering::Greater);
+    }
+}
@@ -0,0 +1,21 @@
+use msys2_packager::symbols::get_build_id;
+use msys2_packager::symbols::SymbolServer;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn build_id_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let _ = get_build_id(&bytes);
+    }
+
+    #[test]
+    fn symbol_server_never_panics(input in any::<String>()) {
+        let _ = input.parse::<SymbolServer>();
+    }
+
+    #[test]
+    fn symbol_server_round_trip(input in "(https://[a-z.]{1,16}(/[a-z]{1,8})?|sentry:[a-z-]{1,8}/[a-z-]{1,8})") {
+        let server: SymbolServer = input.parse().unwrap();
+        prop_assert_eq!(server.to_string(), input);
+    }
+}
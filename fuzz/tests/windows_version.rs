@@ -0,0 +1,24 @@
+use msys2_packager::knowledge::KnowledgeBase;
+use msys2_packager::os_version::analyze_os_version_from_bytes;
+use msys2_packager::os_version::WindowsVersion;
+use proptest::prelude::*;
+use std::path::PathBuf;
+
+proptest! {
+    #[test]
+    fn parse_never_panics(input in any::<String>()) {
+        let _ = input.parse::<WindowsVersion>();
+    }
+
+    #[test]
+    fn roundtrip(major in any::<u16>(), minor in any::<u16>(), build in any::<u32>()) {
+        let version = WindowsVersion::new(major, minor, build);
+        let parsed: WindowsVersion = version.to_string().parse().expect("failed to parse");
+        prop_assert_eq!(parsed, version);
+    }
+
+    #[test]
+    fn analyze_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let _ = analyze_os_version_from_bytes(PathBuf::new(), &bytes, &KnowledgeBase::builtin());
+    }
+}
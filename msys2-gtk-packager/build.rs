@@ -0,0 +1,63 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Run git in the crate dir, returning its trimmed stdout if it succeeded.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().into())
+}
+
+/// Get the path of a file in the git dir, like `HEAD` or `refs/heads/master`.
+///
+/// This goes through git, so refs of worktrees resolve to the shared git dir.
+fn get_git_path(path: &str) -> Option<PathBuf> {
+    let git_path = git(&["rev-parse", "--git-path", path])?;
+    Some(Path::new(env!("CARGO_MANIFEST_DIR")).join(git_path))
+}
+
+/// Record the git commit and its time for `--version --verbose`.
+///
+/// These are skipped if git or the repo is missing, like when building from crates.io.
+/// The commit time is used as the build time, so it changes whenever the commit does,
+/// without rerunning this script on every build.
+/// `SOURCE_DATE_EPOCH` overrides it, for reproducible builds.
+fn main() {
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // Rerun when HEAD moves to another branch or commit, or the branch it points to moves.
+    if let Some(head) = get_git_path("HEAD") {
+        println!("cargo:rerun-if-changed={}", head.display());
+
+        if let Some(head_ref) = std::fs::read_to_string(&head)
+            .ok()
+            .and_then(|head| Some(head.strip_prefix("ref:")?.trim().to_string()))
+        {
+            // The ref is a loose file, or in `packed-refs` if git packed it.
+            // Cargo reruns the script on every build for missing paths, so those are skipped.
+            for path in [head_ref.as_str(), "packed-refs"] {
+                if let Some(path) = get_git_path(path).filter(|path| path.exists()) {
+                    println!("cargo:rerun-if-changed={}", path.display());
+                }
+            }
+        }
+    }
+
+    if let Some(git_hash) = git(&["rev-parse", "--short=12", "HEAD"]) {
+        println!("cargo:rustc-env=BUILD_GIT_HASH={git_hash}");
+    }
+
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .or_else(|| git(&["log", "-1", "--format=%ct", "HEAD"]))
+        .and_then(|timestamp| timestamp.parse::<u64>().ok());
+    if let Some(timestamp) = timestamp {
+        println!("cargo:rustc-env=BUILD_TIMESTAMP={timestamp}");
+    }
+}
@@ -0,0 +1,26 @@
+use camino::Utf8Path;
+use std::path::PathBuf;
+
+/// The name of the libadwaita crate, which apps using libadwaita depend on.
+pub const ADWAITA_CRATE_NAME: &str = "libadwaita";
+
+/// The Adwaita icon theme, relative to an MSYS2 environment or the package dir.
+pub const ADWAITA_ICON_THEME_DIR: &str = "share/icons/Adwaita";
+
+/// The `settings.ini` written for libadwaita apps.
+///
+/// libadwaita brings its own stylesheet, and warns if a GTK theme is set, so no theme is set.
+/// Font metrics are hinted, so text is as crisp as native apps.
+pub const ADWAITA_SETTINGS_INI: &str =
+    "[Settings]\ngtk-icon-theme-name=Adwaita\ngtk-hint-font-metrics=true\n";
+
+/// Get the Adwaita icon theme of an MSYS2 environment, which libadwaita widgets use for their symbolic icons.
+///
+/// The stylesheets of libadwaita are compiled into its dll as resources, so only the icons need to be packaged.
+///
+/// # Returns
+/// Returns None if the adwaita-icon-theme package of the environment is not installed.
+pub fn get_adwaita_icon_theme(environment_path: &Utf8Path) -> Option<PathBuf> {
+    let path = environment_path.join(ADWAITA_ICON_THEME_DIR);
+    path.is_dir().then(|| path.into())
+}
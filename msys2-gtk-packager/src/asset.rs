@@ -0,0 +1,178 @@
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+/// The key of this tool's section in `package.metadata` of a Cargo.toml.
+pub const METADATA_KEY: &str = "msys2-gtk-packager";
+
+/// An error that may occur while parsing an [`Asset`] from a string.
+#[derive(Debug)]
+pub struct AssetFromStrError(String);
+
+impl std::fmt::Display for AssetFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid asset", self.0)
+    }
+}
+
+impl std::error::Error for AssetFromStrError {}
+
+/// A file, dir, or glob of files to copy verbatim into the package
+#[derive(Debug, Clone)]
+pub struct Asset {
+    /// The path or glob pattern to copy from
+    pub src: String,
+
+    /// The path in the package to copy to.
+    ///
+    /// If None, this is `src` for relative paths, or the file name of `src` for absolute paths.
+    /// For globs, the parts of `src` before the first glob pattern are used.
+    pub dest: Option<PathBuf>,
+}
+
+impl Asset {
+    /// Get the files to package for this asset.
+    ///
+    /// Relative `src` paths are resolved against `base_dir`.
+    ///
+    /// # Returns
+    /// Returns a list of `(src, dest)` pairs, where `dest` is relative to the package root.
+    pub fn collect_files(&self, base_dir: &Path) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+        let src = Path::new(&self.src);
+
+        // Split off the leading part of the path without glob patterns.
+        let root: PathBuf = src
+            .components()
+            .take_while(|component| !is_glob_pattern(&component.as_os_str().to_string_lossy()))
+            .collect();
+        let is_glob = root.as_os_str().len() != src.as_os_str().len();
+
+        let dest = match self.dest.clone() {
+            Some(dest) => dest,
+            None if root.is_relative() => root.clone(),
+            None => root
+                .file_name()
+                .with_context(|| format!("asset `{}` has no file name", self.src))?
+                .into(),
+        };
+        ensure!(
+            dest.components()
+                .all(|component| matches!(component, Component::Normal(_))),
+            "asset destination `{}` is not a plain relative path",
+            dest.display()
+        );
+
+        let root = base_dir.join(root);
+        let mut files = Vec::new();
+        if is_glob {
+            let pattern = base_dir.join(src);
+            let pattern = pattern
+                .to_str()
+                .with_context(|| format!("asset `{}` is not unicode", self.src))?;
+            let paths = glob::glob(pattern)
+                .with_context(|| format!("asset `{}` is not a valid glob", self.src))?;
+            for path in paths {
+                let path = path.context("failed to read asset glob match")?;
+                if !path.is_file() {
+                    continue;
+                }
+
+                let relative_path = path
+                    .strip_prefix(&root)
+                    .context("asset glob match is not in the glob root")?;
+                files.push((path.clone(), dest.join(relative_path)));
+            }
+        } else if root.is_dir() {
+            for entry in WalkDir::new(&root) {
+                let entry = entry.context("failed to get dir entry")?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+
+                let relative_path = entry
+                    .path()
+                    .strip_prefix(&root)
+                    .context("dir entry path is not prefixed by the asset dir")?;
+                files.push((entry.path().into(), dest.join(relative_path)));
+            }
+        } else {
+            ensure!(root.is_file(), "asset `{}` does not exist", root.display());
+            files.push((root, dest));
+        }
+
+        ensure!(!files.is_empty(), "asset `{}` matched no files", self.src);
+
+        Ok(files)
+    }
+}
+
+impl std::str::FromStr for Asset {
+    type Err = AssetFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // Don't mistake the `:` of a Windows drive letter for the separator.
+        let separator_search_start = match input.as_bytes() {
+            [drive, b':', ..] if drive.is_ascii_alphabetic() => 2,
+            _ => 0,
+        };
+        let (src, dest) = match input[separator_search_start..].find(':') {
+            Some(index) => {
+                let index = separator_search_start + index;
+                (&input[..index], Some(PathBuf::from(&input[index + 1..])))
+            }
+            None => (input, None),
+        };
+
+        if src.is_empty()
+            || dest
+                .as_ref()
+                .is_some_and(|dest| dest.as_os_str().is_empty())
+        {
+            return Err(AssetFromStrError(input.into()));
+        }
+
+        Ok(Self {
+            src: src.into(),
+            dest,
+        })
+    }
+}
+
+/// This tool's section in `package.metadata` of a Cargo.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackageMetadata {
+    /// Assets to package, as a map of sources to destinations.
+    ///
+    /// An empty destination means the default destination.
+    #[serde(default)]
+    assets: BTreeMap<String, String>,
+}
+
+/// Get the assets listed in the `[package.metadata.msys2-gtk-packager.assets]` section of a package's Cargo.toml.
+pub fn get_manifest_assets(package: &cargo_metadata::Package) -> anyhow::Result<Vec<Asset>> {
+    let metadata = match package.metadata.get(METADATA_KEY) {
+        Some(metadata) => PackageMetadata::deserialize(metadata)
+            .with_context(|| format!("invalid `package.metadata.{METADATA_KEY}`"))?,
+        None => PackageMetadata::default(),
+    };
+
+    Ok(metadata
+        .assets
+        .into_iter()
+        .map(|(src, dest)| Asset {
+            src,
+            dest: (!dest.is_empty()).then(|| dest.into()),
+        })
+        .collect())
+}
+
+/// Check if a path component contains a glob pattern.
+fn is_glob_pattern(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
@@ -1,4 +1,30 @@
 /// The `build` subcommand
 pub mod build;
+/// The `cache` subcommand
+pub mod cache;
+/// The `diff` subcommand
+pub mod diff;
+/// The `doctor` subcommand
+pub mod doctor;
+/// The `explain` subcommand
+pub mod explain;
+/// The `export-env` subcommand
+pub mod export_env;
+/// The `ide-setup` subcommand
+pub mod ide_setup;
+/// The `inspect` subcommand
+pub mod inspect;
+/// The `installer` subcommand
+pub mod installer;
+/// The `min-os` subcommand
+pub mod min_os;
 /// The `package` subcommand
 pub mod package;
+/// The `run` subcommand
+pub mod run;
+/// The `size` subcommand
+pub mod size;
+/// The `test` subcommand
+pub mod test;
+/// The `verify` subcommand
+pub mod verify;
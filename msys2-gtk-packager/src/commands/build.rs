@@ -1,5 +1,24 @@
+use crate::asset::Asset;
+use crate::launcher::LauncherKind;
+use crate::manifest::ManifestMode;
+use crate::package_info::GitStatus;
+use crate::resource::EmbedResources;
+use crate::system_dll::SystemDllRules;
+use crate::theme::Theme;
+use crate::trace::LoadTrace;
+use crate::util::CargoFeatures;
+use crate::util::Debugger;
+use crate::util::RunCommand;
+use crate::PackageOptions;
 use anyhow::ensure;
 use anyhow::Context;
+use camino::Utf8PathBuf;
+use msys2_packager::packager::CrtCheck;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::sbom::SbomFormat;
+use msys2_packager::sign::get_sign_options;
+use msys2_packager::util::UpxOptions;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 #[derive(Debug, argh::FromArgs)]
@@ -9,8 +28,11 @@ use std::path::PathBuf;
     description = "Build the GTK-rs application"
 )]
 pub struct Options {
-    #[argh(option, description = "the target triple")]
-    pub target: String,
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
 
     #[argh(
         option,
@@ -19,8 +41,80 @@ pub struct Options {
     )]
     pub profile: String,
 
-    #[argh(option, long = "bin", description = "the binary name")]
-    pub bin: String,
+    #[argh(option, long = "features", description = "cargo features to enable")]
+    pub features: Vec<String>,
+
+    #[argh(switch, description = "disable the default cargo features")]
+    pub no_default_features: bool,
+
+    #[argh(switch, description = "enable every cargo feature")]
+    pub all_features: bool,
+
+    #[argh(
+        switch,
+        description = "install the rust target with rustup if it is missing, instead of failing"
+    )]
+    pub auto_install_target: bool,
+
+    #[argh(
+        switch,
+        description = "do not check that the MSYS2 packages needed to build, like gtk4 and the C toolchain, are installed"
+    )]
+    pub skip_preflight: bool,
+
+    #[argh(
+        switch,
+        description = "install the MSYS2 packages needed to build that are missing with `pacman -S --needed --noconfirm`, for CI machines that start from a bare MSYS2. The packages include the C toolchain, GTK, GStreamer, and the libraries of crates like libadwaita"
+    )]
+    pub auto_install_msys2_deps: bool,
+
+    #[argh(
+        switch,
+        description = "refuse to package a profile other than `dev` or `test` from uncommitted changes or untracked files in the git checkout"
+    )]
+    pub require_clean_git: bool,
+
+    #[argh(
+        option,
+        short = 'p',
+        long = "package",
+        description = "the workspace package that contains the binary"
+    )]
+    pub package: Option<String>,
+
+    #[argh(
+        option,
+        long = "bin",
+        description = "the binary name. May be passed multiple times to package several binaries together, where the first is the main binary. Defaults to the only bin of the package"
+    )]
+    pub bin: Vec<String>,
+
+    #[argh(
+        switch,
+        description = "package every binary in the selected package, or the root package"
+    )]
+    pub bins: bool,
+
+    #[argh(
+        option,
+        long = "bench",
+        description = "the name of a bench target to package, in place of `--bin`"
+    )]
+    pub bench: Option<String>,
+
+    #[argh(
+        option,
+        long = "test",
+        description = "the name of a test target to package, in place of `--bin`"
+    )]
+    pub test: Option<String>,
+
+    #[argh(
+        option,
+        long = "example",
+        description = "the name of an example target to package, in place of `--bin`"
+    )]
+    pub example: Option<String>,
 
     #[argh(
         option,
@@ -36,13 +130,268 @@ pub struct Options {
     )]
     pub extra_libraries: Vec<String>,
 
+    #[argh(
+        option,
+        description = "a glob pattern of dll names, like `libicudata*.dll`, to never add automatically, even if packaged binaries import them"
+    )]
+    pub exclude: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, which are never packaged"
+    )]
+    pub system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to package from MSYS2 when imported, even if they are considered system dlls"
+    )]
+    pub bundle_dll: Vec<String>,
+
     #[argh(
         option,
         short = 't',
         long = "theme",
-        description = "the path to a theme to package"
+        description = "the path to a theme to package, or `pkg:<name>` for a theme installed by an MSYS2 package"
+    )]
+    pub themes: Vec<Theme>,
+
+    #[argh(
+        option,
+        long = "icon-theme",
+        description = "the path to an icon theme to package"
+    )]
+    pub icon_themes: Vec<PathBuf>,
+
+    #[argh(
+        option,
+        long = "asset",
+        description = "a file, dir, or glob to copy verbatim into the package, as `<src>[:<dest>]`"
+    )]
+    pub assets: Vec<Asset>,
+
+    #[argh(
+        switch,
+        description = "package for libadwaita, with the Adwaita icon theme and no GTK theme. This is on if the app depends on the libadwaita crate"
+    )]
+    pub adwaita: bool,
+
+    #[argh(
+        switch,
+        description = "copy the targets of symlinks in theme dirs, instead of erroring"
+    )]
+    pub follow_theme_symlinks: bool,
+
+    #[argh(
+        switch,
+        description = "do not package the helper exes that GLib needs to spawn subprocesses, like `gspawn-win64-helper.exe`"
+    )]
+    pub no_glib_spawn_helpers: bool,
+
+    #[argh(
+        switch,
+        description = "package the GIO modules of glib-networking, like TLS backends, and the CA certificate bundle, which apps using TLS need"
+    )]
+    pub gio_modules: bool,
+
+    #[argh(
+        switch,
+        description = "package the GTK input method modules, and regenerate their cache"
+    )]
+    pub immodules: bool,
+
+    #[argh(
+        switch,
+        description = "package the GTK print backends and their dependencies, for the GTK print dialog"
+    )]
+    pub print_backends: bool,
+
+    #[argh(
+        switch,
+        description = "package the language specs and style schemes of GtkSourceView 5, for apps with source views"
+    )]
+    pub gtksourceview: bool,
+
+    #[argh(
+        switch,
+        description = "package the enchant providers and hunspell dictionaries, for apps using gspell or libspelling"
+    )]
+    pub spell_check: bool,
+
+    #[argh(
+        option,
+        long = "spell-language",
+        description = "only package the hunspell dictionaries of a language, like `de` for every German dictionary or `en_US`. Implies `--spell-check`"
+    )]
+    pub spell_languages: Vec<String>,
+
+    #[argh(
+        option,
+        long = "typelib",
+        description = "a GObject introspection typelib to package into `lib/girepository-1.0`, like `Gtk-4.0`, with the typelibs and dlls it needs. For apps that embed GJS or PyGObject"
+    )]
+    pub typelibs: Vec<String>,
+
+    #[argh(
+        option,
+        short = 'j',
+        default = "1",
+        description = "the number of threads to use when copying and upx-ing files"
+    )]
+    pub jobs: usize,
+
+    #[argh(
+        switch,
+        description = "remove the old package dir instead of updating it incrementally"
+    )]
+    pub clean: bool,
+
+    #[argh(
+        switch,
+        description = "fail if the packaged MSYS2 binaries would differ from the lockfile of the target, like `gtk-packager.x86_64-pc-windows-gnu.lock`"
+    )]
+    pub locked: bool,
+
+    #[argh(
+        switch,
+        description = "verify each copied file, and write a `SHA256SUMS` file to the package dir"
+    )]
+    pub verify_copies: bool,
+
+    #[argh(
+        option,
+        description = "hard-link dlls that are packaged unchanged from a content-addressed store in this dir, shared between projects. Defaults to the `MSYS2_GTK_PACKAGER_DLL_STORE` env var, and then the `dll-store` dir of the profile dir, if one is set"
+    )]
+    pub dll_store: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "set the timestamps in the headers of packaged exes and dlls to `SOURCE_DATE_EPOCH`, or 0, for reproducible packages"
+    )]
+    pub deterministic: bool,
+
+    #[argh(
+        switch,
+        description = "check that the app is set up to show notifications, with an AppUserModelID and an icon, before packaging"
+    )]
+    pub with_notifications: bool,
+
+    #[argh(
+        switch,
+        description = "link the app icon and version info, from the installer settings, into the built exe"
+    )]
+    pub embed_resources: bool,
+
+    #[argh(
+        option,
+        description = "give the exe an application manifest enabling per-monitor DPI awareness, long paths, and common controls v6, `embed` or `alongside`"
+    )]
+    pub manifest: Option<ManifestMode>,
+
+    #[argh(
+        switch,
+        description = "build through `cargo auditable` to embed the dependency tree in the exes, then check that packaged exes have it and write it to `auditable.json` for vulnerability scanners"
+    )]
+    pub auditable: bool,
+
+    #[argh(
+        option,
+        description = "check the licenses and advisories of the crates and MSYS2 packages against the JSON policy in this file before packaging, and fail if it is broken"
+    )]
+    pub policy: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "write a launcher next to each exe that points GTK at the packaged loaders, schemas, plugins, and data before starting it, `exe`, `bat`, or `powershell`"
+    )]
+    pub launcher: Option<LauncherKind>,
+
+    #[argh(
+        switch,
+        description = "print the files that would be packaged, without packaging them"
+    )]
+    pub dry_run: bool,
+
+    #[argh(
+        switch,
+        description = "report which MSYS2 package each packaged file came from"
+    )]
+    pub report: bool,
+
+    #[argh(
+        switch,
+        description = "report the size of each packaged file and category of files, before and after upx"
+    )]
+    pub size_report: bool,
+
+    #[argh(
+        option,
+        description = "write the dll dependency graph to this file, as JSON if it ends in `.json` and as Graphviz DOT otherwise"
+    )]
+    pub emit_graph: Option<PathBuf>,
+
+    #[argh(
+        option,
+        long = "gresource-dir",
+        description = "experimental: bundle this packaged dir, like `share/icons`, into a compressed `share/data.gresource` that the app must register at runtime"
+    )]
+    pub gresource_dirs: Vec<Utf8PathBuf>,
+
+    #[argh(
+        option,
+        description = "the gettext domain of the app. Every `<language>.po` file in the po dir is compiled to `share/locale/<language>/LC_MESSAGES/<domain>.mo`"
+    )]
+    pub gettext_domain: Option<String>,
+
+    #[argh(
+        option,
+        description = "the dir with the `.po` files of the app, defaulting to `po` next to the Cargo.toml"
+    )]
+    pub po_dir: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "write the licenses of the MSYS2 packages that packaged files came from to THIRD-PARTY-LICENSES.txt"
+    )]
+    pub licenses: bool,
+
+    #[argh(
+        option,
+        description = "write a software bill of materials into the package, `cyclonedx` or `spdx`"
+    )]
+    pub sbom: Option<SbomFormat>,
+
+    #[argh(
+        option,
+        description = "sign packaged exes and dlls with signtool using this .pfx certificate. The password is read from the SIGN_CERT_PASSWORD env var, or the file at the SIGN_CERT_PASSWORD_FILE env var, if set"
+    )]
+    pub sign_cert: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "the RFC 3161 timestamp server to use when signing"
     )]
-    pub themes: Vec<PathBuf>,
+    pub sign_timestamp_url: Option<String>,
+
+    #[argh(
+        option,
+        default = "CrtCheck::Warn",
+        description = "what to do when a packaged binary links against a different C runtime than the MSYS2 environment, `off`, `warn`, or `error`"
+    )]
+    pub crt_check: CrtCheck,
+
+    #[argh(
+        switch,
+        description = "do not always package the support dlls of the toolchain, like `libgcc_s_seh-1.dll`"
+    )]
+    pub no_toolchain_runtime: bool,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
 
     #[argh(
         option,
@@ -57,33 +406,215 @@ pub struct Options {
         description = "run the final binary. The advantage of this over specifiying a custom build command is that you can have the binary load custom themes"
     )]
     pub run: bool,
+
+    #[argh(
+        option,
+        description = "run the final binary under a debugger, `gdb` or `windbg`. Requires `--run`"
+    )]
+    pub debugger: Option<Debugger>,
+
+    #[argh(
+        option,
+        description = "set `GTK_DEBUG` for the final binary, like `interactive`. Requires `--run`"
+    )]
+    pub gtk_debug: Option<String>,
+
+    #[argh(
+        option,
+        description = "set `G_MESSAGES_DEBUG` for the final binary, like `all`. Requires `--run`"
+    )]
+    pub glib_debug: Option<String>,
+
+    #[argh(
+        switch,
+        description = "record which dlls the packaged binary loads, and suggest packaged dlls that were never loaded and dlls that were loaded from MSYS2 instead. Requires `--run`"
+    )]
+    pub trace_loads: bool,
+
+    #[argh(
+        positional,
+        description = "args to pass to cargo verbatim, after `--`, like `--locked`"
+    )]
+    pub cargo_args: Vec<String>,
 }
 
 /// Exec the `build` subcommand.
 pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<()> {
+    let target = crate::util::resolve_target(options.target)?;
+    ensure!(
+        options.run || options.debugger.is_none(),
+        "`--debugger` requires `--run`"
+    );
+    ensure!(
+        options.run || options.gtk_debug.is_none(),
+        "`--gtk-debug` requires `--run`"
+    );
+    ensure!(
+        options.run || options.glib_debug.is_none(),
+        "`--glib-debug` requires `--run`"
+    );
+    ensure!(
+        options.run || !options.trace_loads,
+        "`--trace-loads` requires `--run`"
+    );
+    ensure!(
+        !(options.trace_loads && options.skip_package),
+        "`--trace-loads` cannot be used with `--skip-package`"
+    );
+    ensure!(
+        !(options.trace_loads && options.debugger.is_some()),
+        "`--trace-loads` cannot be used with `--debugger`"
+    );
+
+    ctx.cargo_features = CargoFeatures {
+        features: options.features,
+        no_default_features: options.no_default_features,
+        all_features: options.all_features,
+    };
+    ctx.auto_install_target = options.auto_install_target;
+    ctx.skip_preflight = options.skip_preflight;
+    ctx.auto_install_msys2_deps = options.auto_install_msys2_deps;
+    ctx.auditable = options.auditable;
+
+    // Capture this before anything is written, like the lockfile, so the package info records the checkout as it was.
+    let git_status = GitStatus::get(&ctx.cargo_metadata.workspace_root)?;
+    if options.require_clean_git && !options.dry_run && !options.skip_package {
+        crate::package_info::ensure_clean_git(
+            git_status.as_ref(),
+            &ctx.cargo_metadata.workspace_root,
+            &options.profile,
+        )?;
+    }
+
+    let (kind, bins) = crate::util::get_cargo_targets(
+        &ctx.cargo_metadata,
+        options.package.as_deref(),
+        options.bin,
+        options.bins,
+        options.bench,
+        options.test,
+        options.example,
+    )?;
     ctx.set_build_data(
-        options.target.as_str(),
+        target.as_str(),
         options.profile.as_str(),
-        options.bin.as_str(),
+        options.package.as_deref(),
+        kind,
+        bins,
     )?;
-    ctx.run_cargo_build(options.build_subcommand.as_deref())?;
+    ctx.run_cargo_build(
+        options.build_subcommand.as_deref(),
+        options.cargo_args.into_iter().map(OsString::from).collect(),
+        EmbedResources {
+            version_info: options.embed_resources,
+            manifest: options.manifest == Some(ManifestMode::Embed),
+        },
+    )?;
+
+    let package_options = PackageOptions {
+        upx: false,
+        upx_options: UpxOptions::new(),
+        no_upx: &[],
+        strip: false,
+        split_debug: false,
+        symbol_server: None,
+        jobs: options.jobs,
+        clean: options.clean,
+        overwrite: None,
+        report: options.report,
+        licenses: options.licenses,
+        sbom: options.sbom,
+        sign: get_sign_options(options.sign_cert, options.sign_timestamp_url)?,
+        extra_libraries: &options.extra_libraries,
+        excludes: &options.exclude,
+        system_dlls: &SystemDllRules {
+            system: options.system_dll.clone(),
+            bundle: options.bundle_dll.clone(),
+        },
+        themes: &options.themes,
+        icon_themes: &options.icon_themes,
+        adwaita: options.adwaita,
+        follow_theme_symlinks: options.follow_theme_symlinks,
+        gio_modules: options.gio_modules,
+        immodules: options.immodules,
+        print_backends: options.print_backends,
+        gtksourceview: options.gtksourceview,
+        spell_check: options.spell_check,
+        spell_languages: &options.spell_languages,
+        typelibs: &options.typelibs,
+        glib_spawn_helpers: !options.no_glib_spawn_helpers,
+        assets: &options.assets,
+        crt_check: options.crt_check,
+        toolchain_runtime: !options.no_toolchain_runtime,
+        size_report: options.size_report,
+        gresource_dirs: &options.gresource_dirs,
+        emit_graph: options.emit_graph.as_deref(),
+        locked: options.locked,
+        update_lock_file: false,
+        verify_copies: options.verify_copies,
+        dll_store: options.dll_store.as_deref(),
+        deterministic: options.deterministic,
+        with_notifications: options.with_notifications,
+        manifest: options.manifest,
+        auditable: options.auditable,
+        policy: options.policy.as_deref(),
+        launcher: options.launcher,
+        gettext_domain: options.gettext_domain.as_deref(),
+        po_dir: options.po_dir.as_deref(),
+        git_status: git_status.as_ref(),
+    };
+
+    if options.dry_run {
+        ctx.dry_run(&package_options)?
+            .print(options.output_format)?;
+        return Ok(());
+    }
 
     if !options.skip_package {
-        ctx.package(false, &options.extra_libraries, &options.themes)?;
+        ctx.package(&package_options)?
+            .print(options.output_format)?;
     }
 
     if options.run {
-        let cmd = if options.skip_package {
-            ctx.get_bin_path()?
+        let mut run = if options.skip_package {
+            RunCommand::new(ctx.get_bin_path()?.into())
         } else {
-            ctx.get_packaged_bin_path()?
+            // Only let the packaged binary see packaged dlls, like it would on a user's machine.
+            let mut run = RunCommand::new(ctx.get_packaged_bin_path()?.into());
+            run.restrict_path(ctx.get_package_out_dir()?.into());
+            run
         };
 
-        let status = std::process::Command::new(cmd)
-            .status()
-            .context("failed to run")?;
+        if let Some(debugger) = options.debugger {
+            let environment_bin_path = ctx.get_msys2_environment_path()?.join("bin");
+            let path = debugger.locate(environment_bin_path.as_std_path())?;
+            run.debugger(debugger, path);
+        }
 
-        ensure!(status.success());
+        if let Some(gtk_debug) = options.gtk_debug {
+            run.env("GTK_DEBUG".into(), gtk_debug.into());
+        }
+        if let Some(glib_debug) = options.glib_debug {
+            run.env("G_MESSAGES_DEBUG".into(), glib_debug.into());
+        }
+
+        if options.trace_loads {
+            let mut command = run.build_command()?;
+            let trace = LoadTrace::run(&mut command)?;
+            let suggestions = trace.suggest(
+                ctx.get_package_out_dir()?.as_std_path(),
+                ctx.msys2_installation_path.as_std_path(),
+            )?;
+            print!("{suggestions}");
+
+            ensure!(
+                trace.status.success(),
+                "`{command:?}` exited with nonzero exit code `{}`",
+                trace.status
+            );
+        } else {
+            run.exec().context("failed to run")?;
+        }
     }
 
     Ok(())
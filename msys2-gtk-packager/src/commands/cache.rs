@@ -0,0 +1,259 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::store::DllStore;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "cache",
+    description = "Manage the caches shared between packages"
+)]
+pub struct Options {
+    #[argh(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Subcommand {
+    Gc(GcOptions),
+}
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "gc",
+    description = "Remove the least recently used files of the dll store that no package links"
+)]
+pub struct GcOptions {
+    #[argh(
+        option,
+        description = "the dll store dir. Defaults to the `MSYS2_GTK_PACKAGER_DLL_STORE` env var, and then the `dll-store` dir of the profile dir, if one is set"
+    )]
+    pub dll_store: Option<PathBuf>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_size),
+        description = "remove unused files until the store is at most this size, like `5GB` or `512MiB`"
+    )]
+    pub max_size: Option<u64>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_duration),
+        description = "remove unused files that were not linked for this long, like `30d` or `12h`"
+    )]
+    pub unused_for: Option<Duration>,
+
+    #[argh(
+        switch,
+        description = "report what would be removed, without removing it"
+    )]
+    pub dry_run: bool,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `cache` subcommand.
+///
+/// `profile_dir` is the profile dir, if one was set.
+pub fn exec(options: Options, profile_dir: Option<&Utf8Path>) -> anyhow::Result<()> {
+    match options.subcommand {
+        Subcommand::Gc(options) => exec_gc(options, profile_dir),
+    }
+}
+
+/// Exec the `cache gc` subcommand.
+fn exec_gc(options: GcOptions, profile_dir: Option<&Utf8Path>) -> anyhow::Result<()> {
+    let dir = crate::get_dll_store_dir(options.dll_store.as_deref(), profile_dir)
+        .context("missing dll store, pass `--dll-store` or `--profile-dir`, or set the `MSYS2_GTK_PACKAGER_DLL_STORE` env var")?;
+    if options.max_size.is_none() && options.unused_for.is_none() {
+        eprintln!("warning: neither `--max-size` nor `--unused-for` was passed, so only stale package registrations will be dropped");
+    }
+
+    let report = DllStore::new(dir)
+        .gc(options.max_size, options.unused_for, options.dry_run)
+        .context("failed to garbage-collect the dll store")?;
+    report.print(options.output_format)?;
+
+    if let Some(max_size) = options.max_size {
+        if report.size > max_size {
+            eprintln!(
+                "warning: the store is still {} bytes, as packages link {} bytes of it",
+                report.size, report.in_use_size
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a size, like `5GB` or `512MiB`, into bytes.
+///
+/// Sizes without a unit are in bytes.
+fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("`{input}` is not a valid size"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "tb" => 1000 * 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(format!("`{unit}` is not a valid size unit")),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("`{input}` is too large"))
+}
+
+/// Parse a duration, like `30d` or `12h`.
+fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("`{input}` is not a valid duration"))?;
+    let multiplier: u64 = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "`{input}` does not have a valid duration unit, use `s`, `m`, `h`, `d`, or `w`"
+            ))
+        }
+    };
+
+    number
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("`{input}` is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("5kb").unwrap(), 5_000);
+        assert_eq!(parse_size("5MB").unwrap(), 5_000_000);
+        assert_eq!(parse_size("5GB").unwrap(), 5_000_000_000);
+        assert_eq!(parse_size("5TB").unwrap(), 5_000_000_000_000);
+        assert_eq!(parse_size("5KiB").unwrap(), 5 << 10);
+        assert_eq!(parse_size("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse_size("5gib").unwrap(), 5 << 30);
+        assert_eq!(parse_size("5TiB").unwrap(), 5 << 40);
+        assert_eq!(parse_size(" 5 GB ").unwrap(), 5_000_000_000);
+    }
+
+    #[test]
+    fn parse_size_overflow() {
+        assert_eq!(parse_size("18446744073709551615").unwrap(), u64::MAX);
+        assert_eq!(
+            parse_size("18446744073709551615KB").unwrap_err(),
+            "`18446744073709551615KB` is too large"
+        );
+        assert_eq!(
+            parse_size("16777216TiB").unwrap_err(),
+            "`16777216TiB` is too large"
+        );
+        assert!(parse_size("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn parse_size_bad_input() {
+        assert_eq!(parse_size("").unwrap_err(), "`` is not a valid size");
+        assert_eq!(parse_size("GB").unwrap_err(), "`GB` is not a valid size");
+        assert_eq!(
+            parse_size("-5GB").unwrap_err(),
+            "`-5GB` is not a valid size"
+        );
+        assert_eq!(
+            parse_size("5PB").unwrap_err(),
+            "`PB` is not a valid size unit"
+        );
+        assert_eq!(
+            parse_size("5.5GB").unwrap_err(),
+            "`.5GB` is not a valid size unit"
+        );
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(
+            parse_duration("12h").unwrap(),
+            Duration::from_secs(12 * 60 * 60)
+        );
+        assert_eq!(
+            parse_duration("30d").unwrap(),
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+        assert_eq!(
+            parse_duration(" 2 w ").unwrap(),
+            Duration::from_secs(2 * 7 * 24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn parse_duration_overflow() {
+        assert_eq!(
+            parse_duration("18446744073709551615s").unwrap(),
+            Duration::from_secs(u64::MAX)
+        );
+        assert_eq!(
+            parse_duration("18446744073709551615m").unwrap_err(),
+            "`18446744073709551615m` is too large"
+        );
+        assert!(parse_duration("18446744073709551616s").is_err());
+    }
+
+    #[test]
+    fn parse_duration_bad_input() {
+        assert_eq!(
+            parse_duration("").unwrap_err(),
+            "`` is not a valid duration"
+        );
+        assert_eq!(
+            parse_duration("d").unwrap_err(),
+            "`d` is not a valid duration"
+        );
+        assert_eq!(
+            parse_duration("30").unwrap_err(),
+            "`30` does not have a valid duration unit, use `s`, `m`, `h`, `d`, or `w`"
+        );
+        // Units are case sensitive, as `M` could be months.
+        assert!(parse_duration("30M").is_err());
+        assert!(parse_duration("30days").is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use msys2_packager::diff::diff_packages;
+use msys2_packager::report::OutputFormat;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "diff",
+    description = "Compare two package dirs, and report added, removed, and changed files"
+)]
+pub struct Options {
+    #[argh(positional, description = "the old package dir")]
+    pub old: PathBuf,
+
+    #[argh(positional, description = "the new package dir")]
+    pub new: PathBuf,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `diff` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let diff = diff_packages(&options.old, &options.new)?;
+    diff.print(options.output_format)?;
+
+    Ok(())
+}
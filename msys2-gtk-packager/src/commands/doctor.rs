@@ -0,0 +1,195 @@
+use anyhow::bail;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::pacman::LocalDatabase;
+use std::collections::HashSet;
+use std::process::Command;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "doctor",
+    description = "Check that everything needed to build and package for a target is installed"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+}
+
+/// The accumulated results of the checks.
+#[derive(Debug, Default)]
+struct Doctor {
+    /// The number of problems found
+    problems: usize,
+
+    /// Packages that need to be installed with pacman
+    missing_packages: Vec<String>,
+
+    /// Commands or instructions that fix problems, other than installing packages
+    fixes: Vec<String>,
+}
+
+impl Doctor {
+    /// Report a passing check.
+    fn ok(&self, message: &str) {
+        println!("[ok] {message}");
+    }
+
+    /// Report a failing check.
+    fn problem(&mut self, message: &str, fix: Option<String>) {
+        println!("[missing] {message}");
+        self.problems += 1;
+        self.fixes.extend(fix);
+    }
+
+    /// Check that a package is installed in the environment.
+    ///
+    /// `install` is the name to pass to pacman if it is missing, which may be a group.
+    fn check_package(&mut self, database: &LocalDatabase, name: &str, install: String) {
+        match database.get(name) {
+            Some(package) => self.ok(&format!("package `{name}` ({})", package.version)),
+            None if database.is_installed(name) => {
+                self.ok(&format!("package `{name}` (provided)"));
+            }
+            None => {
+                self.problem(&format!("package `{name}`"), None);
+                self.missing_packages.push(install);
+            }
+        }
+    }
+
+    /// Check that a tool can be run from the PATH.
+    ///
+    /// If the tool is missing, the environment's bin dir is checked so we can suggest adding it to the PATH instead.
+    fn check_tool(&mut self, tool: &str, package: String, environment_bin_path: Option<&Utf8Path>) {
+        let output = Command::new(tool).arg("--version").output();
+        match output {
+            Ok(output) if output.status.success() => {
+                self.ok(&format!("`{tool}` is on the PATH"));
+            }
+            _ => {
+                let environment_bin_path =
+                    environment_bin_path.filter(|path| path.join(format!("{tool}.exe")).exists());
+                match environment_bin_path {
+                    Some(path) => {
+                        self.problem(
+                            &format!("`{tool}` is installed, but not on the PATH"),
+                            Some(format!("add `{path}` to the PATH")),
+                        );
+                    }
+                    None => {
+                        self.problem(&format!("`{tool}` is not installed"), None);
+                        self.missing_packages.push(package);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check that the rust target is installed.
+    fn check_rust_target(&mut self, target: &str) {
+        let output = Command::new("rustup")
+            .args(["target", "list", "--installed"])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if stdout.lines().any(|line| line.trim() == target) {
+                    self.ok(&format!("rust target `{target}`"));
+                } else {
+                    self.problem(
+                        &format!("rust target `{target}`"),
+                        Some(format!("rustup target add {target}")),
+                    );
+                }
+            }
+            _ => {
+                self.problem(
+                    "failed to run `rustup target list --installed`",
+                    Some("install rustup from https://rustup.rs".into()),
+                );
+            }
+        }
+    }
+}
+
+/// Exec the `doctor` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let target = &crate::util::resolve_target(options.target)?;
+    let msys2_environment = msys2_packager::util::target_triple_to_msys2_environment(target)
+        .with_context(|| format!("failed to translate `{target}` into a MSYS2 environment"))?;
+    let package_prefix = msys2_environment.get_package_prefix();
+
+    let mut doctor = Doctor::default();
+
+    let msys2_installation_path = match msys2_packager::util::locate_msys2_installation() {
+        Ok(path) => {
+            doctor.ok(&format!("MSYS2 installation at `{path}`"));
+            Some(path)
+        }
+        Err(error) => {
+            doctor.problem(
+                &format!("MSYS2 installation: {error:#}"),
+                Some("install MSYS2 from https://www.msys2.org and add it to the PATH".into()),
+            );
+            None
+        }
+    };
+
+    let environment_bin_path = msys2_installation_path.as_ref().map(|path| {
+        path.join(msys2_environment.get_prefix().trim_start_matches('/'))
+            .join("bin")
+    });
+
+    if let Some(msys2_installation_path) = msys2_installation_path.as_ref() {
+        match LocalDatabase::load(msys2_installation_path) {
+            Ok(database) => {
+                for package in
+                    crate::preflight::get_required_packages(msys2_environment, &HashSet::new())
+                {
+                    doctor.check_package(&database, &package.name, package.install);
+                }
+            }
+            Err(error) => {
+                doctor.problem(&format!("pacman database: {error:#}"), None);
+            }
+        }
+    }
+
+    doctor.check_rust_target(target);
+    doctor.check_tool(
+        "upx",
+        format!("{package_prefix}upx"),
+        environment_bin_path.as_deref(),
+    );
+    doctor.check_tool(
+        "strip",
+        format!("{package_prefix}binutils"),
+        environment_bin_path.as_deref(),
+    );
+
+    if doctor.problems == 0 {
+        println!();
+        println!("Everything is installed");
+        return Ok(());
+    }
+
+    println!();
+    println!("To fix:");
+    if !doctor.missing_packages.is_empty() {
+        println!(
+            "  {}",
+            crate::preflight::get_install_command(
+                doctor.missing_packages.iter().map(String::as_str)
+            )
+        );
+    }
+    for fix in doctor.fixes.iter() {
+        println!("  {fix}");
+    }
+
+    bail!("found {} problem(s)", doctor.problems);
+}
@@ -0,0 +1,77 @@
+use anyhow::bail;
+use anyhow::Context;
+use msys2_packager::packager::Plan;
+use msys2_packager::report::OutputFormat;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "explain",
+    description = "Print why a file is in a package dir, from the manifest written next to it by `package`"
+)]
+pub struct Options {
+    #[argh(positional, description = "the package dir")]
+    pub package_dir: PathBuf,
+
+    #[argh(
+        positional,
+        description = "the file to explain, relative to the package dir, or a file name if only one file has it"
+    )]
+    pub file: PathBuf,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `explain` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let manifest_path = crate::get_package_manifest_path(&options.package_dir)?;
+    let plan = Plan::load(manifest_path.as_std_path()).with_context(|| {
+        format!(
+            "failed to load the manifest of `{}`, run the `package` subcommand first",
+            options.package_dir.display()
+        )
+    })?;
+
+    let file = options
+        .file
+        .strip_prefix(&options.package_dir)
+        .unwrap_or(&options.file);
+    let dest = resolve_dest(&plan, file)?;
+    plan.explain(&dest).print(options.output_format)?;
+
+    Ok(())
+}
+
+/// Get the destination of a file in a plan, which may be given as only its file name.
+fn resolve_dest(plan: &Plan, file: &Path) -> anyhow::Result<PathBuf> {
+    if plan.files.iter().any(|planned| planned.dest == file) {
+        return Ok(file.into());
+    }
+
+    let matches: Vec<_> = plan
+        .files
+        .iter()
+        .filter(|planned| planned.dest.file_name() == Some(file.as_os_str()))
+        .map(|planned| planned.dest.clone())
+        .collect();
+    match matches.as_slice() {
+        [] => bail!("`{}` is not in the package", file.display()),
+        [dest] => Ok(dest.clone()),
+        _ => bail!(
+            "several files are named `{}`, pass one of {}",
+            file.display(),
+            matches
+                .iter()
+                .map(|dest| format!("`{}`", dest.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::dev_env::export_packages;
+use crate::dev_env::generate_env_bat;
+use crate::dev_env::generate_env_powershell;
+use crate::dev_env::get_package_closure;
+use crate::dev_env::ENV_BAT_FILE_NAME;
+use crate::dev_env::ENV_PS1_FILE_NAME;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use msys2_packager::pacman::LocalDatabase;
+
+/// Packages that are exported by default, without the environment prefix.
+const DEFAULT_PACKAGES: &[&str] = &["gtk4", "pkgconf"];
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "export-env",
+    description = "Export a trimmed, relocatable copy of the MSYS2 runtime needed to build GTK apps, for machines without MSYS2"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(option, description = "the dir to export the runtime to")]
+    pub out: Utf8PathBuf,
+
+    #[argh(
+        option,
+        long = "package",
+        description = "an extra MSYS2 package to export along with its dependencies, like `libadwaita`. The environment prefix is optional"
+    )]
+    pub packages: Vec<String>,
+
+    #[argh(switch, description = "export C headers too")]
+    pub headers: bool,
+}
+
+/// Exec the `export-env` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let target = &crate::util::resolve_target(options.target)?;
+    let msys2_environment = msys2_packager::util::target_triple_to_msys2_environment(target)
+        .with_context(|| format!("failed to translate `{target}` into a MSYS2 environment"))?;
+    let package_prefix = msys2_environment.get_package_prefix();
+    let msys2_installation_path = msys2_packager::util::locate_msys2_installation()?;
+    let database = LocalDatabase::load(&msys2_installation_path)?;
+
+    let names: Vec<_> = DEFAULT_PACKAGES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(options.packages)
+        .map(|name| {
+            if name.starts_with(package_prefix) {
+                name
+            } else {
+                format!("{package_prefix}{name}")
+            }
+        })
+        .collect();
+    let packages = get_package_closure(&database, &names)?;
+
+    let summary = export_packages(
+        &packages,
+        &msys2_installation_path,
+        &options.out,
+        options.headers,
+    )?;
+
+    let bat_path = options.out.join(ENV_BAT_FILE_NAME);
+    std::fs::write(&bat_path, generate_env_bat()?)
+        .with_context(|| format!("failed to write `{bat_path}`"))?;
+    let ps1_path = options.out.join(ENV_PS1_FILE_NAME);
+    std::fs::write(&ps1_path, generate_env_powershell()?)
+        .with_context(|| format!("failed to write `{ps1_path}`"))?;
+
+    println!(
+        "Exported {} packages, {} files ({} bytes) to `{}`",
+        summary.num_packages, summary.num_files, summary.size, options.out
+    );
+    println!(
+        "Rewrote {} pkg-config files to be relocatable",
+        summary.num_pkg_config_files
+    );
+    println!("Run `{ENV_BAT_FILE_NAME}` or dot-source `{ENV_PS1_FILE_NAME}` to build against it");
+
+    Ok(())
+}
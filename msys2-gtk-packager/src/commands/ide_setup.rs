@@ -0,0 +1,125 @@
+use crate::ide::Editor;
+use crate::ide::IdeTarget;
+use anyhow::Context;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "ide-setup",
+    description = "Write editor tasks that build, package, and run the GTK-rs application, and a configuration to debug the packaged binary"
+)]
+pub struct Options {
+    #[argh(option, description = "the editor to set up, `vscode`")]
+    pub editor: Editor,
+
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(
+        option,
+        description = "the build profile",
+        default = "String::from(\"dev\")"
+    )]
+    pub profile: String,
+
+    #[argh(
+        option,
+        short = 'p',
+        long = "package",
+        description = "the workspace package that contains the binary"
+    )]
+    pub package: Option<String>,
+
+    #[argh(option, long = "bin", description = "the binary name")]
+    pub bin: Option<String>,
+
+    #[argh(
+        switch,
+        description = "overwrite existing editor files, instead of adding to them"
+    )]
+    pub force: bool,
+}
+
+/// Exec the `ide-setup` subcommand.
+pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<()> {
+    let target = crate::util::resolve_target(options.target)?;
+    let (kind, bins) = crate::util::get_cargo_targets(
+        &ctx.cargo_metadata,
+        options.package.as_deref(),
+        options.bin.into_iter().collect(),
+        false,
+        None,
+        None,
+        None,
+    )?;
+    ctx.set_build_data(
+        target.as_str(),
+        options.profile.as_str(),
+        options.package.as_deref(),
+        kind,
+        bins,
+    )?;
+    let build_data = ctx.build_data.as_ref().context("missing build data")?;
+    let bin = build_data.get_main_bin();
+
+    let workspace_root = &ctx.cargo_metadata.workspace_root;
+    let package_dir = ctx.get_package_out_dir()?;
+    let program = package_dir.join(format!("{bin}.exe"));
+    let gdb = ctx.get_msys2_environment_path()?.join("bin/gdb.exe");
+    let package_dir = get_workspace_path(workspace_root, &package_dir);
+    let program = get_workspace_path(workspace_root, &program);
+    let target = IdeTarget {
+        target: &target,
+        profile: &options.profile,
+        package: options.package.as_deref(),
+        bin,
+        program: &program,
+        package_dir: &package_dir,
+        gdb: &gdb,
+    };
+
+    match options.editor {
+        Editor::VsCode => {
+            let vscode_dir = workspace_root.join(".vscode");
+            let tasks_path = vscode_dir.join("tasks.json");
+            crate::ide::merge_vscode_file(
+                &tasks_path,
+                "tasks",
+                "label",
+                crate::ide::vscode_tasks(&target),
+                "2.0.0",
+                options.force,
+            )?;
+            let launch_path = vscode_dir.join("launch.json");
+            crate::ide::merge_vscode_file(
+                &launch_path,
+                "configurations",
+                "name",
+                vec![crate::ide::vscode_launch_configuration(&target)],
+                "0.2.0",
+                options.force,
+            )?;
+
+            eprintln!("Wrote `{tasks_path}` and `{launch_path}`");
+            eprintln!("Debugging needs the C/C++ extension, and the `$rustc` problem matcher needs rust-analyzer");
+        }
+    }
+
+    Ok(())
+}
+
+/// Make a path relative to the workspace root with `${workspaceFolder}`, so the editor files can be committed.
+fn get_workspace_path(workspace_root: &Utf8Path, path: &Utf8Path) -> Utf8PathBuf {
+    match path.strip_prefix(workspace_root) {
+        Ok(relative_path) => Utf8PathBuf::from(format!(
+            "${{workspaceFolder}}/{}",
+            relative_path.as_str().replace('\\', "/")
+        )),
+        Err(_) => path.into(),
+    }
+}
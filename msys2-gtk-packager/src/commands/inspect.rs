@@ -0,0 +1,118 @@
+use crate::system_dll::SystemDllRules;
+use anyhow::Context;
+use msys2_packager::packager::Plan;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::resolver::build_import_tree_with_options;
+use msys2_packager::resolver::DirDependencySource;
+use msys2_packager::resolver::Msys2DependencySource;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "inspect",
+    description = "Print the recursive dll import tree of an exe or dll, and where each import is located. For a packaged file, also print why it was packaged"
+)]
+pub struct Options {
+    #[argh(positional, description = "the exe or dll to inspect")]
+    pub path: PathBuf,
+
+    #[argh(
+        option,
+        description = "the target triple, which selects the MSYS2 environment to look up imports in. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(
+        switch,
+        description = "do not look up imports next to the inspected binary"
+    )]
+    pub no_bundle: bool,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, as passed to `package`. May be passed multiple times"
+    )]
+    pub system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names that are packaged even if they are considered system dlls, as passed to `package`. May be passed multiple times"
+    )]
+    pub bundle_dll: Vec<String>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `inspect` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let target = &crate::util::resolve_target(options.target)?;
+    let msys2_environment = msys2_packager::util::target_triple_to_msys2_environment(target)
+        .with_context(|| format!("failed to translate `{target}` into a MSYS2 environment"))?;
+    let msys2_installation_path = msys2_packager::util::locate_msys2_installation()?;
+    let environment_path =
+        msys2_installation_path.join(msys2_environment.get_prefix().trim_start_matches('/'));
+
+    let path = options
+        .path
+        .canonicalize()
+        .with_context(|| format!("failed to canonicalize `{}`", options.path.display()))?;
+    let bundle = if options.no_bundle {
+        None
+    } else {
+        let dir = path.parent().context("binary has no parent dir")?;
+        Some(DirDependencySource::new(dir.into()))
+    };
+    let source = Msys2DependencySource::new(environment_path);
+
+    let system_dlls = SystemDllRules {
+        system: options.system_dll,
+        bundle: options.bundle_dll,
+    };
+    let tree = build_import_tree_with_options(
+        bundle.as_ref(),
+        &source,
+        &path,
+        &system_dlls.to_resolve_options(),
+    )?;
+    if options.output_format == OutputFormat::Text {
+        if let Some(explanation) = explain_packaged_file(&path)? {
+            print!("{explanation}");
+        }
+    }
+    tree.print(options.output_format)?;
+
+    Ok(())
+}
+
+/// Explain why a binary is packaged, if it is in a package dir with a manifest, see [`crate::get_package_manifest_path`].
+fn explain_packaged_file(path: &Path) -> anyhow::Result<Option<String>> {
+    for package_dir in path.ancestors().skip(1) {
+        let manifest_path = match crate::get_package_manifest_path(package_dir) {
+            Ok(manifest_path) => manifest_path,
+            Err(_) => continue,
+        };
+        if !manifest_path.is_file() {
+            continue;
+        }
+
+        let plan = Plan::load(manifest_path.as_std_path())?;
+        let dest = path
+            .strip_prefix(package_dir)
+            .context("binary is not in the package dir")?;
+        let explanation = plan.explain(dest);
+        if explanation.files.is_empty() {
+            return Ok(None);
+        }
+
+        return Ok(Some(explanation.to_string()));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,330 @@
+use crate::installer::innosetup;
+use crate::installer::innosetup::TargetPackage;
+use crate::installer::script;
+use crate::installer::InstallerFormat;
+use crate::installer::InstallerMetadata;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use msys2_packager::sign::get_sign_options;
+use msys2_packager::sign::signtool_sign;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "installer",
+    description = "Generate an installer for a packaged binary, or print the installer settings of a package, including those read from cargo-wix and cargo-bundle metadata"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the installer to generate from the package made by `package`, `innosetup` or `script`. If not set, the installer settings are printed instead"
+    )]
+    pub format: Option<InstallerFormat>,
+
+    #[argh(
+        option,
+        description = "the target triple. Used by `--format`, and defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var. May be passed multiple times to make one installer that installs the package of the best target the machine can run"
+    )]
+    pub target: Vec<String>,
+
+    #[argh(
+        option,
+        description = "the build profile",
+        default = "String::from(\"dev\")"
+    )]
+    pub profile: String,
+
+    #[argh(
+        option,
+        short = 'p',
+        long = "package",
+        description = "the workspace package to use, in place of the root package"
+    )]
+    pub package: Option<String>,
+
+    #[argh(
+        option,
+        long = "bin",
+        description = "the binary name. May be passed multiple times if the package contains several binaries, where the first is the main binary"
+    )]
+    pub bin: Vec<String>,
+
+    #[argh(
+        switch,
+        description = "use the package of every binary in the selected package, or the root package"
+    )]
+    pub bins: bool,
+
+    #[argh(
+        option,
+        description = "where to write the installer script, or the dir to write the files of `script` to. Defaults to next to the package dir"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "the url of the zip archives for `script`, where `{{name}}`, `{{version}}` and `{{target}}` are replaced. Defaults to the assets of the `v{{version}}` release, if the repository of the package is on GitHub"
+    )]
+    pub download_url: Option<String>,
+
+    #[argh(
+        switch,
+        description = "also write `binstall.toml`, with cargo-binstall metadata for the archives of `script`. cargo-binstall only installs the exes of the archives, not the packaged dlls and data files"
+    )]
+    pub binstall: bool,
+
+    #[argh(
+        switch,
+        description = "compile the installer script with the installer tool, like ISCC for `innosetup`"
+    )]
+    pub compile: bool,
+
+    #[argh(
+        option,
+        description = "sign the installer compiled by `--compile` with signtool using this .pfx certificate. The password is read from the SIGN_CERT_PASSWORD env var, or the file at the SIGN_CERT_PASSWORD_FILE env var, if set"
+    )]
+    pub sign_cert: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "the RFC 3161 timestamp server to use when signing"
+    )]
+    pub sign_timestamp_url: Option<String>,
+
+    #[argh(
+        option,
+        description = "the Windows AppUserModelID of the app, overriding the one in the Cargo.toml"
+    )]
+    pub app_user_model_id: Option<String>,
+}
+
+/// Exec the `installer` subcommand.
+pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<()> {
+    let format = match options.format {
+        Some(format) => format,
+        None => {
+            ensure!(!options.compile, "`--compile` requires `--format`");
+            ensure!(
+                options.sign_cert.is_none() && options.sign_timestamp_url.is_none(),
+                "`--sign-cert` and `--sign-timestamp-url` require `--compile`"
+            );
+            ensure!(
+                options.download_url.is_none(),
+                "`--download-url` requires `--format script`"
+            );
+            ensure!(!options.binstall, "`--binstall` requires `--format script`");
+
+            let package = match options.package.as_deref() {
+                Some(package) => crate::util::get_package(&ctx.cargo_metadata, package)?,
+                None => ctx
+                    .cargo_metadata
+                    .root_package()
+                    .context("there is no root package, select one with `--package`")?,
+            };
+            let mut metadata = read_metadata(package)?;
+            override_app_user_model_id(&mut metadata, options.app_user_model_id)?;
+            println!("{metadata}");
+
+            return Ok(());
+        }
+    };
+    ensure!(
+        format == InstallerFormat::Script || options.download_url.is_none(),
+        "`--download-url` requires `--format script`"
+    );
+    ensure!(
+        format == InstallerFormat::Script || !options.binstall,
+        "`--binstall` requires `--format script`"
+    );
+    ensure!(
+        format != InstallerFormat::Script || !options.compile,
+        "`--compile` does not apply to `--format script`"
+    );
+    ensure!(
+        options.compile || options.sign_cert.is_none(),
+        "`--sign-cert` requires `--compile`"
+    );
+    let sign = get_sign_options(options.sign_cert, options.sign_timestamp_url)?;
+    let mut targets = options.target;
+    if targets.is_empty() {
+        targets.push(crate::util::resolve_target(None)?);
+    }
+
+    let (kind, bins) = crate::util::get_cargo_targets(
+        &ctx.cargo_metadata,
+        options.package.as_deref(),
+        options.bin,
+        options.bins,
+        None,
+        None,
+        None,
+    )?;
+    let mut package_dirs = Vec::with_capacity(targets.len());
+    let mut bin_name = String::new();
+    for target in targets.iter() {
+        ctx.set_build_data(
+            target.as_str(),
+            options.profile.as_str(),
+            options.package.as_deref(),
+            kind,
+            bins.clone(),
+        )?;
+
+        let bin_path = ctx.get_packaged_bin_path()?;
+        ensure!(
+            bin_path.exists(),
+            "`{bin_path}` does not exist, run the `package` subcommand for `{target}` first"
+        );
+        bin_name = bin_path
+            .file_name()
+            .context("missing bin file name")?
+            .to_string();
+        package_dirs.push(ctx.get_package_out_dir()?);
+    }
+    let out_dir = match package_dirs.as_slice() {
+        [package_dir] => package_dir
+            .parent()
+            .context("package dir has no parent")?
+            .to_path_buf(),
+        // An installer for several targets does not belong next to any one package dir.
+        _ => {
+            let out_dir = ctx
+                .cargo_metadata
+                .target_directory
+                .join(env!("CARGO_CRATE_NAME"))
+                .join("installer")
+                .join(&options.profile);
+            std::fs::create_dir_all(&out_dir)
+                .with_context(|| format!("failed to create `{out_dir}`"))?;
+            out_dir
+        }
+    };
+    let packages: Vec<_> = package_dirs
+        .iter()
+        .zip(targets.iter())
+        .map(|(package_dir, target)| TargetPackage {
+            package_dir: package_dir.as_std_path(),
+            target,
+        })
+        .collect();
+    let bin_package = ctx.get_bin_package()?;
+    let mut metadata = read_metadata(bin_package)?;
+    override_app_user_model_id(&mut metadata, options.app_user_model_id)?;
+
+    match format {
+        InstallerFormat::InnoSetup => {
+            let script =
+                innosetup::generate_script(&metadata, &packages, &bin_name, out_dir.as_std_path())?;
+            let script_path = options
+                .output
+                .unwrap_or_else(|| out_dir.join(format!("{}.iss", metadata.name)).into());
+            std::fs::write(&script_path, script)
+                .with_context(|| format!("failed to write `{}`", script_path.display()))?;
+            println!("Wrote `{}`", script_path.display());
+
+            if options.compile {
+                let iscc = innosetup::locate_iscc().with_context(|| {
+                    format!(
+                        "failed to locate `{}`, install Inno Setup or add it to the PATH",
+                        innosetup::ISCC_FILE_NAME
+                    )
+                })?;
+                let status = Command::new(&iscc)
+                    .arg(&script_path)
+                    .status()
+                    .with_context(|| format!("failed to run `{}`", iscc.display()))?;
+                if !status.success() {
+                    bail!("`{}` exited with `{status}`", iscc.display());
+                }
+
+                if let Some(sign) = sign.as_ref() {
+                    let installer_path =
+                        out_dir.join(innosetup::get_installer_file_name(&metadata));
+                    signtool_sign(&[installer_path.as_std_path()], sign)
+                        .with_context(|| format!("failed to sign `{installer_path}`"))?;
+                    println!("Signed `{installer_path}`");
+                }
+            }
+        }
+        InstallerFormat::Script => {
+            let url = match options.download_url {
+                Some(url) => url,
+                None => bin_package
+                    .repository
+                    .as_deref()
+                    .and_then(script::get_default_url)
+                    .context(
+                        "the repository of the package is not on GitHub, pass the url of the zip archives with `--download-url`",
+                    )?,
+            };
+            let script_dir = options.output.unwrap_or_else(|| out_dir.clone().into());
+            std::fs::create_dir_all(&script_dir)
+                .with_context(|| format!("failed to create `{}`", script_dir.display()))?;
+
+            let mut archive_sha256s = Vec::with_capacity(packages.len());
+            for package in packages.iter() {
+                let archive_path = script_dir.join(script::get_archive_file_name(
+                    &bin_package.name,
+                    &metadata.version,
+                    package.target,
+                ));
+                script::write_archive(package.package_dir, &archive_path)
+                    .with_context(|| format!("failed to write `{}`", archive_path.display()))?;
+                archive_sha256s.push(msys2_packager::sbom::sha256_file(&archive_path)?);
+                println!("Wrote `{}`", archive_path.display());
+            }
+
+            let install_script = script::generate_install_script(
+                &metadata,
+                &bin_package.name,
+                &packages,
+                &bin_name,
+                &archive_sha256s,
+                &url,
+            )?;
+            let install_script_path = script_dir.join(script::INSTALL_SCRIPT_FILE_NAME);
+            std::fs::write(&install_script_path, install_script)
+                .with_context(|| format!("failed to write `{}`", install_script_path.display()))?;
+            println!("Wrote `{}`", install_script_path.display());
+
+            if options.binstall {
+                let binstall_path = script_dir.join(script::BINSTALL_FILE_NAME);
+                std::fs::write(&binstall_path, script::generate_binstall_metadata(&url))
+                    .with_context(|| format!("failed to write `{}`", binstall_path.display()))?;
+                println!("Wrote `{}`", binstall_path.display());
+                eprintln!(
+                    "warning: cargo-binstall only installs the exes of the archives, so `cargo binstall` installs do not get the packaged dlls and data files"
+                );
+            }
+            println!("Upload the archives to the download url, as the install script only installs archives with these SHA-256s");
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the installer settings of a package.
+fn read_metadata(package: &cargo_metadata::Package) -> anyhow::Result<InstallerMetadata> {
+    InstallerMetadata::from_package(package).with_context(|| {
+        format!(
+            "failed to read installer settings from `{}`",
+            package.manifest_path
+        )
+    })
+}
+
+/// Override the AppUserModelID of installer settings, if one was passed.
+fn override_app_user_model_id(
+    metadata: &mut InstallerMetadata,
+    app_user_model_id: Option<String>,
+) -> anyhow::Result<()> {
+    if let Some(app_user_model_id) = app_user_model_id {
+        crate::installer::validate_app_user_model_id(&app_user_model_id)?;
+        metadata.app_user_model_id = Some(app_user_model_id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anyhow::bail;
+use msys2_packager::knowledge::KnowledgeBase;
+use msys2_packager::os_version::analyze_package_os_version;
+use msys2_packager::os_version::WindowsVersion;
+use msys2_packager::report::OutputFormat;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "min-os",
+    description = "Estimate the minimum Windows version of every exe and dll in a package dir, from the functions they import"
+)]
+pub struct Options {
+    #[argh(positional, description = "the package dir to analyze")]
+    pub path: PathBuf,
+
+    #[argh(
+        option,
+        description = "fail if any binary needs a Windows version newer than this, like `10.0.17763` for Windows 10 LTSC 2019"
+    )]
+    pub max_version: Option<WindowsVersion>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `min-os` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let report = analyze_package_os_version(&options.path, KnowledgeBase::global())?;
+    report.print(options.output_format)?;
+
+    if let Some(max_version) = options.max_version {
+        let num_newer = report.get_binaries_newer_than(max_version).count();
+        if num_newer != 0 {
+            bail!(
+                "`{}` has {num_newer} binaries that need a Windows version newer than {max_version}",
+                options.path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use crate::asset::Asset;
+use crate::package_info::GitStatus;
+use crate::resource::EmbedResources;
+use crate::system_dll::SystemDllRules;
+use crate::theme::Theme;
+use crate::util::CargoFeatures;
+use crate::util::RunCommand;
+use crate::PackageOptions;
+use anyhow::Context;
+use msys2_packager::packager::CrtCheck;
+use msys2_packager::util::UpxOptions;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "run",
+    description = "Build and package the GTK-rs application, then run the packaged binary"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(
+        option,
+        description = "the build profile",
+        default = "String::from(\"dev\")"
+    )]
+    pub profile: String,
+
+    #[argh(option, long = "features", description = "cargo features to enable")]
+    pub features: Vec<String>,
+
+    #[argh(switch, description = "disable the default cargo features")]
+    pub no_default_features: bool,
+
+    #[argh(switch, description = "enable every cargo feature")]
+    pub all_features: bool,
+
+    #[argh(
+        switch,
+        description = "install the rust target with rustup if it is missing, instead of failing"
+    )]
+    pub auto_install_target: bool,
+
+    #[argh(
+        switch,
+        description = "do not check that the MSYS2 packages needed to build, like gtk4 and the C toolchain, are installed"
+    )]
+    pub skip_preflight: bool,
+
+    #[argh(
+        switch,
+        description = "install the MSYS2 packages needed to build that are missing with `pacman -S --needed --noconfirm`, for CI machines that start from a bare MSYS2. The packages include the C toolchain, GTK, GStreamer, and the libraries of crates like libadwaita"
+    )]
+    pub auto_install_msys2_deps: bool,
+
+    #[argh(
+        option,
+        short = 'p',
+        long = "package",
+        description = "the workspace package that contains the binary"
+    )]
+    pub package: Option<String>,
+
+    #[argh(
+        option,
+        long = "bin",
+        description = "the binary name. Defaults to the only bin of the package"
+    )]
+    pub bin: Option<String>,
+
+    #[argh(
+        option,
+        long = "example",
+        description = "the name of an example target to run, in place of `--bin`"
+    )]
+    pub example: Option<String>,
+
+    #[argh(
+        option,
+        long = "extra-library",
+        description = "the name of an extra library to package"
+    )]
+    pub extra_libraries: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names, like `libicudata*.dll`, to never add automatically, even if packaged binaries import them"
+    )]
+    pub exclude: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, which are never packaged"
+    )]
+    pub system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to package from MSYS2 when imported, even if they are considered system dlls"
+    )]
+    pub bundle_dll: Vec<String>,
+
+    #[argh(
+        option,
+        short = 't',
+        long = "theme",
+        description = "the path to a theme to package, or `pkg:<name>` for a theme installed by an MSYS2 package"
+    )]
+    pub themes: Vec<Theme>,
+
+    #[argh(
+        option,
+        long = "icon-theme",
+        description = "the path to an icon theme to package"
+    )]
+    pub icon_themes: Vec<PathBuf>,
+
+    #[argh(
+        option,
+        long = "asset",
+        description = "a file, dir, or glob to copy verbatim into the package, as `<src>[:<dest>]`"
+    )]
+    pub assets: Vec<Asset>,
+
+    #[argh(
+        switch,
+        description = "copy the targets of symlinks in theme dirs, instead of erroring"
+    )]
+    pub follow_theme_symlinks: bool,
+
+    #[argh(
+        option,
+        short = 'j',
+        default = "1",
+        description = "the number of threads to use when copying files"
+    )]
+    pub jobs: usize,
+
+    #[argh(
+        switch,
+        description = "remove the old package dir instead of updating it incrementally"
+    )]
+    pub clean: bool,
+
+    #[argh(
+        switch,
+        description = "fail if the packaged MSYS2 binaries would differ from the lockfile of the target, like `gtk-packager.x86_64-pc-windows-gnu.lock`"
+    )]
+    pub locked: bool,
+
+    #[argh(
+        option,
+        description = "set `GTK_DEBUG` for the packaged binary, like `interactive`"
+    )]
+    pub gtk_debug: Option<String>,
+
+    #[argh(
+        option,
+        description = "set `G_MESSAGES_DEBUG` for the packaged binary, like `all`"
+    )]
+    pub glib_debug: Option<String>,
+
+    #[argh(
+        positional,
+        description = "args to pass to the packaged binary, after `--`"
+    )]
+    pub args: Vec<String>,
+}
+
+/// Exec the `run` subcommand.
+///
+/// # Returns
+/// Returns the exit code of the packaged binary.
+pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<i32> {
+    let target = crate::util::resolve_target(options.target)?;
+    ctx.cargo_features = CargoFeatures {
+        features: options.features,
+        no_default_features: options.no_default_features,
+        all_features: options.all_features,
+    };
+    ctx.auto_install_target = options.auto_install_target;
+    ctx.skip_preflight = options.skip_preflight;
+    ctx.auto_install_msys2_deps = options.auto_install_msys2_deps;
+
+    // Capture this before anything is written, like the lockfile, so the package info records the checkout as it was.
+    let git_status = GitStatus::get(&ctx.cargo_metadata.workspace_root)?;
+
+    let (kind, bins) = crate::util::get_cargo_targets(
+        &ctx.cargo_metadata,
+        options.package.as_deref(),
+        options.bin.into_iter().collect(),
+        false,
+        None,
+        None,
+        options.example,
+    )?;
+    ctx.set_build_data(
+        target.as_str(),
+        options.profile.as_str(),
+        options.package.as_deref(),
+        kind,
+        bins,
+    )?;
+    ctx.run_cargo_build(None, Vec::new(), EmbedResources::default())?;
+
+    let package_options = PackageOptions {
+        upx: false,
+        upx_options: UpxOptions::new(),
+        no_upx: &[],
+        strip: false,
+        split_debug: false,
+        symbol_server: None,
+        jobs: options.jobs,
+        clean: options.clean,
+        overwrite: None,
+        report: false,
+        licenses: false,
+        sbom: None,
+        sign: None,
+        extra_libraries: &options.extra_libraries,
+        excludes: &options.exclude,
+        system_dlls: &SystemDllRules {
+            system: options.system_dll.clone(),
+            bundle: options.bundle_dll.clone(),
+        },
+        themes: &options.themes,
+        icon_themes: &options.icon_themes,
+        adwaita: false,
+        follow_theme_symlinks: options.follow_theme_symlinks,
+        gio_modules: false,
+        immodules: false,
+        print_backends: false,
+        gtksourceview: false,
+        spell_check: false,
+        spell_languages: &[],
+        typelibs: &[],
+        glib_spawn_helpers: true,
+        assets: &options.assets,
+        crt_check: CrtCheck::Warn,
+        toolchain_runtime: true,
+        size_report: false,
+        gresource_dirs: &[],
+        emit_graph: None,
+        locked: options.locked,
+        update_lock_file: false,
+        verify_copies: false,
+        dll_store: None,
+        deterministic: false,
+        policy: None,
+        launcher: None,
+        with_notifications: false,
+        manifest: None,
+        auditable: false,
+        gettext_domain: None,
+        po_dir: None,
+        git_status: git_status.as_ref(),
+    };
+    ctx.package(&package_options)?;
+
+    // Only let the packaged binary see packaged dlls, like it would on a user's machine.
+    let mut run = RunCommand::new(ctx.get_packaged_bin_path()?.into());
+    run.restrict_path(ctx.get_package_out_dir()?.into());
+    for arg in options.args {
+        run.arg(arg.into());
+    }
+    if let Some(gtk_debug) = options.gtk_debug {
+        run.env("GTK_DEBUG".into(), gtk_debug.into());
+    }
+    if let Some(glib_debug) = options.glib_debug {
+        run.env("G_MESSAGES_DEBUG".into(), glib_debug.into());
+    }
+
+    let status = run.status().context("failed to run")?;
+
+    // A process killed by a signal has no exit code, so report a generic failure instead.
+    Ok(status.code().unwrap_or(1))
+}
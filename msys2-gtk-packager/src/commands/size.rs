@@ -0,0 +1,195 @@
+use crate::asset::Asset;
+use crate::system_dll::SystemDllRules;
+use crate::theme::Theme;
+use crate::PackageOptions;
+use anyhow::Context;
+use msys2_packager::packager::CrtCheck;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::util::UpxOptions;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "size",
+    description = "Print the size of each file and category of files in the package, before and after upx. Packaged sizes are read from the last `package` run"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(
+        option,
+        description = "the build profile",
+        default = "String::from(\"dev\")"
+    )]
+    pub profile: String,
+
+    #[argh(
+        option,
+        short = 'p',
+        long = "package",
+        description = "the workspace package that contains the binary"
+    )]
+    pub package: Option<String>,
+
+    #[argh(option, long = "bin", description = "the binary name")]
+    pub bin: Option<String>,
+
+    #[argh(
+        option,
+        long = "example",
+        description = "the name of an example target to measure, in place of `--bin`"
+    )]
+    pub example: Option<String>,
+
+    #[argh(
+        option,
+        long = "extra-library",
+        description = "the name of an extra library to package"
+    )]
+    pub extra_libraries: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names, like `libicudata*.dll`, to never add automatically, even if packaged binaries import them"
+    )]
+    pub exclude: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, which are never packaged"
+    )]
+    pub system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to package from MSYS2 when imported, even if they are considered system dlls"
+    )]
+    pub bundle_dll: Vec<String>,
+
+    #[argh(
+        option,
+        short = 't',
+        long = "theme",
+        description = "the path to a theme to package, or `pkg:<name>` for a theme installed by an MSYS2 package"
+    )]
+    pub themes: Vec<Theme>,
+
+    #[argh(
+        option,
+        long = "icon-theme",
+        description = "the path to an icon theme to package"
+    )]
+    pub icon_themes: Vec<PathBuf>,
+
+    #[argh(
+        option,
+        long = "asset",
+        description = "a file, dir, or glob to copy verbatim into the package, as `<src>[:<dest>]`"
+    )]
+    pub assets: Vec<Asset>,
+
+    #[argh(
+        switch,
+        description = "copy the targets of symlinks in theme dirs, instead of erroring"
+    )]
+    pub follow_theme_symlinks: bool,
+
+    #[argh(
+        switch,
+        description = "do not always package the support dlls of the toolchain, like `libgcc_s_seh-1.dll`"
+    )]
+    pub no_toolchain_runtime: bool,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `size` subcommand.
+pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<()> {
+    let target = crate::util::resolve_target(options.target)?;
+    let (kind, bins) = crate::util::get_cargo_targets(
+        &ctx.cargo_metadata,
+        options.package.as_deref(),
+        options.bin.into_iter().collect(),
+        false,
+        None,
+        None,
+        options.example,
+    )?;
+    ctx.set_build_data(
+        target.as_str(),
+        options.profile.as_str(),
+        options.package.as_deref(),
+        kind,
+        bins,
+    )?;
+
+    let package_options = PackageOptions {
+        upx: false,
+        upx_options: UpxOptions::new(),
+        no_upx: &[],
+        strip: false,
+        split_debug: false,
+        symbol_server: None,
+        jobs: 1,
+        clean: false,
+        overwrite: None,
+        report: false,
+        licenses: false,
+        sbom: None,
+        sign: None,
+        extra_libraries: &options.extra_libraries,
+        excludes: &options.exclude,
+        system_dlls: &SystemDllRules {
+            system: options.system_dll.clone(),
+            bundle: options.bundle_dll.clone(),
+        },
+        themes: &options.themes,
+        icon_themes: &options.icon_themes,
+        adwaita: false,
+        follow_theme_symlinks: options.follow_theme_symlinks,
+        gio_modules: false,
+        immodules: false,
+        print_backends: false,
+        gtksourceview: false,
+        spell_check: false,
+        spell_languages: &[],
+        typelibs: &[],
+        glib_spawn_helpers: true,
+        assets: &options.assets,
+        crt_check: CrtCheck::Off,
+        toolchain_runtime: !options.no_toolchain_runtime,
+        size_report: true,
+        gresource_dirs: &[],
+        emit_graph: None,
+        locked: false,
+        update_lock_file: false,
+        verify_copies: false,
+        dll_store: None,
+        deterministic: false,
+        policy: None,
+        launcher: None,
+        with_notifications: false,
+        manifest: None,
+        auditable: false,
+        gettext_domain: None,
+        po_dir: None,
+        git_status: None,
+    };
+    let report = ctx.dry_run(&package_options)?;
+    report
+        .sizes
+        .context("missing size report")?
+        .print(options.output_format)?;
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::util::CargoFeatures;
+use crate::util::RunCommand;
+use anyhow::bail;
+use msys2_packager::report::OutputFormat;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "test",
+    description = "Build the test binaries, bundle them with the dlls they need, and run them"
+)]
+pub struct Options {
+    #[argh(
+        option,
+        description = "the target triple. Defaults to the one of the active MSYS2 environment, from the `MSYSTEM` env var"
+    )]
+    pub target: Option<String>,
+
+    #[argh(
+        option,
+        description = "the build profile",
+        default = "String::from(\"dev\")"
+    )]
+    pub profile: String,
+
+    #[argh(option, long = "features", description = "cargo features to enable")]
+    pub features: Vec<String>,
+
+    #[argh(switch, description = "disable the default cargo features")]
+    pub no_default_features: bool,
+
+    #[argh(switch, description = "enable every cargo feature")]
+    pub all_features: bool,
+
+    #[argh(
+        switch,
+        description = "install the rust target with rustup if it is missing, instead of failing"
+    )]
+    pub auto_install_target: bool,
+
+    #[argh(
+        switch,
+        description = "do not check that the MSYS2 packages needed to build, like gtk4 and the C toolchain, are installed"
+    )]
+    pub skip_preflight: bool,
+
+    #[argh(
+        switch,
+        description = "install the MSYS2 packages needed to build that are missing with `pacman -S --needed --noconfirm`, for CI machines that start from a bare MSYS2. The packages include the C toolchain, GTK, GStreamer, and the libraries of crates like libadwaita"
+    )]
+    pub auto_install_msys2_deps: bool,
+
+    #[argh(
+        option,
+        long = "extra-library",
+        description = "the name of an extra library to bundle"
+    )]
+    pub extra_libraries: Vec<String>,
+
+    #[argh(
+        option,
+        short = 'j',
+        default = "1",
+        description = "the number of threads to use when copying files"
+    )]
+    pub jobs: usize,
+
+    #[argh(switch, description = "bundle the test binaries, but do not run them")]
+    pub no_run: bool,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the bundling results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+
+    #[argh(
+        positional,
+        description = "args to pass to each test binary, after `--`"
+    )]
+    pub args: Vec<String>,
+}
+
+/// Exec the `test` subcommand.
+pub fn exec(mut ctx: crate::Context, options: Options) -> anyhow::Result<()> {
+    let target = &crate::util::resolve_target(options.target)?;
+    let profile = options.profile.as_str();
+
+    ctx.cargo_features = CargoFeatures {
+        features: options.features,
+        no_default_features: options.no_default_features,
+        all_features: options.all_features,
+    };
+    ctx.auto_install_target = options.auto_install_target;
+    ctx.skip_preflight = options.skip_preflight;
+    ctx.auto_install_msys2_deps = options.auto_install_msys2_deps;
+
+    let test_bins = ctx.build_tests(target, profile)?;
+    let bundle_dir = ctx.get_test_bundle_dir(target, profile)?;
+    ctx.bundle_tests(
+        &test_bins,
+        &bundle_dir,
+        &options.extra_libraries,
+        options.jobs,
+    )?
+    .print(options.output_format)?;
+
+    if options.no_run {
+        return Ok(());
+    }
+
+    // Run every test binary from the bundle, with only the bundle on the PATH,
+    // like it would be on a machine without MSYS2.
+    let mut failed = Vec::new();
+    for test_bin in test_bins.iter() {
+        let name = test_bin.file_name().unwrap_or(test_bin.as_str());
+        let mut run = RunCommand::new(bundle_dir.join(name).into());
+        run.restrict_path(bundle_dir.clone().into());
+        for arg in options.args.iter() {
+            run.arg(arg.into());
+        }
+
+        eprintln!("Running `{name}`");
+        if let Err(error) = run.exec() {
+            eprintln!("{error:#}");
+            failed.push(name);
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "{} test binaries failed: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+
+    Ok(())
+}
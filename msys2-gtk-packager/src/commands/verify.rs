@@ -0,0 +1,56 @@
+use crate::system_dll::SystemDllRules;
+use anyhow::bail;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::verify::verify_package_with_options;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "verify",
+    description = "Check that every import of every exe and dll in a package dir is satisfied"
+)]
+pub struct Options {
+    #[argh(positional, description = "the package dir to verify")]
+    pub path: PathBuf,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, as passed to `package`. May be passed multiple times"
+    )]
+    pub system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names that are packaged even if they are considered system dlls, as passed to `package`. May be passed multiple times"
+    )]
+    pub bundle_dll: Vec<String>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// Exec the `verify` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    let system_dlls = SystemDllRules {
+        system: options.system_dll,
+        bundle: options.bundle_dll,
+    };
+    let report =
+        verify_package_with_options(&options.path, None, &system_dlls.to_resolve_options())?;
+    report.print(options.output_format)?;
+
+    if !report.is_ok() {
+        bail!(
+            "`{}` has {} unresolved imports",
+            options.path.display(),
+            report.unresolved.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// The env var that enables crash reports, in addition to `--crash-report`.
+pub const CRASH_REPORT_ENV: &str = "MSYS2_GTK_PACKAGER_CRASH_REPORT";
+
+/// Env vars that are included in crash reports.
+///
+/// Everything else is left out, as it may contain secrets.
+const REPORTED_ENV_VARS: &[&str] = &[
+    "MSYSTEM",
+    "MSYSTEM_PREFIX",
+    "CARGO_BUILD_TARGET",
+    "RUSTUP_TOOLCHAIN",
+    "PROCESSOR_ARCHITECTURE",
+];
+
+/// Words that mark an arg as holding a secret.
+const SECRET_WORDS: &[&str] = &["password", "secret", "token", "key"];
+
+/// Check if crash reports were enabled with the env var.
+pub fn is_enabled_by_env() -> bool {
+    std::env::var_os(CRASH_REPORT_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Install a panic hook that writes a crash report to a file, and prints its path.
+///
+/// Reports are written to `dir`, or the temp dir if it is None.
+/// The default hook still runs, so the panic message is printed as usual.
+pub fn install_hook(dir: Option<PathBuf>) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let dir = dir.clone().unwrap_or_else(std::env::temp_dir);
+        let _ = std::fs::create_dir_all(&dir);
+        let path = get_report_path(&dir);
+        match std::fs::write(&path, create_report(info)) {
+            Ok(()) => {
+                eprintln!("A crash report was written to `{}`", path.display());
+                eprintln!(
+                    "Please attach it to a bug report, after checking it for anything private."
+                );
+            }
+            Err(error) => {
+                eprintln!(
+                    "failed to write crash report to `{}`: {error}",
+                    path.display()
+                );
+            }
+        }
+    }));
+}
+
+/// Get the path to write a new crash report to, in the given dir.
+fn get_report_path(dir: &Path) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    dir.join(format!(
+        "{}-crash-{secs}-{}.txt",
+        env!("CARGO_PKG_NAME"),
+        std::process::id()
+    ))
+}
+
+/// Make the text of a crash report.
+fn create_report(info: &PanicHookInfo<'_>) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "# Crash report");
+    let _ = writeln!(report);
+    let _ = writeln!(report, "{}", crate::get_version_info());
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "## Panic");
+    let _ = writeln!(report, "{info}");
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "## Args");
+    for arg in scrub_args(std::env::args().skip(1)) {
+        let _ = writeln!(report, "{arg}");
+    }
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "## Environment");
+    let _ = writeln!(
+        report,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    if let Ok(current_dir) = std::env::current_dir() {
+        let _ = writeln!(report, "current dir: {}", current_dir.display());
+    }
+    for name in REPORTED_ENV_VARS {
+        if let Some(value) = std::env::var_os(name) {
+            let _ = writeln!(report, "{name}: {}", value.to_string_lossy());
+        }
+    }
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "## Backtrace");
+    let _ = writeln!(report, "{}", Backtrace::force_capture());
+
+    report
+}
+
+/// Replace the values of args that look like they hold secrets.
+///
+/// This covers `--name value`, `--name=value`, and `name=value`, where the name contains a word like `password`.
+fn scrub_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let is_secret = |name: &str| {
+        let name = name.to_ascii_lowercase();
+        SECRET_WORDS.iter().any(|word| name.contains(word))
+    };
+
+    let mut scrubbed = Vec::new();
+    let mut scrub_next = false;
+    for arg in args {
+        if scrub_next {
+            scrubbed.push("<redacted>".into());
+            scrub_next = false;
+            continue;
+        }
+
+        match arg.split_once('=') {
+            Some((name, _)) if is_secret(name) => {
+                scrubbed.push(format!("{name}=<redacted>"));
+            }
+            Some(_) => scrubbed.push(arg),
+            None => {
+                scrub_next = arg.starts_with('-') && is_secret(&arg);
+                scrubbed.push(arg);
+            }
+        }
+    }
+
+    scrubbed
+}
@@ -0,0 +1,201 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::extract::get_package_root;
+use msys2_packager::pacman::InstalledPackage;
+use msys2_packager::pacman::LocalDatabase;
+use msys2_packager::pkg_config::get_environment_prefixes;
+use msys2_packager::pkg_config::is_pkg_config_file;
+use msys2_packager::pkg_config::relocate_pkg_config;
+use msys2_packager::pkg_config::PKG_CONFIG_DIRS;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Dirs, relative to a package root, that are not needed to build against the runtime.
+const SKIPPED_DIRS: &[&str] = &[
+    "share/doc",
+    "share/gtk-doc",
+    "share/info",
+    "share/locale",
+    "share/man",
+];
+
+/// The dir of headers, relative to a package root.
+const INCLUDE_DIR: &str = "include";
+
+/// The file name of the batch script that sets up a shell to use the runtime.
+pub const ENV_BAT_FILE_NAME: &str = "env.bat";
+
+/// The file name of the PowerShell script that sets up a shell to use the runtime.
+pub const ENV_PS1_FILE_NAME: &str = "env.ps1";
+
+/// The number of packages and files in an exported runtime
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    /// The number of packages exported
+    pub num_packages: usize,
+
+    /// The number of files copied
+    pub num_files: usize,
+
+    /// The number of pkg-config files rewritten to be relocatable
+    pub num_pkg_config_files: usize,
+
+    /// The total size of the copied files, in bytes
+    pub size: u64,
+}
+
+/// Get an installed package, and every package it depends on.
+///
+/// Dependencies that are not installed, like ones that are only provided by another package, are skipped.
+pub fn get_package_closure<'a>(
+    database: &'a LocalDatabase,
+    names: &[String],
+) -> anyhow::Result<Vec<&'a InstalledPackage>> {
+    let mut stack = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let package = database
+            .get(name)
+            .with_context(|| format!("`{name}` is not installed"))?;
+        stack.push(package);
+    }
+
+    let mut visited = HashSet::new();
+    let mut packages = Vec::new();
+    while let Some(package) = stack.pop() {
+        if !visited.insert(package.name.as_str()) {
+            continue;
+        }
+
+        stack.extend(
+            package
+                .depends
+                .iter()
+                .filter_map(|depend| database.get(depend)),
+        );
+        packages.push(package);
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(packages)
+}
+
+/// Check if a file, relative to a package root, belongs in an exported runtime.
+///
+/// Docs, man pages, and translations are skipped, along with static libraries, which are much larger than import libraries.
+/// Headers are only kept if `headers` is true.
+pub fn is_exported_file(path: &Utf8Path, headers: bool) -> bool {
+    if path.starts_with(INCLUDE_DIR) {
+        return headers;
+    }
+    if SKIPPED_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return false;
+    }
+
+    let file_name = path.file_name().unwrap_or_default();
+    let is_static_lib =
+        path.starts_with("lib") && file_name.ends_with(".a") && !file_name.ends_with(".dll.a");
+
+    !is_static_lib
+}
+
+/// Generate a batch script that adds the runtime to the PATH and pkg-config search path of the current shell.
+///
+/// The script finds the runtime from its own location, so the runtime can be moved.
+pub fn generate_env_bat() -> anyhow::Result<String> {
+    let mut bat = String::new();
+    writeln!(bat, "@echo off")?;
+    // `%~dp0` is the dir of the script, with a trailing backslash.
+    writeln!(bat, "set \"MSYS2_RUNTIME_DIR=%~dp0\"")?;
+    writeln!(bat, "set \"PATH=%MSYS2_RUNTIME_DIR%bin;%PATH%\"")?;
+    let pkg_config_path = PKG_CONFIG_DIRS
+        .iter()
+        .map(|dir| format!("%MSYS2_RUNTIME_DIR%{}", dir.replace('/', "\\")))
+        .collect::<Vec<_>>()
+        .join(";");
+    writeln!(bat, "set \"PKG_CONFIG_PATH={pkg_config_path}\"")?;
+    writeln!(
+        bat,
+        "set \"PKG_CONFIG=%MSYS2_RUNTIME_DIR%bin\\pkgconf.exe\""
+    )?;
+
+    // Batch scripts need CRLF line endings.
+    Ok(bat.replace('\n', "\r\n"))
+}
+
+/// Generate a PowerShell script that adds the runtime to the PATH and pkg-config search path of the current shell.
+///
+/// The script must be dot-sourced, like `. .\env.ps1`, to change the current shell.
+pub fn generate_env_powershell() -> anyhow::Result<String> {
+    let mut ps1 = String::new();
+    writeln!(ps1, "$RuntimeDir = $PSScriptRoot")?;
+    writeln!(
+        ps1,
+        "$env:PATH = (Join-Path $RuntimeDir 'bin') + ';' + $env:PATH"
+    )?;
+    let pkg_config_path = PKG_CONFIG_DIRS
+        .iter()
+        .map(|dir| format!("(Join-Path $RuntimeDir '{}')", dir.replace('/', "\\")))
+        .collect::<Vec<_>>()
+        .join(" + ';' + ");
+    writeln!(ps1, "$env:PKG_CONFIG_PATH = {pkg_config_path}")?;
+    writeln!(
+        ps1,
+        "$env:PKG_CONFIG = Join-Path $RuntimeDir 'bin\\pkgconf.exe'"
+    )?;
+
+    Ok(ps1.replace('\n', "\r\n"))
+}
+
+/// Copy the files of installed packages into a runtime dir, rewriting pkg-config files to be relocatable.
+///
+/// Files are copied from their package root, so `ucrt64/bin/libgtk-4-1.dll` ends up at `bin/libgtk-4-1.dll`.
+/// See [`is_exported_file`] for which files are copied.
+pub fn export_packages(
+    packages: &[&InstalledPackage],
+    msys2_installation_path: &Utf8Path,
+    out_dir: &Utf8Path,
+    headers: bool,
+) -> anyhow::Result<ExportSummary> {
+    let mut summary = ExportSummary::default();
+    for package in packages.iter() {
+        let root = get_package_root(&package.name);
+        let environment_prefixes = get_environment_prefixes(Some(msys2_installation_path), root);
+        let files = package
+            .load_files()
+            .with_context(|| format!("failed to load files for `{}`", package.name))?;
+        for file in files {
+            let relative_path = match file.strip_prefix(root) {
+                Ok(relative_path) if !root.is_empty() => relative_path,
+                _ => continue,
+            };
+            if !is_exported_file(relative_path, headers) {
+                continue;
+            }
+
+            let src = msys2_installation_path.join(&file);
+            let dest = out_dir.join(relative_path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create `{parent}`"))?;
+            }
+
+            if is_pkg_config_file(relative_path) {
+                let contents = std::fs::read_to_string(&src)
+                    .with_context(|| format!("failed to read `{src}`"))?;
+                let contents = relocate_pkg_config(&contents, &environment_prefixes);
+                std::fs::write(&dest, &contents)
+                    .with_context(|| format!("failed to write `{dest}`"))?;
+                summary.size += u64::try_from(contents.len())?;
+                summary.num_pkg_config_files += 1;
+            } else {
+                // Symlinks are copied as the files they point to.
+                summary.size += std::fs::copy(&src, &dest)
+                    .with_context(|| format!("failed to copy `{src}` to `{dest}`"))?;
+            }
+            summary.num_files += 1;
+        }
+        summary.num_packages += 1;
+    }
+
+    Ok(summary)
+}
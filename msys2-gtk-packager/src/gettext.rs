@@ -0,0 +1,474 @@
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The magic number at the start of a little-endian `.mo` file.
+const MO_MAGIC: u32 = 0x950412de;
+
+/// The separator between the context and the id of a message, in `.mo` keys.
+const CONTEXT_SEPARATOR: char = '\u{4}';
+
+/// The po dir used if none is configured, relative to the dir of the Cargo.toml.
+pub const DEFAULT_PO_DIR: &str = "po";
+
+/// The gettext section of this tool's section in `package.metadata` of a Cargo.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct GettextMetadata {
+    /// The gettext domain of the app
+    gettext_domain: Option<String>,
+
+    /// The dir with the `.po` files of the app
+    po_dir: Option<PathBuf>,
+}
+
+/// The gettext domain of the user's app, and where its translations are
+#[derive(Debug, Clone)]
+pub struct GettextDomain {
+    /// The domain name, which is the name of the `.mo` files, like `myapp`.
+    pub name: String,
+
+    /// The dir with one `<language>.po` file per language, like `po/de.po`.
+    pub po_dir: PathBuf,
+}
+
+impl GettextDomain {
+    /// Get the gettext domain listed in the `[package.metadata.msys2-gtk-packager]` section of a package's Cargo.toml.
+    ///
+    /// The po dir is resolved against the dir of the Cargo.toml, and defaults to `po`.
+    ///
+    /// # Returns
+    /// Returns None if no domain is listed.
+    pub fn from_manifest(package: &cargo_metadata::Package) -> anyhow::Result<Option<Self>> {
+        let metadata = match package.metadata.get(crate::asset::METADATA_KEY) {
+            Some(metadata) => GettextMetadata::deserialize(metadata).with_context(|| {
+                format!("invalid `package.metadata.{}`", crate::asset::METADATA_KEY)
+            })?,
+            None => GettextMetadata::default(),
+        };
+        let name = match metadata.gettext_domain {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        let manifest_dir = package
+            .manifest_path
+            .parent()
+            .context("manifest path has no parent")?;
+        let po_dir = manifest_dir.as_std_path().join(
+            metadata
+                .po_dir
+                .as_deref()
+                .unwrap_or(Path::new(DEFAULT_PO_DIR)),
+        );
+
+        Ok(Some(Self { name, po_dir }))
+    }
+
+    /// Compile every `.po` file in the po dir into `share/locale/<language>/LC_MESSAGES/<domain>.mo` in a dir.
+    ///
+    /// Fuzzy and untranslated messages are left out, like `msgfmt` does by default.
+    ///
+    /// # Returns
+    /// Returns the paths of the `.mo` files, relative to `out_dir`, one per language.
+    pub fn compile(&self, out_dir: &Utf8Path) -> anyhow::Result<Vec<Utf8PathBuf>> {
+        ensure!(
+            !self.name.is_empty() && !self.name.contains(['/', '\\']),
+            "`{}` is not a valid gettext domain",
+            self.name
+        );
+
+        let mut po_paths = Vec::new();
+        for entry in std::fs::read_dir(&self.po_dir)
+            .with_context(|| format!("failed to read `{}`", self.po_dir.display()))?
+        {
+            let entry = entry.context("failed to get dir entry")?;
+            let path = entry.path();
+            let is_po = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("po"));
+            if is_po && entry.file_type()?.is_file() {
+                po_paths.push(path);
+            }
+        }
+        po_paths.sort();
+
+        let mut mo_paths = Vec::with_capacity(po_paths.len());
+        for po_path in po_paths.iter() {
+            let language = po_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .with_context(|| format!("`{}` has no language name", po_path.display()))?;
+
+            let po = std::fs::read_to_string(po_path)
+                .with_context(|| format!("failed to read `{}`", po_path.display()))?;
+            let messages = parse_po(&po)
+                .with_context(|| format!("failed to parse `{}`", po_path.display()))?;
+
+            let mo_path = Utf8Path::new("share/locale")
+                .join(language)
+                .join("LC_MESSAGES")
+                .join(format!("{}.mo", self.name));
+            let full_mo_path = out_dir.join(&mo_path);
+            let mo_dir = full_mo_path.parent().context("mo path has no parent")?;
+            std::fs::create_dir_all(mo_dir)
+                .with_context(|| format!("failed to create `{mo_dir}`"))?;
+            std::fs::write(&full_mo_path, write_mo(&messages))
+                .with_context(|| format!("failed to write `{full_mo_path}`"))?;
+            mo_paths.push(mo_path);
+        }
+
+        Ok(mo_paths)
+    }
+}
+
+/// A message of a `.po` file that is being parsed.
+#[derive(Debug, Default)]
+struct PoEntry {
+    /// Whether the entry is marked fuzzy
+    fuzzy: bool,
+
+    /// The message context
+    context: Option<String>,
+
+    /// The message id
+    id: Option<String>,
+
+    /// The plural message id
+    id_plural: Option<String>,
+
+    /// The translations, one per plural form
+    translations: BTreeMap<usize, String>,
+}
+
+impl PoEntry {
+    /// Get the string that continuation lines are appended to.
+    fn last_string(&mut self, keyword: &str) -> Option<&mut String> {
+        match keyword {
+            "msgctxt" => self.context.as_mut(),
+            "msgid" => self.id.as_mut(),
+            "msgid_plural" => self.id_plural.as_mut(),
+            _ => self.translations.values_mut().next_back(),
+        }
+    }
+
+    /// Get the `.mo` key and value of this entry.
+    ///
+    /// # Returns
+    /// Returns None if this entry is fuzzy or untranslated, and is not the header.
+    fn into_message(self) -> anyhow::Result<Option<(String, String)>> {
+        let id = self.id.context("entry is missing `msgid`")?;
+        let is_header = id.is_empty() && self.context.is_none();
+        if (self.fuzzy && !is_header) || self.translations.values().all(|text| text.is_empty()) {
+            return Ok(None);
+        }
+
+        let mut key = match self.context {
+            Some(context) => format!("{context}{CONTEXT_SEPARATOR}{id}"),
+            None => id,
+        };
+        if let Some(id_plural) = self.id_plural {
+            key.push('\0');
+            key.push_str(&id_plural);
+        }
+        let value = self
+            .translations
+            .into_values()
+            .collect::<Vec<_>>()
+            .join("\0");
+
+        Ok(Some((key, value)))
+    }
+}
+
+/// Parse the messages of a `.po` file.
+///
+/// # Returns
+/// Returns the `.mo` keys and values of translated messages, sorted by key.
+fn parse_po(po: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut messages = BTreeMap::new();
+    let mut entry = PoEntry::default();
+    let mut keyword = String::new();
+
+    let mut finish_entry = |entry: &mut PoEntry| -> anyhow::Result<()> {
+        let entry = std::mem::take(entry);
+        if let Some((key, value)) = entry.into_message()? {
+            messages.insert(key, value);
+        }
+        Ok(())
+    };
+
+    for (line_index, line) in po.lines().enumerate() {
+        let line = line.trim();
+        let line_number = line_index + 1;
+
+        // A comment or a new id starts a new entry, once the current one has a translation.
+        let starts_entry =
+            line.starts_with('#') || line.starts_with("msgctxt ") || line.starts_with("msgid ");
+        if starts_entry && !entry.translations.is_empty() {
+            finish_entry(&mut entry)?;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(flags) = line.strip_prefix("#,") {
+            if flags.split(',').any(|flag| flag.trim() == "fuzzy") {
+                entry.fuzzy = true;
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('"') {
+            let text = parse_po_string(line)
+                .with_context(|| format!("invalid string on line {line_number}"))?;
+            entry
+                .last_string(&keyword)
+                .with_context(|| format!("unexpected string on line {line_number}"))?
+                .push_str(&text);
+            continue;
+        }
+
+        let (line_keyword, rest) = line
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("invalid line {line_number}"))?;
+        let text = parse_po_string(rest.trim())
+            .with_context(|| format!("invalid string on line {line_number}"))?;
+        match line_keyword {
+            "msgctxt" => entry.context = Some(text),
+            "msgid" => entry.id = Some(text),
+            "msgid_plural" => entry.id_plural = Some(text),
+            "msgstr" => {
+                entry.translations.insert(0, text);
+            }
+            _ => {
+                let index = line_keyword
+                    .strip_prefix("msgstr[")
+                    .and_then(|index| index.strip_suffix(']'))
+                    .and_then(|index| index.parse().ok())
+                    .with_context(|| {
+                        format!("unknown keyword `{line_keyword}` on line {line_number}")
+                    })?;
+                entry.translations.insert(index, text);
+            }
+        }
+        keyword = line_keyword.into();
+    }
+    if entry.id.is_some() {
+        finish_entry(&mut entry)?;
+    }
+
+    Ok(messages)
+}
+
+/// Parse a quoted `.po` string, processing C-style escapes.
+fn parse_po_string(input: &str) -> anyhow::Result<String> {
+    let inner = input
+        .strip_prefix('"')
+        .and_then(|input| input.strip_suffix('"'))
+        .context("string is not quoted")?;
+
+    let mut output = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next().context("string ends with a `\\`")? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\u{7}',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'v' => '\u{b}',
+            '"' => '"',
+            '\\' => '\\',
+            c => bail!("unknown escape `\\{c}`"),
+        };
+        output.push(escaped);
+    }
+
+    Ok(output)
+}
+
+/// Write messages, sorted by key, as a little-endian `.mo` file without a hash table.
+fn write_mo(messages: &BTreeMap<String, String>) -> Vec<u8> {
+    const HEADER_SIZE: usize = 7 * 4;
+
+    let num_messages = messages.len();
+    let keys_table_offset = HEADER_SIZE;
+    let values_table_offset = keys_table_offset + num_messages * 8;
+    let strings_offset = values_table_offset + num_messages * 8;
+
+    let mut tables = Vec::with_capacity(num_messages * 16);
+    let mut strings = Vec::new();
+    let mut add_strings = |strings_iter: &mut dyn Iterator<Item = &String>| {
+        for string in strings_iter {
+            let offset = strings_offset + strings.len();
+            tables.extend((string.len() as u32).to_le_bytes());
+            tables.extend((offset as u32).to_le_bytes());
+            strings.extend(string.as_bytes());
+            strings.push(0);
+        }
+    };
+    add_strings(&mut messages.keys());
+    add_strings(&mut messages.values());
+
+    let mut mo = Vec::with_capacity(strings_offset + strings.len());
+    for value in [
+        MO_MAGIC,
+        0,
+        num_messages as u32,
+        keys_table_offset as u32,
+        values_table_offset as u32,
+        0,
+        strings_offset as u32,
+    ] {
+        mo.extend(value.to_le_bytes());
+    }
+    mo.extend(tables);
+    mo.extend(strings);
+
+    mo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read the little-endian u32 at an offset of a `.mo` file.
+    fn read_u32(mo: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(mo[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Read the string of a `.mo` table entry, checking that it is nul-terminated.
+    fn read_string(mo: &[u8], table_offset: usize) -> &str {
+        let len = read_u32(mo, table_offset) as usize;
+        let offset = read_u32(mo, table_offset + 4) as usize;
+        assert_eq!(mo[offset + len], 0, "string is not nul-terminated");
+        std::str::from_utf8(&mo[offset..offset + len]).unwrap()
+    }
+
+    #[test]
+    fn po_string_escapes() {
+        assert_eq!(
+            parse_po_string(r#""tab\there\nquote\" backslash\\ bell\a""#).unwrap(),
+            "tab\there\nquote\" backslash\\ bell\u{7}"
+        );
+        assert_eq!(parse_po_string(r#""""#).unwrap(), "");
+        assert!(parse_po_string(r#""unknown \q""#).is_err());
+        assert!(parse_po_string(r#""trailing \""#).is_err());
+        assert!(parse_po_string("not quoted").is_err());
+    }
+
+    #[test]
+    fn po_multi_line_strings() {
+        let po = r#"
+msgid ""
+"Hello, "
+"world"
+msgstr ""
+"Hallo, "
+"Welt"
+"#;
+        let messages = parse_po(po).unwrap();
+        assert_eq!(
+            messages.get("Hello, world").map(String::as_str),
+            Some("Hallo, Welt")
+        );
+    }
+
+    #[test]
+    fn po_plurals_and_contexts() {
+        let po = r#"
+msgid "file"
+msgid_plural "files"
+msgstr[0] "Datei"
+msgstr[1] "Dateien"
+
+msgctxt "menu"
+msgid "Open"
+msgstr "Öffnen"
+"#;
+        let messages = parse_po(po).unwrap();
+        assert_eq!(
+            messages.get("file\0files").map(String::as_str),
+            Some("Datei\0Dateien")
+        );
+        assert_eq!(
+            messages.get("menu\u{4}Open").map(String::as_str),
+            Some("Öffnen")
+        );
+        assert!(parse_po("msgid \"a\"\nmsgstr[x] \"b\"\n").is_err());
+    }
+
+    #[test]
+    fn po_skips_fuzzy_and_untranslated() {
+        let po = r#"
+#, fuzzy
+msgid ""
+msgstr "Content-Type: text/plain; charset=UTF-8\n"
+
+#, c-format, fuzzy
+msgid "Fuzzy"
+msgstr "Unscharf"
+
+msgid "Untranslated"
+msgstr ""
+
+# A translator comment
+msgid "Kept"
+msgstr "Behalten"
+"#;
+        let messages = parse_po(po).unwrap();
+        let keys: Vec<_> = messages.keys().map(String::as_str).collect();
+        // The header is kept even if it is fuzzy, like msgfmt does.
+        assert_eq!(keys, ["", "Kept"]);
+    }
+
+    #[test]
+    fn mo_layout() {
+        let mut messages = BTreeMap::new();
+        messages.insert(String::new(), "header".to_string());
+        messages.insert("b".to_string(), "B".to_string());
+        messages.insert("a\0as".to_string(), "A\0As".to_string());
+        let mo = write_mo(&messages);
+
+        assert_eq!(read_u32(&mo, 0), MO_MAGIC);
+        assert_eq!(read_u32(&mo, 4), 0, "bad revision");
+        let num_messages = read_u32(&mo, 8) as usize;
+        assert_eq!(num_messages, 3);
+        let keys_table_offset = read_u32(&mo, 12) as usize;
+        let values_table_offset = read_u32(&mo, 16) as usize;
+        assert_eq!(keys_table_offset, 28);
+        assert_eq!(values_table_offset, keys_table_offset + num_messages * 8);
+        // There is no hash table, so gettext falls back to a binary search of the sorted keys.
+        assert_eq!(read_u32(&mo, 20), 0, "bad hash table size");
+        assert_eq!(
+            read_u32(&mo, 24) as usize,
+            values_table_offset + num_messages * 8,
+            "bad hash table offset"
+        );
+
+        let entries: Vec<_> = (0..num_messages)
+            .map(|i| {
+                (
+                    read_string(&mo, keys_table_offset + i * 8),
+                    read_string(&mo, values_table_offset + i * 8),
+                )
+            })
+            .collect();
+        assert_eq!(entries, [("", "header"), ("a\0as", "A\0As"), ("b", "B")]);
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use std::process::Command;
+
+/// The dir of GIO modules, relative to an MSYS2 environment or the package dir.
+pub const GIO_MODULES_DIR: &str = "lib/gio/modules";
+
+/// The CA certificate bundle, relative to an MSYS2 environment or the package dir.
+pub const CA_BUNDLE_PATH: &str = "etc/ssl/certs/ca-bundle.crt";
+
+/// Add every GIO module of an MSYS2 environment to a packager, along with the CA certificate bundle.
+///
+/// GIO modules provide the TLS backends and proxy resolvers of glib-networking,
+/// without which libsoup and other TLS users fail at runtime.
+///
+/// # Returns
+/// Returns the number of modules added.
+pub fn add_gio_modules(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+) -> anyhow::Result<usize> {
+    let num_modules =
+        add_module_dir(packager, environment_path, GIO_MODULES_DIR)?.with_context(|| {
+            format!(
+                "`{}` does not exist, install the glib-networking package of the MSYS2 environment",
+                environment_path.join(GIO_MODULES_DIR)
+            )
+        })?;
+
+    let ca_bundle = environment_path.join(CA_BUNDLE_PATH);
+    if ca_bundle.is_file() {
+        packager.add_file(
+            Some(ca_bundle.into()),
+            CA_BUNDLE_PATH.into(),
+            FileFlags::empty(),
+        );
+    } else {
+        eprintln!(
+            "warning: `{ca_bundle}` does not exist, so no CA certificates will be packaged. Install the ca-certificates package of the MSYS2 environment"
+        );
+    }
+
+    Ok(num_modules)
+}
+
+/// Add every dll in a dir of modules of an MSYS2 environment to a packager, at the same path in the package.
+///
+/// `modules_dir` is relative to the environment, like [`GIO_MODULES_DIR`].
+///
+/// # Returns
+/// Returns the number of modules added, or None if the dir does not exist.
+pub fn add_module_dir(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+    modules_dir: &str,
+) -> anyhow::Result<Option<usize>> {
+    let path = environment_path.join(modules_dir);
+    let dir_entries = match path.read_dir_utf8() {
+        Ok(dir_entries) => dir_entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read `{path}`"));
+        }
+    };
+
+    let mut num_modules = 0;
+    for dir_entry in dir_entries {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name();
+        if !file_name.ends_with(".dll") || !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+
+        packager.add_file(
+            Some(dir_entry.path().into()),
+            Utf8Path::new(modules_dir).join(file_name).into(),
+            FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+        );
+        num_modules += 1;
+    }
+
+    Ok(Some(num_modules))
+}
+
+/// Regenerate the `giomodule.cache` of a dir of GIO modules, like [`GIO_MODULES_DIR`] in a package dir.
+///
+/// `gio_querymodules` is the path to `gio-querymodules.exe`.
+pub fn update_module_cache(
+    modules_dir: &Utf8Path,
+    gio_querymodules: &Utf8Path,
+) -> anyhow::Result<()> {
+    let output = Command::new(gio_querymodules)
+        .arg(modules_dir)
+        .output()
+        .with_context(|| format!("failed to run `{gio_querymodules}`"))?;
+    ensure!(
+        output.status.success(),
+        "failed to update the GIO module cache for `{modules_dir}`: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use std::fmt::Write as _;
+use std::process::Command;
+use walkdir::WalkDir;
+
+/// The path of the resource bundle, relative to the package top level.
+pub const GRESOURCE_PATH: &str = "share/data.gresource";
+
+/// Bundle dirs of a package dir into one compressed GResource file, and remove the bundled files.
+///
+/// `dirs` are relative to the package top level, like `share/icons`.
+/// Each file is stored at `/<path relative to the package top level>` in the bundle.
+/// The app must register the bundle at runtime for GTK to find the files, see [`get_guidance`].
+/// The bundled files are still in the packaging plan, so callers should remove them with
+/// [`msys2_packager::packager::Plan::remove_dir`] before saving anything made from it.
+///
+/// # Returns
+/// Returns the number of files that were bundled.
+pub fn bundle(
+    package_dir: &Utf8Path,
+    dirs: &[Utf8PathBuf],
+    glib_compile_resources: &Utf8Path,
+) -> anyhow::Result<usize> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        ensure!(
+            dir.is_relative() && dir.components().all(|c| c.as_str() != ".."),
+            "`{dir}` is not a plain relative path"
+        );
+        let full_dir = package_dir.join(dir);
+        ensure!(
+            full_dir.is_dir(),
+            "`{full_dir}` is not a dir in the package"
+        );
+
+        for entry in WalkDir::new(&full_dir).sort_by_file_name() {
+            let entry = entry.context("failed to read dir entry")?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative_path = entry
+                .path()
+                .strip_prefix(package_dir)
+                .context("dir entry is not in the package dir")?;
+            let relative_path = Utf8PathBuf::try_from(relative_path.to_path_buf())
+                .context("packaged file path is not unicode")?;
+            files.push(relative_path);
+        }
+    }
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, "<gresources>")?;
+    writeln!(xml, r#"  <gresource prefix="/">"#)?;
+    for file in files.iter() {
+        writeln!(
+            xml,
+            r#"    <file compressed="true">{}</file>"#,
+            escape_xml(&file.as_str().replace('\\', "/"))
+        )?;
+    }
+    writeln!(xml, "  </gresource>")?;
+    writeln!(xml, "</gresources>")?;
+
+    // The manifest is kept out of the package dir, so it is not packaged.
+    let manifest_path = package_dir.with_extension("gresource.xml");
+    std::fs::write(&manifest_path, xml)
+        .with_context(|| format!("failed to write `{manifest_path}`"))?;
+
+    let target = package_dir.join(GRESOURCE_PATH);
+    let output = Command::new(glib_compile_resources)
+        .arg(format!("--sourcedir={package_dir}"))
+        .arg(format!("--target={target}"))
+        .arg(&manifest_path)
+        .output()
+        .with_context(|| format!("failed to run `{glib_compile_resources}`"))?;
+    ensure!(
+        output.status.success(),
+        "failed to compile `{manifest_path}`: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    for dir in dirs {
+        let full_dir = package_dir.join(dir);
+        std::fs::remove_dir_all(&full_dir)
+            .with_context(|| format!("failed to remove `{full_dir}`"))?;
+    }
+
+    Ok(files.len())
+}
+
+/// Get instructions for loading a bundle made by [`bundle`] from an app.
+pub fn get_guidance(dirs: &[Utf8PathBuf]) -> String {
+    let mut guidance = String::new();
+    let _ = writeln!(
+        guidance,
+        "Bundled {} into `{GRESOURCE_PATH}`. The app must register it before creating any widgets:",
+        dirs.iter()
+            .map(|dir| format!("`{dir}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let _ = writeln!(
+        guidance,
+        "  let exe_dir = std::env::current_exe()?.parent().unwrap().to_path_buf();"
+    );
+    let _ = writeln!(
+        guidance,
+        "  gio::resources_register(&gio::Resource::load(exe_dir.join(\"{GRESOURCE_PATH}\"))?);"
+    );
+    if dirs.iter().any(|dir| dir.starts_with("share/icons")) {
+        let _ = writeln!(
+            guidance,
+            "Icon themes are then found with `IconTheme::add_resource_path(\"/share/icons\")`."
+        );
+    }
+    let _ = write!(
+        guidance,
+        "Data read with plain file APIs, like gettext catalogs, cannot be loaded from the bundle."
+    );
+
+    guidance
+}
+
+/// Escape text for use in XML.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,56 @@
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use walkdir::WalkDir;
+
+/// The data dir of GtkSourceView 5, relative to an MSYS2 environment or the package dir.
+pub const GTKSOURCEVIEW_DIR: &str = "share/gtksourceview-5";
+
+/// The dirs of GtkSourceView data to package, relative to [`GTKSOURCEVIEW_DIR`].
+///
+/// `language-specs` holds the syntax highlighting definitions, and `styles` holds the style schemes.
+const GTKSOURCEVIEW_DATA_DIRS: &[&str] = &["language-specs", "styles"];
+
+/// Add the language specs and style schemes of GtkSourceView 5 in an MSYS2 environment to a packager.
+///
+/// Without them, `GtkSourceLanguageManager` finds no languages, so views render plain text,
+/// and `GtkSourceStyleSchemeManager` finds no schemes, which makes some apps crash.
+///
+/// # Returns
+/// Returns the number of files added.
+pub fn add_gtksourceview_data(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+) -> anyhow::Result<usize> {
+    let mut num_files = 0;
+    for dir in GTKSOURCEVIEW_DATA_DIRS {
+        let dest_dir = Utf8Path::new(GTKSOURCEVIEW_DIR).join(dir);
+        let path = environment_path.join(&dest_dir);
+        ensure!(
+            path.is_dir(),
+            "`{path}` does not exist, install the gtksourceview5 package of the MSYS2 environment"
+        );
+
+        for entry in WalkDir::new(&path).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("failed to read `{path}`"))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(&path)
+                .context("dir entry path is not prefixed by the data dir")?;
+            packager.add_file(
+                Some(entry.path().into()),
+                dest_dir.as_std_path().join(relative_path),
+                FileFlags::empty(),
+            );
+            num_files += 1;
+        }
+    }
+
+    Ok(num_files)
+}
@@ -0,0 +1,177 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use serde_json::json;
+use serde_json::Value;
+
+/// The prefix of the labels of generated tasks and launch configurations.
+///
+/// Entries with these labels are replaced when the setup is regenerated.
+const LABEL_PREFIX: &str = "msys2-gtk-packager";
+
+/// An error that may occur while parsing an [`Editor`] from a string.
+#[derive(Debug)]
+pub struct EditorFromStrError(String);
+
+impl std::fmt::Display for EditorFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a supported editor", self.0)
+    }
+}
+
+impl std::error::Error for EditorFromStrError {}
+
+/// An editor to write project setup for
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Editor {
+    /// Visual Studio Code, with `.vscode/tasks.json` and `.vscode/launch.json`
+    VsCode,
+}
+
+impl std::str::FromStr for Editor {
+    type Err = EditorFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "vscode" => Ok(Self::VsCode),
+            _ => Err(EditorFromStrError(input.into())),
+        }
+    }
+}
+
+/// What the generated tasks build, and where the packaged exe ends up
+#[derive(Debug)]
+pub struct IdeTarget<'a> {
+    /// The target triple
+    pub target: &'a str,
+
+    /// The build profile
+    pub profile: &'a str,
+
+    /// The workspace package that contains the binary, if one was selected
+    pub package: Option<&'a str>,
+
+    /// The binary name
+    pub bin: &'a str,
+
+    /// The path to the packaged exe
+    pub program: &'a Utf8Path,
+
+    /// The path to the package dir, which the exe is run in
+    pub package_dir: &'a Utf8Path,
+
+    /// The path to GDB, from the MSYS2 environment
+    pub gdb: &'a Utf8Path,
+}
+
+impl IdeTarget<'_> {
+    /// Get the args that select this target, for the `build`, `package`, and `run` subcommands.
+    fn get_args(&self, subcommand: &str) -> Vec<String> {
+        let mut args = vec![
+            subcommand.to_string(),
+            "--target".into(),
+            self.target.into(),
+            "--profile".into(),
+            self.profile.into(),
+        ];
+        if let Some(package) = self.package {
+            args.extend(["--package".into(), package.into()]);
+        }
+        args.extend(["--bin".into(), self.bin.into()]);
+        args
+    }
+}
+
+/// Generate the VS Code tasks that build, package, and run a target.
+pub fn vscode_tasks(target: &IdeTarget) -> Vec<Value> {
+    ["build", "package", "run"]
+        .into_iter()
+        .map(|subcommand| {
+            let mut task = json!({
+                "label": format!("{LABEL_PREFIX}: {subcommand}"),
+                "type": "process",
+                "command": env!("CARGO_PKG_NAME"),
+                "args": target.get_args(subcommand),
+                "problemMatcher": ["$rustc"],
+            });
+            if subcommand == "build" {
+                task["group"] = json!({
+                    "kind": "build",
+                    "isDefault": true,
+                });
+            }
+            task
+        })
+        .collect()
+}
+
+/// Generate the VS Code launch configuration that debugs the packaged exe with GDB, after packaging it.
+pub fn vscode_launch_configuration(target: &IdeTarget) -> Value {
+    json!({
+        "name": format!("{LABEL_PREFIX}: {}", target.bin),
+        "type": "cppdbg",
+        "request": "launch",
+        "program": target.program.as_str(),
+        "args": [],
+        "cwd": target.package_dir.as_str(),
+        "MIMode": "gdb",
+        "miDebuggerPath": target.gdb.as_str(),
+        "preLaunchTask": format!("{LABEL_PREFIX}: package"),
+    })
+}
+
+/// Add entries to a VS Code JSON file, like `tasks.json`, replacing entries with the same name.
+///
+/// `key` is the array that holds the entries, and `name_key` is the field that names them.
+/// If `overwrite` is true, or the file does not exist, a new file with the format `version` is written.
+///
+/// # Errors
+/// Returns an error if the existing file cannot be parsed, which happens if it has comments.
+pub fn merge_vscode_file(
+    path: &Utf8Path,
+    key: &str,
+    name_key: &str,
+    entries: Vec<Value>,
+    version: &str,
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    let mut file = if !overwrite && path.exists() {
+        let data = std::fs::read(path).with_context(|| format!("failed to read `{path}`"))?;
+        serde_json::from_slice(&data).with_context(|| {
+            format!(
+                "failed to parse `{path}`, which may have comments. Pass `--force` to overwrite it"
+            )
+        })?
+    } else {
+        json!({
+            "version": version,
+        })
+    };
+
+    let file_object = file
+        .as_object_mut()
+        .with_context(|| format!("`{path}` is not a JSON object"))?;
+    let existing = file_object
+        .entry(key)
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .with_context(|| format!("`{key}` in `{path}` is not an array"))?;
+    for entry in entries {
+        let name = entry[name_key].clone();
+        match existing
+            .iter_mut()
+            .find(|existing| existing[name_key] == name)
+        {
+            Some(existing) => *existing = entry,
+            None => existing.push(entry),
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("failed to create `{parent}`"))?;
+    }
+    let mut data = serde_json::to_string_pretty(&file)?;
+    data.push('\n');
+    std::fs::write(path, data).with_context(|| format!("failed to write `{path}`"))?;
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use camino::Utf8Path;
+use msys2_packager::packager::Packager;
+
+/// The dir of GTK input method modules, relative to an MSYS2 environment or the package dir.
+pub const IMMODULES_DIR: &str = "lib/gtk-4.0/4.0.0/immodules";
+
+/// Add every GTK input method module of an MSYS2 environment to a packager.
+///
+/// Input method modules provide input methods beyond the built-in Windows IME support, like for East-Asian languages.
+///
+/// # Returns
+/// Returns the number of modules added.
+pub fn add_immodules(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+) -> anyhow::Result<usize> {
+    match crate::gio_modules::add_module_dir(packager, environment_path, IMMODULES_DIR)? {
+        Some(num_modules) => Ok(num_modules),
+        None => {
+            eprintln!("warning: `{}` does not exist, so no input method modules will be packaged. Only the built-in Windows IME support will be available", environment_path.join(IMMODULES_DIR));
+            Ok(0)
+        }
+    }
+}
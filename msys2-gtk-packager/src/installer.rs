@@ -0,0 +1,470 @@
+/// Inno Setup scripts
+pub mod innosetup;
+
+/// Install scripts and cargo-binstall metadata
+pub mod script;
+
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+
+/// The key of the cargo-wix section in `package.metadata` of a Cargo.toml.
+const WIX_METADATA_KEY: &str = "wix";
+
+/// The key of the cargo-bundle section in `package.metadata` of a Cargo.toml.
+const BUNDLE_METADATA_KEY: &str = "bundle";
+
+/// The max length of a Windows AppUserModelID.
+const MAX_APP_USER_MODEL_ID_LEN: usize = 128;
+
+/// An error that may occur while parsing an [`InstallerFormat`] from a string.
+#[derive(Debug)]
+pub struct InstallerFormatFromStrError(String);
+
+impl std::fmt::Display for InstallerFormatFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid installer format", self.0)
+    }
+}
+
+impl std::error::Error for InstallerFormatFromStrError {}
+
+/// A kind of installer to generate
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum InstallerFormat {
+    /// An Inno Setup script
+    InnoSetup,
+
+    /// Zip archives of the package, and a PowerShell install script for them
+    Script,
+}
+
+impl std::str::FromStr for InstallerFormat {
+    type Err = InstallerFormatFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "innosetup" => Ok(Self::InnoSetup),
+            "script" => Ok(Self::Script),
+            _ => Err(InstallerFormatFromStrError(input.into())),
+        }
+    }
+}
+
+/// The `[package.metadata.wix]` section used by cargo-wix
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct WixMetadata {
+    /// The product name
+    product_name: Option<String>,
+
+    /// The product manufacturer
+    manufacturer: Option<String>,
+
+    /// The product description
+    description: Option<String>,
+
+    /// The path to the product icon
+    product_icon: Option<Utf8PathBuf>,
+
+    /// The path to the license file
+    license: Option<Utf8PathBuf>,
+
+    /// The GUID used to upgrade earlier installs
+    upgrade_guid: Option<String>,
+}
+
+/// The `[package.metadata.bundle]` section used by cargo-bundle
+#[derive(Debug, Default, Deserialize)]
+struct BundleMetadata {
+    /// The app name
+    name: Option<String>,
+
+    /// The reverse-DNS app identifier, like `com.example.app`
+    identifier: Option<String>,
+
+    /// The short app description
+    short_description: Option<String>,
+
+    /// Paths or globs of app icons, in several sizes and formats
+    #[serde(default)]
+    icon: Vec<String>,
+
+    /// The copyright notice
+    copyright: Option<String>,
+}
+
+/// The installer section of this tool's section in `package.metadata` of a Cargo.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackagerMetadata {
+    /// The Windows AppUserModelID
+    app_user_model_id: Option<String>,
+
+    /// The document types the app opens
+    #[serde(default)]
+    file_types: Vec<FileTypeMetadata>,
+
+    /// The background services of the app
+    #[serde(default)]
+    services: Vec<ServiceMetadata>,
+}
+
+/// A `[[package.metadata.msys2-gtk-packager.services]]` entry
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ServiceMetadata {
+    /// The service name
+    name: String,
+
+    /// The service name shown by the Services console
+    display_name: Option<String>,
+
+    /// The service description
+    description: Option<String>,
+
+    /// The path to the service exe, relative to the package top level
+    exe: Utf8PathBuf,
+
+    /// When the service starts
+    #[serde(default)]
+    start: ServiceStartType,
+
+    /// The delay before restarting the service after it fails
+    restart_delay_secs: Option<u32>,
+}
+
+/// When a Windows service starts
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceStartType {
+    /// At boot
+    #[default]
+    Auto,
+
+    /// Shortly after boot, once other auto services have started
+    DelayedAuto,
+
+    /// When it is started manually, or by another program
+    Demand,
+
+    /// Never
+    Disabled,
+}
+
+impl ServiceStartType {
+    /// Get the name of this start type, as used by `sc.exe`.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::DelayedAuto => "delayed-auto",
+            Self::Demand => "demand",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+/// A background service exe, which an installer registers as a Windows service
+#[derive(Debug)]
+pub struct Service {
+    /// The service name, like `HelloService`
+    pub name: String,
+
+    /// The service name shown by the Services console
+    pub display_name: String,
+
+    /// The service description
+    pub description: Option<String>,
+
+    /// The path to the service exe, relative to the package top level.
+    ///
+    /// The exe must be packaged with the app, so its dlls are resolved into the same package.
+    pub exe: Utf8PathBuf,
+
+    /// When the service starts
+    pub start: ServiceStartType,
+
+    /// The delay before restarting the service after it fails.
+    ///
+    /// If None, the service is not restarted.
+    pub restart_delay_secs: Option<u32>,
+}
+
+/// A `[[package.metadata.msys2-gtk-packager.file-types]]` entry
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FileTypeMetadata {
+    /// The file extension, like `txt`
+    extension: String,
+
+    /// The file type description shown by Explorer
+    description: Option<String>,
+
+    /// The path to the `.ico` for files of this type
+    icon: Option<Utf8PathBuf>,
+}
+
+/// A document type that the app opens, which an installer associates with it
+#[derive(Debug)]
+pub struct FileType {
+    /// The file extension, without the leading `.`, like `txt`
+    pub extension: String,
+
+    /// The file type description shown by Explorer, like `Text Document`
+    pub description: String,
+
+    /// The absolute path to the `.ico` for files of this type.
+    ///
+    /// If None, Explorer shows the app icon.
+    pub icon: Option<Utf8PathBuf>,
+}
+
+/// Settings for building an installer for a package.
+///
+/// These are read from the cargo-wix and cargo-bundle sections of the package's Cargo.toml, if present,
+/// so projects that already use those tools do not need to repeat them.
+/// cargo-wix settings take precedence over cargo-bundle settings, which take precedence over the package fields.
+#[derive(Debug)]
+pub struct InstallerMetadata {
+    /// The app name
+    pub name: String,
+
+    /// The app version
+    pub version: String,
+
+    /// The reverse-DNS app identifier, like `com.example.app`
+    pub identifier: Option<String>,
+
+    /// The app publisher
+    pub publisher: Option<String>,
+
+    /// The app description
+    pub description: Option<String>,
+
+    /// The copyright notice
+    pub copyright: Option<String>,
+
+    /// The absolute path to the app icon.
+    ///
+    /// For cargo-bundle, the first `.ico` icon is used.
+    pub icon: Option<Utf8PathBuf>,
+
+    /// The absolute path to the license file
+    pub license_file: Option<Utf8PathBuf>,
+
+    /// The GUID used to upgrade earlier installs, from cargo-wix
+    pub upgrade_guid: Option<String>,
+
+    /// The Windows AppUserModelID, which groups windows on the taskbar and identifies the app to notifications.
+    ///
+    /// This is read from `app-user-model-id` in this tool's section, and defaults to the identifier.
+    pub app_user_model_id: Option<String>,
+
+    /// The document types the app opens, from `file-types` in this tool's section
+    pub file_types: Vec<FileType>,
+
+    /// The background services of the app, from `services` in this tool's section
+    pub services: Vec<Service>,
+}
+
+impl InstallerMetadata {
+    /// Read the installer settings of a package.
+    pub fn from_package(package: &cargo_metadata::Package) -> anyhow::Result<Self> {
+        let wix: WixMetadata = get_metadata_section(package, WIX_METADATA_KEY)?;
+        let bundle: BundleMetadata = get_metadata_section(package, BUNDLE_METADATA_KEY)?;
+        let packager: PackagerMetadata = get_metadata_section(package, crate::asset::METADATA_KEY)?;
+        let manifest_dir = package
+            .manifest_path
+            .parent()
+            .context("manifest path has no parent")?;
+
+        // cargo-bundle icon entries may be globs, but for Windows only a literal `.ico` is useful.
+        let bundle_icon = bundle
+            .icon
+            .iter()
+            .find(|icon| icon.to_ascii_lowercase().ends_with(".ico"))
+            .map(Utf8PathBuf::from);
+
+        let app_user_model_id = packager
+            .app_user_model_id
+            .or_else(|| bundle.identifier.clone());
+        if let Some(app_user_model_id) = app_user_model_id.as_deref() {
+            validate_app_user_model_id(app_user_model_id)?;
+        }
+
+        let file_types = packager
+            .file_types
+            .into_iter()
+            .map(|file_type| {
+                let extension = file_type.extension.trim_start_matches('.').to_string();
+                ensure!(
+                    !extension.is_empty()
+                        && extension
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                    "`{}` is not a valid file extension",
+                    file_type.extension
+                );
+                let description = file_type
+                    .description
+                    .unwrap_or_else(|| format!("{} file", extension.to_ascii_uppercase()));
+
+                Ok(FileType {
+                    extension,
+                    description,
+                    icon: file_type.icon.map(|icon| manifest_dir.join(icon)),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let services = packager
+            .services
+            .into_iter()
+            .map(|service| {
+                ensure!(
+                    !service.name.is_empty()
+                        && !service.name.contains(|c: char| c.is_whitespace()
+                            || c == '"'
+                            || c == '/'
+                            || c == '\\'),
+                    "`{}` is not a valid service name",
+                    service.name
+                );
+
+                Ok(Service {
+                    display_name: service.display_name.unwrap_or_else(|| service.name.clone()),
+                    name: service.name,
+                    description: service.description,
+                    exe: service.exe,
+                    start: service.start,
+                    restart_delay_secs: service.restart_delay_secs,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name: wix
+                .product_name
+                .or(bundle.name)
+                .unwrap_or_else(|| package.name.clone()),
+            version: package.version.to_string(),
+            identifier: bundle.identifier,
+            publisher: wix.manufacturer.or_else(|| {
+                package
+                    .authors
+                    .first()
+                    .map(|author| strip_author_email(author).to_string())
+            }),
+            description: wix
+                .description
+                .or(bundle.short_description)
+                .or_else(|| package.description.clone()),
+            copyright: bundle.copyright,
+            icon: wix
+                .product_icon
+                .or(bundle_icon)
+                .map(|icon| manifest_dir.join(icon)),
+            license_file: wix
+                .license
+                .or_else(|| package.license_file.clone())
+                .map(|license| manifest_dir.join(license)),
+            upgrade_guid: wix.upgrade_guid,
+            app_user_model_id,
+            file_types,
+            services,
+        })
+    }
+}
+
+impl std::fmt::Display for InstallerMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let optional = |value: Option<&str>| value.unwrap_or("(none)").to_string();
+
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "identifier: {}", optional(self.identifier.as_deref()))?;
+        writeln!(f, "publisher: {}", optional(self.publisher.as_deref()))?;
+        writeln!(f, "description: {}", optional(self.description.as_deref()))?;
+        writeln!(f, "copyright: {}", optional(self.copyright.as_deref()))?;
+        writeln!(
+            f,
+            "icon: {}",
+            optional(self.icon.as_ref().map(|icon| icon.as_str()))
+        )?;
+        writeln!(
+            f,
+            "license file: {}",
+            optional(self.license_file.as_ref().map(|license| license.as_str()))
+        )?;
+        writeln!(
+            f,
+            "upgrade guid: {}",
+            optional(self.upgrade_guid.as_deref())
+        )?;
+        write!(
+            f,
+            "app user model id: {}",
+            optional(self.app_user_model_id.as_deref())
+        )?;
+        for file_type in self.file_types.iter() {
+            write!(
+                f,
+                "\nfile type: .{} ({}), icon: {}",
+                file_type.extension,
+                file_type.description,
+                optional(file_type.icon.as_ref().map(|icon| icon.as_str()))
+            )?;
+        }
+        for service in self.services.iter() {
+            write!(
+                f,
+                "\nservice: {} ({}), exe: {}, start: {}",
+                service.name,
+                service.display_name,
+                service.exe,
+                service.start.get_name()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that an AppUserModelID is one Windows accepts.
+///
+/// Ids are at most 128 chars and have no spaces, like `Company.Product.SubProduct.Version`.
+pub fn validate_app_user_model_id(app_user_model_id: &str) -> anyhow::Result<()> {
+    ensure!(!app_user_model_id.is_empty(), "the AppUserModelID is empty");
+    ensure!(
+        app_user_model_id.chars().count() <= MAX_APP_USER_MODEL_ID_LEN,
+        "the AppUserModelID `{app_user_model_id}` is longer than {MAX_APP_USER_MODEL_ID_LEN} chars"
+    );
+    ensure!(
+        !app_user_model_id.contains(char::is_whitespace),
+        "the AppUserModelID `{app_user_model_id}` contains whitespace"
+    );
+
+    Ok(())
+}
+
+/// Deserialize a section of `package.metadata`, or get the default if it is missing.
+fn get_metadata_section<T>(package: &cargo_metadata::Package, key: &str) -> anyhow::Result<T>
+where
+    T: Default + for<'de> Deserialize<'de>,
+{
+    match package.metadata.get(key) {
+        Some(metadata) => {
+            T::deserialize(metadata).with_context(|| format!("invalid `package.metadata.{key}`"))
+        }
+        None => Ok(T::default()),
+    }
+}
+
+/// Strip the trailing `<email>` from a Cargo.toml author.
+fn strip_author_email(author: &str) -> &str {
+    match author.find('<') {
+        Some(index) => author[..index].trim(),
+        None => author.trim(),
+    }
+}
@@ -0,0 +1,587 @@
+use crate::installer::InstallerMetadata;
+use crate::installer::ServiceStartType;
+use anyhow::ensure;
+use anyhow::Context;
+use msys2_packager::packager::CACHE_FILE_NAME;
+use std::fmt::Write as _;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// The name of the Inno Setup compiler.
+pub const ISCC_FILE_NAME: &str = "ISCC.exe";
+
+/// The dir that file type icons are installed to, relative to the app dir.
+const FILE_TYPE_ICONS_DIR: &str = "file-types";
+
+/// Dirs that Inno Setup is usually installed to.
+const ISCC_INSTALL_DIRS: &[&str] = &[
+    r"C:\Program Files (x86)\Inno Setup 6",
+    r"C:\Program Files\Inno Setup 6",
+];
+
+/// A package dir to install, and the target triple it was built for
+pub struct TargetPackage<'a> {
+    /// The package dir
+    pub package_dir: &'a Path,
+
+    /// The target triple
+    pub target: &'a str,
+}
+
+/// Get the file name of the installer that ISCC compiles from a script made by [`generate_script`].
+pub fn get_installer_file_name(metadata: &InstallerMetadata) -> String {
+    format!("{}.exe", get_output_base_file_name(metadata))
+}
+
+/// Get the `OutputBaseFilename` of the installer, which is its file name without the extension.
+fn get_output_base_file_name(metadata: &InstallerMetadata) -> String {
+    format!("{}-{}-setup", metadata.name, metadata.version)
+}
+
+/// Generate an Inno Setup script that installs every file in a package dir.
+///
+/// `packages` has one package per target.
+/// If there are several, the installer picks the package of the best target the machine can run,
+/// like the `aarch64` package on ARM64 Windows and the `x86_64` package on x64 Windows.
+/// `bin_name` is the file name of the main binary, which shortcuts point to.
+/// `out_dir` is where ISCC writes the installer.
+pub fn generate_script(
+    metadata: &InstallerMetadata,
+    packages: &[TargetPackage],
+    bin_name: &str,
+    out_dir: &Path,
+) -> anyhow::Result<String> {
+    ensure!(!packages.is_empty(), "missing package");
+    for package in packages.iter() {
+        for service in metadata.services.iter() {
+            ensure!(
+                package.package_dir.join(&service.exe).is_file(),
+                "the exe of service `{}`, `{}`, is not in the package for `{}`. Package it with the app by passing its bin to `package` with `--bin`",
+                service.name,
+                service.exe,
+                package.target
+            );
+        }
+    }
+
+    let mut script = String::new();
+
+    // Prefer a stable id, so upgrades replace earlier installs instead of installing side-by-side.
+    let app_id = match (
+        metadata.upgrade_guid.as_deref(),
+        metadata.identifier.as_deref(),
+    ) {
+        (Some(guid), _) => format!("{{{}}}", guid.trim_matches(['{', '}'])),
+        (None, Some(identifier)) => identifier.to_string(),
+        (None, None) => metadata.name.clone(),
+    };
+
+    writeln!(script, "[Setup]")?;
+    writeln!(script, "AppId={}", escape_constants(&app_id))?;
+    writeln!(script, "AppName={}", escape_constants(&metadata.name))?;
+    writeln!(script, "AppVersion={}", escape_constants(&metadata.version))?;
+    if let Some(publisher) = metadata.publisher.as_deref() {
+        writeln!(script, "AppPublisher={}", escape_constants(publisher))?;
+    }
+    if let Some(copyright) = metadata.copyright.as_deref() {
+        writeln!(script, "AppCopyright={}", escape_constants(copyright))?;
+    }
+    writeln!(
+        script,
+        "DefaultDirName={{autopf}}\\{}",
+        escape_constants(&metadata.name)
+    )?;
+    writeln!(
+        script,
+        "DefaultGroupName={}",
+        escape_constants(&metadata.name)
+    )?;
+    writeln!(
+        script,
+        "OutputDir={}",
+        escape_constants(&out_dir.display().to_string())
+    )?;
+    writeln!(
+        script,
+        "OutputBaseFilename={}",
+        escape_constants(&get_output_base_file_name(metadata))
+    )?;
+    if let Some(license_file) = metadata.license_file.as_ref() {
+        writeln!(
+            script,
+            "LicenseFile={}",
+            escape_constants(license_file.as_str())
+        )?;
+    }
+    if let Some(icon) = metadata.icon.as_ref() {
+        writeln!(script, "SetupIconFile={}", escape_constants(icon.as_str()))?;
+    }
+    writeln!(
+        script,
+        "UninstallDisplayName={}",
+        escape_constants(&metadata.name)
+    )?;
+    writeln!(
+        script,
+        "UninstallDisplayIcon={{app}}\\{}",
+        escape_constants(bin_name)
+    )?;
+    if !metadata.file_types.is_empty() {
+        writeln!(script, "ChangesAssociations=yes")?;
+    }
+    if !metadata.services.is_empty() {
+        writeln!(script, "PrivilegesRequired=admin")?;
+    }
+    writeln!(script, "Compression=lzma2")?;
+    writeln!(script, "SolidCompression=yes")?;
+    let mut architectures: Vec<_> = packages
+        .iter()
+        .filter_map(|package| get_architecture(package.target))
+        .collect();
+    // 32-bit packages install anywhere, so they do not limit the allowed architectures.
+    let has_32_bit = architectures.len() < packages.len();
+    architectures.sort();
+    architectures.dedup();
+    if !architectures.is_empty() {
+        let architectures = architectures.join(" or ");
+        if !has_32_bit {
+            writeln!(script, "ArchitecturesAllowed={architectures}")?;
+        }
+        writeln!(script, "ArchitecturesInstallIn64BitMode={architectures}")?;
+    }
+    writeln!(script)?;
+
+    writeln!(script, "[Files]")?;
+    for package in packages.iter() {
+        let check = match get_check(package.target, packages) {
+            Some(check) => format!("; Check: {check}"),
+            None => String::new(),
+        };
+        for entry in WalkDir::new(package.package_dir).sort_by_file_name() {
+            let entry = entry.context("failed to read package dir entry")?;
+            if !entry.file_type().is_file() || entry.file_name() == CACHE_FILE_NAME {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(package.package_dir)
+                .context("package file is not in the package dir")?;
+            let mut dest_dir = String::from("{app}");
+            if let Some(parent) = relative_path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                dest_dir.push('\\');
+                dest_dir.push_str(&escape_constants(
+                    &parent.display().to_string().replace('/', "\\"),
+                ));
+            }
+
+            writeln!(
+                script,
+                "Source: {}; DestDir: {}; Flags: ignoreversion{check}",
+                quote(&escape_constants(&entry.path().display().to_string())),
+                quote(&dest_dir),
+            )?;
+        }
+    }
+    for file_type in metadata.file_types.iter() {
+        if let Some(icon) = file_type.icon.as_ref() {
+            writeln!(
+                script,
+                "Source: {}; DestDir: {}; DestName: {}; Flags: ignoreversion",
+                quote(&escape_constants(icon.as_str())),
+                quote(&format!("{{app}}\\{FILE_TYPE_ICONS_DIR}")),
+                quote(&format!("{}.ico", file_type.extension)),
+            )?;
+        }
+    }
+    writeln!(script)?;
+
+    if !metadata.file_types.is_empty() {
+        write_file_type_registry(&mut script, metadata, bin_name)?;
+    }
+
+    writeln!(script, "[Icons]")?;
+    write!(
+        script,
+        "Name: {}; Filename: {}",
+        quote(&format!("{{group}}\\{}", escape_constants(&metadata.name))),
+        quote(&format!("{{app}}\\{}", escape_constants(bin_name)))
+    )?;
+    // Shortcuts with the app's AppUserModelID keep its windows and pinned shortcut grouped on the taskbar.
+    if let Some(app_user_model_id) = metadata.app_user_model_id.as_deref() {
+        write!(
+            script,
+            "; AppUserModelID: {}",
+            quote(&escape_constants(app_user_model_id))
+        )?;
+    }
+    writeln!(script)?;
+    writeln!(
+        script,
+        "Name: {}; Filename: \"{{uninstallexe}}\"",
+        quote(&format!(
+            "{{group}}\\Uninstall {}",
+            escape_constants(&metadata.name)
+        ))
+    )?;
+
+    if !metadata.services.is_empty() {
+        writeln!(script)?;
+        write_service_sections(&mut script, metadata)?;
+    }
+
+    Ok(script)
+}
+
+/// Write the `[Run]` and `[UninstallRun]` sections that register the app's services with `sc.exe`,
+/// and the `[Code]` section that stops running services before their files are replaced.
+///
+/// `sc.exe` has no way to escape quotes, so display names and descriptions with them are rejected.
+///
+/// Services are created, or reconfigured if they already exist from an earlier install,
+/// and removed on uninstall.
+fn write_service_sections(script: &mut String, metadata: &InstallerMetadata) -> anyhow::Result<()> {
+    const SC: &str = "{sys}\\sc.exe";
+
+    for service in metadata.services.iter() {
+        for (field, value) in [
+            ("display name", Some(service.display_name.as_str())),
+            ("description", service.description.as_deref()),
+        ] {
+            if let Some(value) = value {
+                ensure!(
+                    !value.contains('"'),
+                    "the {field} of service `{}` contains a quote, which `sc.exe` cannot be passed",
+                    service.name
+                );
+            }
+        }
+    }
+
+    let write_sc = |script: &mut String, parameters: String, extra: &str| {
+        writeln!(
+            script,
+            "Filename: {}; Parameters: {}; Flags: runhidden{extra}",
+            quote(SC),
+            quote(&parameters)
+        )
+    };
+
+    writeln!(script, "[Run]")?;
+    for service in metadata.services.iter() {
+        let name = escape_constants(&service.name);
+        let exe = escape_constants(&service.exe.as_str().replace('/', "\\"));
+        let display_name = escape_constants(&service.display_name);
+        let start = service.start.get_name();
+        let status = format!(
+            "; StatusMsg: {}",
+            quote(&format!("Registering the {display_name} service..."))
+        );
+
+        // The exe path is quoted inside the binPath value, as the app dir usually has spaces.
+        for command in ["create", "config"] {
+            write_sc(
+                script,
+                format!(
+                    "{command} \"{name}\" binPath= \"\\\"{{app}}\\{exe}\\\"\" start= {start} DisplayName= \"{display_name}\""
+                ),
+                &status,
+            )?;
+        }
+        if let Some(description) = service.description.as_deref() {
+            write_sc(
+                script,
+                format!(
+                    "description \"{name}\" \"{}\"",
+                    escape_constants(description)
+                ),
+                "",
+            )?;
+        }
+        if let Some(restart_delay_secs) = service.restart_delay_secs {
+            let delay_ms = u64::from(restart_delay_secs) * 1000;
+            write_sc(
+                script,
+                format!(
+                    "failure \"{name}\" reset= 86400 actions= restart/{delay_ms}/restart/{delay_ms}/restart/{delay_ms}"
+                ),
+                "",
+            )?;
+        }
+        if matches!(
+            service.start,
+            ServiceStartType::Auto | ServiceStartType::DelayedAuto
+        ) {
+            write_sc(script, format!("start \"{name}\""), "")?;
+        }
+    }
+    writeln!(script)?;
+
+    writeln!(script, "[UninstallRun]")?;
+    for service in metadata.services.iter() {
+        let name = escape_constants(&service.name);
+        for command in ["stop", "delete"] {
+            write_sc(
+                script,
+                format!("{command} \"{name}\""),
+                &format!("; RunOnceId: {}", quote(&format!("{command}-{name}"))),
+            )?;
+        }
+    }
+    writeln!(script)?;
+
+    // Upgrades replace the service exes, which fails while they run.
+    // `net stop` waits for the service to stop, unlike `sc stop`, and fails harmlessly if it is not installed.
+    writeln!(script, "[Code]")?;
+    writeln!(
+        script,
+        "function PrepareToInstall(var NeedsRestart: Boolean): String;"
+    )?;
+    writeln!(script, "var")?;
+    writeln!(script, "  ResultCode: Integer;")?;
+    writeln!(script, "begin")?;
+    for service in metadata.services.iter() {
+        writeln!(
+            script,
+            "  Exec(ExpandConstant('{{sys}}\\net.exe'), '{}', '', SW_HIDE, ewWaitUntilTerminated, ResultCode);",
+            format!("stop \"{}\"", service.name).replace('\'', "''")
+        )?;
+    }
+    writeln!(script, "  Result := '';")?;
+    writeln!(script, "end;")?;
+
+    Ok(())
+}
+
+/// Write the `[Registry]` section that associates file types with the app.
+///
+/// Each file type gets a ProgID like `com.example.app.txt`, which is removed on uninstall.
+fn write_file_type_registry(
+    script: &mut String,
+    metadata: &InstallerMetadata,
+    bin_name: &str,
+) -> anyhow::Result<()> {
+    let prog_id_prefix = match metadata.identifier.as_deref() {
+        Some(identifier) => identifier.to_string(),
+        None => metadata
+            .name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect(),
+    };
+    let open_command = format!("\"{{app}}\\{}\" \"%1\"", escape_constants(bin_name));
+
+    writeln!(script, "[Registry]")?;
+    for file_type in metadata.file_types.iter() {
+        let prog_id = escape_constants(&format!("{prog_id_prefix}.{}", file_type.extension));
+        let class_key = format!("Software\\Classes\\{prog_id}");
+        let icon = match file_type.icon.as_ref() {
+            Some(_) => format!(
+                "{{app}}\\{FILE_TYPE_ICONS_DIR}\\{}.ico,0",
+                file_type.extension
+            ),
+            None => format!("{{app}}\\{},0", escape_constants(bin_name)),
+        };
+
+        writeln!(
+            script,
+            "Root: HKA; Subkey: {}; ValueType: string; ValueName: {}; ValueData: \"\"; Flags: uninsdeletevalue",
+            quote(&format!(
+                "Software\\Classes\\.{}\\OpenWithProgids",
+                file_type.extension
+            )),
+            quote(&prog_id)
+        )?;
+        writeln!(
+            script,
+            "Root: HKA; Subkey: {}; ValueType: string; ValueName: \"\"; ValueData: {}; Flags: uninsdeletekey",
+            quote(&class_key),
+            quote(&escape_constants(&file_type.description))
+        )?;
+        writeln!(
+            script,
+            "Root: HKA; Subkey: {}; ValueType: string; ValueName: \"\"; ValueData: {}",
+            quote(&format!("{class_key}\\DefaultIcon")),
+            quote(&icon)
+        )?;
+        writeln!(
+            script,
+            "Root: HKA; Subkey: {}; ValueType: string; ValueName: \"\"; ValueData: {}",
+            quote(&format!("{class_key}\\shell\\open\\command")),
+            quote(&open_command)
+        )?;
+    }
+    writeln!(script)?;
+
+    Ok(())
+}
+
+/// Find the Inno Setup compiler on the PATH, or in its usual install dirs.
+pub fn locate_iscc() -> Option<std::path::PathBuf> {
+    crate::util::find_on_path(ISCC_FILE_NAME).or_else(|| {
+        ISCC_INSTALL_DIRS
+            .iter()
+            .map(|dir| Path::new(dir).join(ISCC_FILE_NAME))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Get the Inno Setup architecture identifier of a 64-bit target triple.
+///
+/// # Returns
+/// Returns None for 32-bit targets, which install in 32-bit mode.
+fn get_architecture(target: &str) -> Option<&'static str> {
+    match target.split('-').next()? {
+        "x86_64" => Some("x64compatible"),
+        "aarch64" => Some("arm64"),
+        _ => None,
+    }
+}
+
+/// Get the Inno Setup check that selects the package of a target, when installing one of several packages.
+///
+/// A package is installed if the machine can run it and cannot run the package of a better target,
+/// where `aarch64` is better than `x86_64`, which is better than 32-bit targets.
+///
+/// # Returns
+/// Returns None if the package is always installed.
+fn get_check(target: &str, packages: &[TargetPackage]) -> Option<String> {
+    if packages.len() < 2 {
+        return None;
+    }
+
+    let get_rank_and_check = |target: &str| match target.split('-').next() {
+        Some("aarch64") => (2, Some("IsArm64")),
+        Some("x86_64") => (1, Some("IsX64Compatible")),
+        _ => (0, None),
+    };
+
+    let (rank, check) = get_rank_and_check(target);
+    let mut checks: Vec<_> = check.map(String::from).into_iter().collect();
+    for package in packages.iter() {
+        let (other_rank, other_check) = get_rank_and_check(package.target);
+        if other_rank > rank {
+            if let Some(other_check) = other_check {
+                checks.push(format!("not {other_check}"));
+            }
+        }
+    }
+    checks.dedup();
+
+    if checks.is_empty() {
+        None
+    } else {
+        Some(checks.join(" and "))
+    }
+}
+
+/// Escape the `{` that starts an Inno Setup constant.
+fn escape_constants(value: &str) -> String {
+    value.replace('{', "{{")
+}
+
+/// Quote a parameter value, doubling any quotes inside it.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::installer::Service;
+
+    fn make_metadata(services: Vec<Service>) -> InstallerMetadata {
+        InstallerMetadata {
+            name: "Hello".into(),
+            version: "1.0.0".into(),
+            identifier: None,
+            publisher: None,
+            description: None,
+            copyright: None,
+            icon: None,
+            license_file: None,
+            upgrade_guid: None,
+            app_user_model_id: None,
+            file_types: Vec::new(),
+            services,
+        }
+    }
+
+    fn make_service(name: &str) -> Service {
+        Service {
+            name: name.into(),
+            display_name: format!("{name} Display"),
+            description: None,
+            exe: "bin/service.exe".into(),
+            start: ServiceStartType::Demand,
+            restart_delay_secs: None,
+        }
+    }
+
+    #[test]
+    fn service_sections() {
+        let mut service = make_service("HelloService");
+        service.display_name = "Hello {app} Service".into();
+        service.description = Some("Says hello".into());
+        service.start = ServiceStartType::Auto;
+        service.restart_delay_secs = Some(5);
+        let metadata = make_metadata(vec![service]);
+
+        let mut script = String::new();
+        write_service_sections(&mut script, &metadata).unwrap();
+        assert_eq!(
+            script,
+            r#"[Run]
+Filename: "{sys}\sc.exe"; Parameters: "create ""HelloService"" binPath= ""\""{app}\bin\service.exe\"""" start= auto DisplayName= ""Hello {{app} Service"""; Flags: runhidden; StatusMsg: "Registering the Hello {{app} Service service..."
+Filename: "{sys}\sc.exe"; Parameters: "config ""HelloService"" binPath= ""\""{app}\bin\service.exe\"""" start= auto DisplayName= ""Hello {{app} Service"""; Flags: runhidden; StatusMsg: "Registering the Hello {{app} Service service..."
+Filename: "{sys}\sc.exe"; Parameters: "description ""HelloService"" ""Says hello"""; Flags: runhidden
+Filename: "{sys}\sc.exe"; Parameters: "failure ""HelloService"" reset= 86400 actions= restart/5000/restart/5000/restart/5000"; Flags: runhidden
+Filename: "{sys}\sc.exe"; Parameters: "start ""HelloService"""; Flags: runhidden
+
+[UninstallRun]
+Filename: "{sys}\sc.exe"; Parameters: "stop ""HelloService"""; Flags: runhidden; RunOnceId: "stop-HelloService"
+Filename: "{sys}\sc.exe"; Parameters: "delete ""HelloService"""; Flags: runhidden; RunOnceId: "delete-HelloService"
+
+[Code]
+function PrepareToInstall(var NeedsRestart: Boolean): String;
+var
+  ResultCode: Integer;
+begin
+  Exec(ExpandConstant('{sys}\net.exe'), 'stop "HelloService"', '', SW_HIDE, ewWaitUntilTerminated, ResultCode);
+  Result := '';
+end;
+"#
+        );
+    }
+
+    #[test]
+    fn service_sections_stop_every_service_before_install() {
+        let metadata = make_metadata(vec![make_service("First"), make_service("It's")]);
+
+        let mut script = String::new();
+        write_service_sections(&mut script, &metadata).unwrap();
+        let code = &script[script.find("[Code]").unwrap()..];
+        assert!(code.contains("'stop \"First\"'"), "{code}");
+        assert!(code.contains("'stop \"It''s\"'"), "{code}");
+        // Manual services are not started by the installer.
+        assert!(!script.contains("Parameters: \"start "), "{script}");
+    }
+
+    #[test]
+    fn service_sections_reject_quotes() {
+        let mut service = make_service("HelloService");
+        service.display_name = "Hello \"Service\"".into();
+        let mut script = String::new();
+        let error = write_service_sections(&mut script, &make_metadata(vec![service])).unwrap_err();
+        assert!(error.to_string().contains("display name"), "{error}");
+
+        let mut service = make_service("HelloService");
+        service.description = Some("Says \"hello\"".into());
+        let mut script = String::new();
+        let error = write_service_sections(&mut script, &make_metadata(vec![service])).unwrap_err();
+        assert!(error.to_string().contains("description"), "{error}");
+    }
+}
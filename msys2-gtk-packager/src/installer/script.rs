@@ -0,0 +1,295 @@
+use crate::installer::innosetup::TargetPackage;
+use crate::installer::InstallerMetadata;
+use anyhow::ensure;
+use anyhow::Context;
+use std::fmt::Write as _;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// The file name of the generated install script.
+pub const INSTALL_SCRIPT_FILE_NAME: &str = "install.ps1";
+
+/// The file name of the generated cargo-binstall metadata.
+pub const BINSTALL_FILE_NAME: &str = "binstall.toml";
+
+/// The placeholders of download urls, and the cargo-binstall placeholders they become.
+const URL_PLACEHOLDERS: &[(&str, &str)] = &[
+    ("{name}", "{ name }"),
+    ("{version}", "{ version }"),
+    ("{target}", "{ target }"),
+];
+
+/// Get the file name of the zip archive of a package, like `app-1.0.0-x86_64-pc-windows-gnu.zip`.
+pub fn get_archive_file_name(package_name: &str, version: &str, target: &str) -> String {
+    format!("{package_name}-{version}-{target}.zip")
+}
+
+/// Write the zip archive of a package dir, with the files of the dir at its top level.
+///
+/// Entries are sorted and have no timestamps, so the same package makes the same archive.
+pub fn write_archive(package_dir: &Path, archive_path: &Path) -> anyhow::Result<()> {
+    let file = std::fs::File::create(archive_path)
+        .with_context(|| format!("failed to create `{}`", archive_path.display()))?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+
+    for entry in WalkDir::new(package_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry
+            .path()
+            .strip_prefix(package_dir)?
+            .to_str()
+            .with_context(|| format!("`{}` is not valid unicode", entry.path().display()))?
+            .replace('\\', "/");
+        if entry.file_type().is_dir() {
+            zip.add_directory(name, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            let mut file = std::fs::File::open(entry.path())
+                .with_context(|| format!("failed to open `{}`", entry.path().display()))?;
+            std::io::copy(&mut file, &mut zip)?;
+        }
+    }
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Get the download url of the zip archives of a package from its repository, if it is on GitHub.
+///
+/// The url has the placeholders of [`expand_url`], and points at the assets of the `v{version}` release.
+pub fn get_default_url(repository: &str) -> Option<String> {
+    let repository = repository.trim_end_matches('/').trim_end_matches(".git");
+    repository.starts_with("https://github.com/").then(|| {
+        format!("{repository}/releases/download/v{{version}}/{{name}}-{{version}}-{{target}}.zip")
+    })
+}
+
+/// Replace the `{name}` and `{version}` placeholders of a download url.
+///
+/// `{target}` is left for the install script to replace, as it picks the target when it runs.
+pub fn expand_url(url: &str, package_name: &str, version: &str) -> String {
+    url.replace("{name}", package_name)
+        .replace("{version}", version)
+}
+
+/// Generate a PowerShell script that downloads the zip archive of a package and installs it for the current user.
+///
+/// The archive has the files of the package dir at its top level.
+/// They are installed to `%LOCALAPPDATA%\Programs\{package_name}`, replacing an earlier install,
+/// and a Start menu shortcut to `bin_name`, the file name of the main binary, is made.
+/// The install dir is not added to the user `PATH`, as other programs would then load the packaged dlls.
+/// If there are several packages, the script picks the best target the machine can run, like [`crate::installer::innosetup::generate_script`].
+/// `archive_sha256s` are the SHA-256s of the archives of `packages`, which the script checks before extracting the download.
+pub fn generate_install_script(
+    metadata: &InstallerMetadata,
+    package_name: &str,
+    packages: &[TargetPackage],
+    bin_name: &str,
+    archive_sha256s: &[String],
+    url: &str,
+) -> anyhow::Result<String> {
+    ensure!(
+        url.contains("{target}") || packages.len() == 1,
+        "the download url must contain `{{target}}` to install several targets"
+    );
+    ensure!(
+        packages.len() == archive_sha256s.len(),
+        "every package needs the SHA-256 of its archive"
+    );
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+
+    let mut ps1 = String::new();
+    writeln!(
+        ps1,
+        "# Installs {} {} for the current user.",
+        metadata.name, metadata.version
+    )?;
+    writeln!(ps1, "# Run with: irm <url of this script> | iex")?;
+    writeln!(ps1, "$ErrorActionPreference = 'Stop'")?;
+    writeln!(ps1, "$ProgressPreference = 'SilentlyContinue'")?;
+    writeln!(ps1)?;
+
+    // Machines can run the packages of their own arch, and ARM64 can also run x64.
+    writeln!(ps1, "$Targets = @{{")?;
+    for package in packages.iter() {
+        let arch = match package.target.split('-').next() {
+            Some("aarch64") => "ARM64",
+            Some("x86_64") => "AMD64",
+            _ => "x86",
+        };
+        writeln!(ps1, "    {} = {}", quote(arch), quote(package.target))?;
+    }
+    writeln!(ps1, "}}")?;
+    writeln!(ps1, "$Sha256s = @{{")?;
+    for (package, sha256) in packages.iter().zip(archive_sha256s) {
+        writeln!(ps1, "    {} = {}", quote(package.target), quote(sha256))?;
+    }
+    writeln!(ps1, "}}")?;
+    writeln!(ps1, "$Archs = switch ($env:PROCESSOR_ARCHITECTURE) {{")?;
+    writeln!(ps1, "    'ARM64' {{ @('ARM64', 'AMD64', 'x86') }}")?;
+    writeln!(ps1, "    'AMD64' {{ @('AMD64', 'x86') }}")?;
+    writeln!(ps1, "    default {{ @('x86') }}")?;
+    writeln!(ps1, "}}")?;
+    writeln!(
+        ps1,
+        "$Target = $Archs | Where-Object {{ $Targets.ContainsKey($_) }} | ForEach-Object {{ $Targets[$_] }} | Select-Object -First 1"
+    )?;
+    writeln!(
+        ps1,
+        "if (-not $Target) {{ throw \"{} is not available for $env:PROCESSOR_ARCHITECTURE\" }}",
+        metadata.name.replace(['"', '`', '$'], "")
+    )?;
+    writeln!(ps1)?;
+
+    writeln!(
+        ps1,
+        "$Url = {}.Replace('{{target}}', $Target)",
+        quote(&expand_url(url, package_name, &metadata.version))
+    )?;
+    writeln!(
+        ps1,
+        "$InstallDir = Join-Path $env:LOCALAPPDATA {}",
+        quote(&format!("Programs\\{package_name}"))
+    )?;
+    writeln!(
+        ps1,
+        "$Temp = Join-Path ([System.IO.Path]::GetTempPath()) ([System.IO.Path]::GetRandomFileName())"
+    )?;
+    writeln!(ps1, "New-Item -ItemType Directory -Path $Temp | Out-Null")?;
+    writeln!(ps1, "try {{")?;
+    writeln!(ps1, "    $Archive = Join-Path $Temp 'package.zip'")?;
+    writeln!(ps1, "    Write-Host \"Downloading $Url\"")?;
+    writeln!(
+        ps1,
+        "    Invoke-WebRequest -Uri $Url -OutFile $Archive -UseBasicParsing"
+    )?;
+    writeln!(
+        ps1,
+        "    $Sha256 = (Get-FileHash -Path $Archive -Algorithm SHA256).Hash"
+    )?;
+    writeln!(
+        ps1,
+        "    if ($Sha256 -ne $Sha256s[$Target]) {{ throw \"The SHA-256 of $Url is $Sha256, expected $($Sha256s[$Target])\" }}"
+    )?;
+    writeln!(
+        ps1,
+        "    Expand-Archive -Path $Archive -DestinationPath (Join-Path $Temp 'package')"
+    )?;
+    writeln!(
+        ps1,
+        "    if (Test-Path $InstallDir) {{ Remove-Item -Recurse -Force $InstallDir }}"
+    )?;
+    writeln!(
+        ps1,
+        "    New-Item -ItemType Directory -Path (Split-Path $InstallDir) -Force | Out-Null"
+    )?;
+    writeln!(
+        ps1,
+        "    Move-Item -Path (Join-Path $Temp 'package') -Destination $InstallDir"
+    )?;
+    writeln!(ps1, "}} finally {{")?;
+    writeln!(
+        ps1,
+        "    Remove-Item -Recurse -Force $Temp -ErrorAction SilentlyContinue"
+    )?;
+    writeln!(ps1, "}}")?;
+    writeln!(ps1)?;
+
+    writeln!(
+        ps1,
+        "$Shortcut = Join-Path ([Environment]::GetFolderPath('Programs')) {}",
+        quote(&format!("{}.lnk", metadata.name))
+    )?;
+    writeln!(
+        ps1,
+        "$Link = (New-Object -ComObject WScript.Shell).CreateShortcut($Shortcut)"
+    )?;
+    writeln!(
+        ps1,
+        "$Link.TargetPath = Join-Path $InstallDir {}",
+        quote(bin_name)
+    )?;
+    writeln!(ps1, "$Link.WorkingDirectory = $InstallDir")?;
+    writeln!(ps1, "$Link.Save()")?;
+    writeln!(ps1, "Write-Host \"Installed to $InstallDir\"")?;
+
+    Ok(ps1.replace('\n', "\r\n"))
+}
+
+/// Generate the `[package.metadata.binstall]` section of a Cargo.toml that points cargo-binstall at the zip archives of a package.
+///
+/// The placeholders of the download url are converted to the ones of cargo-binstall.
+pub fn generate_binstall_metadata(url: &str) -> String {
+    let mut pkg_url = url.to_string();
+    for (placeholder, binstall_placeholder) in URL_PLACEHOLDERS {
+        pkg_url = pkg_url.replace(placeholder, binstall_placeholder);
+    }
+
+    let mut toml = String::new();
+    toml.push_str("[package.metadata.binstall]\n");
+    toml.push_str(&format!("pkg-url = {}\n", toml_string(&pkg_url)));
+    toml.push_str("bin-dir = \"{ bin }{ binary-ext }\"\n");
+    toml.push_str("pkg-fmt = \"zip\"\n");
+    toml
+}
+
+/// Quote a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_script_makes_a_shortcut_instead_of_changing_path() {
+        let metadata = InstallerMetadata {
+            name: "Hello".into(),
+            version: "1.0.0".into(),
+            identifier: None,
+            publisher: None,
+            description: None,
+            copyright: None,
+            icon: None,
+            license_file: None,
+            upgrade_guid: None,
+            app_user_model_id: None,
+            file_types: Vec::new(),
+            services: Vec::new(),
+        };
+        let packages = [TargetPackage {
+            package_dir: Path::new("hello"),
+            target: "x86_64-pc-windows-gnu",
+        }];
+        let script = generate_install_script(
+            &metadata,
+            "hello",
+            &packages,
+            "hello.exe",
+            &["0".repeat(64)],
+            "https://example.com/{name}-{version}-{target}.zip",
+        )
+        .unwrap();
+
+        assert!(script.contains(
+            "$Shortcut = Join-Path ([Environment]::GetFolderPath('Programs')) 'Hello.lnk'\r\n"
+        ));
+        assert!(script.contains("$Link.TargetPath = Join-Path $InstallDir 'hello.exe'\r\n"));
+        assert!(!script.contains("SetEnvironmentVariable"));
+    }
+}
@@ -0,0 +1,255 @@
+use msys2_packager::packager::Plan;
+use std::fmt::Write as _;
+
+/// The env vars a launcher sets, with paths relative to the package dir.
+///
+/// These point GTK at the loaders, schemas, plugins, and data of the package,
+/// instead of paths baked into the MSYS2 build or left over from another install.
+/// Launchers only set the vars whose path is in the package, as GTK fails to load files that a var points to but that do not exist,
+/// while it falls back to its defaults if the var is unset.
+pub const LAUNCHER_ENV: &[(&str, &str)] = &[
+    (
+        "GDK_PIXBUF_MODULE_FILE",
+        "lib/gdk-pixbuf-2.0/2.10.0/loaders.cache",
+    ),
+    ("GSETTINGS_SCHEMA_DIR", "share/glib-2.0/schemas"),
+    ("GST_PLUGIN_PATH", "lib/gstreamer-1.0"),
+    ("GIO_MODULE_DIR", "lib/gio/modules"),
+    ("GI_TYPELIB_PATH", crate::typelib::TYPELIB_DIR),
+    ("XDG_DATA_DIRS", "share"),
+];
+
+/// The suffix of the launcher file name, which is placed after the name of the exe it starts.
+pub const LAUNCHER_SUFFIX: &str = "-launcher";
+
+/// The name of the generated launcher crate.
+pub const LAUNCHER_CRATE_NAME: &str = "gtk-packager-launcher";
+
+/// The Cargo.toml of the generated launcher crate.
+///
+/// The empty workspace keeps it out of any workspace it is generated in.
+pub const LAUNCHER_CARGO_TOML: &str = r#"[package]
+name = "gtk-packager-launcher"
+version = "0.0.0"
+edition = "2021"
+publish = false
+
+[workspace]
+
+[profile.release]
+opt-level = "z"
+lto = true
+codegen-units = 1
+panic = "abort"
+strip = true
+"#;
+
+/// An error that may occur while parsing a [`LauncherKind`] from a string.
+#[derive(Debug)]
+pub struct LauncherKindFromStrError(String);
+
+impl std::fmt::Display for LauncherKindFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid launcher kind", self.0)
+    }
+}
+
+impl std::error::Error for LauncherKindFromStrError {}
+
+/// A kind of launcher, which sets up the environment of a packaged exe before starting it
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LauncherKind {
+    /// A small exe, built from a generated crate for the same target as the app.
+    Exe,
+
+    /// A batch script.
+    Bat,
+
+    /// A PowerShell script.
+    PowerShell,
+}
+
+impl LauncherKind {
+    /// Get the file name of the launcher for an exe, like `app-launcher.bat` for `app.exe`.
+    pub fn get_file_name(self, exe_name: &str) -> String {
+        let stem = exe_name.strip_suffix(".exe").unwrap_or(exe_name);
+        let extension = match self {
+            Self::Exe => "exe",
+            Self::Bat => "bat",
+            Self::PowerShell => "ps1",
+        };
+        format!("{stem}{LAUNCHER_SUFFIX}.{extension}")
+    }
+}
+
+impl std::str::FromStr for LauncherKind {
+    type Err = LauncherKindFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "exe" => Ok(Self::Exe),
+            "bat" => Ok(Self::Bat),
+            "powershell" => Ok(Self::PowerShell),
+            _ => Err(LauncherKindFromStrError(input.into())),
+        }
+    }
+}
+
+/// Get the env vars of [`LAUNCHER_ENV`] whose path is a file or dir in a plan.
+pub fn get_launcher_env(plan: &Plan) -> Vec<(&'static str, &'static str)> {
+    LAUNCHER_ENV
+        .iter()
+        .copied()
+        .filter(|(_, path)| plan.files.iter().any(|file| file.dest.starts_with(path)))
+        .collect()
+}
+
+/// Generate a batch script that sets `env`, with paths relative to the dir of the script, and starts `exe_name` from that dir.
+pub fn generate_bat(exe_name: &str, env: &[(&str, &str)]) -> anyhow::Result<String> {
+    let mut bat = String::new();
+    writeln!(bat, "@echo off")?;
+    writeln!(bat, "setlocal")?;
+    // `%~dp0` is the dir of the script, with a trailing backslash.
+    writeln!(bat, "set \"APP_DIR=%~dp0\"")?;
+    for (key, path) in env {
+        writeln!(bat, "set \"{key}=%APP_DIR%{}\"", path.replace('/', "\\"))?;
+    }
+    writeln!(bat, "\"%APP_DIR%{exe_name}\" %*")?;
+    writeln!(bat, "exit /b %ERRORLEVEL%")?;
+
+    // Batch scripts need CRLF line endings.
+    Ok(bat.replace('\n', "\r\n"))
+}
+
+/// Generate a PowerShell script that sets `env`, with paths relative to the dir of the script, and starts `exe_name` from that dir.
+pub fn generate_powershell(exe_name: &str, env: &[(&str, &str)]) -> anyhow::Result<String> {
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+
+    let mut ps1 = String::new();
+    writeln!(ps1, "$AppDir = $PSScriptRoot")?;
+    for (key, path) in env {
+        writeln!(
+            ps1,
+            "$env:{key} = Join-Path $AppDir {}",
+            quote(&path.replace('/', "\\"))
+        )?;
+    }
+    writeln!(ps1, "& (Join-Path $AppDir {}) @args", quote(exe_name))?;
+    writeln!(ps1, "exit $LASTEXITCODE")?;
+
+    Ok(ps1.replace('\n', "\r\n"))
+}
+
+/// Generate the `main.rs` of the launcher crate.
+///
+/// The launcher finds the exe to start from its own name, by removing [`LAUNCHER_SUFFIX`],
+/// so one build can be copied for every exe of a package.
+/// The launcher is built before packaging, so it checks which paths of [`LAUNCHER_ENV`] exist when it starts.
+/// If `app_user_model_id` is set, the launcher calls `SetCurrentProcessExplicitAppUserModelID` with it first,
+/// so pinning and jump lists of the launcher belong to the app.
+pub fn generate_exe_source(app_user_model_id: Option<&str>) -> anyhow::Result<String> {
+    let mut source = String::new();
+    writeln!(source, "#![windows_subsystem = \"windows\"]")?;
+    writeln!(source)?;
+    writeln!(source, "use std::process::Command;")?;
+    writeln!(source)?;
+    writeln!(source, "const ENV: &[(&str, &str)] = &[")?;
+    for (key, path) in LAUNCHER_ENV {
+        writeln!(source, "    ({key:?}, {path:?}),")?;
+    }
+    writeln!(source, "];")?;
+    writeln!(source)?;
+    if let Some(app_user_model_id) = app_user_model_id {
+        writeln!(
+            source,
+            "const APP_USER_MODEL_ID: &str = {app_user_model_id:?};"
+        )?;
+        writeln!(source)?;
+        writeln!(source, "#[link(name = \"shell32\")]")?;
+        writeln!(source, "extern \"system\" {{")?;
+        writeln!(
+            source,
+            "    fn SetCurrentProcessExplicitAppUserModelID(app_id: *const u16) -> i32;"
+        )?;
+        writeln!(source, "}}")?;
+        writeln!(source)?;
+    }
+    writeln!(source, "fn main() {{")?;
+    if app_user_model_id.is_some() {
+        writeln!(
+            source,
+            r#"    let app_id: Vec<u16> = APP_USER_MODEL_ID.encode_utf16().chain(std::iter::once(0)).collect();
+    // This only fails for invalid ids, which are rejected when the launcher is generated.
+    unsafe {{
+        SetCurrentProcessExplicitAppUserModelID(app_id.as_ptr());
+    }}
+"#
+        )?;
+    }
+    writeln!(
+        source,
+        r#"    let launcher = std::env::current_exe().expect("failed to get the launcher path");
+    let dir = launcher.parent().expect("the launcher path has no parent");
+    let stem = launcher
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .expect("the launcher name is not valid unicode");
+    let name = stem.strip_suffix({LAUNCHER_SUFFIX:?}).unwrap_or(stem);
+
+    // Windows cannot replace a process, so wait on the app and pass on its exit code.
+    let status = Command::new(dir.join(format!("{{name}}.exe")))
+        .args(std::env::args_os().skip(1))
+        .envs(
+            ENV.iter()
+                .map(|(key, path)| (key, dir.join(path)))
+                .filter(|(_, path)| path.exists()),
+        )
+        .status()
+        .expect("failed to start the app");
+    std::process::exit(status.code().unwrap_or(1));
+}}"#
+    )?;
+
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launcher_env_only_has_packaged_paths() {
+        let plan: Plan = serde_json::from_str(
+            r#"{
+                "files": [
+                    { "src": "app.exe", "dest": "app.exe", "flags": ["exe"], "size": 1 },
+                    { "src": "a.typelib", "dest": "lib/girepository-1.0/Gtk-4.0.typelib", "flags": [], "size": 1 },
+                    { "src": "hello.css", "dest": "share/hello/hello.css", "flags": [], "size": 1 }
+                ],
+                "unresolved": [],
+                "total_size": 3
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            get_launcher_env(&plan),
+            [
+                ("GI_TYPELIB_PATH", "lib/girepository-1.0"),
+                ("XDG_DATA_DIRS", "share")
+            ]
+        );
+    }
+
+    #[test]
+    fn scripts_set_the_given_env() {
+        let env = [("XDG_DATA_DIRS", "share")];
+
+        let bat = generate_bat("app.exe", &env).unwrap();
+        assert!(bat.contains("set \"XDG_DATA_DIRS=%APP_DIR%share\"\r\n"));
+        assert!(!bat.contains("GDK_PIXBUF_MODULE_FILE"));
+
+        let ps1 = generate_powershell("app.exe", &env).unwrap();
+        assert!(ps1.contains("$env:XDG_DATA_DIRS = Join-Path $AppDir 'share'\r\n"));
+        assert!(!ps1.contains("GDK_PIXBUF_MODULE_FILE"));
+    }
+}
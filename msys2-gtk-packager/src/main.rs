@@ -1,31 +1,156 @@
+mod adwaita;
+mod asset;
 mod commands;
+mod crash;
+mod dev_env;
+mod gettext;
+mod gio_modules;
+mod gresource;
+mod gtksourceview;
+mod ide;
+mod immodules;
+mod installer;
+mod launcher;
+mod manifest;
+mod notifications;
+mod package_info;
+mod preflight;
+mod print_backends;
+mod resource;
+mod spawn_helpers;
+mod spell_check;
+mod system_dll;
+mod theme;
+mod trace;
+mod typelib;
 mod util;
 
+use crate::asset::Asset;
+use crate::gettext::GettextDomain;
+use crate::installer::InstallerMetadata;
+use crate::launcher::LauncherKind;
+use crate::manifest::ManifestMode;
+use crate::notifications::NotificationSupport;
+use crate::package_info::GitStatus;
+use crate::package_info::PackageInfo;
+use crate::package_info::PACKAGE_INFO_FILE_NAME;
+use crate::resource::EmbedResources;
+use crate::system_dll::SystemDllRules;
+use crate::theme::Theme;
+use crate::util::CargoFeatures;
+use crate::util::TargetKind;
 use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context as _;
 use camino::Utf8Path;
 use camino::Utf8PathBuf;
 use msys2::Msys2Environment;
+use msys2_packager::auditable::AuditableManifest;
+use msys2_packager::knowledge::KnowledgeBase;
+use msys2_packager::lock::Lockfile;
+use msys2_packager::packager::write_checksums;
+use msys2_packager::packager::CrtCheck;
 use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::FileReason;
+use msys2_packager::packager::OverwritePolicy;
 use msys2_packager::packager::Packager;
+use msys2_packager::packager::CACHE_FILE_NAME;
+use msys2_packager::pacman::LocalDatabase;
+use msys2_packager::policy::Policy;
+use msys2_packager::policy::PolicyComponent;
+use msys2_packager::policy::PolicyComponentKind;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::report::PackageReport;
+use msys2_packager::sbom::Sbom;
+use msys2_packager::sbom::SbomComponent;
+use msys2_packager::sbom::SbomFormat;
+use msys2_packager::sign::SignOptions;
+use msys2_packager::store::DllStore;
+use msys2_packager::symbols::SymbolServer;
+use msys2_packager::util::get_reproducible_timestamp;
+use msys2_packager::util::UpxOptions;
+use msys2_packager::version::VersionInfo;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::fs::File;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
+use std::process::Command;
 use walkdir::WalkDir;
 
+/// The name of the lockfile, placed in the workspace root.
+const LOCK_FILE_NAME: &str = "gtk-packager.lock";
+
+/// The env var that sets the profile dir, in place of `--profile-dir`.
+const PROFILE_DIR_ENV: &str = "MSYS2_GTK_PACKAGER_PROFILE_DIR";
+
+/// The env var that sets the dll store dir, in place of `--dll-store`.
+const DLL_STORE_ENV: &str = "MSYS2_GTK_PACKAGER_DLL_STORE";
+
+/// The name of the dll store dir in a profile dir.
+const DLL_STORE_DIR_NAME: &str = "dll-store";
+
+/// The `settings.ini` written for apps that do not use libadwaita.
+const SETTINGS_INI: &str = "[Settings]\ngtk-theme-name=Dracula\n";
+
 #[derive(Debug, argh::FromArgs)]
 #[argh(description = "A tool to aide in building GTK-rs programs for Windows, backed by MSYS2")]
 struct Options {
     #[argh(subcommand)]
-    subcommand: Subcommand,
+    subcommand: Option<Subcommand>,
+
+    #[argh(switch, description = "print the version")]
+    version: bool,
+
+    #[argh(
+        switch,
+        description = "with `--version`, print build info like the git hash and crate versions"
+    )]
+    verbose: bool,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the version printed to stdout, `text` or `json`"
+    )]
+    output_format: OutputFormat,
+
+    #[argh(
+        switch,
+        description = "if this tool crashes, write a crash report to a file in the temp dir, or the profile dir if one is set. This can also be enabled with the MSYS2_GTK_PACKAGER_CRASH_REPORT env var"
+    )]
+    crash_report: bool,
+
+    #[argh(
+        option,
+        description = "the dir for caches and logs that may be shared between checkouts, like on a build farm. Defaults to a dir in the cargo target dir. This can also be set with the MSYS2_GTK_PACKAGER_PROFILE_DIR env var"
+    )]
+    profile_dir: Option<Utf8PathBuf>,
 }
 
 #[derive(Debug, argh::FromArgs)]
 #[argh(subcommand)]
 enum Subcommand {
     Build(crate::commands::build::Options),
+    Cache(crate::commands::cache::Options),
+    Diff(crate::commands::diff::Options),
+    Doctor(crate::commands::doctor::Options),
+    Explain(crate::commands::explain::Options),
+    ExportEnv(crate::commands::export_env::Options),
+    IdeSetup(crate::commands::ide_setup::Options),
+    Inspect(crate::commands::inspect::Options),
+    Installer(crate::commands::installer::Options),
+    Knowledge(msys2_packager::cli::knowledge::Options),
+    MinOs(crate::commands::min_os::Options),
     Package(crate::commands::package::Options),
+    Run(crate::commands::run::Options),
+    Size(crate::commands::size::Options),
+    Test(crate::commands::test::Options),
+    Verify(crate::commands::verify::Options),
 }
 
 /// The CLI context
@@ -44,54 +169,144 @@ pub struct Context {
 
     /// Data needed to perform a `cargo build`.
     pub build_data: Option<BuildData>,
+
+    /// The cargo features to build with
+    pub cargo_features: CargoFeatures,
+
+    /// Whether to build through `cargo auditable`
+    pub auditable: bool,
+
+    /// Whether to install the rust target with rustup if it is missing, instead of failing
+    pub auto_install_target: bool,
+
+    /// Whether to skip checking that the MSYS2 packages needed to build are installed, see [`crate::preflight::run_preflight`]
+    pub skip_preflight: bool,
+
+    /// Whether to install the MSYS2 packages needed to build with pacman if they are missing, instead of failing
+    pub auto_install_msys2_deps: bool,
+
+    /// The dir for caches and logs that may be shared between checkouts, if it was overridden
+    pub profile_dir: Option<Utf8PathBuf>,
 }
 
 impl Context {
     /// Make a new [`Context`].
-    pub fn new() -> anyhow::Result<Self> {
+    ///
+    /// `profile_dir` overrides the dir for caches and logs, see [`Context::get_profile_dir`].
+    pub fn new(profile_dir: Option<Utf8PathBuf>) -> anyhow::Result<Self> {
         let msys2_installation_path = msys2_packager::util::locate_msys2_installation()
             .context("failed to locate MSYS2 installation")?;
 
-        // This is required, as all current subcommands will need this data.
-        //
-        // If this changes in the future, make this optional.
+        // This is required, as all subcommands that make a context need this data.
         let cargo_metadata = cargo_metadata::MetadataCommand::new()
             .exec()
             .context("failed to get cargo metadata")?;
 
-        Ok(Self {
+        let ctx = Self {
             msys2_installation_path,
             msys2_environment: None,
             cargo_metadata,
             profile: None,
             build_data: None,
-        })
+            cargo_features: CargoFeatures::default(),
+            auditable: false,
+            auto_install_target: false,
+            skip_preflight: false,
+            auto_install_msys2_deps: false,
+            profile_dir,
+        };
+        KnowledgeBase::init_global(ctx.get_profile_dir()?.as_std_path());
+
+        Ok(ctx)
     }
 
-    /// Run a cargo build.
+    /// Get the dir for caches and logs that may be shared between checkouts.
+    ///
+    /// This defaults to this tool's dir in the cargo target dir,
+    /// and can be pointed at a per-machine dir so build farms can share caches.
+    /// Build outputs, like package dirs, are always kept in the cargo target dir.
+    pub fn get_profile_dir(&self) -> anyhow::Result<Utf8PathBuf> {
+        let profile_dir = match self.profile_dir.as_ref() {
+            Some(profile_dir) => profile_dir.clone(),
+            None => self
+                .cargo_metadata
+                .target_directory
+                .join(env!("CARGO_CRATE_NAME")),
+        };
+        std::fs::create_dir_all(&profile_dir)
+            .with_context(|| format!("failed to create `{profile_dir}`"))?;
+
+        Ok(profile_dir)
+    }
+
+    /// Make a cargo command for the given target and profile, using MSYS2's pkg-config.
     ///
-    /// `bin` is not validated before the command is invoked.
-    pub fn run_cargo_build(&self, build: Option<&str>) -> anyhow::Result<()> {
+    /// If the workspace pins a toolchain, see [`crate::util::get_toolchain`], it is checked and used.
+    /// The rust target is checked too, see [`crate::util::ensure_rust_target`],
+    /// along with the MSYS2 packages, unless [`Context::skip_preflight`] is set.
+    /// Missing MSYS2 packages are installed if [`Context::auto_install_msys2_deps`] is set.
+    pub fn create_cargo_build(
+        &self,
+        target: &str,
+        profile: &str,
+    ) -> anyhow::Result<crate::util::CargoBuild> {
         let msys2_installation_path = &self.msys2_installation_path;
         let msys2_environment = self
             .msys2_environment
             .context("missing `msys2_environment`")?;
-        let build_data = self.build_data.as_ref().context("missing build data")?;
-        let target = build_data.target.as_str();
-        let profile = build_data.profile.as_str();
-        let bin = build_data.bin.as_str();
+
+        if self.auto_install_msys2_deps || !self.skip_preflight {
+            // Tests are built without build data, so they only need the base packages.
+            let crates: HashSet<&str> = match self.build_data.as_ref() {
+                Some(_) => self
+                    .get_normal_dependencies()?
+                    .into_iter()
+                    .map(|package| package.name.as_str())
+                    .collect(),
+                None => HashSet::new(),
+            };
+            let packages = crate::preflight::get_required_packages(msys2_environment, &crates);
+            if self.auto_install_msys2_deps {
+                crate::preflight::install_missing_packages(msys2_installation_path, packages)?;
+            } else {
+                crate::preflight::run_preflight(
+                    msys2_installation_path,
+                    msys2_environment,
+                    packages,
+                )?;
+            }
+        }
 
         let rel_prefix = msys2_environment.get_prefix().trim_start_matches('/');
         let env_sysroot = msys2_installation_path.join(rel_prefix);
 
+        // pkgconf prepends the sysroot to absolute prefixes, so a moved installation only breaks files that hardcode another path.
+        let pkg_config_problems = msys2_packager::pkg_config::check_pkg_config_dirs(
+            env_sysroot.as_std_path(),
+            Some(msys2_installation_path.as_std_path()),
+        )
+        .context("failed to check pkg-config files")?;
+        if let Some(problem) = pkg_config_problems.first() {
+            eprintln!(
+                "warning: {problem}. {} pkg-config files in the MSYS2 environment have prefixes that do not exist, so crates may fail to link against it",
+                pkg_config_problems.len()
+            );
+        }
+
+        let toolchain = crate::util::get_toolchain(&self.cargo_metadata)?;
+        if let Some(toolchain) = toolchain.as_deref() {
+            crate::util::validate_toolchain(toolchain)?;
+        }
+        crate::util::ensure_rust_target(target, toolchain.as_deref(), self.auto_install_target)?;
+
         let mut cargo_build = crate::util::CargoBuild::new();
-        if let Some(build) = build {
-            cargo_build.build(build.into());
+        if let Some(toolchain) = toolchain {
+            cargo_build.toolchain(toolchain);
         }
         cargo_build
             .target(target.into())
             .profile(profile.into())
-            .bin(bin.into())
+            .features(self.cargo_features.clone())
             .env(
                 // TODO: Consider ripping out pkg-config and locating all these libs manually so users can use pkg-config for other stuff, or extend those env vars.
                 "PKG_CONFIG_SYSROOT_DIR".into(),
@@ -103,46 +318,308 @@ impl Context {
                     .join(env_sysroot)
                     .join("lib/pkgconfig")
                     .into(),
-            )
-            .exec()
+            );
+
+        Ok(cargo_build)
+    }
+
+    /// Run a cargo build.
+    ///
+    /// `bins` are not validated before the command is invoked.
+    ///
+    /// `args` are passed to cargo verbatim.
+    ///
+    /// `embed_resources` are linked into the built binaries, see [`Context::compile_resources`].
+    ///
+    /// Bench and test binaries have hashed file names,
+    /// so their paths are taken from cargo's output and saved in the build data.
+    pub fn run_cargo_build(
+        &mut self,
+        build: Option<&str>,
+        args: Vec<OsString>,
+        embed_resources: EmbedResources,
+    ) -> anyhow::Result<()> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let target = build_data.target.as_str();
+        let profile = build_data.profile.as_str();
+        let kind = build_data.kind;
+
+        let mut cargo_build = self.create_cargo_build(target, profile)?;
+        cargo_build.auditable(self.auditable);
+        if let Some(build) = build {
+            cargo_build.build(build.into());
+        }
+        if let Some(package) = build_data.package.as_ref() {
+            cargo_build.package(package.clone());
+        }
+        cargo_build.args(args);
+
+        // Link the resources with `cargo rustc`, as rustflags from `--config` or the env would replace the user's.
+        // It only takes rustc args for one target, so each bin is then built on its own.
+        let bin_groups: Vec<&[String]> = if embed_resources.is_empty() {
+            vec![&build_data.bins]
+        } else {
+            ensure!(
+                build.is_none(),
+                "resources cannot be embedded with a build subcommand other than `build`"
+            );
+            let object_path = self.compile_resources(embed_resources)?;
+            cargo_build
+                .rustc_arg("-C".into())
+                .rustc_arg(format!("link-arg={object_path}").into());
+            build_data.bins.chunks(1).collect()
+        };
+
+        let mut executables = HashMap::new();
+        for bins in bin_groups {
+            let mut cargo_build = cargo_build.clone();
+            for bin in bins {
+                cargo_build.cargo_target(kind, bin.clone());
+            }
+
+            if kind.get_output_dir().is_some() {
+                cargo_build.exec()?;
+                continue;
+            }
+
+            executables.extend(
+                cargo_build
+                    .exec_messages()?
+                    .into_iter()
+                    .filter_map(|message| match message {
+                        cargo_metadata::Message::CompilerArtifact(artifact)
+                            if artifact
+                                .target
+                                .kind
+                                .iter()
+                                .any(|artifact_kind| artifact_kind == kind.get_cargo_kind()) =>
+                        {
+                            Some((artifact.target.name, artifact.executable?))
+                        }
+                        _ => None,
+                    }),
+            );
+        }
+        if kind.get_output_dir().is_none() {
+            if let Some(build_data) = self.build_data.as_mut() {
+                build_data.executables = executables;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the test binaries of the workspace packages for a target, without running them.
+    ///
+    /// This will update associated data, like the msys2 environment.
+    ///
+    /// # Returns
+    /// Returns the paths to the test binaries.
+    pub fn build_tests(&mut self, target: &str, profile: &str) -> anyhow::Result<Vec<Utf8PathBuf>> {
+        let msys2_environment = msys2_packager::util::target_triple_to_msys2_environment(target)
+            .with_context(|| format!("failed to translate `{target}` into a MSYS2 environment"))?;
+        self.msys2_environment = Some(msys2_environment);
+
+        let messages = self
+            .create_cargo_build(target, profile)?
+            .build("test".into())
+            .arg("--no-run".into())
+            .exec_messages()?;
+
+        Ok(messages
+            .into_iter()
+            .filter_map(|message| match message {
+                cargo_metadata::Message::CompilerArtifact(artifact) if artifact.profile.test => {
+                    artifact.executable
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Get the dir where test binaries are bundled with their dependencies.
+    pub fn get_test_bundle_dir(&self, target: &str, profile: &str) -> anyhow::Result<Utf8PathBuf> {
+        // "dev" profile maps to "debug" in target folder
+        let profile = if profile == "dev" { "debug" } else { profile };
+
+        let out_dir = self
+            .cargo_metadata
+            .target_directory
+            .join(target)
+            .join(env!("CARGO_CRATE_NAME"))
+            .join("tests")
+            .join(target)
+            .join(profile);
+
+        std::fs::create_dir_all(&out_dir).context("failed to create test bundle dir")?;
+
+        Ok(out_dir)
+    }
+
+    /// Bundle test binaries with the dlls they need.
+    pub fn bundle_tests(
+        &self,
+        test_bins: &[Utf8PathBuf],
+        bundle_dir: &Utf8Path,
+        extra_libraries: &[String],
+        jobs: usize,
+    ) -> anyhow::Result<PackageReport> {
+        let msys2_environment = self
+            .msys2_environment
+            .context("missing msys2 environment")?;
+
+        let mut packager = Packager::new(
+            self.msys2_installation_path.clone(),
+            msys2_environment,
+            bundle_dir.into(),
+        );
+        packager
+            .resolve_unknown_libraries(true)
+            .jobs(jobs)
+            .incremental(true)
+            .add_file(
+                None,
+                "gdbus.exe".into(),
+                FileFlags::EXE | FileFlags::ADD_DEPS,
+            );
+        for test_bin in test_bins {
+            let name = test_bin.file_name().context("test binary has no name")?;
+            packager.add_file(
+                Some(test_bin.into()),
+                name.into(),
+                FileFlags::EXE | FileFlags::ADD_DEPS,
+            );
+        }
+        for library in extra_libraries {
+            packager.add_file(None, library.into(), FileFlags::LIB | FileFlags::ADD_DEPS);
+        }
+
+        packager.package().context("failed to bundle tests")
     }
 
     /// Validate and set cargo build data.
     ///
     /// This will update associated data, like the msys2 environment.
     ///
+    /// If `package` is set, `bins` must be in that package.
+    /// If `bins` is empty, the only bin of `package`, or the root package if it is None, is used.
+    ///
     /// Currently, `profile` is not validated.
-    pub fn set_build_data(&mut self, target: &str, profile: &str, bin: &str) -> anyhow::Result<()> {
+    pub fn set_build_data(
+        &mut self,
+        target: &str,
+        profile: &str,
+        package: Option<&str>,
+        kind: TargetKind,
+        bins: Vec<String>,
+    ) -> anyhow::Result<()> {
         let msys2_environment = msys2_packager::util::target_triple_to_msys2_environment(target)
             .with_context(|| format!("failed to translate `{target}` into a MSYS2 environment"))?;
 
-        // Validate bin
-        let bin_is_valid = self
-            .cargo_metadata
-            .packages
-            .iter()
-            .flat_map(|package| {
-                package
-                    .targets
-                    .iter()
-                    .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
-            })
-            .any(|target| target.name == bin);
+        let bins = if bins.is_empty() {
+            ensure!(kind == TargetKind::Bin, "missing {}", kind.get_cargo_kind());
+            vec![self.get_default_bin(package)?]
+        } else {
+            bins
+        };
 
-        ensure!(bin_is_valid, "`{}` is not a valid bin", bin);
+        // Validate bins
+        let packages = match package {
+            Some(package) => vec![crate::util::get_package(&self.cargo_metadata, package)?],
+            None => self.cargo_metadata.packages.iter().collect(),
+        };
+        for bin in bins.iter() {
+            let bin_is_valid = packages
+                .iter()
+                .flat_map(|package| {
+                    package.targets.iter().filter(|target| {
+                        target
+                            .kind
+                            .iter()
+                            .any(|target_kind| target_kind == kind.get_cargo_kind())
+                    })
+                })
+                .any(|target| &target.name == bin);
+
+            match package {
+                Some(package) => ensure!(
+                    bin_is_valid,
+                    "`{}` is not a valid {} in `{}`",
+                    bin,
+                    kind.get_cargo_kind(),
+                    package
+                ),
+                None => ensure!(
+                    bin_is_valid,
+                    "`{}` is not a valid {}",
+                    bin,
+                    kind.get_cargo_kind()
+                ),
+            }
+        }
 
         self.msys2_environment = Some(msys2_environment);
         self.build_data = Some(BuildData {
             target: target.into(),
             profile: profile.into(),
-            bin: bin.into(),
+            package: package.map(String::from),
+            kind,
+            bins,
+            executables: HashMap::new(),
         });
         Ok(())
     }
 
-    /// Get the path to the binary that cargo will produce
+    /// Get the only bin of a package, or the root package if it is None.
+    fn get_default_bin(&self, package: Option<&str>) -> anyhow::Result<String> {
+        let package = match package {
+            Some(package) => crate::util::get_package(&self.cargo_metadata, package)?,
+            None => self
+                .cargo_metadata
+                .root_package()
+                .context("there is no root package, select a bin with `--bin`, or its package with `--package`")?,
+        };
+        let bins: Vec<_> = package
+            .targets
+            .iter()
+            .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+            .map(|target| target.name.as_str())
+            .collect();
+
+        match bins.as_slice() {
+            [bin] => Ok(bin.to_string()),
+            [] => bail!("`{}` has no bins", package.name),
+            _ => bail!(
+                "`{}` has several bins, select them with `--bin` or `--bins`: {}",
+                package.name,
+                bins.iter()
+                    .map(|bin| format!("`{bin}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Get the path to the main binary that cargo will produce
     pub fn get_bin_path(&self) -> anyhow::Result<Utf8PathBuf> {
         let build_data = self.build_data.as_ref().context("missing build data")?;
+        self.get_bin_path_of(build_data.get_main_bin())
+    }
+
+    /// Get the path to a binary that cargo will produce
+    pub fn get_bin_path_of(&self, bin: &str) -> anyhow::Result<Utf8PathBuf> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+
+        if let Some(executable) = build_data.executables.get(bin) {
+            return Ok(executable.clone());
+        }
+        let output_dir = build_data.kind.get_output_dir().with_context(|| {
+            format!(
+                "the path of {} `{}` is only known after building it",
+                build_data.kind.get_cargo_kind(),
+                bin
+            )
+        })?;
 
         let mut profile = build_data.profile.as_str();
         // "dev" profile maps to "debug" in target folder
@@ -151,15 +628,15 @@ impl Context {
         }
 
         let mut path = self.cargo_metadata.target_directory.clone();
-        path.extend([
-            build_data.target.as_str(),
-            profile,
-            build_data.get_bin_name().as_str(),
-        ]);
+        path.extend([build_data.target.as_str(), profile]);
+        if !output_dir.is_empty() {
+            path.push(output_dir);
+        }
+        path.push(get_bin_file_name(bin));
         Ok(path)
     }
 
-    /// Get the path where the packager will output the binary
+    /// Get the path where the packager will output the main binary
     pub fn get_packaged_bin_path(&self) -> anyhow::Result<Utf8PathBuf> {
         let build_data = self.build_data.as_ref().context("missing build data")?;
         Ok(self
@@ -167,18 +644,38 @@ impl Context {
             .join(build_data.get_bin_name().as_str()))
     }
 
+    /// Get the path to the lockfile of the workspace for the target being built.
+    ///
+    /// Each target packages different binaries, so each has its own lockfile, like `gtk-packager.x86_64-pc-windows-gnu.lock`.
+    pub fn get_lock_file_path(&self) -> anyhow::Result<Utf8PathBuf> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let (stem, extension) = LOCK_FILE_NAME
+            .rsplit_once('.')
+            .context("lockfile name has no extension")?;
+
+        Ok(self
+            .cargo_metadata
+            .workspace_root
+            .join(format!("{stem}.{}.{extension}", build_data.target)))
+    }
+
     /// Get the out dir where package artifacts will be placed.
     pub fn get_package_out_dir(&self) -> anyhow::Result<Utf8PathBuf> {
         let build_data = self.build_data.as_ref().context("missing build data")?;
         let target = build_data.target.as_str();
         let mut profile = build_data.profile.as_str();
-        let bin = build_data.bin.as_str();
 
         // "dev" profile maps to "debug" in target folder
         if profile == "dev" {
             profile = "debug";
         }
 
+        // Multiple bins share one package, named after the cargo package.
+        let bin = match build_data.bins.as_slice() {
+            [bin] => bin.as_str(),
+            _ => self.get_bin_package()?.name.as_str(),
+        };
+
         let target_dir = &self.cargo_metadata.target_directory.join(target);
 
         // This is the dir where we can place whatever we want in.
@@ -193,33 +690,200 @@ impl Context {
         Ok(out_dir)
     }
 
-    /// Package a binary.
+    /// Get the dir that the debug info of stripped files is kept in, next to the package dir.
     ///
-    /// Note that this will not perform a build before-hand.
-    pub fn package(
-        &self,
-        upx: bool,
-        extra_libraries: &[String],
-        themes: &[PathBuf],
-    ) -> anyhow::Result<Packager> {
-        let msys2_environment = self
-            .msys2_environment
-            .context("missing msys2 environment")?;
-        let build_data = self.build_data.as_ref().context("missing build data")?;
+    /// This is like `{target}/{profile}/debug-symbols/{bin}`, so the debug info is not shipped with the package.
+    pub fn get_debug_symbols_dir(&self) -> anyhow::Result<Utf8PathBuf> {
+        let package_dir = self.get_package_out_dir()?;
+        let profile_dir = package_dir.parent().context("package dir has no parent")?;
+        let bin = package_dir
+            .file_name()
+            .context("package dir has no file name")?;
+
+        Ok(profile_dir.join("debug-symbols").join(bin))
+    }
+
+    /// Get the path to the manifest of the package dir, which records every packaged file and why it was packaged.
+    ///
+    /// It is kept next to the package dir, so it is not shipped with the app.
+    pub fn get_package_manifest_path(&self) -> anyhow::Result<Utf8PathBuf> {
+        let package_dir = self.get_package_out_dir()?;
+        let path = get_package_manifest_path(package_dir.as_std_path())?;
+        let dir = path.parent().context("manifest path has no parent")?;
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create `{dir}`"))?;
+
+        Ok(path)
+    }
+
+    /// Get the path to the package info of the package dir, see [`PackageInfo`].
+    ///
+    /// It is written to a `package-info` dir next to the package dir, and packaged from there.
+    pub fn get_package_info_path(&self) -> anyhow::Result<Utf8PathBuf> {
         let package_dir = self.get_package_out_dir()?;
+        let profile_dir = package_dir.parent().context("package dir has no parent")?;
+        let name = package_dir
+            .file_name()
+            .context("package dir has no file name")?;
+        let dir = profile_dir.join("package-info");
+        std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{dir}`"))?;
 
-        // TODO: Consider making this step optional.
-        // With dependency tracking, we could greatly speed up builds by not copying dependant artifacts.
-        // Clear out old contents
-        match std::fs::remove_dir_all(&package_dir) {
+        Ok(dir.join(format!("{name}.json")))
+    }
+
+    /// Get the dir that the translations of the package dir are compiled to, see [`GettextDomain::compile`].
+    ///
+    /// It is a dir in a `translations` dir next to the package dir, named after it, which starts out empty,
+    /// so languages that were removed are not packaged.
+    pub fn get_translations_dir(&self) -> anyhow::Result<Utf8PathBuf> {
+        let package_dir = self.get_package_out_dir()?;
+        let profile_dir = package_dir.parent().context("package dir has no parent")?;
+        let name = package_dir
+            .file_name()
+            .context("package dir has no file name")?;
+        let dir = profile_dir.join("translations").join(name);
+        match std::fs::remove_dir_all(&dir) {
             Ok(()) => {}
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                // Pass, it already does not exist.
-            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
             Err(e) => {
-                return Err(e).context("failed to remove old package dir");
+                return Err(e).with_context(|| format!("failed to remove `{dir}`"));
+            }
+        }
+        std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{dir}`"))?;
+
+        Ok(dir)
+    }
+
+    /// Generate and compile a resource script for the bin package.
+    ///
+    /// The script and object are written to `{target}/{profile}/resources` in this tool's dir of the cargo target dir.
+    ///
+    /// # Returns
+    /// Returns the path to the compiled object, which can be passed to the linker.
+    pub fn compile_resources(&self, resources: EmbedResources) -> anyhow::Result<Utf8PathBuf> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let package = self.get_bin_package()?;
+        let metadata = if resources.version_info {
+            let metadata = InstallerMetadata::from_package(package).with_context(|| {
+                format!(
+                    "failed to read installer settings from `{}`",
+                    package.manifest_path
+                )
+            })?;
+            if metadata.icon.is_none() {
+                eprintln!("warning: no app icon is set, so the exe will have the default icon");
             }
+            Some(metadata)
+        } else {
+            None
+        };
+
+        let mut profile = build_data.profile.as_str();
+        if profile == "dev" {
+            profile = "debug";
+        }
+        let resources_dir = self
+            .cargo_metadata
+            .target_directory
+            .join(build_data.target.as_str())
+            .join(env!("CARGO_CRATE_NAME"))
+            .join(build_data.target.as_str())
+            .join(profile)
+            .join("resources");
+        std::fs::create_dir_all(&resources_dir)
+            .with_context(|| format!("failed to create `{resources_dir}`"))?;
+
+        let manifest_path = if resources.manifest {
+            let path = resources_dir.join("app.manifest");
+            std::fs::write(&path, crate::manifest::APP_MANIFEST)
+                .with_context(|| format!("failed to write `{path}`"))?;
+            Some(path)
+        } else {
+            None
+        };
+        let rc =
+            crate::resource::generate_rc(package, metadata.as_ref(), manifest_path.as_deref())?;
+
+        let rc_path = resources_dir.join(format!("{}.rc", package.name));
+        std::fs::write(&rc_path, rc).with_context(|| format!("failed to write `{rc_path}`"))?;
+        let object_path = resources_dir.join(format!("{}.res.o", package.name));
+        crate::resource::compile_rc(&self.get_msys2_environment_path()?, &rc_path, &object_path)?;
+
+        Ok(object_path)
+    }
+
+    /// Generate and build the launcher crate for the target.
+    ///
+    /// The crate is written to `{target}/{profile}/launcher` in this tool's dir of the cargo target dir.
+    /// The launcher takes the AppUserModelID from the installer settings of the bin package.
+    ///
+    /// # Returns
+    /// Returns the path to the built launcher exe.
+    pub fn build_launcher(&self) -> anyhow::Result<Utf8PathBuf> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let package_dir = self.get_package_out_dir()?;
+        let launcher_dir = package_dir
+            .parent()
+            .context("package dir has no parent")?
+            .join("launcher");
+        let src_dir = launcher_dir.join("src");
+        std::fs::create_dir_all(&src_dir)
+            .with_context(|| format!("failed to create `{src_dir}`"))?;
+
+        let manifest_path = launcher_dir.join("Cargo.toml");
+        std::fs::write(&manifest_path, crate::launcher::LAUNCHER_CARGO_TOML)
+            .with_context(|| format!("failed to write `{manifest_path}`"))?;
+        let main_path = src_dir.join("main.rs");
+        let package = self.get_bin_package()?;
+        let metadata = InstallerMetadata::from_package(package).with_context(|| {
+            format!(
+                "failed to read installer settings from `{}`",
+                package.manifest_path
+            )
+        })?;
+        let source = crate::launcher::generate_exe_source(metadata.app_user_model_id.as_deref())?;
+        std::fs::write(&main_path, source)
+            .with_context(|| format!("failed to write `{main_path}`"))?;
+
+        let target_dir = launcher_dir.join("target");
+        let mut cargo_build = crate::util::CargoBuild::new();
+        if let Some(toolchain) = crate::util::get_toolchain(&self.cargo_metadata)? {
+            cargo_build.toolchain(toolchain);
         }
+        cargo_build
+            .target(build_data.target.clone())
+            .profile("release".into())
+            .arg("--manifest-path".into())
+            .arg(manifest_path.into())
+            .arg("--target-dir".into())
+            .arg(target_dir.clone().into());
+        cargo_build.exec().context("failed to build the launcher")?;
+
+        Ok(target_dir
+            .join(build_data.target.as_str())
+            .join("release")
+            .join(format!("{}.exe", crate::launcher::LAUNCHER_CRATE_NAME)))
+    }
+
+    /// Get the path to the MSYS2 environment, like `C:/msys64/ucrt64`.
+    pub fn get_msys2_environment_path(&self) -> anyhow::Result<Utf8PathBuf> {
+        let msys2_environment = self
+            .msys2_environment
+            .context("missing msys2 environment")?;
+        Ok(self
+            .msys2_installation_path
+            .join(msys2_environment.get_prefix().trim_start_matches('/')))
+    }
+
+    /// Make a [`Packager`] with all the files needed to package a binary.
+    ///
+    /// Note that this will not perform a build before-hand,
+    /// and that nothing is written to the package dir.
+    pub fn create_packager(&self, options: &PackageOptions) -> anyhow::Result<Packager> {
+        let msys2_environment = self
+            .msys2_environment
+            .context("missing msys2 environment")?;
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let package_dir = self.get_package_out_dir()?;
 
         let mut packager = Packager::new(
             self.msys2_installation_path.clone(),
@@ -228,25 +892,81 @@ impl Context {
         );
         packager
             .resolve_unknown_libraries(true)
-            .upx(upx)
-            .add_file(
-                Some(self.get_bin_path()?.into()),
-                build_data.get_bin_name().into(),
-                FileFlags::EXE | FileFlags::UPX | FileFlags::ADD_DEPS,
+            .upx(options.upx)
+            .upx_options(options.upx_options.clone())
+            .strip(options.strip)
+            .jobs(options.jobs)
+            .incremental(options.overwrite.is_none())
+            .overwrite(options.overwrite.unwrap_or_default())
+            .licenses(options.licenses)
+            .crt_check(options.crt_check)
+            .toolchain_runtime(options.toolchain_runtime)
+            .verify_copies(options.verify_copies)
+            // Packaging writes more files after the packager, so the checksums are written at the end of it.
+            .checksums(false)
+            .dll_store(
+                get_dll_store_dir(options.dll_store, self.profile_dir.as_deref())
+                    .map(DllStore::new),
             )
-            .add_file(
-                None,
-                "gdbus.exe".into(), // gdbus.exe is needed for GTK apps to function on Windows
-                FileFlags::EXE | FileFlags::UPX | FileFlags::ADD_DEPS,
+            .normalize_pe_timestamps(
+                options
+                    .deterministic
+                    .then(get_reproducible_timestamp)
+                    .transpose()?,
+            )
+            .sign(options.sign.clone());
+        if options.split_debug {
+            packager.debug_symbols_dir(Some(self.get_debug_symbols_dir()?.into()));
+        }
+        for pattern in options.excludes {
+            packager.exclude(pattern.clone());
+        }
+        for pattern in options.no_upx {
+            packager.no_upx(pattern.clone());
+        }
+        let bin_package = self.get_bin_package()?;
+        let mut system_dlls = SystemDllRules::from_manifest(bin_package).with_context(|| {
+            format!(
+                "failed to read system dlls from `{}`",
+                bin_package.manifest_path
+            )
+        })?;
+        system_dlls.extend(options.system_dlls).apply(&mut packager);
+        if options.locked {
+            let path = self.get_lock_file_path()?;
+            ensure!(
+                path.exists(),
+                "`--locked` was passed, but `{path}` does not exist"
             );
-
-        // TODO: This should be fleshed-out more as a generic file-copying option.
+            packager.locked(Some(Lockfile::load(path.as_std_path())?));
+        }
+        for bin in build_data.bins.iter() {
+            packager.add_file(
+                Some(self.get_bin_path_of(bin)?.into()),
+                get_bin_file_name(bin).into(),
+                FileFlags::EXE | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+            );
+        }
         // Copy extra libraries
-        for library in extra_libraries.iter() {
+        for library in options.extra_libraries.iter() {
             packager.add_file(
                 None,
                 library.into(),
-                FileFlags::LIB | FileFlags::UPX | FileFlags::ADD_DEPS,
+                FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+            );
+        }
+
+        packager.reason(FileReason::Profile("gtk".into())).add_file(
+            None,
+            "gdbus.exe".into(), // gdbus.exe is needed for GTK apps to function on Windows
+            FileFlags::EXE | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+        );
+        if options.glib_spawn_helpers {
+            packager.reason(FileReason::Profile("glib-spawn-helpers".into()));
+            crate::spawn_helpers::add_spawn_helpers(
+                &mut packager,
+                &self.get_msys2_environment_path()?,
+                msys2_environment.get_arch(),
             );
         }
 
@@ -254,10 +974,11 @@ impl Context {
         // TODO: This is technically optional, maybe allow users to disable to inclusion of the media backend?
         // TODO: Allow customization based on gtk target and media backend
         let msys2_environment_path = packager.get_msys2_environment_path();
+        packager.reason(FileReason::Profile("gstreamer".into()));
         packager.add_file(
             Some(msys2_environment_path.join_os("lib/gtk-4.0/4.0.0/media/libmedia-gstreamer.dll")),
             "lib/gtk-4.0/4.0.0/media/libmedia-gstreamer.dll".into(),
-            FileFlags::LIB | FileFlags::UPX | FileFlags::ADD_DEPS,
+            FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
         );
         // DLLS included as part of gstreamer:
         let gstreamer_dlls = &[
@@ -271,7 +992,7 @@ impl Context {
             packager.add_file(
                 None,
                 dll.into(),
-                FileFlags::LIB | FileFlags::UPX | FileFlags::ADD_DEPS,
+                FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
             );
         }
 
@@ -324,101 +1045,1139 @@ impl Context {
             packager.add_file(
                 Some(msys2_environment_path.join_os(format!("lib/gstreamer-1.0/{plugin}"))),
                 format!("lib/gstreamer-1.0/{plugin}").into(),
-                FileFlags::LIB | FileFlags::UPX | FileFlags::ADD_DEPS,
+                FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
             );
         }
 
-        // Copy themes
-        if !themes.is_empty() {
-            let themes_dest = Utf8Path::new("share").join("themes");
+        if options.gio_modules {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("gio-modules".into()));
+            crate::gio_modules::add_gio_modules(&mut packager, &environment_path)
+                .context("failed to add GIO modules")?;
+        }
+        if options.immodules {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("immodules".into()));
+            crate::immodules::add_immodules(&mut packager, &environment_path)
+                .context("failed to add input method modules")?;
+        }
+        if options.print_backends {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("print-backends".into()));
+            crate::print_backends::add_print_backends(&mut packager, &environment_path)
+                .context("failed to add print backends")?;
+        }
+        if options.gtksourceview {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("gtksourceview".into()));
+            crate::gtksourceview::add_gtksourceview_data(&mut packager, &environment_path)
+                .context("failed to add GtkSourceView data")?;
+        }
+        if options.spell_check || !options.spell_languages.is_empty() {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("spell-check".into()));
+            let summary = crate::spell_check::add_spell_check(
+                &mut packager,
+                &environment_path,
+                options.spell_languages,
+            )
+            .context("failed to add spell checking")?;
+            eprintln!(
+                "Packaging {} enchant providers and {} dictionaries",
+                summary.num_providers, summary.num_dictionaries
+            );
+        }
+        if !options.typelibs.is_empty() {
+            let environment_path = self.get_msys2_environment_path()?;
+            packager.reason(FileReason::Profile("typelibs".into()));
+            let typelibs =
+                crate::typelib::add_typelibs(&mut packager, &environment_path, options.typelibs)
+                    .context("failed to add typelibs")?;
+            eprintln!("Packaging {} typelibs", typelibs.len());
+        }
 
-            for theme in themes {
-                let theme = PathBuf::from(theme)
-                    .canonicalize()
-                    .context("failed to canonicalize theme path")?;
-                let theme_name = theme.file_name().context("theme has no name")?;
-                let theme_dest = themes_dest.join_os(theme_name);
-                for dir_entry in WalkDir::new(&theme) {
-                    let dir_entry = dir_entry.context("failed to get dir entry")?;
-                    let relative_path = dir_entry
-                        .path()
-                        .strip_prefix(&theme)
-                        .context("dir entry path is not prefixed by the theme dir")?;
-
-                    let dest_path = theme_dest.join(relative_path);
-                    let file_type = dir_entry.file_type();
-                    if file_type.is_file() {
-                        packager.add_file(
-                            Some(dir_entry.path().into()),
-                            dest_path,
-                            FileFlags::empty(),
-                        );
-                    } else if file_type.is_dir() {
-                        // Pass, packager will make it for us
-                    } else {
-                        bail!("symlink in theme folder");
-                    }
+        // Copy themes
+        let themes_dest = Utf8Path::new("share").join("themes");
+        let mut database = None;
+        for theme in options.themes {
+            match theme {
+                Theme::Dir(theme) => {
+                    packager.reason(FileReason::Theme(get_theme_name(theme)?));
+                    add_theme_dir(
+                        &mut packager,
+                        theme,
+                        &themes_dest,
+                        options.follow_theme_symlinks,
+                    )?;
+                }
+                Theme::Package(name) => {
+                    // Only load the database if it is needed, as it is slow to read.
+                    let database = match database.as_mut() {
+                        Some(database) => database,
+                        None => database.insert(
+                            LocalDatabase::load(&self.msys2_installation_path)
+                                .context("failed to load the pacman database")?,
+                        ),
+                    };
+                    packager.reason(FileReason::Theme(name.clone()));
+                    crate::theme::add_theme_package(
+                        &mut packager,
+                        database,
+                        &self.msys2_installation_path,
+                        name,
+                    )
+                    .with_context(|| format!("failed to add theme package `{name}`"))?;
                 }
             }
         }
 
-        packager.package().context("failed to package")?;
-
-        // Write out settings.ini
+        // libadwaita brings its own stylesheet, which GTK themes would fight with.
+        let adwaita = self.uses_adwaita(options)?;
+        if adwaita && !options.themes.is_empty() {
+            eprintln!("warning: the app uses libadwaita, which ignores GTK themes, so `--theme` may have no effect");
+        }
+        let icon_themes = self.get_icon_themes(options)?;
+        if adwaita
+            && !icon_themes
+                .iter()
+                .any(|icon_theme| is_adwaita_icon_theme(icon_theme))
         {
-            let etc = package_dir.join("etc");
-            std::fs::create_dir(&etc).context("failed to create etc dir")?;
+            eprintln!(
+                "warning: `{}` does not exist, so libadwaita may show missing icons. Install the adwaita-icon-theme package of the MSYS2 environment",
+                self.get_msys2_environment_path()?.join(crate::adwaita::ADWAITA_ICON_THEME_DIR)
+            );
+        }
 
-            // TODO: Allow customization based on gtk target
-            let gtk = etc.join("gtk-4.0");
-            std::fs::create_dir(&gtk).context("failed to create gtk dir")?;
+        // Copy icon themes
+        let icon_themes_dest = Utf8Path::new("share").join("icons");
+        for icon_theme in icon_themes.iter() {
+            packager.reason(FileReason::Theme(get_theme_name(icon_theme)?));
+            add_theme_dir(
+                &mut packager,
+                icon_theme,
+                &icon_themes_dest,
+                options.follow_theme_symlinks,
+            )?;
+        }
 
-            let mut file =
-                File::create(gtk.join("settings.ini")).context("failed to open settings.ini")?;
-            // TODO: Allow customization
-            file.write_all(b"[Settings]\ngtk-theme-name=Dracula\n")
-                .context("failed to write out settings.ini")?;
-            file.flush().context("failed to flush")?;
-            file.sync_all().context("failed to sync")?;
+        // Copy assets, from the Cargo.toml and then the command line.
+        packager.reason(FileReason::Explicit);
+        let manifest_dir = bin_package
+            .manifest_path
+            .parent()
+            .context("manifest path has no parent")?;
+        let manifest_assets =
+            crate::asset::get_manifest_assets(bin_package).with_context(|| {
+                format!("failed to read assets from `{}`", bin_package.manifest_path)
+            })?;
+        for asset in manifest_assets.iter() {
+            add_asset(&mut packager, asset, manifest_dir.as_std_path())?;
+        }
+        let current_dir = std::env::current_dir().context("failed to get current dir")?;
+        for asset in options.assets {
+            add_asset(&mut packager, asset, &current_dir)?;
         }
 
         Ok(packager)
     }
-}
 
-/// Info needed to run a `cargo build`
-pub struct BuildData {
+    /// Get the cargo package that contains the main binary being built.
+    pub fn get_bin_package(&self) -> anyhow::Result<&cargo_metadata::Package> {
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let bin = build_data.get_main_bin();
+        if let Some(package) = build_data.package.as_deref() {
+            return crate::util::get_package(&self.cargo_metadata, package);
+        }
+        self.cargo_metadata
+            .packages
+            .iter()
+            .find(|package| {
+                package.targets.iter().any(|target| {
+                    target.name == bin
+                        && target
+                            .kind
+                            .iter()
+                            .any(|kind| kind == build_data.kind.get_cargo_kind())
+                })
+            })
+            .with_context(|| format!("failed to find the package of `{bin}`"))
+    }
+
+    /// Plan out packaging a binary, without writing anything to the package dir.
+    ///
+    /// Note that this will not perform a build before-hand.
+    pub fn dry_run(&self, options: &PackageOptions) -> anyhow::Result<PackageReport> {
+        let mut packager = self.create_packager(options)?;
+        let mut report = packager.dry_run().context("failed to plan package")?;
+        if options.report {
+            report.ownership = Some(
+                packager
+                    .package_report_for_plan(&report.plan)
+                    .context("failed to report package ownership")?,
+            );
+        }
+        if options.size_report {
+            report.sizes = Some(
+                packager
+                    .size_report_for_plan(&report.plan)
+                    .context("failed to report package sizes")?,
+            );
+        }
+        if let Some(path) = options.emit_graph {
+            write_dependency_graph(&packager, path)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Package a binary.
+    ///
+    /// Note that this will not perform a build before-hand.
+    pub fn package(&self, options: &PackageOptions) -> anyhow::Result<PackageReport> {
+        let package_dir = self.get_package_out_dir()?;
+
+        // Check this first, so a misconfigured app fails before the slow parts of packaging.
+        let notification_support = if options.with_notifications {
+            Some(NotificationSupport::check(self.get_bin_package()?)?)
+        } else {
+            None
+        };
+
+        // Clear out old contents, if requested.
+        // Otherwise, the packager will only update what changed since the last run.
+        if options.clean {
+            match std::fs::remove_dir_all(&package_dir) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    // Pass, it already does not exist.
+                }
+                Err(e) => {
+                    return Err(e).context("failed to remove old package dir");
+                }
+            }
+        }
+
+        // The cache of an incremental run does not describe files that the overwrite policy left alone.
+        if options.overwrite.is_some() {
+            let cache_path = package_dir.join(CACHE_FILE_NAME);
+            match std::fs::remove_file(&cache_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to remove `{cache_path}`"));
+                }
+            }
+        }
+
+        let mut packager = self.create_packager(options)?;
+        if options.launcher == Some(LauncherKind::Exe) {
+            // Package the launcher like the app exes, so it is stripped, upx-ed, and signed with them.
+            let launcher_path = self.build_launcher()?;
+            let build_data = self.build_data.as_ref().context("missing build data")?;
+            for bin in build_data.bins.iter() {
+                packager.add_file(
+                    Some(launcher_path.clone().into()),
+                    LauncherKind::Exe
+                        .get_file_name(&get_bin_file_name(bin))
+                        .into(),
+                    FileFlags::EXE | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+                );
+            }
+        }
+        if let Some(policy) = options.policy {
+            self.check_policy(policy, &mut packager)?;
+        }
+
+        // The package info is written outside the package dir and packaged, so it is in the manifest like other files.
+        let build_data = self.build_data.as_ref().context("missing build data")?;
+        let package_info_path = self.get_package_info_path()?;
+        PackageInfo {
+            name: &self.get_bin_package()?.name,
+            version: self.get_bin_package()?.version.to_string(),
+            target: &build_data.target,
+            profile: &build_data.profile,
+            git: options.git_status,
+        }
+        .save(&package_info_path)?;
+        packager.reason(FileReason::Explicit).add_file(
+            Some(package_info_path.into()),
+            PACKAGE_INFO_FILE_NAME.into(),
+            FileFlags::empty(),
+        );
+
+        // Compile the translations of the app outside the package dir, and package them like other files.
+        if let Some(domain) = self.get_gettext_domain(options)? {
+            let translations_dir = self.get_translations_dir()?;
+            let mo_paths = domain
+                .compile(&translations_dir)
+                .context("failed to compile translations")?;
+            for mo_path in mo_paths.iter() {
+                packager.add_file(
+                    Some(translations_dir.join(mo_path).into()),
+                    mo_path.clone().into(),
+                    FileFlags::empty(),
+                );
+            }
+            eprintln!(
+                "Compiled {} translations for gettext domain `{}`",
+                mo_paths.len(),
+                domain.name
+            );
+        }
+
+        let mut report = packager.package().context("failed to package")?;
+
+        // Write out settings.ini
+        {
+            let etc = package_dir.join("etc");
+            std::fs::create_dir_all(&etc).context("failed to create etc dir")?;
+
+            // TODO: Allow customization based on gtk target
+            let gtk = etc.join("gtk-4.0");
+            std::fs::create_dir_all(&gtk).context("failed to create gtk dir")?;
+
+            let mut file =
+                File::create(gtk.join("settings.ini")).context("failed to open settings.ini")?;
+            let settings = get_settings_ini(self.uses_adwaita(options)?);
+            file.write_all(settings.as_bytes())
+                .context("failed to write out settings.ini")?;
+            file.flush().context("failed to flush")?;
+            file.sync_all().context("failed to sync")?;
+        }
+
+        // Build icon theme caches, so GTK does not need to scan every icon on startup.
+        let icon_themes = self.get_icon_themes(options)?;
+        if !icon_themes.is_empty() {
+            let update_icon_cache = self
+                .get_msys2_environment_path()?
+                .join("bin/gtk4-update-icon-cache.exe");
+            for icon_theme in icon_themes.iter() {
+                let name = icon_theme
+                    .canonicalize()
+                    .context("failed to canonicalize icon theme path")?;
+                let name = name.file_name().context("icon theme has no name")?;
+                let icon_theme_dir = package_dir.join("share/icons").join_os(name);
+
+                let output = Command::new(&update_icon_cache)
+                    .args(["--force", "--ignore-theme-index", "--quiet"])
+                    .arg(&icon_theme_dir)
+                    .output()
+                    .with_context(|| format!("failed to run `{update_icon_cache}`"))?;
+                ensure!(
+                    output.status.success(),
+                    "failed to update the icon cache for `{}`: {}",
+                    icon_theme_dir.display(),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+
+        // GIO only loads modules listed in the cache, which GTK also uses for input method modules.
+        let gio_querymodules = self
+            .get_msys2_environment_path()?
+            .join("bin/gio-querymodules.exe");
+        if options.gio_modules {
+            crate::gio_modules::update_module_cache(
+                &package_dir.join(crate::gio_modules::GIO_MODULES_DIR),
+                &gio_querymodules,
+            )?;
+        }
+        let immodules_dir = package_dir.join(crate::immodules::IMMODULES_DIR);
+        if options.immodules && immodules_dir.is_dir() {
+            crate::gio_modules::update_module_cache(&immodules_dir, &gio_querymodules)?;
+        }
+        let print_backends_dir = package_dir.join(crate::print_backends::PRINT_BACKENDS_DIR);
+        if options.print_backends && print_backends_dir.is_dir() {
+            crate::gio_modules::update_module_cache(&print_backends_dir, &gio_querymodules)?;
+        }
+
+        // Bundle data dirs last, as the steps above read them from the package dir.
+        // The bundled files are removed from the plan, so the reports and manifests below match the package dir.
+        if !options.gresource_dirs.is_empty() {
+            let glib_compile_resources = self
+                .get_msys2_environment_path()?
+                .join("bin/glib-compile-resources.exe");
+            crate::gresource::bundle(
+                &package_dir,
+                options.gresource_dirs,
+                &glib_compile_resources,
+            )
+            .context("failed to bundle data dirs")?;
+            for dir in options.gresource_dirs.iter() {
+                report.plan.remove_dir(dir.as_std_path());
+            }
+            eprintln!("{}", crate::gresource::get_guidance(options.gresource_dirs));
+        }
+
+        if (options.report || options.sbom.is_some()) && report.ownership.is_none() {
+            report.ownership = Some(
+                packager
+                    .package_report_for_plan(&report.plan)
+                    .context("failed to report package ownership")?,
+            );
+        }
+        if options.size_report {
+            report.sizes = Some(
+                packager
+                    .size_report_for_plan(&report.plan)
+                    .context("failed to report package sizes")?,
+            );
+        }
+        if let Some(path) = options.emit_graph {
+            write_dependency_graph(&packager, path)?;
+        }
+        report
+            .plan
+            .save(self.get_package_manifest_path()?.as_std_path())
+            .context("failed to save the package manifest")?;
+
+        if options.update_lock_file && !options.locked {
+            packager
+                .lockfile_for_plan(&report.plan)?
+                .save(self.get_lock_file_path()?.as_std_path())
+                .context("failed to save lockfile")?;
+        }
+
+        if let Some(format) = options.sbom {
+            let ownership = report
+                .ownership
+                .as_ref()
+                .context("missing package ownership")?;
+            let mut sbom = self.create_sbom()?;
+            sbom.add_packaged_files(
+                &self.msys2_installation_path,
+                package_dir.as_std_path(),
+                ownership,
+            )?;
+
+            let path = package_dir.join(format.get_file_name());
+            std::fs::write(&path, sbom.to_json(format)?)
+                .with_context(|| format!("failed to write `{path}`"))?;
+        }
+
+        if options.auditable {
+            let build_data = self.build_data.as_ref().context("missing build data")?;
+            let mut manifest = AuditableManifest::new();
+            for bin in build_data.bins.iter() {
+                manifest
+                    .add_binary(package_dir.as_std_path(), get_bin_file_name(bin).into())
+                    .context("failed to verify the dependency audit data")?;
+            }
+            manifest.save(package_dir.as_std_path())?;
+        }
+
+        if let Some(symbol_server) = options.symbol_server {
+            let num_files = msys2_packager::symbols::upload_symbols(
+                symbol_server,
+                package_dir.as_std_path(),
+                self.get_debug_symbols_dir()?.as_std_path(),
+            )
+            .with_context(|| format!("failed to upload debug symbols to `{symbol_server}`"))?;
+            eprintln!("Uploaded {num_files} symbol files to `{symbol_server}`");
+        }
+
+        if options.manifest == Some(ManifestMode::Alongside) {
+            let build_data = self.build_data.as_ref().context("missing build data")?;
+            for bin in build_data.bins.iter() {
+                let path = package_dir.join(format!("{}.manifest", get_bin_file_name(bin)));
+                std::fs::write(&path, crate::manifest::APP_MANIFEST)
+                    .with_context(|| format!("failed to write `{path}`"))?;
+            }
+        }
+
+        if let Some(launcher) = options.launcher {
+            let build_data = self.build_data.as_ref().context("missing build data")?;
+            let env = crate::launcher::get_launcher_env(&report.plan);
+            for bin in build_data.bins.iter() {
+                let exe_name = get_bin_file_name(bin);
+                let script = match launcher {
+                    LauncherKind::Exe => continue,
+                    LauncherKind::Bat => crate::launcher::generate_bat(&exe_name, &env)?,
+                    LauncherKind::PowerShell => {
+                        crate::launcher::generate_powershell(&exe_name, &env)?
+                    }
+                };
+                let path = package_dir.join(launcher.get_file_name(&exe_name));
+                std::fs::write(&path, script)
+                    .with_context(|| format!("failed to write `{path}`"))?;
+            }
+        }
+
+        // This must be last, so the checksums describe every file of the finished package.
+        if options.verify_copies {
+            write_checksums(package_dir.as_std_path())?;
+        }
+
+        if let Some(notification_support) = notification_support {
+            eprintln!(
+                "Notifications are attributed to AppUserModelID `{}`. Start the app from the shortcut made by `installer`, or call `SetCurrentProcessExplicitAppUserModelID` with it, or toasts will not show",
+                notification_support.app_user_model_id
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Get the gettext domain of the app, from the command line or the Cargo.toml.
+    ///
+    /// A po dir from the command line is resolved against the current dir.
+    fn get_gettext_domain(
+        &self,
+        options: &PackageOptions,
+    ) -> anyhow::Result<Option<GettextDomain>> {
+        let bin_package = self.get_bin_package()?;
+        let mut domain = GettextDomain::from_manifest(bin_package).with_context(|| {
+            format!(
+                "failed to read the gettext domain from `{}`",
+                bin_package.manifest_path
+            )
+        })?;
+
+        if let Some(name) = options.gettext_domain {
+            let po_dir = match domain {
+                Some(domain) => domain.po_dir,
+                None => bin_package
+                    .manifest_path
+                    .parent()
+                    .context("manifest path has no parent")?
+                    .join(crate::gettext::DEFAULT_PO_DIR)
+                    .into(),
+            };
+            domain = Some(GettextDomain {
+                name: name.into(),
+                po_dir,
+            });
+        }
+        if let Some(po_dir) = options.po_dir {
+            let domain = domain.as_mut().context(
+                "`--po-dir` was passed, but there is no gettext domain. Pass `--gettext-domain` or set `gettext-domain` in the Cargo.toml",
+            )?;
+            let current_dir = std::env::current_dir().context("failed to get current dir")?;
+            domain.po_dir = current_dir.join(po_dir);
+        }
+
+        Ok(domain)
+    }
+
+    /// Whether the binary uses libadwaita, as in it depends on the libadwaita crate, or [`PackageOptions::adwaita`] is set.
+    pub fn uses_adwaita(&self, options: &PackageOptions) -> anyhow::Result<bool> {
+        if options.adwaita {
+            return Ok(true);
+        }
+
+        Ok(self
+            .get_normal_dependencies()?
+            .iter()
+            .any(|package| package.name == crate::adwaita::ADWAITA_CRATE_NAME))
+    }
+
+    /// Get the icon themes to package, which includes the Adwaita icon theme for libadwaita apps.
+    fn get_icon_themes(&self, options: &PackageOptions) -> anyhow::Result<Vec<PathBuf>> {
+        let mut icon_themes = options.icon_themes.to_vec();
+        if self.uses_adwaita(options)?
+            && !icon_themes
+                .iter()
+                .any(|icon_theme| is_adwaita_icon_theme(icon_theme))
+        {
+            icon_themes.extend(crate::adwaita::get_adwaita_icon_theme(
+                &self.get_msys2_environment_path()?,
+            ));
+        }
+
+        Ok(icon_themes)
+    }
+
+    /// Get the crates that the package of the binary depends on, directly or indirectly.
+    ///
+    /// Only normal dependencies are included, as build and dev dependencies do not end up in the binary.
+    fn get_normal_dependencies(&self) -> anyhow::Result<Vec<&cargo_metadata::Package>> {
+        let packages: HashMap<_, _> = self
+            .cargo_metadata
+            .packages
+            .iter()
+            .map(|package| (&package.id, package))
+            .collect();
+        let nodes: HashMap<_, _> = self
+            .cargo_metadata
+            .resolve
+            .as_ref()
+            .context("missing cargo resolve graph")?
+            .nodes
+            .iter()
+            .map(|node| (&node.id, node))
+            .collect();
+
+        let root = self.get_bin_package()?;
+        let mut dependencies = Vec::new();
+        let mut visited = HashSet::from([&root.id]);
+        let mut queue = VecDeque::from([&root.id]);
+        while let Some(id) = queue.pop_front() {
+            let node = match nodes.get(id) {
+                Some(node) => node,
+                None => continue,
+            };
+            for dep in node.deps.iter() {
+                let is_normal = dep
+                    .dep_kinds
+                    .iter()
+                    .any(|info| info.kind == cargo_metadata::DependencyKind::Normal);
+                if !is_normal || !visited.insert(&dep.pkg) {
+                    continue;
+                }
+
+                let package = packages
+                    .get(&dep.pkg)
+                    .with_context(|| format!("missing package `{}`", dep.pkg))?;
+                dependencies.push(*package);
+                queue.push_back(&dep.pkg);
+            }
+        }
+
+        Ok(dependencies)
+    }
+
+    /// Check the crates of the binary and the MSYS2 packages it would be packaged with against the policy in a file.
+    ///
+    /// # Errors
+    /// Returns an error listing every violation, if there are any.
+    fn check_policy(&self, path: &Path, packager: &mut Packager) -> anyhow::Result<()> {
+        let policy = Policy::load(path)?;
+        let root = self.get_bin_package()?;
+
+        let mut components = Vec::new();
+        for package in std::iter::once(root).chain(self.get_normal_dependencies()?) {
+            let mut component = PolicyComponent::new(
+                PolicyComponentKind::Crate,
+                package.name.clone(),
+                package.version.to_string(),
+            );
+            if let Some(license) = package.license.as_deref() {
+                component.license(license.to_string());
+            }
+            components.push(component);
+        }
+
+        let ownership = packager
+            .package_report()
+            .context("failed to report package ownership")?;
+        let database = LocalDatabase::load(&self.msys2_installation_path)
+            .context("failed to load the pacman database")?;
+        for package in ownership.packages {
+            let mut component = PolicyComponent::new(
+                PolicyComponentKind::Msys2Package,
+                package.name,
+                package.version,
+            );
+            if let Some(installed) = database.get(&component.name) {
+                component.pacman_licenses(&installed.licenses);
+            }
+            components.push(component);
+        }
+
+        let report = policy.check(&components);
+        ensure!(
+            report.is_ok(),
+            "the app breaks the policy in `{}`:\n{report}",
+            path.display()
+        );
+
+        if policy.cargo_deny {
+            let status = Command::new("cargo")
+                .arg("deny")
+                .arg("--manifest-path")
+                .arg(&root.manifest_path)
+                .args(["check", "advisories"])
+                .status()
+                .context(
+                    "failed to run `cargo deny`, install it with `cargo install cargo-deny`",
+                )?;
+            ensure!(
+                status.success(),
+                "`cargo deny check advisories` found problems with the crates"
+            );
+        }
+
+        eprintln!(
+            "Checked {} components against `{}`",
+            report.num_components,
+            path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Make an [`Sbom`] with the package of the binary as the main component,
+    /// and the crates it depends on as components.
+    ///
+    /// Only normal dependencies are included, as build and dev dependencies do not end up in the binary.
+    fn create_sbom(&self) -> anyhow::Result<Sbom> {
+        let root = self.get_bin_package()?;
+
+        let create_component = |package: &cargo_metadata::Package| {
+            let mut component =
+                SbomComponent::new(package.name.clone(), package.version.to_string());
+            if package
+                .source
+                .as_ref()
+                .is_some_and(|source| source.is_crates_io())
+            {
+                component.purl(format!("pkg:cargo/{}@{}", package.name, package.version));
+            }
+            if let Some(license) = package.license.as_deref() {
+                // Older crates use `/` instead of `OR`.
+                component.spdx_license(&license.replace('/', " OR "));
+            }
+            component
+        };
+
+        let mut sbom = Sbom::new(create_component(root));
+        for package in self.get_normal_dependencies()? {
+            sbom.component(create_component(package));
+        }
+
+        Ok(sbom)
+    }
+}
+
+/// Options for [`Context::package`].
+pub struct PackageOptions<'a> {
+    /// Whether to upx
+    pub upx: bool,
+
+    /// The options to run upx with
+    pub upx_options: UpxOptions,
+
+    /// Patterns of file names to never upx
+    pub no_upx: &'a [String],
+
+    /// Whether to strip symbols from exes and dlls
+    pub strip: bool,
+
+    /// Whether to keep the debug info of stripped files in a separate dir, see [`Context::get_debug_symbols_dir`]
+    pub split_debug: bool,
+
+    /// The symbol server to upload the split debug info to, if any. Requires [`PackageOptions::split_debug`]
+    pub symbol_server: Option<&'a SymbolServer>,
+
+    /// The number of threads to use when copying and upx-ing files
+    pub jobs: usize,
+
+    /// Whether to clear out the package dir instead of updating it incrementally
+    pub clean: bool,
+
+    /// What to do with files that already exist in the package dir, instead of updating it incrementally
+    pub overwrite: Option<OverwritePolicy>,
+
+    /// Whether to report which MSYS2 package each packaged file came from
+    pub report: bool,
+
+    /// Whether to write the licenses of the MSYS2 packages that packaged files came from
+    pub licenses: bool,
+
+    /// The format of the SBOM to write into the package, if any
+    pub sbom: Option<SbomFormat>,
+
+    /// How to sign packaged binaries, if they should be signed
+    pub sign: Option<SignOptions>,
+
+    /// The names of extra libraries to package
+    pub extra_libraries: &'a [String],
+
+    /// Glob patterns of dll names to never add automatically
+    pub excludes: &'a [String],
+
+    /// Changes to which dlls are considered system dlls, in addition to those in the Cargo.toml
+    pub system_dlls: &'a SystemDllRules,
+
+    /// Themes to package
+    pub themes: &'a [Theme],
+
+    /// Paths to icon themes to package
+    pub icon_themes: &'a [PathBuf],
+
+    /// Whether to package for libadwaita, even if the binary does not depend on the libadwaita crate
+    pub adwaita: bool,
+
+    /// Whether to copy the targets of symlinks in theme dirs, instead of erroring
+    pub follow_theme_symlinks: bool,
+
+    /// Whether to package the GIO modules of glib-networking, like TLS backends, and the CA certificate bundle
+    pub gio_modules: bool,
+
+    /// Whether to package the GTK input method modules
+    pub immodules: bool,
+
+    /// Whether to package the GTK print backends
+    pub print_backends: bool,
+
+    /// Whether to package the language specs and style schemes of GtkSourceView
+    pub gtksourceview: bool,
+
+    /// Whether to package the enchant providers and hunspell dictionaries, for gspell and libspelling
+    pub spell_check: bool,
+
+    /// The languages of the hunspell dictionaries to package, like `de` or `en_US`, or empty for every dictionary.
+    ///
+    /// Setting any implies [`PackageOptions::spell_check`].
+    pub spell_languages: &'a [String],
+
+    /// The GObject introspection typelibs to package, like `Gtk-4.0`, along with the typelibs they depend on
+    pub typelibs: &'a [String],
+
+    /// Whether to package the helper exes that GLib needs to spawn subprocesses
+    pub glib_spawn_helpers: bool,
+
+    /// Files, dirs, and globs to copy verbatim into the package, in addition to those in the Cargo.toml
+    pub assets: &'a [Asset],
+
+    /// What to do when a packaged binary links against a different C runtime than the MSYS2 environment
+    pub crt_check: CrtCheck,
+
+    /// Whether to always package the support dlls of the toolchain
+    pub toolchain_runtime: bool,
+
+    /// Whether to report the size of each packaged file and category of files
+    pub size_report: bool,
+
+    /// Packaged dirs to bundle into a compressed GResource file, relative to the package top level
+    pub gresource_dirs: &'a [Utf8PathBuf],
+
+    /// The file to write the dll dependency graph to, if any
+    pub emit_graph: Option<&'a Path>,
+
+    /// Whether to fail if the packaged MSYS2 binaries would differ from the lockfile
+    pub locked: bool,
+
+    /// Whether to write the lockfile of the target, unless `locked` is set
+    pub update_lock_file: bool,
+
+    /// How the built exes get an application manifest, if they do.
+    ///
+    /// Only [`ManifestMode::Alongside`] is handled while packaging, as embedding happens while building.
+    pub manifest: Option<ManifestMode>,
+
+    /// Whether to check that the app exes have the dependency audit data of `cargo auditable`, and write it to the package dir
+    pub auditable: bool,
+
+    /// The file of the license and advisory policy to check the crates and MSYS2 packages against before packaging, if any
+    pub policy: Option<&'a Path>,
+
+    /// The kind of launcher to write next to each exe, which sets up the environment before starting it
+    pub launcher: Option<LauncherKind>,
+
+    /// Whether to check that the app is set up to show notifications, before packaging
+    pub with_notifications: bool,
+
+    /// Whether to verify each copied file, and write a `SHA256SUMS` file to the package dir
+    pub verify_copies: bool,
+
+    /// The dir of a dll store to hard-link unchanged dlls from, overriding the `MSYS2_GTK_PACKAGER_DLL_STORE` env var
+    pub dll_store: Option<&'a Path>,
+
+    /// Whether to set the timestamps in the headers of packaged exes and dlls, for reproducible packages
+    pub deterministic: bool,
+
+    /// The gettext domain of the app, overriding the one in the Cargo.toml
+    pub gettext_domain: Option<&'a str>,
+
+    /// The dir with the `.po` files of the app, overriding the one in the Cargo.toml
+    pub po_dir: Option<&'a Path>,
+
+    /// The git status of the checkout, captured before anything was built or written, which is recorded in the package info
+    pub git_status: Option<&'a GitStatus>,
+}
+
+/// Info needed to run a `cargo build`
+pub struct BuildData {
     /// The target triple
     pub target: String,
 
     /// The build profile
     pub profile: String,
 
-    /// The target binary
-    pub bin: String,
+    /// The package of the target binaries, if one was selected
+    pub package: Option<String>,
+
+    /// The kind of the target binaries
+    pub kind: TargetKind,
+
+    /// The names of the target binaries.
+    ///
+    /// The first is the main binary, which is run and described by the SBOM.
+    pub bins: Vec<String>,
+
+    /// The paths to built binaries, by name, if cargo reported them.
+    ///
+    /// This is only set for benches and tests, as their file names are hashed.
+    pub executables: HashMap<String, Utf8PathBuf>,
 }
 
 impl BuildData {
-    /// Get the binary file name.
+    /// Get the name of the main binary.
+    pub fn get_main_bin(&self) -> &str {
+        &self.bins[0]
+    }
+
+    /// Get the main binary file name.
     pub fn get_bin_name(&self) -> String {
-        // We assume the user is targeting windows and add an `.exe`
-        // as it is not possible to get here with a non-windows without erroring out.
-        format!("{}.exe", self.bin)
+        get_bin_file_name(self.get_main_bin())
+    }
+}
+
+/// Get the file name of a binary.
+fn get_bin_file_name(bin: &str) -> String {
+    // We assume the user is targeting windows and add an `.exe`
+    // as it is not possible to get here with a non-windows without erroring out.
+    format!("{bin}.exe")
+}
+
+/// Get the contents of the `settings.ini` written to `etc/gtk-4.0` in the package dir.
+fn get_settings_ini(uses_adwaita: bool) -> &'static str {
+    // TODO: Allow customization
+    if uses_adwaita {
+        crate::adwaita::ADWAITA_SETTINGS_INI
+    } else {
+        SETTINGS_INI
+    }
+}
+
+/// Check if an icon theme dir is the Adwaita icon theme.
+fn is_adwaita_icon_theme(icon_theme: &Path) -> bool {
+    icon_theme.file_name() == Some(OsStr::new("Adwaita"))
+}
+
+/// Get the name of a theme dir, which is its file name.
+fn get_theme_name(theme: &Path) -> anyhow::Result<String> {
+    let theme = theme
+        .canonicalize()
+        .context("failed to canonicalize theme path")?;
+    let theme_name = theme.file_name().context("theme has no name")?;
+
+    Ok(theme_name.to_string_lossy().into_owned())
+}
+
+/// Get the path to the manifest of a package dir, see [`Context::get_package_manifest_path`].
+///
+/// Manifests are kept in a `manifests` dir next to the package dirs, named after them.
+pub fn get_package_manifest_path(package_dir: &Path) -> anyhow::Result<Utf8PathBuf> {
+    let package_dir = Utf8Path::from_path(package_dir)
+        .with_context(|| format!("`{}` is not utf8", package_dir.display()))?;
+    let profile_dir = package_dir.parent().context("package dir has no parent")?;
+    let name = package_dir
+        .file_name()
+        .context("package dir has no file name")?;
+
+    Ok(profile_dir.join("manifests").join(format!("{name}.json")))
+}
+
+/// Add every file in a theme dir to the packager, under `dest/<theme name>`.
+///
+/// If `follow_symlinks` is true, symlinks are replaced with copies of their targets.
+/// Otherwise, a symlink is an error.
+fn add_theme_dir(
+    packager: &mut Packager,
+    theme: &Path,
+    dest: &Utf8Path,
+    follow_symlinks: bool,
+) -> anyhow::Result<()> {
+    let theme = theme
+        .canonicalize()
+        .context("failed to canonicalize theme path")?;
+    let theme_name = theme.file_name().context("theme has no name")?;
+    let theme_dest = dest.join_os(theme_name);
+    for dir_entry in WalkDir::new(&theme).follow_links(follow_symlinks) {
+        let dir_entry = dir_entry.context("failed to get dir entry")?;
+        let relative_path = dir_entry
+            .path()
+            .strip_prefix(&theme)
+            .context("dir entry path is not prefixed by the theme dir")?;
+
+        let dest_path = theme_dest.join(relative_path);
+        let file_type = dir_entry.file_type();
+        if file_type.is_file() {
+            packager.add_file(Some(dir_entry.path().into()), dest_path, FileFlags::empty());
+        } else if file_type.is_dir() {
+            // Pass, packager will make it for us
+        } else {
+            bail!(
+                "symlink in theme folder at `{}`, use `--follow-theme-symlinks` to copy its target",
+                dir_entry.path().display()
+            );
+        }
     }
+
+    Ok(())
+}
+
+/// Add the files of an asset to the packager.
+///
+/// Relative asset paths are resolved against `base_dir`.
+fn add_asset(packager: &mut Packager, asset: &Asset, base_dir: &Path) -> anyhow::Result<()> {
+    let files = asset
+        .collect_files(base_dir)
+        .with_context(|| format!("failed to collect files for asset `{}`", asset.src))?;
+    for (src, dest) in files {
+        packager.add_file(Some(src), dest, FileFlags::empty());
+    }
+
+    Ok(())
+}
+
+/// Write the dependency graph of a packager that has planned its files.
+fn write_dependency_graph(packager: &Packager, path: &Path) -> anyhow::Result<()> {
+    let graph = packager
+        .dependency_graph()
+        .context("no dependency graph was recorded")?;
+    graph
+        .write(path)
+        .context("failed to write the dependency graph")
+}
+
+/// Get the version and build info of this binary.
+fn get_version_info() -> VersionInfo {
+    let mut info = VersionInfo::new(
+        env!("CARGO_PKG_NAME").into(),
+        env!("CARGO_PKG_VERSION").into(),
+    );
+    info.git_hash(option_env!("BUILD_GIT_HASH").map(String::from))
+        .build_timestamp(
+            option_env!("BUILD_TIMESTAMP").and_then(|timestamp| timestamp.parse().ok()),
+        );
+    info
+}
+
+/// Get the dir of the dll store, if there is one.
+///
+/// This is `dll_store`, then the `MSYS2_GTK_PACKAGER_DLL_STORE` env var,
+/// and then the `dll-store` dir of the profile dir, if one was set, as it is meant to be shared.
+fn get_dll_store_dir(dll_store: Option<&Path>, profile_dir: Option<&Utf8Path>) -> Option<PathBuf> {
+    dll_store
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(DLL_STORE_ENV).map(PathBuf::from))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .or_else(|| profile_dir.map(|profile_dir| profile_dir.join(DLL_STORE_DIR_NAME).into()))
 }
 
 fn main() -> anyhow::Result<()> {
     let options: Options = argh::from_env();
-    let ctx = Context::new()?;
 
-    match options.subcommand {
+    let profile_dir = options
+        .profile_dir
+        .or_else(|| std::env::var(PROFILE_DIR_ENV).ok().map(Utf8PathBuf::from))
+        .filter(|profile_dir| !profile_dir.as_str().is_empty());
+
+    if options.crash_report || crate::crash::is_enabled_by_env() {
+        crate::crash::install_hook(
+            profile_dir
+                .as_ref()
+                .map(|profile_dir| profile_dir.join("crash-reports").into()),
+        );
+    }
+
+    if options.version {
+        get_version_info().print(options.output_format, options.verbose)?;
+        return Ok(());
+    }
+
+    // Contexts also find the default profile dir, but subcommands without one only use an explicit dir.
+    if let Some(profile_dir) = profile_dir.as_ref() {
+        KnowledgeBase::init_global(profile_dir.as_std_path());
+    }
+
+    let subcommand = options
+        .subcommand
+        .context("missing subcommand, see `--help`")?;
+    match subcommand {
         Subcommand::Build(options) => {
+            let ctx = Context::new(profile_dir)?;
             crate::commands::build::exec(ctx, options)?;
         }
+        Subcommand::Cache(options) => {
+            // The caches are found from the command line and profile dir, so this does not make a context.
+            crate::commands::cache::exec(options, profile_dir.as_deref())?;
+        }
+        Subcommand::Diff(options) => {
+            // Diffing only looks at package dirs, so it does not make a context.
+            crate::commands::diff::exec(options)?;
+        }
+        Subcommand::Doctor(options) => {
+            // The doctor checks the installation itself, so it cannot make a context.
+            crate::commands::doctor::exec(options)?;
+        }
+        Subcommand::Explain(options) => {
+            // Explaining only reads the manifest of a package dir, so it does not make a context.
+            crate::commands::explain::exec(options)?;
+        }
+        Subcommand::ExportEnv(options) => {
+            // Exporting only reads the MSYS2 installation, so it does not make a context.
+            crate::commands::export_env::exec(options)?;
+        }
+        Subcommand::IdeSetup(options) => {
+            let ctx = Context::new(profile_dir)?;
+            crate::commands::ide_setup::exec(ctx, options)?;
+        }
+        Subcommand::Inspect(options) => {
+            // Inspecting does not need a cargo project, so it does not make a context.
+            crate::commands::inspect::exec(options)?;
+        }
+        Subcommand::Installer(options) => {
+            let ctx = Context::new(profile_dir)?;
+            crate::commands::installer::exec(ctx, options)?;
+        }
+        Subcommand::Knowledge(options) => {
+            let ctx = Context::new(profile_dir)?;
+            msys2_packager::cli::knowledge::exec(
+                options,
+                Some(ctx.get_profile_dir()?.as_std_path()),
+                Some(ctx.msys2_installation_path.as_std_path()),
+            )?;
+        }
+        Subcommand::MinOs(options) => {
+            // Analyzing a package dir does not need a cargo project, so it does not make a context.
+            crate::commands::min_os::exec(options)?;
+        }
         Subcommand::Package(options) => {
+            let ctx = Context::new(profile_dir)?;
             crate::commands::package::exec(ctx, options)?;
         }
+        Subcommand::Run(options) => {
+            let ctx = Context::new(profile_dir)?;
+            let code = crate::commands::run::exec(ctx, options)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Subcommand::Size(options) => {
+            let ctx = Context::new(profile_dir)?;
+            crate::commands::size::exec(ctx, options)?;
+        }
+        Subcommand::Test(options) => {
+            let ctx = Context::new(profile_dir)?;
+            crate::commands::test::exec(ctx, options)?;
+        }
+        Subcommand::Verify(options) => {
+            // Verifying only looks at the package dir, so it does not make a context.
+            crate::commands::verify::exec(options)?;
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_ini_matches_golden() {
+        // hello-gtk4 does not use libadwaita.
+        assert_eq!(
+            get_settings_ini(false),
+            include_str!("../tests/golden/hello-gtk4.settings.ini")
+        );
+        assert_eq!(
+            get_settings_ini(true),
+            include_str!("../tests/golden/adwaita.settings.ini")
+        );
+    }
+}
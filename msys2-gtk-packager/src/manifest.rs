@@ -0,0 +1,69 @@
+/// A Windows application manifest for GTK apps.
+///
+/// It enables per-monitor DPI awareness, so windows are not blurry on HiDPI displays,
+/// long path support, and common controls v6, which native file dialogs use.
+pub const APP_MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0">
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity type="win32" name="Microsoft.Windows.Common-Controls" version="6.0.0.0" processorArchitecture="*" publicKeyToken="6595b64144ccf1df" language="*"/>
+    </dependentAssembly>
+  </dependency>
+  <application xmlns="urn:schemas-microsoft-com:asm.v3">
+    <windowsSettings>
+      <dpiAware xmlns="http://schemas.microsoft.com/SMI/2005/WindowsSettings">true/pm</dpiAware>
+      <dpiAwareness xmlns="http://schemas.microsoft.com/SMI/2016/WindowsSettings">PerMonitorV2, PerMonitor</dpiAwareness>
+      <longPathAware xmlns="http://schemas.microsoft.com/SMI/2016/WindowsSettings">true</longPathAware>
+    </windowsSettings>
+  </application>
+  <compatibility xmlns="urn:schemas-microsoft-com:compatibility.v1">
+    <application>
+      <!-- Windows 10 and 11 -->
+      <supportedOS Id="{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"/>
+    </application>
+  </compatibility>
+  <trustInfo xmlns="urn:schemas-microsoft-com:asm.v3">
+    <security>
+      <requestedPrivileges>
+        <requestedExecutionLevel level="asInvoker" uiAccess="false"/>
+      </requestedPrivileges>
+    </security>
+  </trustInfo>
+</assembly>
+"#;
+
+/// An error that may occur while parsing a [`ManifestMode`] from a string.
+#[derive(Debug)]
+pub struct ManifestModeFromStrError(String);
+
+impl std::fmt::Display for ManifestModeFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid manifest mode", self.0)
+    }
+}
+
+impl std::error::Error for ManifestModeFromStrError {}
+
+/// How to give a built exe an application manifest
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ManifestMode {
+    /// Link the manifest into the exe as a resource, while building.
+    Embed,
+
+    /// Write the manifest next to the packaged exe, as `<exe>.manifest`.
+    ///
+    /// Windows ignores this if the exe already has an embedded manifest.
+    Alongside,
+}
+
+impl std::str::FromStr for ManifestMode {
+    type Err = ManifestModeFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "embed" => Ok(Self::Embed),
+            "alongside" => Ok(Self::Alongside),
+            _ => Err(ManifestModeFromStrError(input.into())),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::installer::InstallerMetadata;
+use anyhow::bail;
+use anyhow::Context;
+
+/// The pieces a packaged app needs for `GNotification` toasts to show on Windows
+#[derive(Debug)]
+pub struct NotificationSupport {
+    /// The AppUserModelID that toasts are attributed to
+    pub app_user_model_id: String,
+}
+
+impl NotificationSupport {
+    /// Check that a package has everything it needs to show notifications.
+    ///
+    /// Windows only shows toasts for apps that were started from a shortcut with an AppUserModelID,
+    /// or that set one themselves, and it shows the icon of that shortcut on them.
+    ///
+    /// # Errors
+    /// Returns an error listing every missing piece and how to add it.
+    pub fn check(package: &cargo_metadata::Package) -> anyhow::Result<Self> {
+        let metadata = InstallerMetadata::from_package(package).with_context(|| {
+            format!(
+                "failed to read installer settings from `{}`",
+                package.manifest_path
+            )
+        })?;
+
+        let mut problems = Vec::new();
+        if metadata.app_user_model_id.is_none() {
+            problems.push(format!(
+                "there is no AppUserModelID, which toasts are attributed to. Set `app-user-model-id` in `[package.metadata.{}]`",
+                crate::asset::METADATA_KEY
+            ));
+        }
+        match metadata.icon.as_ref() {
+            Some(icon) if !icon.is_file() => {
+                problems.push(format!("the app icon `{icon}` does not exist"));
+            }
+            Some(_) => {}
+            None => {
+                problems.push(
+                    "there is no app icon, which toasts show. Set `product-icon` in `[package.metadata.wix]`, or add an `.ico` to `icon` in `[package.metadata.bundle]`".into(),
+                );
+            }
+        }
+
+        if !problems.is_empty() {
+            let problems = problems
+                .iter()
+                .map(|problem| format!("  - {problem}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            bail!("the app is not set up for notifications:\n{problems}");
+        }
+
+        Ok(Self {
+            app_user_model_id: metadata
+                .app_user_model_id
+                .context("missing AppUserModelID")?,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use serde::Serialize;
+use std::process::Command;
+
+/// The file name of the package info written to package dirs.
+pub const PACKAGE_INFO_FILE_NAME: &str = "package-info.json";
+
+/// The state of the git checkout a package was made from
+#[derive(Debug, Serialize)]
+pub struct GitStatus {
+    /// The full hash of the checked out commit
+    pub commit: String,
+
+    /// Whether there are uncommitted changes, including untracked files that are not ignored
+    pub dirty: bool,
+
+    /// The uncommitted changes, from `git status --porcelain`, like ` M src/main.rs`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+}
+
+impl GitStatus {
+    /// Get the git status of the checkout that contains a dir.
+    ///
+    /// # Returns
+    /// Returns None if git is not installed or the dir is not in a git checkout.
+    pub fn get(dir: &Utf8Path) -> anyhow::Result<Option<Self>> {
+        let output = match Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(dir)
+            .output()
+        {
+            Ok(output) => output,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error).context("failed to run `git`"),
+        };
+        // This also fails for repos without commits, which have nothing to record.
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let commit = String::from_utf8(output.stdout)
+            .context("git commit is not utf8")?
+            .trim()
+            .to_string();
+
+        let output = Command::new("git")
+            .args(["status", "--porcelain"])
+            .current_dir(dir)
+            .output()
+            .context("failed to run `git status`")?;
+        ensure!(
+            output.status.success(),
+            "`git status` exited with `{}`: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        let changes: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(String::from)
+            .collect();
+
+        Ok(Some(Self {
+            commit,
+            dirty: !changes.is_empty(),
+            changes,
+        }))
+    }
+}
+
+/// Where a package came from, which is written to [`PACKAGE_INFO_FILE_NAME`] in the package dir
+#[derive(Debug, Serialize)]
+pub struct PackageInfo<'a> {
+    /// The cargo package name
+    pub name: &'a str,
+
+    /// The cargo package version
+    pub version: String,
+
+    /// The target triple
+    pub target: &'a str,
+
+    /// The build profile
+    pub profile: &'a str,
+
+    /// The git checkout the package was built from, if it was built from one
+    pub git: Option<&'a GitStatus>,
+}
+
+impl PackageInfo<'_> {
+    /// Write this info to a file, which is packaged as [`PACKAGE_INFO_FILE_NAME`].
+    pub fn save(&self, path: &Utf8Path) -> anyhow::Result<()> {
+        let mut data = serde_json::to_string_pretty(self)?;
+        data.push('\n');
+        std::fs::write(path, data).with_context(|| format!("failed to write `{path}`"))?;
+
+        Ok(())
+    }
+}
+
+/// Check that a release package is not built from uncommitted changes, for `--require-clean-git`.
+///
+/// `status` is the status of the checkout at `workspace_root`, from [`GitStatus::get`],
+/// which should be captured once before anything is written, and also recorded in the package info.
+/// Packages of the `dev` and `test` profiles are allowed, as they are not shipped.
+pub fn ensure_clean_git(
+    status: Option<&GitStatus>,
+    workspace_root: &Utf8Path,
+    profile: &str,
+) -> anyhow::Result<()> {
+    if matches!(profile, "dev" | "test") {
+        return Ok(());
+    }
+
+    let status = status.with_context(|| {
+        format!("`--require-clean-git` was passed, but `{workspace_root}` is not in a git checkout with a commit")
+    })?;
+    if status.dirty {
+        let num_shown = 10;
+        let mut changes = status
+            .changes
+            .iter()
+            .take(num_shown)
+            .map(|change| format!("  {change}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if status.changes.len() > num_shown {
+            changes.push_str(&format!(
+                "\n  and {} more",
+                status.changes.len() - num_shown
+            ));
+        }
+        bail!(
+            "refusing to package the `{profile}` profile from uncommitted changes, commit or stash them first:\n{changes}"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2::Msys2Environment;
+use msys2_packager::pacman::LocalDatabase;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Packages that are needed in the MSYS2 environment, without the environment prefix.
+///
+/// The toolchain is checked separately, as it depends on the environment.
+const REQUIRED_PACKAGES: &[&str] = &[
+    "pkgconf",
+    "gtk4",
+    "gstreamer",
+    "gst-plugins-base",
+    "gst-plugins-good",
+    "gst-plugins-bad",
+];
+
+/// The path to pacman, relative to the MSYS2 installation.
+const PACMAN_PATH: &str = "usr/bin/pacman.exe";
+
+/// A package that must be installed in an MSYS2 environment
+#[derive(Debug)]
+pub struct RequiredPackage {
+    /// The package name, like `mingw-w64-ucrt-x86_64-gtk4`
+    pub name: String,
+
+    /// The name to pass to pacman if it is missing, which may be a group, like `mingw-w64-ucrt-x86_64-toolchain`
+    pub install: String,
+}
+
+/// Crates that link against MSYS2 libraries, and the packages with those libraries, without the environment prefix.
+///
+/// GTK 4 and GStreamer are always required, as every package gets the GStreamer media backend of GTK 4.
+const CRATE_PACKAGES: &[(&str, &str)] = &[
+    ("gtk", "gtk3"),
+    ("libadwaita", "libadwaita"),
+    ("sourceview5", "gtksourceview5"),
+    ("webkit6", "webkitgtk-6.0"),
+];
+
+/// Get the packages that must be installed in an MSYS2 environment to build and package for it.
+///
+/// The C toolchain comes first, followed by GTK, GStreamer, and pkg-config,
+/// and then the packages needed by `crates`, which are the crates the app depends on.
+pub fn get_required_packages(
+    environment: Msys2Environment,
+    crates: &HashSet<&str>,
+) -> Vec<RequiredPackage> {
+    let package_prefix = environment.get_package_prefix();
+    let compiler = get_compiler_package(environment);
+
+    let mut packages = vec![RequiredPackage {
+        name: format!("{package_prefix}{compiler}"),
+        install: format!("{package_prefix}toolchain"),
+    }];
+    let crate_packages = CRATE_PACKAGES
+        .iter()
+        .filter(|(crate_name, _)| crates.contains(crate_name))
+        .map(|(_, package)| package);
+    packages.extend(
+        REQUIRED_PACKAGES
+            .iter()
+            .chain(crate_packages)
+            .map(|package| {
+                let name = format!("{package_prefix}{package}");
+                RequiredPackage {
+                    install: name.clone(),
+                    name,
+                }
+            }),
+    );
+
+    packages
+}
+
+/// Get the pacman command that installs packages.
+pub fn get_install_command<'a, I>(packages: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut command = String::from("pacman -S --needed");
+    for package in packages {
+        command.push(' ');
+        command.push_str(package);
+    }
+
+    command
+}
+
+/// Get the packages that are not installed.
+fn get_missing_packages(
+    msys2_installation_path: &Utf8Path,
+    packages: Vec<RequiredPackage>,
+) -> anyhow::Result<Vec<RequiredPackage>> {
+    let database = LocalDatabase::load(msys2_installation_path)
+        .context("failed to load the pacman database")?;
+
+    Ok(packages
+        .into_iter()
+        .filter(|package| !database.is_installed(&package.name))
+        .collect())
+}
+
+/// Check that the packages needed to build and package for an MSYS2 environment are installed.
+///
+/// `packages` are from [`get_required_packages`].
+///
+/// # Errors
+/// Returns an error with a `pacman -S` command that installs every missing package.
+pub fn run_preflight(
+    msys2_installation_path: &Utf8Path,
+    environment: Msys2Environment,
+    packages: Vec<RequiredPackage>,
+) -> anyhow::Result<()> {
+    let missing = get_missing_packages(msys2_installation_path, packages)?;
+    ensure!(
+        missing.is_empty(),
+        "{} are not installed in the `{}` environment, install them with `{}`, pass `--auto-install-msys2-deps`, or pass `--skip-preflight`",
+        missing
+            .iter()
+            .map(|package| format!("`{}`", package.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        environment.get_name(),
+        get_install_command(missing.iter().map(|package| package.install.as_str()))
+    );
+
+    Ok(())
+}
+
+/// Install the packages needed to build and package for an MSYS2 environment that are missing, with pacman.
+///
+/// `packages` are from [`get_required_packages`].
+/// pacman is run without confirmation, so this is meant for CI machines that start from a bare MSYS2 installation.
+pub fn install_missing_packages(
+    msys2_installation_path: &Utf8Path,
+    packages: Vec<RequiredPackage>,
+) -> anyhow::Result<()> {
+    let missing = get_missing_packages(msys2_installation_path, packages)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    // Outside of an MSYS2 shell, pacman is not on the PATH.
+    let pacman = msys2_installation_path.join(PACMAN_PATH);
+    let pacman = if pacman.exists() {
+        pacman.into_std_path_buf()
+    } else {
+        PathBuf::from("pacman")
+    };
+    let install: Vec<_> = missing
+        .iter()
+        .map(|package| package.install.as_str())
+        .collect();
+    eprintln!("Installing {}", install.join(", "));
+    let status = Command::new(&pacman)
+        .args(["-S", "--needed", "--noconfirm"])
+        .args(&install)
+        .status()
+        .with_context(|| format!("failed to run `{}`", pacman.display()))?;
+    ensure!(
+        status.success(),
+        "`{}` exited with `{status}`",
+        get_install_command(install.iter().copied())
+    );
+
+    Ok(())
+}
+
+/// Get the name of the package providing the compiler of an environment, without the environment prefix.
+fn get_compiler_package(environment: Msys2Environment) -> &'static str {
+    match environment {
+        Msys2Environment::Clang64 | Msys2Environment::Clang32 | Msys2Environment::ClangArm64 => {
+            "clang"
+        }
+        _ => "gcc",
+    }
+}
@@ -0,0 +1,24 @@
+use camino::Utf8Path;
+use msys2_packager::packager::Packager;
+
+/// The dir of GTK print backends, relative to an MSYS2 environment or the package dir.
+pub const PRINT_BACKENDS_DIR: &str = "lib/gtk-4.0/4.0.0/printbackends";
+
+/// Add every GTK print backend of an MSYS2 environment to a packager, with their dependencies.
+///
+/// Print backends, like printing to a file, are used by the GTK print dialog.
+///
+/// # Returns
+/// Returns the number of backends added.
+pub fn add_print_backends(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+) -> anyhow::Result<usize> {
+    match crate::gio_modules::add_module_dir(packager, environment_path, PRINT_BACKENDS_DIR)? {
+        Some(num_backends) => Ok(num_backends),
+        None => {
+            eprintln!("warning: `{}` does not exist, so no print backends will be packaged. `GtkPrintOperation` will only use the native Windows print dialog", environment_path.join(PRINT_BACKENDS_DIR));
+            Ok(0)
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::installer::InstallerMetadata;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use std::fmt::Write as _;
+use std::process::Command;
+
+/// The names of resource compilers in an MSYS2 environment, in the order they are tried.
+///
+/// Clang environments may only have `llvm-windres`.
+const WINDRES_FILE_NAMES: &[&str] = &["windres.exe", "llvm-windres.exe"];
+
+/// Which resources to link into built binaries
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EmbedResources {
+    /// Whether to embed the app icon and version info
+    pub version_info: bool,
+
+    /// Whether to embed the application manifest, see [`crate::manifest::APP_MANIFEST`]
+    pub manifest: bool,
+}
+
+impl EmbedResources {
+    /// Check if no resources are embedded.
+    pub fn is_empty(self) -> bool {
+        !self.version_info && !self.manifest
+    }
+}
+
+/// Generate a resource script.
+///
+/// If `metadata` is given, the app icon and version info of the package are included.
+/// Settings are read like the installer settings, so the exe matches its installer.
+/// If `manifest_path` is given, that application manifest is included.
+pub fn generate_rc(
+    package: &cargo_metadata::Package,
+    metadata: Option<&InstallerMetadata>,
+    manifest_path: Option<&Utf8Path>,
+) -> anyhow::Result<String> {
+    let mut rc = String::new();
+    if let Some(manifest_path) = manifest_path {
+        // CREATEPROCESS_MANIFEST_RESOURCE_ID, RT_MANIFEST
+        writeln!(rc, "1 24 {}", quote(manifest_path.as_str()))?;
+        writeln!(rc)?;
+    }
+    if let Some(metadata) = metadata {
+        write_version_info(&mut rc, package, metadata)?;
+    }
+
+    Ok(rc)
+}
+
+/// Write the app icon and version info of a package to a resource script.
+fn write_version_info(
+    rc: &mut String,
+    package: &cargo_metadata::Package,
+    metadata: &InstallerMetadata,
+) -> anyhow::Result<()> {
+    // Version resources have four 16-bit parts.
+    let version = [
+        package.version.major,
+        package.version.minor,
+        package.version.patch,
+        0,
+    ]
+    .map(|part| u16::try_from(part).unwrap_or(u16::MAX));
+    let version = version.map(|part| part.to_string()).join(",");
+
+    if let Some(icon) = metadata.icon.as_ref() {
+        ensure!(icon.is_file(), "the app icon `{icon}` does not exist");
+        writeln!(rc, "1 ICON {}", quote(icon.as_str()))?;
+        writeln!(rc)?;
+    }
+
+    writeln!(rc, "1 VERSIONINFO")?;
+    writeln!(rc, "FILEVERSION {version}")?;
+    writeln!(rc, "PRODUCTVERSION {version}")?;
+    // VOS_NT_WINDOWS32
+    writeln!(rc, "FILEOS 0x40004")?;
+    // VFT_APP
+    writeln!(rc, "FILETYPE 0x1")?;
+    writeln!(rc, "BEGIN")?;
+    writeln!(rc, "  BLOCK \"StringFileInfo\"")?;
+    writeln!(rc, "  BEGIN")?;
+    // US English, Unicode
+    writeln!(rc, "    BLOCK \"040904B0\"")?;
+    writeln!(rc, "    BEGIN")?;
+    let mut write_value = |name: &str, value: Option<&str>| -> std::fmt::Result {
+        match value {
+            Some(value) => writeln!(rc, "      VALUE \"{name}\", {}", quote(value)),
+            None => Ok(()),
+        }
+    };
+    write_value("CompanyName", metadata.publisher.as_deref())?;
+    write_value(
+        "FileDescription",
+        Some(metadata.description.as_deref().unwrap_or(&metadata.name)),
+    )?;
+    write_value("FileVersion", Some(&metadata.version))?;
+    write_value("LegalCopyright", metadata.copyright.as_deref())?;
+    write_value("ProductName", Some(&metadata.name))?;
+    write_value("ProductVersion", Some(&metadata.version))?;
+    writeln!(rc, "    END")?;
+    writeln!(rc, "  END")?;
+    writeln!(rc, "  BLOCK \"VarFileInfo\"")?;
+    writeln!(rc, "  BEGIN")?;
+    writeln!(rc, "    VALUE \"Translation\", 0x409, 1200")?;
+    writeln!(rc, "  END")?;
+    writeln!(rc, "END")?;
+
+    Ok(())
+}
+
+/// Compile a resource script into a COFF object with windres, which can be passed to the linker.
+///
+/// `environment_path` is the MSYS2 environment to take windres from, like `C:/msys64/ucrt64`.
+pub fn compile_rc(
+    environment_path: &Utf8Path,
+    rc_path: &Utf8Path,
+    object_path: &Utf8Path,
+) -> anyhow::Result<()> {
+    let windres = WINDRES_FILE_NAMES
+        .iter()
+        .map(|name| environment_path.join("bin").join(name))
+        .find(|path| path.is_file())
+        .context(
+            "failed to locate windres, install the binutils package of the MSYS2 environment",
+        )?;
+
+    let output = Command::new(&windres)
+        .arg("--input")
+        .arg(rc_path)
+        .arg("--output")
+        .arg(object_path)
+        .arg("--output-format=coff")
+        .output()
+        .with_context(|| format!("failed to run `{windres}`"))?;
+    ensure!(
+        output.status.success(),
+        "failed to compile `{rc_path}`: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+/// Quote a resource script string, escaping quotes and backslashes.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\"\""))
+}
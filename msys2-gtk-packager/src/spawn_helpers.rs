@@ -0,0 +1,45 @@
+use camino::Utf8Path;
+use msys2::Msys2Arch;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+
+/// Get the file names of the helper exes that GLib runs for `g_spawn_*`, for an arch.
+///
+/// GLib names them after whether it was built for 64-bit Windows, so aarch64 uses the `win64` helpers.
+pub fn get_spawn_helper_file_names(arch: Msys2Arch) -> [&'static str; 2] {
+    match arch {
+        Msys2Arch::I686 => ["gspawn-win32-helper.exe", "gspawn-win32-helper-console.exe"],
+        _ => ["gspawn-win64-helper.exe", "gspawn-win64-helper-console.exe"],
+    }
+}
+
+/// Add the GLib spawn helpers of an MSYS2 environment to a packager, next to the GLib dll.
+///
+/// GLib runs these to start subprocesses, so without them `g_spawn_*` and `GSubprocess` fail at runtime.
+/// Newer GLib versions may not ship them, so missing helpers are skipped with a warning.
+///
+/// # Returns
+/// Returns the number of helpers added.
+pub fn add_spawn_helpers(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+    arch: Msys2Arch,
+) -> usize {
+    let mut num_helpers = 0;
+    for file_name in get_spawn_helper_file_names(arch) {
+        let path = environment_path.join("bin").join(file_name);
+        if !path.is_file() {
+            eprintln!("warning: `{path}` does not exist, so it will not be packaged. `g_spawn_*` may fail at runtime");
+            continue;
+        }
+
+        packager.add_file(
+            Some(path.into()),
+            file_name.into(),
+            FileFlags::EXE | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+        );
+        num_helpers += 1;
+    }
+
+    num_helpers
+}
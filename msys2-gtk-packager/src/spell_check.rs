@@ -0,0 +1,191 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use walkdir::WalkDir;
+
+/// The dir of enchant providers, relative to an MSYS2 environment or the package dir.
+pub const ENCHANT_PROVIDERS_DIR: &str = "lib/enchant-2";
+
+/// The data dirs of enchant, like the provider ordering, relative to an MSYS2 environment or the package dir.
+const ENCHANT_DATA_DIRS: &[&str] = &["share/enchant", "share/enchant-2"];
+
+/// The dir of hunspell dictionaries, relative to an MSYS2 environment or the package dir.
+pub const HUNSPELL_DIR: &str = "share/hunspell";
+
+/// What was added by [`add_spell_check`]
+#[derive(Debug, Default)]
+pub struct SpellCheckSummary {
+    /// The number of enchant providers
+    pub num_providers: usize,
+
+    /// The number of hunspell dictionaries, each of which is a `.dic` and `.aff` file
+    pub num_dictionaries: usize,
+}
+
+/// Add the enchant providers and hunspell dictionaries of an MSYS2 environment to a packager, for gspell and libspelling.
+///
+/// `languages` selects dictionaries, like `de` for every German dictionary or `en_US` for only American English.
+/// Every dictionary is added if it is empty.
+pub fn add_spell_check(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+    languages: &[String],
+) -> anyhow::Result<SpellCheckSummary> {
+    let num_providers =
+        crate::gio_modules::add_module_dir(packager, environment_path, ENCHANT_PROVIDERS_DIR)?
+            .with_context(|| {
+                format!(
+                    "`{}` does not exist, install the enchant package of the MSYS2 environment",
+                    environment_path.join(ENCHANT_PROVIDERS_DIR)
+                )
+            })?;
+
+    for dir in ENCHANT_DATA_DIRS {
+        let path = environment_path.join(dir);
+        if !path.is_dir() {
+            continue;
+        }
+        for entry in WalkDir::new(&path).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("failed to read `{path}`"))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(environment_path)
+                .context("dir entry path is not prefixed by the environment")?;
+            packager.add_file(
+                Some(entry.path().into()),
+                relative_path.into(),
+                FileFlags::empty(),
+            );
+        }
+    }
+
+    let num_dictionaries = add_dictionaries(packager, environment_path, languages)?;
+
+    Ok(SpellCheckSummary {
+        num_providers,
+        num_dictionaries,
+    })
+}
+
+/// Add the hunspell dictionaries of an MSYS2 environment that match `languages` to a packager.
+///
+/// # Returns
+/// Returns the number of dictionaries added.
+fn add_dictionaries(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+    languages: &[String],
+) -> anyhow::Result<usize> {
+    let path = environment_path.join(HUNSPELL_DIR);
+    let dir_entries = match path.read_dir_utf8() {
+        Ok(dir_entries) => dir_entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("warning: `{path}` does not exist, so no dictionaries will be packaged. Install the hunspell dictionary packages of the MSYS2 environment, like hunspell-en");
+            return Ok(0);
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read `{path}`"));
+        }
+    };
+
+    let mut dictionaries = Vec::new();
+    for dir_entry in dir_entries {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name();
+        let Some(name) = file_name.strip_suffix(".dic") else {
+            continue;
+        };
+        if !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+        if languages.is_empty()
+            || languages
+                .iter()
+                .any(|language| is_dictionary_for_language(name, language))
+        {
+            dictionaries.push(name.to_string());
+        }
+    }
+    dictionaries.sort();
+
+    for language in languages.iter() {
+        if !dictionaries
+            .iter()
+            .any(|name| is_dictionary_for_language(name, language))
+        {
+            eprintln!("warning: there is no dictionary for `{language}` in `{path}`");
+        }
+    }
+
+    for name in dictionaries.iter() {
+        for extension in ["dic", "aff"] {
+            let file_name = format!("{name}.{extension}");
+            let src = path.join(&file_name);
+            if !src.is_file() {
+                eprintln!(
+                    "warning: `{src}` does not exist, so the `{name}` dictionary may not load"
+                );
+                continue;
+            }
+            packager.add_file(
+                Some(src.into()),
+                Utf8Path::new(HUNSPELL_DIR).join(file_name).into(),
+                FileFlags::empty(),
+            );
+        }
+    }
+
+    Ok(dictionaries.len())
+}
+
+/// Check if a dictionary, like `de_DE`, is for a language, like `de` or `de_DE`.
+///
+/// Languages without a region match every region.
+fn is_dictionary_for_language(name: &str, language: &str) -> bool {
+    let language = language.replace('-', "_");
+    name.eq_ignore_ascii_case(&language)
+        || name
+            .get(..language.len() + 1)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{language}_")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_without_region_matches_every_region() {
+        assert!(is_dictionary_for_language("de", "de"));
+        assert!(is_dictionary_for_language("de_DE", "de"));
+        assert!(is_dictionary_for_language("de_AT", "de"));
+        assert!(!is_dictionary_for_language("de", "de_DE"));
+        assert!(!is_dictionary_for_language("de_AT", "de_DE"));
+    }
+
+    #[test]
+    fn language_may_use_a_hyphen() {
+        assert!(is_dictionary_for_language("en_US", "en-US"));
+        assert!(is_dictionary_for_language("en_US", "en_US"));
+        assert!(!is_dictionary_for_language("en_GB", "en-US"));
+    }
+
+    #[test]
+    fn language_is_case_insensitive() {
+        assert!(is_dictionary_for_language("en_US", "en-us"));
+        assert!(is_dictionary_for_language("EN_us", "en_US"));
+        assert!(is_dictionary_for_language("de_DE", "DE"));
+    }
+
+    #[test]
+    fn language_must_match_whole() {
+        assert!(!is_dictionary_for_language("eo", "en"));
+        assert!(!is_dictionary_for_language("en", "eo"));
+        assert!(!is_dictionary_for_language("ena", "en"));
+        assert!(!is_dictionary_for_language("e", "en"));
+    }
+}
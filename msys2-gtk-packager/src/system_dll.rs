@@ -0,0 +1,73 @@
+use anyhow::Context;
+use msys2_packager::packager::Packager;
+use msys2_packager::resolver::ResolveOptions;
+use serde::Deserialize;
+
+/// The system dll settings of this tool's section in `package.metadata` of a Cargo.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SystemDllMetadata {
+    /// Glob patterns of dll names to treat as system dlls
+    #[serde(default)]
+    system_dlls: Vec<String>,
+
+    /// Glob patterns of dll names to package, even if they are considered system dlls
+    #[serde(default)]
+    bundle_dlls: Vec<String>,
+}
+
+/// Changes to which dlls are considered system dlls, provided by the OS
+#[derive(Debug, Default, Clone)]
+pub struct SystemDllRules {
+    /// Glob patterns of dll names to treat as system dlls, which are never packaged
+    pub system: Vec<String>,
+
+    /// Glob patterns of dll names to package from MSYS2 when imported, even if they are considered system dlls
+    pub bundle: Vec<String>,
+}
+
+impl SystemDllRules {
+    /// Get the rules listed in the `[package.metadata.msys2-gtk-packager]` section of a package's Cargo.toml,
+    /// as `system-dlls` and `bundle-dlls`.
+    pub fn from_manifest(package: &cargo_metadata::Package) -> anyhow::Result<Self> {
+        let metadata = match package.metadata.get(crate::asset::METADATA_KEY) {
+            Some(metadata) => SystemDllMetadata::deserialize(metadata).with_context(|| {
+                format!("invalid `package.metadata.{}`", crate::asset::METADATA_KEY)
+            })?,
+            None => SystemDllMetadata::default(),
+        };
+
+        Ok(Self {
+            system: metadata.system_dlls,
+            bundle: metadata.bundle_dlls,
+        })
+    }
+
+    /// Add the rules of another set of rules to this one.
+    pub fn extend(&mut self, other: &Self) -> &mut Self {
+        self.system.extend(other.system.iter().cloned());
+        self.bundle.extend(other.bundle.iter().cloned());
+        self
+    }
+
+    /// Apply these rules to a packager.
+    pub fn apply(&self, packager: &mut Packager) {
+        for pattern in self.system.iter() {
+            packager.system_dll(pattern.clone());
+        }
+        for pattern in self.bundle.iter() {
+            packager.bundle_dll(pattern.clone());
+        }
+    }
+    /// Get these rules as [`ResolveOptions`], for checking imports without a packager.
+    pub fn to_resolve_options(&self) -> ResolveOptions {
+        let mut options = ResolveOptions::new();
+        for pattern in self.system.iter() {
+            options.system(pattern.clone());
+        }
+        for pattern in self.bundle.iter() {
+            options.bundle(pattern.clone());
+        }
+        options
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::extract::get_package_root;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use msys2_packager::pacman::InstalledPackage;
+use msys2_packager::pacman::LocalDatabase;
+use std::path::PathBuf;
+
+/// The prefix of a theme that comes from an installed MSYS2 package.
+const PACKAGE_PREFIX: &str = "pkg:";
+
+/// An error that may occur while parsing a [`Theme`] from a string.
+#[derive(Debug)]
+pub struct ThemeFromStrError(String);
+
+impl std::fmt::Display for ThemeFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid theme", self.0)
+    }
+}
+
+impl std::error::Error for ThemeFromStrError {}
+
+/// A theme to package
+#[derive(Debug, Clone)]
+pub enum Theme {
+    /// A theme dir
+    Dir(PathBuf),
+
+    /// An MSYS2 package that installs a theme, like `mingw-w64-ucrt-x86_64-materia-gtk-theme`.
+    ///
+    /// This is written as `pkg:<name>`.
+    Package(String),
+}
+
+impl std::str::FromStr for Theme {
+    type Err = ThemeFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.strip_prefix(PACKAGE_PREFIX) {
+            Some("") => Err(ThemeFromStrError(input.into())),
+            Some(name) => Ok(Self::Package(name.into())),
+            None if input.is_empty() => Err(ThemeFromStrError(input.into())),
+            None => Ok(Self::Dir(input.into())),
+        }
+    }
+}
+
+/// Add the theme files of an installed MSYS2 package to the packager.
+///
+/// Files under `share/themes` are copied to the same path in the package.
+/// GTK theme engine dlls are copied too, along with their dll dependencies.
+/// Themes often depend on a separate engine package,
+/// so the engines of the direct dependencies of the package are copied as well,
+/// but nothing else of them, and not their own dependencies, which would pull in most of GTK.
+pub fn add_theme_package(
+    packager: &mut Packager,
+    database: &LocalDatabase,
+    msys2_installation_path: &Utf8Path,
+    name: &str,
+) -> anyhow::Result<()> {
+    let package = database.get(name).with_context(|| {
+        format!("theme package `{name}` is not installed, install it with `pacman -S {name}`")
+    })?;
+
+    let found_theme = add_package_files(packager, msys2_installation_path, package, true)?;
+    ensure!(found_theme, "`{name}` does not install a theme");
+
+    // Dependencies that are not installed are left to pacman to complain about.
+    for depend in package
+        .depends
+        .iter()
+        .filter_map(|depend| database.get(depend))
+    {
+        add_package_files(packager, msys2_installation_path, depend, false)?;
+    }
+
+    Ok(())
+}
+
+/// Add the theme engines of an installed MSYS2 package to the packager, and its themes if `themes` is true.
+///
+/// # Returns
+/// Returns whether the package has any theme files.
+fn add_package_files(
+    packager: &mut Packager,
+    msys2_installation_path: &Utf8Path,
+    package: &InstalledPackage,
+    themes: bool,
+) -> anyhow::Result<bool> {
+    let root = get_package_root(&package.name);
+    let files = package
+        .load_files()
+        .with_context(|| format!("failed to load files for `{}`", package.name))?;
+
+    let mut found_theme = false;
+    for file in files {
+        let relative_path = match file.strip_prefix(root) {
+            Ok(relative_path) => relative_path,
+            Err(_) => continue,
+        };
+        let src = msys2_installation_path.join(&file);
+
+        if relative_path.starts_with("share/themes") {
+            found_theme = true;
+            if themes {
+                packager.add_file(Some(src.into()), relative_path.into(), FileFlags::empty());
+            }
+        } else if is_theme_engine(relative_path) {
+            packager.add_file(
+                Some(src.into()),
+                relative_path.into(),
+                FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+            );
+        }
+    }
+
+    Ok(found_theme)
+}
+
+/// Check if a path, relative to a package root, is a GTK theme engine dll.
+///
+/// GTK 2 loads engines from `lib/gtk-2.0/<version>/engines`,
+/// and GTK 3 loads them from `lib/gtk-3.0/<version>/theming-engines`.
+fn is_theme_engine(path: &Utf8Path) -> bool {
+    let is_dll = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dll"));
+    let in_engine_dir = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .is_some_and(|dir| dir == "engines" || dir == "theming-engines");
+
+    is_dll && path.starts_with("lib") && in_engine_dir
+}
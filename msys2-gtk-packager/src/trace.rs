@@ -0,0 +1,200 @@
+use anyhow::Context;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::ExitStatus;
+use walkdir::WalkDir;
+
+/// How often the modules of a traced process are enumerated.
+#[cfg(windows)]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// The modules a process loaded while it ran
+#[derive(Debug)]
+pub struct LoadTrace {
+    /// The exit status of the process
+    pub status: ExitStatus,
+
+    /// The full paths of every module seen loaded in the process, including the exe
+    pub modules: BTreeSet<PathBuf>,
+}
+
+impl LoadTrace {
+    /// Run a command, recording which modules it loads.
+    ///
+    /// The modules of the process are enumerated periodically until it exits,
+    /// so a module that is loaded and unloaded between two polls may be missed.
+    #[cfg(windows)]
+    pub fn run(command: &mut Command) -> anyhow::Result<Self> {
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("failed to spawn `{command:?}`"))?;
+        let pid = child.id();
+
+        let mut modules = BTreeSet::new();
+        let status = loop {
+            // Enumeration fails while the process is starting or exiting, so those polls are skipped.
+            if let Some(snapshot) = snapshot_modules(pid) {
+                modules.extend(snapshot);
+            }
+
+            if let Some(status) = child.try_wait().context("failed to wait for process")? {
+                break status;
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        Ok(Self { status, modules })
+    }
+
+    /// Run a command, recording which modules it loads.
+    ///
+    /// This is only supported on Windows.
+    #[cfg(not(windows))]
+    pub fn run(command: &mut Command) -> anyhow::Result<Self> {
+        anyhow::bail!("tracing loads of `{command:?}` is only supported on Windows");
+    }
+
+    /// Compare the loaded modules against a package dir.
+    ///
+    /// `msys2_installation_path` is used to find modules that were loaded from MSYS2 instead of the package.
+    pub fn suggest(
+        &self,
+        package_dir: &Path,
+        msys2_installation_path: &Path,
+    ) -> anyhow::Result<LoadSuggestions> {
+        let loaded: BTreeSet<String> = self
+            .modules
+            .iter()
+            .map(|path| normalize_path(path))
+            .collect();
+
+        let mut unused = Vec::new();
+        for entry in WalkDir::new(package_dir) {
+            let entry = entry.context("failed to read package dir entry")?;
+            let path = entry.path();
+            let is_dll = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("dll"));
+            if !entry.file_type().is_file() || !is_dll {
+                continue;
+            }
+
+            if !loaded.contains(&normalize_path(path)) {
+                unused.push(
+                    path.strip_prefix(package_dir)
+                        .context("package file is not in the package dir")?
+                        .into(),
+                );
+            }
+        }
+
+        let msys2_installation_path = normalize_path(msys2_installation_path);
+        let missing = self
+            .modules
+            .iter()
+            .filter(|path| normalize_path(path).starts_with(&msys2_installation_path))
+            .cloned()
+            .collect();
+
+        Ok(LoadSuggestions { unused, missing })
+    }
+}
+
+/// Suggestions for refining a package, from a [`LoadTrace`]
+#[derive(Debug)]
+pub struct LoadSuggestions {
+    /// Packaged dlls that were never loaded, relative to the package dir.
+    ///
+    /// These may be excludable, though they may also be needed by code paths the run did not hit.
+    pub unused: Vec<PathBuf>,
+
+    /// Modules that were loaded from the MSYS2 installation instead of the package.
+    ///
+    /// These are usually loaded with `LoadLibrary` from an absolute path, so they are not found as imports.
+    pub missing: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for LoadSuggestions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Packaged dlls that were never loaded:")?;
+        if self.unused.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for path in self.unused.iter() {
+            writeln!(f, "  {}", path.display())?;
+        }
+
+        writeln!(f, "Modules loaded from MSYS2 instead of the package:")?;
+        if self.missing.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for path in self.missing.iter() {
+            writeln!(f, "  {}", path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Normalize a path for comparison, like Windows does.
+///
+/// Matching is case-insensitive, and `/` and `\` are treated the same.
+fn normalize_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let path = path.strip_prefix(r"\\?\").unwrap_or(&path);
+    path.replace('/', "\\").to_ascii_lowercase()
+}
+
+/// Get the paths of the modules currently loaded in a process.
+///
+/// # Returns
+/// Returns None if the modules could not be enumerated.
+#[cfg(windows)]
+fn snapshot_modules(pid: u32) -> Option<Vec<PathBuf>> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::CreateToolhelp32Snapshot;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::Module32FirstW;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::Module32NextW;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::MODULEENTRY32W;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::TH32CS_SNAPMODULE;
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::TH32CS_SNAPMODULE32;
+
+    // SAFETY: This is called with valid flags, and the returned handle is checked.
+    let snapshot =
+        unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, pid) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut modules = Vec::new();
+    let mut entry = MODULEENTRY32W {
+        dwSize: std::mem::size_of::<MODULEENTRY32W>() as u32,
+        ..Default::default()
+    };
+    // SAFETY: The snapshot handle is valid and `entry` is initialized with its size.
+    let mut ok = unsafe { Module32FirstW(snapshot, &mut entry) };
+    while ok != 0 {
+        let len = entry
+            .szExePath
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(entry.szExePath.len());
+        modules.push(OsString::from_wide(&entry.szExePath[..len]).into());
+
+        // SAFETY: The snapshot handle is valid and `entry` is initialized with its size.
+        ok = unsafe { Module32NextW(snapshot, &mut entry) };
+    }
+
+    // SAFETY: The snapshot handle is valid and is not used after this.
+    unsafe {
+        CloseHandle(snapshot);
+    }
+
+    Some(modules)
+}
@@ -0,0 +1,262 @@
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Path;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use std::collections::BTreeSet;
+
+/// The dir of GObject introspection typelibs, relative to an MSYS2 environment or the package dir.
+pub const TYPELIB_DIR: &str = "lib/girepository-1.0";
+
+/// The magic bytes at the start of every typelib.
+const TYPELIB_MAGIC: &[u8; 16] = b"GOBJ\nMETADATA\r\n\x1a";
+
+/// The offset of the dependencies string offset in the typelib header.
+const DEPENDENCIES_OFFSET: usize = 36;
+
+/// The offset of the shared libraries string offset in the typelib header.
+const SHARED_LIBRARY_OFFSET: usize = 52;
+
+/// What a typelib needs at runtime, from its header
+#[derive(Debug, Default)]
+pub struct TypelibInfo {
+    /// The typelibs this typelib depends on, like `GObject-2.0`
+    pub dependencies: Vec<String>,
+
+    /// The dlls that implement the namespace, like `libgtk-4-1.dll`
+    pub shared_libraries: Vec<String>,
+}
+
+/// Parse the header of a typelib.
+///
+/// See `gitypelib-internal.h` in GLib for the format.
+pub fn parse_typelib(bytes: &[u8]) -> anyhow::Result<TypelibInfo> {
+    ensure!(
+        bytes.starts_with(TYPELIB_MAGIC),
+        "not a typelib, the magic is missing"
+    );
+
+    let get_string = |header_offset: usize| -> anyhow::Result<Option<&str>> {
+        let offset = bytes
+            .get(header_offset..header_offset + 4)
+            .context("the typelib header is truncated")?;
+        let offset = usize::try_from(u32::from_le_bytes(offset.try_into()?))?;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let rest = bytes
+            .get(offset..)
+            .context("a typelib string is out of bounds")?;
+        let end = rest
+            .iter()
+            .position(|b| *b == 0)
+            .context("a typelib string is not terminated")?;
+        let string = std::str::from_utf8(&rest[..end]).context("a typelib string is not utf8")?;
+
+        Ok(Some(string))
+    };
+    let split = |string: Option<&str>, separator: char| -> Vec<String> {
+        string
+            .unwrap_or_default()
+            .split(separator)
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect()
+    };
+
+    Ok(TypelibInfo {
+        dependencies: split(get_string(DEPENDENCIES_OFFSET)?, '|'),
+        shared_libraries: split(get_string(SHARED_LIBRARY_OFFSET)?, ','),
+    })
+}
+
+/// Add typelibs of an MSYS2 environment to a packager, along with the typelibs they depend on and the dlls that implement them.
+///
+/// `names` are namespaces with versions, like `Gtk-4.0`.
+/// A namespace without a version, like `Gtk`, is allowed if the environment only has one version of it.
+///
+/// # Returns
+/// Returns the names of every added typelib, sorted.
+pub fn add_typelibs(
+    packager: &mut Packager,
+    environment_path: &Utf8Path,
+    names: &[String],
+) -> anyhow::Result<BTreeSet<String>> {
+    let typelib_dir = environment_path.join(TYPELIB_DIR);
+    ensure!(
+        typelib_dir.is_dir(),
+        "`{typelib_dir}` does not exist, install the gobject-introspection-runtime package of the MSYS2 environment"
+    );
+
+    let mut stack = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        stack.push(resolve_typelib_name(&typelib_dir, name)?);
+    }
+
+    let mut added = BTreeSet::new();
+    while let Some(name) = stack.pop() {
+        if added.contains(&name) {
+            continue;
+        }
+
+        let file_name = format!("{name}.typelib");
+        let path = typelib_dir.join(&file_name);
+        let bytes = std::fs::read(&path).with_context(|| {
+            format!("failed to read `{path}`, which may need another package to be installed")
+        })?;
+        let info = parse_typelib(&bytes).with_context(|| format!("failed to parse `{path}`"))?;
+
+        packager.add_file(
+            Some(path.into()),
+            Utf8Path::new(TYPELIB_DIR).join(file_name).into(),
+            FileFlags::empty(),
+        );
+        for library in info.shared_libraries {
+            packager.add_file(
+                None,
+                library.into(),
+                FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+            );
+        }
+        stack.extend(info.dependencies);
+        added.insert(name);
+    }
+
+    Ok(added)
+}
+
+/// Get the full name of a typelib in a dir, like `Gtk-4.0` for `Gtk`.
+fn resolve_typelib_name(typelib_dir: &Utf8Path, name: &str) -> anyhow::Result<String> {
+    if name.contains('-') {
+        return Ok(name.to_string());
+    }
+
+    let prefix = format!("{name}-");
+    let mut matches = Vec::new();
+    for dir_entry in typelib_dir
+        .read_dir_utf8()
+        .with_context(|| format!("failed to read `{typelib_dir}`"))?
+    {
+        let dir_entry = dir_entry?;
+        if let Some(stem) = dir_entry.file_name().strip_suffix(".typelib") {
+            if stem.starts_with(&prefix) {
+                matches.push(stem.to_string());
+            }
+        }
+    }
+    matches.sort();
+
+    match matches.as_slice() {
+        [] => bail!("there is no typelib for `{name}` in `{typelib_dir}`"),
+        [single] => Ok(single.clone()),
+        _ => bail!(
+            "`{name}` has several typelibs, pass one of {}",
+            matches
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The size of the typelib headers built by [`make_typelib`].
+    const HEADER_SIZE: usize = 64;
+
+    /// Make a typelib header, with the given dependencies and shared libraries strings after it.
+    fn make_typelib(dependencies: Option<&str>, shared_libraries: Option<&str>) -> Vec<u8> {
+        let mut typelib = vec![0; HEADER_SIZE];
+        typelib[..TYPELIB_MAGIC.len()].copy_from_slice(TYPELIB_MAGIC);
+        for (header_offset, string) in [
+            (DEPENDENCIES_OFFSET, dependencies),
+            (SHARED_LIBRARY_OFFSET, shared_libraries),
+        ] {
+            if let Some(string) = string {
+                let offset = typelib.len() as u32;
+                set_string_offset(&mut typelib, header_offset, offset);
+                typelib.extend_from_slice(string.as_bytes());
+                typelib.push(0);
+            }
+        }
+        typelib
+    }
+
+    /// Set a string offset in a typelib header.
+    fn set_string_offset(typelib: &mut [u8], header_offset: usize, offset: u32) {
+        typelib[header_offset..header_offset + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    #[test]
+    fn header_strings() {
+        let typelib = make_typelib(
+            Some("GObject-2.0|Gio-2.0||Pango-1.0"),
+            Some("libgtk-4-1.dll,libgraphene-1.0-0.dll"),
+        );
+        let info = parse_typelib(&typelib).unwrap();
+        assert_eq!(info.dependencies, ["GObject-2.0", "Gio-2.0", "Pango-1.0"]);
+        assert_eq!(
+            info.shared_libraries,
+            ["libgtk-4-1.dll", "libgraphene-1.0-0.dll"]
+        );
+    }
+
+    #[test]
+    fn missing_strings_are_empty() {
+        let info = parse_typelib(&make_typelib(None, None)).unwrap();
+        assert!(info.dependencies.is_empty());
+        assert!(info.shared_libraries.is_empty());
+    }
+
+    #[test]
+    fn missing_magic() {
+        let mut typelib = make_typelib(Some("GObject-2.0"), None);
+        typelib[0] = b'X';
+        let error = parse_typelib(&typelib).unwrap_err();
+        assert_eq!(error.to_string(), "not a typelib, the magic is missing");
+        assert!(parse_typelib(&TYPELIB_MAGIC[..8]).is_err());
+    }
+
+    #[test]
+    fn truncated_header() {
+        let typelib = make_typelib(None, None);
+        for len in [
+            TYPELIB_MAGIC.len(),
+            DEPENDENCIES_OFFSET + 2,
+            SHARED_LIBRARY_OFFSET,
+        ] {
+            let error = parse_typelib(&typelib[..len]).unwrap_err();
+            assert_eq!(error.to_string(), "the typelib header is truncated");
+        }
+    }
+
+    #[test]
+    fn bad_string_offsets() {
+        let mut typelib = make_typelib(None, None);
+        set_string_offset(&mut typelib, DEPENDENCIES_OFFSET, u32::MAX);
+        let error = parse_typelib(&typelib).unwrap_err();
+        assert_eq!(error.to_string(), "a typelib string is out of bounds");
+
+        let mut typelib = make_typelib(None, Some("libgtk-4-1.dll"));
+        typelib.pop();
+        let error = parse_typelib(&typelib).unwrap_err();
+        assert_eq!(error.to_string(), "a typelib string is not terminated");
+
+        // An offset at the end of the file has no room for the terminator.
+        let mut typelib = make_typelib(None, None);
+        set_string_offset(&mut typelib, SHARED_LIBRARY_OFFSET, HEADER_SIZE as u32);
+        let error = parse_typelib(&typelib).unwrap_err();
+        assert_eq!(error.to_string(), "a typelib string is not terminated");
+
+        let mut typelib = make_typelib(Some("GObject-2.0"), None);
+        let last = typelib.len() - 2;
+        typelib[last] = 0xFF;
+        let error = parse_typelib(&typelib).unwrap_err();
+        assert_eq!(error.to_string(), "a typelib string is not utf8");
+    }
+}
@@ -1,10 +1,359 @@
 use anyhow::ensure;
 use anyhow::Context;
+use msys2::Msys2Environment;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+
+/// An error that may occur while parsing a [`Debugger`] from a string.
+#[derive(Debug)]
+pub struct DebuggerFromStrError(String);
+
+impl std::fmt::Display for DebuggerFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a supported debugger", self.0)
+    }
+}
+
+impl std::error::Error for DebuggerFromStrError {}
+
+/// A debugger to launch a binary under
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Debugger {
+    /// GDB, from the MSYS2 environment
+    Gdb,
+
+    /// WinDbg, from the Windows SDK or the Microsoft Store
+    WinDbg,
+}
+
+impl Debugger {
+    /// Locate the debugger executable.
+    ///
+    /// GDB is preferred from the MSYS2 environment bin dir, as it matches the toolchain.
+    /// Otherwise, the PATH is searched.
+    pub fn locate(self, environment_bin_path: &Path) -> anyhow::Result<PathBuf> {
+        match self {
+            Self::Gdb => {
+                let gdb = environment_bin_path.join("gdb.exe");
+                if gdb.exists() {
+                    return Ok(gdb);
+                }
+
+                find_on_path("gdb.exe").with_context(|| {
+                    format!(
+                        "failed to locate `gdb.exe` in `{}` or on the PATH",
+                        environment_bin_path.display()
+                    )
+                })
+            }
+            Self::WinDbg => find_on_path("windbg.exe")
+                .or_else(|| find_on_path("WinDbgX.exe"))
+                .context("failed to locate `windbg.exe` or `WinDbgX.exe` on the PATH"),
+        }
+    }
+}
+
+impl std::str::FromStr for Debugger {
+    type Err = DebuggerFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "gdb" => Ok(Self::Gdb),
+            "windbg" => Ok(Self::WinDbg),
+            _ => Err(DebuggerFromStrError(input.into())),
+        }
+    }
+}
+
+/// The kind of cargo target to package
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TargetKind {
+    /// A bin target
+    Bin,
+
+    /// A bench target
+    Bench,
+
+    /// A test target
+    Test,
+
+    /// An example target
+    Example,
+}
+
+impl TargetKind {
+    /// Get the name cargo uses for this kind of target, like in `cargo metadata`.
+    pub fn get_cargo_kind(self) -> &'static str {
+        match self {
+            Self::Bin => "bin",
+            Self::Bench => "bench",
+            Self::Test => "test",
+            Self::Example => "example",
+        }
+    }
+
+    /// Get the cargo flag that selects a target of this kind, like `--bin`.
+    pub fn get_cargo_flag(self) -> &'static str {
+        match self {
+            Self::Bin => "--bin",
+            Self::Bench => "--bench",
+            Self::Test => "--test",
+            Self::Example => "--example",
+        }
+    }
+
+    /// Get the dir cargo places binaries of this kind in, relative to the profile dir.
+    ///
+    /// # Returns
+    /// Returns None for benches and tests, as their file names are hashed,
+    /// so their paths must be taken from cargo's output.
+    pub fn get_output_dir(self) -> Option<&'static str> {
+        match self {
+            Self::Bin => Some(""),
+            Self::Example => Some("examples"),
+            Self::Bench | Self::Test => None,
+        }
+    }
+}
+
+/// Get the targets to package from the `--bin`, `--bins`, `--bench`, `--test`, and `--example` CLI options.
+///
+/// Only one kind of target may be selected.
+/// `--bins` selects every bin in `package`, or the root package if it is None.
+/// If nothing is selected, no bins are returned, and [`crate::Context::set_build_data`] picks the only bin of the package.
+pub fn get_cargo_targets(
+    cargo_metadata: &cargo_metadata::Metadata,
+    package: Option<&str>,
+    bin: Vec<String>,
+    bins: bool,
+    bench: Option<String>,
+    test: Option<String>,
+    example: Option<String>,
+) -> anyhow::Result<(TargetKind, Vec<String>)> {
+    let bin = if bins {
+        ensure!(bin.is_empty(), "`--bin` cannot be used with `--bins`");
+        let package = match package {
+            Some(package) => get_package(cargo_metadata, package)?,
+            None => cargo_metadata
+                .root_package()
+                .context("`--bins` requires a root package, or `--package`")?,
+        };
+        package
+            .targets
+            .iter()
+            .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+            .map(|target| target.name.clone())
+            .collect()
+    } else {
+        bin
+    };
+
+    let mut targets = [
+        (TargetKind::Bin, bin),
+        (TargetKind::Bench, Vec::from_iter(bench)),
+        (TargetKind::Test, Vec::from_iter(test)),
+        (TargetKind::Example, Vec::from_iter(example)),
+    ]
+    .into_iter()
+    .filter(|(_, names)| !names.is_empty());
+
+    let Some(target) = targets.next() else {
+        return Ok((TargetKind::Bin, Vec::new()));
+    };
+    ensure!(
+        targets.next().is_none(),
+        "only one of `--bin`, `--bench`, `--test`, or `--example` may be used"
+    );
+
+    Ok(target)
+}
+
+/// Get a workspace package by name.
+pub fn get_package<'a>(
+    cargo_metadata: &'a cargo_metadata::Metadata,
+    name: &str,
+) -> anyhow::Result<&'a cargo_metadata::Package> {
+    cargo_metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|package| package.name == name)
+        .with_context(|| format!("`{name}` is not a package in the workspace"))
+}
+
+/// Get the target triple to build for, which defaults to the one of the active MSYS2 environment.
+///
+/// The environment is read from the `MSYSTEM` env var, which MSYS2 shells set, like `UCRT64`.
+pub fn resolve_target(target: Option<String>) -> anyhow::Result<String> {
+    if let Some(target) = target {
+        return Ok(target);
+    }
+
+    let msystem = std::env::var("MSYSTEM")
+        .ok()
+        .filter(|msystem| !msystem.is_empty())
+        .context("missing `--target`. Pass one, or run from an MSYS2 shell so it can be inferred from the `MSYSTEM` env var")?;
+    let msys2_environment: Msys2Environment = msystem
+        .parse()
+        .with_context(|| format!("failed to infer the target from `MSYSTEM={msystem}`"))?;
+    let target = msys2_environment.get_target_triple().with_context(|| {
+        format!("`MSYSTEM={msystem}` has no target triple, pass one with `--target`")
+    })?;
+    eprintln!("Using `--target {target}`, from `MSYSTEM={msystem}`");
+
+    // The packaged dlls come from the environment of the target, which may not be the active one.
+    if let Some(target_environment) =
+        msys2_packager::util::target_triple_to_msys2_environment(target)
+    {
+        if target_environment != msys2_environment {
+            eprintln!(
+                "warning: `{target}` packages the dlls of the `{}` environment, not `{}`",
+                target_environment.get_name(),
+                msys2_environment.get_name()
+            );
+        }
+    }
+
+    Ok(target.into())
+}
+
+/// The toolchain settings of a workspace
+#[derive(Debug, Default, Deserialize)]
+struct ToolchainMetadata {
+    /// The rustup toolchain to build with, like `1.77.0`
+    #[serde(default)]
+    toolchain: Option<String>,
+}
+
+/// Get the pinned rustup toolchain of a workspace, if it has one.
+///
+/// It is read from `[workspace.metadata.msys2-gtk-packager]`,
+/// falling back to `[package.metadata.msys2-gtk-packager]` of the root package.
+pub fn get_toolchain(metadata: &cargo_metadata::Metadata) -> anyhow::Result<Option<String>> {
+    let key = crate::asset::METADATA_KEY;
+    if let Some(value) = metadata.workspace_metadata.get(key) {
+        let toolchain_metadata = ToolchainMetadata::deserialize(value)
+            .with_context(|| format!("invalid `workspace.metadata.{key}`"))?;
+        if toolchain_metadata.toolchain.is_some() {
+            return Ok(toolchain_metadata.toolchain);
+        }
+    }
+
+    let value = match metadata
+        .root_package()
+        .and_then(|package| package.metadata.get(key))
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let toolchain_metadata = ToolchainMetadata::deserialize(value)
+        .with_context(|| format!("invalid `package.metadata.{key}`"))?;
+
+    Ok(toolchain_metadata.toolchain)
+}
+
+/// Check that a rustup toolchain is installed.
+pub fn validate_toolchain(toolchain: &str) -> anyhow::Result<()> {
+    let output = Command::new("rustup")
+        .args(["which", "--toolchain", toolchain, "cargo"])
+        .output()
+        .context("failed to run `rustup`, which is needed to build with a pinned toolchain")?;
+    ensure!(
+        output.status.success(),
+        "the pinned toolchain `{toolchain}` is not installed, install it with `rustup toolchain install {toolchain}`"
+    );
+
+    Ok(())
+}
+
+/// Check that the standard library of a rust target is installed with rustup, installing it if `auto_install` is set.
+///
+/// `toolchain` is the pinned toolchain to check, if the workspace has one.
+/// If rustup is not installed, nothing is checked, as rust may come from elsewhere, like an MSYS2 package.
+pub fn ensure_rust_target(
+    target: &str,
+    toolchain: Option<&str>,
+    auto_install: bool,
+) -> anyhow::Result<()> {
+    let toolchain_args: Vec<&str> = match toolchain {
+        Some(toolchain) => vec!["--toolchain", toolchain],
+        None => Vec::new(),
+    };
+
+    let output = match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .args(&toolchain_args)
+        .output()
+    {
+        Ok(output) => output,
+        // Without rustup, cargo reports a missing target itself.
+        Err(_) if !auto_install => return Ok(()),
+        Err(error) => {
+            return Err(error)
+                .context("failed to run `rustup`, which `--auto-install-target` needs")
+        }
+    };
+    ensure!(
+        output.status.success(),
+        "`rustup target list --installed` exited with `{}`: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.lines().any(|line| line.trim() == target) {
+        return Ok(());
+    }
+
+    let mut command = format!("rustup target add {target}");
+    for arg in toolchain_args.iter() {
+        command.push(' ');
+        command.push_str(arg);
+    }
+    ensure!(
+        auto_install,
+        "the rust target `{target}` is not installed, install it with `{command}`, or pass `--auto-install-target`"
+    );
+
+    eprintln!("Installing the rust target `{target}`");
+    let status = Command::new("rustup")
+        .args(["target", "add", target])
+        .args(&toolchain_args)
+        .status()
+        .context("failed to run `rustup`")?;
+    ensure!(status.success(), "`{command}` exited with `{status}`");
+
+    Ok(())
+}
+
+/// Find an executable on the PATH.
+pub fn find_on_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The cargo features to enable
+#[derive(Debug, Default, Clone)]
+pub struct CargoFeatures {
+    /// Features to enable
+    pub features: Vec<String>,
+
+    /// Whether to disable the default features
+    pub no_default_features: bool,
+
+    /// Whether to enable every feature
+    pub all_features: bool,
+}
 
 /// A builder to build a cargo build command
+#[derive(Clone)]
 pub struct CargoBuild {
     /// The `build` cargo subcommand command to run.
     ///
@@ -17,8 +366,29 @@ pub struct CargoBuild {
     /// The build profile
     pub profile: Option<String>,
 
-    /// The bin to build
-    pub bin: Option<String>,
+    /// The rustup toolchain to build with, instead of the default one
+    pub toolchain: Option<String>,
+
+    /// Whether to build through `cargo auditable`, which embeds the dependency tree in binaries
+    pub auditable: bool,
+
+    /// The package to build, in a workspace
+    pub package: Option<String>,
+
+    /// The targets to build, like bins
+    pub cargo_targets: Vec<(TargetKind, String)>,
+
+    /// The features to enable
+    pub features: CargoFeatures,
+
+    /// Extra args for the command
+    pub args: Vec<OsString>,
+
+    /// Args for rustc, for the built targets only.
+    ///
+    /// If there are any, the command is `cargo rustc`, which only takes them with one target.
+    /// Unlike rustflags, these do not replace the rustflags of the user's cargo config or env.
+    pub rustc_args: Vec<OsString>,
 
     /// The environment for the command
     pub envs: HashMap<OsString, OsString>,
@@ -31,7 +401,13 @@ impl CargoBuild {
             build: None,
             target: None,
             profile: None,
-            bin: None,
+            toolchain: None,
+            auditable: false,
+            package: None,
+            cargo_targets: Vec::new(),
+            features: CargoFeatures::default(),
+            args: Vec::new(),
+            rustc_args: Vec::new(),
             envs: HashMap::new(),
         }
     }
@@ -42,6 +418,18 @@ impl CargoBuild {
         self
     }
 
+    /// Set the rustup toolchain.
+    pub fn toolchain(&mut self, toolchain: String) -> &mut Self {
+        self.toolchain = Some(toolchain);
+        self
+    }
+
+    /// Set whether to build through `cargo auditable`.
+    pub fn auditable(&mut self, auditable: bool) -> &mut Self {
+        self.auditable = auditable;
+        self
+    }
+
     /// Set the target.
     pub fn target(&mut self, target: String) -> &mut Self {
         self.target = Some(target);
@@ -54,9 +442,39 @@ impl CargoBuild {
         self
     }
 
-    /// Set the bin to build
-    pub fn bin(&mut self, bin: String) -> &mut Self {
-        self.bin = Some(bin);
+    /// Set the package to build, in a workspace.
+    pub fn package(&mut self, package: String) -> &mut Self {
+        self.package = Some(package);
+        self
+    }
+
+    /// Add a target to build, like a bin
+    pub fn cargo_target(&mut self, kind: TargetKind, name: String) -> &mut Self {
+        self.cargo_targets.push((kind, name));
+        self
+    }
+
+    /// Set the features to enable.
+    pub fn features(&mut self, features: CargoFeatures) -> &mut Self {
+        self.features = features;
+        self
+    }
+
+    /// Add an arg
+    pub fn arg(&mut self, arg: OsString) -> &mut Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Add args, which are passed to cargo verbatim.
+    pub fn args(&mut self, args: Vec<OsString>) -> &mut Self {
+        self.args.extend(args);
+        self
+    }
+
+    /// Add an arg for rustc, see [`CargoBuild::rustc_args`].
+    pub fn rustc_arg(&mut self, arg: OsString) -> &mut Self {
+        self.rustc_args.push(arg);
         self
     }
 
@@ -71,11 +489,26 @@ impl CargoBuild {
         let build = self.build.as_deref();
         let target = self.target.as_deref();
         let profile = self.profile.as_deref();
-        let bin = self.bin.as_deref();
         let envs = &self.envs;
 
         let mut command = Command::new("cargo");
-        command.arg(build.unwrap_or("build")).envs(envs);
+        if let Some(toolchain) = self.toolchain.as_deref() {
+            command.arg(format!("+{toolchain}"));
+        }
+        if self.auditable {
+            command.arg("auditable");
+        }
+        let default_build = if self.rustc_args.is_empty() {
+            "build"
+        } else {
+            ensure!(
+                build.is_none(),
+                "rustc args cannot be passed to the `{}` subcommand",
+                build.unwrap_or_default()
+            );
+            "rustc"
+        };
+        command.arg(build.unwrap_or(default_build)).envs(envs);
 
         if let Some(target) = target {
             command.args(["--target", target]);
@@ -85,13 +518,185 @@ impl CargoBuild {
             command.args(["--profile", profile]);
         }
 
-        if let Some(bin) = bin {
-            command.args(["--bin", bin]);
+        if let Some(package) = self.package.as_deref() {
+            command.args(["--package", package]);
+        }
+
+        for (kind, name) in self.cargo_targets.iter() {
+            command.args([kind.get_cargo_flag(), name]);
+        }
+
+        if !self.features.features.is_empty() {
+            command.args(["--features", &self.features.features.join(",")]);
+        }
+        if self.features.no_default_features {
+            command.arg("--no-default-features");
+        }
+        if self.features.all_features {
+            command.arg("--all-features");
+        }
+
+        command.args(&self.args);
+        if !self.rustc_args.is_empty() {
+            command.arg("--").args(&self.rustc_args);
+        }
+
+        Ok(command)
+    }
+
+    /// Run this command, and get its exit status.
+    pub fn status(&self) -> anyhow::Result<ExitStatus> {
+        let mut command = self.build_command()?;
+        command
+            .status()
+            .with_context(|| format!("failed to run `{command:?}`"))
+    }
+
+    /// Run this command.
+    pub fn exec(&self) -> anyhow::Result<()> {
+        let mut command = self.build_command()?;
+        let status = command
+            .status()
+            .with_context(|| format!("failed to run `{command:?}`"))?;
+
+        ensure!(
+            status.success(),
+            "`{command:?}` exited with nonzero exit code `{status}`",
+        );
+
+        Ok(())
+    }
+
+    /// Run this command, collecting the JSON messages cargo prints.
+    ///
+    /// Diagnostics are still rendered to stderr.
+    pub fn exec_messages(&self) -> anyhow::Result<Vec<cargo_metadata::Message>> {
+        let mut command = self.build_command()?;
+        command
+            .arg("--message-format=json-render-diagnostics")
+            .stdout(Stdio::piped());
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("failed to run `{command:?}`"))?;
+
+        let stdout = child.stdout.take().context("missing stdout")?;
+        let messages = cargo_metadata::Message::parse_stream(BufReader::new(stdout))
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to read cargo messages");
+        let status = child
+            .wait()
+            .with_context(|| format!("failed to wait for `{command:?}`"))?;
+        let messages = messages?;
+
+        ensure!(
+            status.success(),
+            "`{command:?}` exited with nonzero exit code `{status}`",
+        );
+
+        Ok(messages)
+    }
+}
+
+/// A builder to build a command that runs a built binary
+pub struct RunCommand {
+    /// The binary to run
+    pub bin: PathBuf,
+
+    /// If set, the PATH is restricted to this dir and the Windows system dirs.
+    pub restricted_path: Option<PathBuf>,
+
+    /// The debugger to run the binary under, and its location
+    pub debugger: Option<(Debugger, PathBuf)>,
+
+    /// The args to pass to the binary
+    pub args: Vec<OsString>,
+
+    /// The environment for the command
+    pub envs: HashMap<OsString, OsString>,
+}
+
+impl RunCommand {
+    /// Make a new [`RunCommand`].
+    pub fn new(bin: PathBuf) -> Self {
+        Self {
+            bin,
+            restricted_path: None,
+            debugger: None,
+            args: Vec::new(),
+            envs: HashMap::new(),
+        }
+    }
+
+    /// Restrict the PATH to the given dir and the Windows system dirs.
+    ///
+    /// This keeps dlls from being loaded from an MSYS2 installation on the PATH,
+    /// so a packaged binary behaves like it would on a user's machine.
+    pub fn restrict_path(&mut self, dir: PathBuf) -> &mut Self {
+        self.restricted_path = Some(dir);
+        self
+    }
+
+    /// Run the binary under a debugger, located at the given path.
+    pub fn debugger(&mut self, debugger: Debugger, path: PathBuf) -> &mut Self {
+        self.debugger = Some((debugger, path));
+        self
+    }
+
+    /// Add an arg to pass to the binary
+    pub fn arg(&mut self, arg: OsString) -> &mut Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Add an env
+    pub fn env(&mut self, key: OsString, value: OsString) -> &mut Self {
+        self.envs.insert(key, value);
+        self
+    }
+
+    /// Build this command.
+    pub fn build_command(&self) -> anyhow::Result<Command> {
+        let mut command = match self.debugger.as_ref() {
+            Some((Debugger::Gdb, path)) => {
+                let mut command = Command::new(path);
+                command.arg("--args").arg(&self.bin);
+                command
+            }
+            Some((Debugger::WinDbg, path)) => {
+                let mut command = Command::new(path);
+                command.arg(&self.bin);
+                command
+            }
+            None => Command::new(&self.bin),
+        };
+        command.args(&self.args);
+
+        if let Some(dir) = self.restricted_path.as_ref() {
+            let system_root =
+                std::env::var_os("SystemRoot").unwrap_or_else(|| OsString::from("C:\\Windows"));
+            let system_root = PathBuf::from(system_root);
+            let path = std::env::join_paths([
+                dir.clone(),
+                system_root.join("System32"),
+                system_root.clone(),
+            ])
+            .context("failed to join PATH")?;
+            command.env("PATH", path);
         }
 
+        command.envs(&self.envs);
+
         Ok(command)
     }
 
+    /// Run this command, and get its exit status.
+    pub fn status(&self) -> anyhow::Result<ExitStatus> {
+        let mut command = self.build_command()?;
+        command
+            .status()
+            .with_context(|| format!("failed to run `{command:?}`"))
+    }
+
     /// Run this command.
     pub fn exec(&self) -> anyhow::Result<()> {
         let mut command = self.build_command()?;
@@ -0,0 +1,54 @@
+//! Build and package `examples/hello-gtk4` with the real GTK4 of an MSYS2 installation.
+//!
+//! This needs MSYS2 with `gtk4` installed, and the `x86_64-pc-windows-gnu` rust target,
+//! so it is ignored by default. Run it with `cargo test -p msys2-gtk-packager --test hello_gtk4 -- --ignored`.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The target to package for.
+const TARGET: &str = "x86_64-pc-windows-gnu";
+
+/// Get the workspace target dir, which the packager puts its package dirs in.
+fn get_target_dir(workspace_dir: &Path) -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .map(|dir| workspace_dir.join(dir))
+        .unwrap_or_else(|| workspace_dir.join("target"))
+}
+
+#[test]
+#[ignore = "needs MSYS2 with gtk4 installed"]
+fn hello_gtk4() {
+    let workspace_dir = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let example_dir = workspace_dir.join("examples/hello-gtk4");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_msys2-gtk-packager"))
+        .current_dir(&example_dir)
+        .args(["package", "--target", TARGET, "--features", "gtk"])
+        .status()
+        .unwrap();
+    assert!(status.success(), "packaging failed with {status}");
+
+    let package_dir = get_target_dir(workspace_dir)
+        .join(TARGET)
+        .join("msys2_gtk_packager")
+        .join(TARGET)
+        .join("release/hello-gtk4");
+    for file in [
+        "hello-gtk4.exe",
+        "libgtk-4-1.dll",
+        "libglib-2.0-0.dll",
+        "share/hello-gtk4/hello.css",
+    ] {
+        assert!(
+            package_dir.join(file).is_file(),
+            "`{file}` is missing from `{}`",
+            package_dir.display()
+        );
+    }
+
+    let settings = std::fs::read_to_string(package_dir.join("etc/gtk-4.0/settings.ini")).unwrap();
+    assert_eq!(settings, include_str!("golden/hello-gtk4.settings.ini"));
+}
@@ -0,0 +1,340 @@
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use msys2::Msys2Environment;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use msys2_packager::resolver::resolve_dependencies;
+use msys2_packager::resolver::DependencySource;
+use msys2_packager::util::is_api_set_dll;
+use msys2_packager::util::is_system_dll;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Graph sizes, in number of libraries.
+const GRAPH_SIZES: &[usize] = &[100, 1_000, 5_000];
+
+/// Plan sizes, in number of files.
+const PLAN_SIZES: &[usize] = &[1_000, 10_000];
+
+/// The max number of non-system imports per library.
+const MAX_IMPORTS: usize = 12;
+
+/// A small, deterministic random number generator, so benchmark inputs are stable between runs.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    fn next_below(&mut self, n: usize) -> usize {
+        (self.next() % (n as u64)) as usize
+    }
+}
+
+/// An in-memory dependency graph.
+struct SyntheticSource {
+    imports: HashMap<PathBuf, Vec<String>>,
+    libraries: HashMap<String, PathBuf>,
+}
+
+impl SyntheticSource {
+    /// Make a graph of `size` libraries, where each library imports up to [`MAX_IMPORTS`] libraries,
+    /// a few system dlls, an api set, and sometimes a library that does not exist.
+    fn new(size: usize) -> Self {
+        let mut rng = Lcg(size as u64);
+        let mut imports = HashMap::with_capacity(size + 1);
+        let mut libraries = HashMap::with_capacity(size);
+
+        for i in 0..size {
+            let name = format!("lib{i}.dll");
+            let path = PathBuf::from(format!("C:/msys64/ucrt64/bin/{name}"));
+
+            let num_imports = rng.next_below(MAX_IMPORTS);
+            let mut library_imports: Vec<String> = (0..num_imports)
+                .map(|_| format!("lib{}.dll", rng.next_below(size)))
+                .collect();
+            library_imports.push("KERNEL32.dll".into());
+            library_imports.push("msvcrt.dll".into());
+            library_imports.push("api-ms-win-crt-runtime-l1-1-0.dll".into());
+            if rng.next_below(50) == 0 {
+                library_imports.push(format!("missing{i}.dll"));
+            }
+
+            imports.insert(path.clone(), library_imports);
+            libraries.insert(name, path);
+        }
+
+        // The root imports a handful of libraries, which should pull in most of the graph.
+        let root_imports = (0..MAX_IMPORTS)
+            .map(|_| format!("lib{}.dll", rng.next_below(size)))
+            .collect();
+        imports.insert(PathBuf::from("app.exe"), root_imports);
+
+        Self { imports, libraries }
+    }
+}
+
+impl DependencySource for SyntheticSource {
+    fn get_imports(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        Ok(self.imports.get(path).cloned().unwrap_or_default())
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        Ok(self.libraries.get(name).cloned())
+    }
+}
+
+fn bench_resolve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resolve_dependencies");
+    for size in GRAPH_SIZES.iter().copied() {
+        let source = SyntheticSource::new(size);
+        let root = Path::new("app.exe");
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &source, |b, source| {
+            b.iter(|| resolve_dependencies(source, &[root]).expect("failed to resolve"));
+        });
+    }
+    group.finish();
+}
+
+fn bench_plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+    for size in PLAN_SIZES.iter().copied() {
+        // Data files, like those found in themes, so this measures planning and not PE parsing.
+        let src_dir = std::env::temp_dir()
+            .join("msys2-packager-bench-files")
+            .join(size.to_string());
+        std::fs::create_dir_all(&src_dir).expect("failed to create bench files dir");
+        let files: Vec<(PathBuf, PathBuf)> = (0..size)
+            .map(|i| {
+                let src = src_dir.join(format!("file{i}.txt"));
+                if !src.exists() {
+                    std::fs::write(&src, i.to_string()).expect("failed to write bench file");
+                }
+                let dest = PathBuf::from(format!("share/data/{}/file{i}.txt", i % 32));
+                (src, dest)
+            })
+            .collect();
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &files, |b, files| {
+            b.iter(|| {
+                let mut packager = Packager::new(
+                    "C:/msys64".into(),
+                    Msys2Environment::Ucrt64,
+                    std::env::temp_dir().join("msys2-packager-bench-out"),
+                );
+                for (src, dest) in files.iter() {
+                    packager.add_file(Some(src.clone()), dest.clone(), FileFlags::empty());
+                }
+                packager.plan().expect("failed to plan")
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_classify(c: &mut Criterion) {
+    let mut rng = Lcg(0);
+    let names: Vec<String> = (0..10_000)
+        .map(|i| match rng.next_below(3) {
+            0 => format!("lib{i}.dll"),
+            1 => "KERNEL32.dll".into(),
+            _ => format!("api-ms-win-core-synch-l1-{}-0.dll", i % 3),
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("classify");
+    group.throughput(Throughput::Elements(names.len() as u64));
+    group.bench_function("is_system_dll", |b| {
+        b.iter(|| names.iter().filter(|name| is_system_dll(name)).count());
+    });
+    group.bench_function("is_api_set_dll", |b| {
+        b.iter(|| names.iter().filter(|name| is_api_set_dll(name)).count());
+    });
+    group.finish();
+}
+
+/// Options for producing a machine-readable report.
+///
+/// Criterion rejects arguments it does not know about,
+/// so these are parsed by hand and criterion is left unconfigured when they are present.
+struct ReportOptions {
+    /// Where to write the report
+    report: PathBuf,
+
+    /// A previous report to compare against
+    baseline: Option<PathBuf>,
+
+    /// The allowed slowdown, in percent, before a benchmark is considered a regression
+    threshold: f64,
+}
+
+impl ReportOptions {
+    fn from_args() -> Result<Option<Self>, String> {
+        let mut report = None;
+        let mut baseline = None;
+        let mut threshold = 10.0;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bench-report" => {
+                    report = Some(args.next().ok_or("missing value for `--bench-report`")?);
+                }
+                "--bench-baseline" => {
+                    baseline = Some(args.next().ok_or("missing value for `--bench-baseline`")?);
+                }
+                "--bench-threshold" => {
+                    let value = args.next().ok_or("missing value for `--bench-threshold`")?;
+                    threshold = value
+                        .parse()
+                        .map_err(|e| format!("invalid `--bench-threshold`: {e}"))?;
+                }
+                _ => {}
+            }
+        }
+
+        match report {
+            Some(report) => Ok(Some(Self {
+                report: report.into(),
+                baseline: baseline.map(PathBuf::from),
+                threshold,
+            })),
+            None if baseline.is_some() => {
+                Err("`--bench-baseline` requires `--bench-report`".into())
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Collect mean times, in nanoseconds, keyed by benchmark id, from a criterion output directory.
+fn collect_estimates(dir: &Path) -> anyhow::Result<BTreeMap<String, f64>> {
+    let mut estimates = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_name() != "benchmark.json" || !entry.path().ends_with("new/benchmark.json") {
+            continue;
+        }
+        let new_dir = entry.path().parent().expect("missing parent");
+
+        let benchmark: serde_json::Value = serde_json::from_slice(&std::fs::read(entry.path())?)?;
+        let estimate: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(new_dir.join("estimates.json"))?)?;
+
+        let id = benchmark["full_id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing `full_id`"))?;
+        let mean = estimate["mean"]["point_estimate"]
+            .as_f64()
+            .ok_or_else(|| anyhow::anyhow!("missing mean for `{id}`"))?;
+        estimates.insert(id.to_string(), mean);
+    }
+
+    Ok(estimates)
+}
+
+/// Write the report and compare it to the baseline, if there is one.
+///
+/// # Returns
+/// Returns false if a benchmark regressed past the threshold.
+fn write_report(options: &ReportOptions, criterion_dir: &Path) -> anyhow::Result<bool> {
+    let estimates = collect_estimates(criterion_dir)?;
+    let report = serde_json::json!({ "mean_ns": estimates });
+    std::fs::write(&options.report, serde_json::to_vec_pretty(&report)?)?;
+    println!("Wrote benchmark report to `{}`", options.report.display());
+
+    let baseline = match options.baseline.as_ref() {
+        Some(baseline) => baseline,
+        None => return Ok(true),
+    };
+    let baseline: serde_json::Value = match std::fs::read(baseline) {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!(
+                "Baseline `{}` does not exist, skipping comparison",
+                baseline.display()
+            );
+            return Ok(true);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut passed = true;
+    for (id, mean) in estimates.iter() {
+        let baseline_mean = match baseline["mean_ns"][id].as_f64() {
+            Some(baseline_mean) => baseline_mean,
+            None => {
+                println!("{id}: new benchmark");
+                continue;
+            }
+        };
+
+        let change = (mean / baseline_mean - 1.0) * 100.0;
+        let regressed = change > options.threshold;
+        println!(
+            "{id}: {baseline_mean:.0}ns -> {mean:.0}ns ({change:+.2}%){}",
+            if regressed { " REGRESSED" } else { "" }
+        );
+        passed &= !regressed;
+    }
+
+    Ok(passed)
+}
+
+fn main() -> ExitCode {
+    let report_options = match ReportOptions::from_args() {
+        Ok(report_options) => report_options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let criterion_dir = std::env::temp_dir().join("msys2-packager-bench");
+    let mut criterion = match report_options.as_ref() {
+        Some(_) => {
+            // Start from a clean slate, so stale results never end up in the report.
+            let _ = std::fs::remove_dir_all(&criterion_dir);
+            Criterion::default()
+                .output_directory(&criterion_dir)
+                .without_plots()
+        }
+        None => Criterion::default().configure_from_args(),
+    };
+
+    bench_resolve(&mut criterion);
+    bench_plan(&mut criterion);
+    bench_classify(&mut criterion);
+    criterion.final_summary();
+
+    if let Some(report_options) = report_options {
+        match write_report(&report_options, &criterion_dir) {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!(
+                    "one or more benchmarks regressed by more than {}%",
+                    report_options.threshold
+                );
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                eprintln!("failed to write benchmark report: {e:?}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,158 @@
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The PE section that `cargo auditable` embeds the dependency list in.
+pub const AUDITABLE_SECTION_NAME: &str = ".dep-v0";
+
+/// The file name of the audit data written to package dirs.
+pub const AUDITABLE_FILE_NAME: &str = "auditable.json";
+
+/// The largest decompressed audit data that will be read, which is the limit `cargo auditable` readers use.
+const MAX_AUDIT_DATA_SIZE: usize = 8 * 1024 * 1024;
+
+/// The dependency tree that `cargo auditable` embeds in binaries.
+///
+/// See <https://github.com/rust-secure-code/cargo-auditable/blob/master/PARSING.md>.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AuditData {
+    /// The crates built into the binary, which refer to their dependencies by index
+    pub packages: Vec<AuditPackage>,
+}
+
+/// A crate built into a binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AuditPackage {
+    /// The crate name
+    pub name: String,
+
+    /// The crate version
+    pub version: String,
+
+    /// Where the crate came from, like `crates.io`, `git`, `local`, or `registry`
+    pub source: String,
+
+    /// Whether the crate is only used at build time, which is `build`, or also at runtime, which is `runtime`
+    #[serde(default = "get_default_kind", skip_serializing_if = "is_default_kind")]
+    pub kind: String,
+
+    /// The indices of the dependencies of the crate in [`AuditData::packages`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<usize>,
+
+    /// Whether this is the crate of the binary
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub root: bool,
+}
+
+/// The default [`AuditPackage::kind`].
+fn get_default_kind() -> String {
+    "runtime".into()
+}
+
+/// Check if a kind is the default [`AuditPackage::kind`].
+fn is_default_kind(kind: &str) -> bool {
+    kind == "runtime"
+}
+
+/// The audit data of the binaries in a package, which is written to [`AUDITABLE_FILE_NAME`] for vulnerability scanners
+#[derive(Debug, Default, Clone, Serialize)]
+#[non_exhaustive]
+pub struct AuditableManifest {
+    /// The audit data of each binary, keyed by its path relative to the package dir
+    pub binaries: BTreeMap<PathBuf, AuditData>,
+}
+
+impl AuditableManifest {
+    /// Make an empty [`AuditableManifest`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the audit data of a packaged binary, and add it.
+    ///
+    /// `path` is relative to `package_dir`.
+    ///
+    /// # Errors
+    /// Returns an error if the binary has no audit data, like if it was not built with `cargo auditable` or was compressed with UPX.
+    pub fn add_binary(&mut self, package_dir: &Path, path: PathBuf) -> anyhow::Result<()> {
+        let audit_data = read_audit_data(&package_dir.join(&path))?.with_context(|| {
+            format!(
+                "`{}` has no `{AUDITABLE_SECTION_NAME}` section. Build it with `cargo auditable`, and do not compress it with UPX",
+                path.display()
+            )
+        })?;
+        self.binaries.insert(path, audit_data);
+
+        Ok(())
+    }
+
+    /// Write this manifest to [`AUDITABLE_FILE_NAME`] in a package dir.
+    pub fn save(&self, package_dir: &Path) -> anyhow::Result<()> {
+        let path = package_dir.join(AUDITABLE_FILE_NAME);
+        let mut data = serde_json::to_string_pretty(self)?;
+        data.push('\n');
+        std::fs::write(&path, data)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Read the audit data that `cargo auditable` embedded in an exe or dll.
+///
+/// # Returns
+/// Returns None if the binary has no audit data.
+pub fn read_audit_data(path: &Path) -> anyhow::Result<Option<AuditData>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    read_audit_data_from_bytes(&bytes)
+        .with_context(|| format!("failed to read audit data from `{}`", path.display()))
+}
+
+/// Read the audit data that `cargo auditable` embedded in an exe or dll, already loaded into memory.
+///
+/// # Returns
+/// Returns None if the binary has no audit data.
+pub fn read_audit_data_from_bytes(bytes: &[u8]) -> anyhow::Result<Option<AuditData>> {
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
+    let Some(section) = pe
+        .sections
+        .iter()
+        .find(|section| section.name().ok() == Some(AUDITABLE_SECTION_NAME))
+    else {
+        return Ok(None);
+    };
+
+    // The raw data is padded to the file alignment, while the virtual size is the real size.
+    let start = usize::try_from(section.pointer_to_raw_data)?;
+    let len = usize::try_from(section.virtual_size.min(section.size_of_raw_data))?;
+    let compressed = start
+        .checked_add(len)
+        .and_then(|end| bytes.get(start..end))
+        .context("the audit data section is out of bounds")?;
+
+    let data =
+        miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, MAX_AUDIT_DATA_SIZE)
+            .map_err(|error| anyhow::anyhow!("failed to decompress audit data: {error}"))?;
+    let audit_data: AuditData =
+        serde_json::from_slice(&data).context("failed to parse audit data")?;
+    for package in audit_data.packages.iter() {
+        ensure!(
+            package
+                .dependencies
+                .iter()
+                .all(|index| *index < audit_data.packages.len()),
+            "`{}` depends on a package that does not exist",
+            package.name
+        );
+    }
+
+    Ok(Some(audit_data))
+}
@@ -0,0 +1,171 @@
+use crate::util::UpxLevel;
+use crate::util::UpxOptions;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// The name of the cache file, placed in the top level of the out dir.
+pub const CACHE_FILE_NAME: &str = ".msys2-packager-cache.json";
+
+/// The current version of the cache format.
+///
+/// Caches with a different version are discarded.
+pub(crate) const CACHE_VERSION: u32 = 1;
+
+/// A record of the files that a previous packaging run placed in the out dir.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    /// The cache format version
+    pub version: u32,
+
+    /// Entries, keyed by their relative destination path.
+    pub entries: BTreeMap<PathBuf, CacheEntry>,
+}
+
+impl Cache {
+    /// Make a new, empty [`Cache`].
+    pub fn new() -> Self {
+        Self {
+            version: CACHE_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Load the cache from the given out dir.
+    ///
+    /// # Returns
+    /// Returns an empty cache if the cache file does not exist, is corrupt, or has an unknown version.
+    pub fn load(out_dir: &Path) -> Self {
+        let path = out_dir.join(CACHE_FILE_NAME);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Self::new();
+            }
+            Err(e) => {
+                eprintln!("failed to read `{}`, ignoring: {e}", path.display());
+                return Self::new();
+            }
+        };
+
+        match serde_json::from_slice::<Self>(&data) {
+            Ok(cache) if cache.version == CACHE_VERSION => cache,
+            Ok(_) => Self::new(),
+            Err(e) => {
+                eprintln!("failed to parse `{}`, ignoring: {e}", path.display());
+                Self::new()
+            }
+        }
+    }
+
+    /// Remove the cache file from the given out dir.
+    ///
+    /// This is done before touching any packaged files,
+    /// so that an interrupted run cannot leave a cache that describes stale files.
+    pub fn invalidate(out_dir: &Path) -> anyhow::Result<()> {
+        let path = out_dir.join(CACHE_FILE_NAME);
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("failed to remove `{}`", path.display())),
+        }
+    }
+
+    /// Save the cache into the given out dir.
+    pub fn save(&self, out_dir: &Path) -> anyhow::Result<()> {
+        let path = out_dir.join(CACHE_FILE_NAME);
+        let data = serde_json::to_vec_pretty(self).context("failed to serialize cache")?;
+        std::fs::write(&path, data)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// A cache entry for a single packaged file.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// The source path
+    pub src: PathBuf,
+
+    /// The size of the source, in bytes
+    pub size: u64,
+
+    /// The modification time of the source, as seconds since the unix epoch
+    pub modified_secs: u64,
+
+    /// The sub-second part of the modification time of the source
+    pub modified_nanos: u32,
+
+    /// Whether the file was upx-ed
+    pub upx: bool,
+
+    /// The upx binary the file was upx-ed with, if it was upx-ed
+    #[serde(default)]
+    pub upx_path: Option<PathBuf>,
+
+    /// The upx compression level arg the file was upx-ed with, like `-9`, if one was passed
+    #[serde(default)]
+    pub upx_level: Option<String>,
+
+    /// Whether the file was upx-ed with LZMA compression
+    #[serde(default)]
+    pub upx_lzma: bool,
+
+    /// Whether the file was stripped
+    #[serde(default)]
+    pub strip: bool,
+
+    /// Whether the debug info of the file was split into a separate file before it was stripped
+    #[serde(default)]
+    pub split_debug: bool,
+
+    /// The certificate the file was signed with, if it was signed
+    #[serde(default)]
+    pub signed_with: Option<PathBuf>,
+
+    /// The timestamp the PE headers of the file were set to, if they were set
+    #[serde(default)]
+    pub pe_timestamp: Option<u32>,
+}
+
+impl CacheEntry {
+    /// Make a new [`CacheEntry`] from the current state of the given source file.
+    ///
+    /// `upx` has the options the file is upx-ed with, if it is upx-ed, so changing them repackages it.
+    pub fn new(
+        src: &Path,
+        strip: bool,
+        split_debug: bool,
+        upx: Option<&UpxOptions>,
+        signed_with: Option<&Path>,
+        pe_timestamp: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        let metadata = std::fs::metadata(src)
+            .with_context(|| format!("failed to get metadata for `{}`", src.display()))?;
+        let modified = metadata
+            .modified()
+            .context("failed to get modification time")?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(Self {
+            src: src.into(),
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            upx: upx.is_some(),
+            upx_path: upx.map(|upx| upx.get_path().into()),
+            upx_level: upx.and_then(|upx| upx.get_level()).map(UpxLevel::get_arg),
+            upx_lzma: upx.is_some_and(|upx| upx.get_lzma()),
+            strip,
+            split_debug,
+            signed_with: signed_with.map(PathBuf::from),
+            pe_timestamp,
+        })
+    }
+}
@@ -0,0 +1,2 @@
+/// The `knowledge` subcommand
+pub mod knowledge;
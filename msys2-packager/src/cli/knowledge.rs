@@ -0,0 +1,116 @@
+use crate::knowledge::download;
+use crate::knowledge::get_profile_path;
+use crate::knowledge::KNOWLEDGE_BASE_FILE_NAME;
+use crate::knowledge::KNOWLEDGE_BASE_URL;
+use crate::trust::TrustPolicy;
+use anyhow::ensure;
+use anyhow::Context;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The options of the `knowledge` subcommand.
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "knowledge",
+    description = "manage what the packager knows about Windows, like its system dlls"
+)]
+pub struct Options {
+    /// the subcommand to run
+    #[argh(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+/// The subcommands of the `knowledge` subcommand.
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Subcommand {
+    /// The `knowledge update` subcommand
+    Update(UpdateOptions),
+}
+
+/// The options of the `knowledge update` subcommand.
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "update",
+    description = "download the latest system dll and api set tables, which are used instead of the built-in ones"
+)]
+pub struct UpdateOptions {
+    /// The url to download from
+    #[argh(
+        option,
+        default = "KNOWLEDGE_BASE_URL.into()",
+        description = "the url to download from. Defaults to the latest release"
+    )]
+    pub url: String,
+
+    /// The lowercase hex SHA-256 that the download must have
+    #[argh(
+        option,
+        description = "the lowercase hex SHA-256 that the download must have, like the one published with the release"
+    )]
+    pub sha256: Option<String>,
+
+    /// A trust policy that the download must satisfy
+    #[argh(
+        option,
+        description = "a JSON trust policy that the download must satisfy, with a pinned SHA-256 for `knowledge.json` or valid signatures"
+    )]
+    pub trust_policy: Option<PathBuf>,
+
+    /// Where to write the tables
+    #[argh(
+        option,
+        description = "where to write the tables. Defaults to the profile dir"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+/// Exec the `knowledge` subcommand.
+///
+/// Knowledge bases are written to `profile_dir` by default, if there is one, where [`crate::knowledge::KnowledgeBase::init_global`] looks for them.
+/// Signatures are checked with the keyring of the MSYS2 installation at `msys2_installation_path`.
+pub fn exec(
+    options: Options,
+    profile_dir: Option<&Path>,
+    msys2_installation_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    match options.subcommand {
+        Subcommand::Update(options) => exec_update(options, profile_dir, msys2_installation_path),
+    }
+}
+
+/// Exec the `knowledge update` subcommand.
+fn exec_update(
+    options: UpdateOptions,
+    profile_dir: Option<&Path>,
+    msys2_installation_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut policy = match options.trust_policy.as_deref() {
+        Some(path) => TrustPolicy::load(path)?,
+        None => TrustPolicy::default(),
+    };
+    if let Some(sha256) = options.sha256.as_deref() {
+        policy.pin(KNOWLEDGE_BASE_FILE_NAME, sha256)?;
+    }
+    ensure!(
+        policy.allow_signed || policy.sha256.contains_key(KNOWLEDGE_BASE_FILE_NAME),
+        "the download cannot be trusted, pass `--sha256` or a `--trust-policy` that pins or allows signed files"
+    );
+
+    let output = options
+        .output
+        .or_else(|| profile_dir.map(get_profile_path))
+        .context("there is no profile dir, pass `--output`")?;
+
+    let knowledge_base = download(&options.url, &output, &policy, msys2_installation_path)?;
+    println!(
+        "Wrote {} system dlls and {} api set prefixes to `{}`",
+        knowledge_base.system_dlls.len(),
+        knowledge_base.api_set_prefixes.len(),
+        output.display()
+    );
+
+    Ok(())
+}
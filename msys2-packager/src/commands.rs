@@ -0,0 +1,6 @@
+/// The `extract` subcommand
+pub mod extract;
+/// The `list-deps` subcommand
+pub mod list_deps;
+/// The `which` subcommand
+pub mod which;
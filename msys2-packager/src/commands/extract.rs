@@ -0,0 +1,86 @@
+use anyhow::bail;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use msys2_packager::extract::extract_installed_package;
+use msys2_packager::extract::extract_package_archive;
+use msys2_packager::pacman::LocalDatabase;
+use msys2_packager::trust::TrustPolicy;
+use msys2_packager::util::locate_msys2_installation;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract",
+    description = "extract files from an installed package or a downloaded `.pkg.tar.zst` archive"
+)]
+pub struct Options {
+    #[argh(
+        positional,
+        description = "the name of an installed package, or the path to a package archive"
+    )]
+    pub package: String,
+
+    #[argh(
+        positional,
+        description = "paths to extract, relative to the package's environment prefix, like `share/icons`"
+    )]
+    pub paths: Vec<Utf8PathBuf>,
+
+    #[argh(option, long = "out", short = 'o', description = "the output dir")]
+    pub out: PathBuf,
+
+    #[argh(
+        option,
+        description = "a JSON trust policy that package archives must satisfy, with pinned SHA-256s or valid repo signatures"
+    )]
+    pub trust_policy: Option<PathBuf>,
+}
+
+/// Exec the `extract` subcommand.
+pub fn exec(options: Options) -> anyhow::Result<()> {
+    if options.paths.is_empty() {
+        bail!("no paths to extract");
+    }
+
+    let archive_path = Path::new(&options.package);
+    let extracted = if archive_path.is_file() {
+        if let Some(path) = options.trust_policy.as_deref() {
+            let policy = TrustPolicy::load(path)?;
+            let msys2_installation_path = locate_msys2_installation().ok();
+            policy
+                .check_file(
+                    archive_path,
+                    msys2_installation_path
+                        .as_ref()
+                        .map(|path| path.as_std_path()),
+                )
+                .context("the archive is not trusted")?;
+        }
+
+        extract_package_archive(archive_path, &options.paths, &options.out)
+            .with_context(|| format!("failed to extract from `{}`", archive_path.display()))?
+    } else {
+        let msys2_installation_path = locate_msys2_installation()?;
+        let database = LocalDatabase::load(&msys2_installation_path)
+            .context("failed to load the pacman database")?;
+        let package = database
+            .get(&options.package)
+            .with_context(|| format!("`{}` is not installed", options.package))?;
+
+        extract_installed_package(
+            &msys2_installation_path,
+            package,
+            &options.paths,
+            &options.out,
+        )
+        .with_context(|| format!("failed to extract from `{}`", package.name))?
+    };
+
+    for path in extracted.iter() {
+        println!("{path}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use anyhow::Context;
+use msys2::Msys2Environment;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::resolver::resolve_dependencies_with_options;
+use msys2_packager::resolver::DependencySource;
+use msys2_packager::resolver::Msys2DependencySource;
+use msys2_packager::resolver::ResolveOptions;
+use msys2_packager::util::is_api_set_dll;
+use msys2_packager::util::locate_msys2_installation;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "list-deps",
+    description = "list the dlls a binary imports, and where they resolve in the MSYS2 environment"
+)]
+pub struct Options {
+    #[argh(positional, description = "the path to the exe or dll")]
+    pub path: PathBuf,
+
+    #[argh(switch, description = "also list the dependencies of dependencies")]
+    pub recursive: bool,
+
+    #[argh(
+        option,
+        description = "the MSYS2 environment to resolve in, defaults to the `MSYSTEM` env var"
+    )]
+    pub environment: Option<Msys2Environment>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// A dll import, and where it resolved.
+#[derive(Debug, Serialize)]
+struct Dependency {
+    /// The binary with the import
+    importer: PathBuf,
+
+    /// The name of the imported dll
+    name: String,
+
+    /// Where the dll resolved, if it did
+    path: Option<PathBuf>,
+
+    /// Whether this is an api set dll, which is provided by the OS
+    api_set: bool,
+}
+
+/// Exec the `list-deps` subcommand.
+///
+/// `resolve_options` has the system dll rules of the command line.
+pub fn exec(options: Options, resolve_options: &ResolveOptions) -> anyhow::Result<()> {
+    let msys2_environment = crate::get_msys2_environment(options.environment)?;
+    let msys2_installation_path = locate_msys2_installation()?;
+    let environment_path =
+        msys2_installation_path.join(msys2_environment.get_prefix().trim_start_matches('/'));
+    let source = Msys2DependencySource::new(environment_path);
+
+    let dependencies = if options.recursive {
+        let resolution =
+            resolve_dependencies_with_options(&source, &[options.path.as_path()], resolve_options)?;
+        let paths: HashMap<_, _> = resolution
+            .libraries
+            .iter()
+            .map(|library| (library.name.as_str(), library.path.as_path()))
+            .collect();
+
+        resolution
+            .imports
+            .iter()
+            .map(|import| Dependency {
+                importer: import.importer.clone(),
+                name: import.name.clone(),
+                path: paths
+                    .get(import.name.as_str())
+                    .map(|path| path.to_path_buf()),
+                api_set: is_api_set_dll(&import.name),
+            })
+            .collect::<Vec<_>>()
+    } else {
+        let imports = source
+            .get_imports(&options.path)
+            .with_context(|| format!("failed to get imports for `{}`", options.path.display()))?;
+
+        imports
+            .into_iter()
+            .filter(|name| !resolve_options.is_system_dll(name))
+            .map(|name| {
+                let api_set = is_api_set_dll(&name);
+                let path = if api_set {
+                    None
+                } else {
+                    source
+                        .lookup(&name)
+                        .with_context(|| format!("failed to locate `{name}`"))?
+                };
+
+                Ok(Dependency {
+                    importer: options.path.clone(),
+                    name,
+                    path,
+                    api_set,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+
+    match options.output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&dependencies)?;
+            println!("{json}");
+        }
+        _ => {
+            for dependency in dependencies.iter() {
+                let resolution = match (dependency.path.as_ref(), dependency.api_set) {
+                    (_, true) => "(api set)".to_string(),
+                    (Some(path), false) => format!("`{}`", path.display()),
+                    (None, false) => "(not found)".to_string(),
+                };
+                println!(
+                    "`{}` -> `{}` => {resolution}",
+                    dependency.importer.display(),
+                    dependency.name,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
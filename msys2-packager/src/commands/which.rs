@@ -0,0 +1,128 @@
+use anyhow::bail;
+use anyhow::Context;
+use msys2::Msys2Environment;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::resolver::ResolveOptions;
+use msys2_packager::util::get_msys2_lookup_paths;
+use msys2_packager::util::is_api_set_dll;
+use msys2_packager::util::locate_msys2_installation;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(
+    subcommand,
+    name = "which",
+    description = "show where a library or exe resolves in the MSYS2 environment"
+)]
+pub struct Options {
+    #[argh(positional, description = "the file name, like `libgtk-4-1.dll`")]
+    pub name: String,
+
+    #[argh(
+        option,
+        description = "the MSYS2 environment to look in, defaults to the `MSYSTEM` env var"
+    )]
+    pub environment: Option<Msys2Environment>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    pub output_format: OutputFormat,
+}
+
+/// A path that was tried.
+#[derive(Debug, Serialize)]
+struct TriedPath {
+    /// The path
+    path: PathBuf,
+
+    /// Whether the path exists
+    exists: bool,
+}
+
+/// The result of a lookup.
+#[derive(Debug, Serialize)]
+struct Lookup {
+    /// The name that was looked up
+    name: String,
+
+    /// Whether the name is a system dll, which the packager skips
+    system: bool,
+
+    /// Whether the name is an api set dll, which the packager skips
+    api_set: bool,
+
+    /// The paths that were tried, in order
+    tried: Vec<TriedPath>,
+
+    /// The path the name resolves to, if it resolves
+    path: Option<PathBuf>,
+}
+
+/// Exec the `which` subcommand.
+///
+/// `resolve_options` has the system dll rules of the command line.
+pub fn exec(options: Options, resolve_options: &ResolveOptions) -> anyhow::Result<()> {
+    let msys2_environment = crate::get_msys2_environment(options.environment)?;
+    let msys2_installation_path = locate_msys2_installation()?;
+    let environment_path =
+        msys2_installation_path.join(msys2_environment.get_prefix().trim_start_matches('/'));
+
+    let mut tried = Vec::new();
+    let mut path = None;
+    for candidate in get_msys2_lookup_paths(&environment_path, OsStr::new(&options.name)) {
+        let exists = candidate
+            .try_exists()
+            .context("failed to check if file exists")?;
+        if exists && path.is_none() {
+            path = Some(candidate.clone());
+        }
+        tried.push(TriedPath {
+            path: candidate,
+            exists,
+        });
+    }
+
+    let lookup = Lookup {
+        system: resolve_options.is_system_dll(&options.name),
+        api_set: is_api_set_dll(&options.name),
+        name: options.name,
+        tried,
+        path,
+    };
+
+    match options.output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&lookup)?;
+            println!("{json}");
+        }
+        _ => {
+            for tried in lookup.tried.iter() {
+                let status = if tried.exists { "found" } else { "missing" };
+                println!("[{status}] {}", tried.path.display());
+            }
+            if lookup.system {
+                println!("`{}` is a system dll, so it is never packaged", lookup.name);
+            }
+            if lookup.api_set {
+                println!(
+                    "`{}` is an api set dll, so it is never packaged",
+                    lookup.name
+                );
+            }
+            if let Some(path) = lookup.path.as_ref() {
+                println!("`{}` resolves to `{}`", lookup.name, path.display());
+            }
+        }
+    }
+
+    if lookup.path.is_none() {
+        bail!("`{}` was not found in `{environment_path}`", lookup.name);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use crate::packager::CACHE_FILE_NAME;
+use crate::report::OutputFormat;
+use crate::sbom::sha256_file;
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+/// A file that is only in one of the packages.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct DiffFile {
+    /// The file path, relative to the package top level.
+    pub path: PathBuf,
+
+    /// The file size, in bytes.
+    pub size: u64,
+}
+
+/// A file that is in both packages, but differs.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ChangedFile {
+    /// The file path, relative to the package top level.
+    pub path: PathBuf,
+
+    /// The size in the old package, in bytes.
+    pub old_size: u64,
+
+    /// The size in the new package, in bytes.
+    pub new_size: u64,
+
+    /// The lowercase hex SHA-256 in the old package.
+    pub old_sha256: String,
+
+    /// The lowercase hex SHA-256 in the new package.
+    pub new_sha256: String,
+}
+
+/// The differences between two package dirs.
+///
+/// The incremental packaging cache file is ignored.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct PackageDiff {
+    /// Files only in the new package, sorted by path.
+    pub added: Vec<DiffFile>,
+
+    /// Files only in the old package, sorted by path.
+    pub removed: Vec<DiffFile>,
+
+    /// Files in both packages with different contents, sorted by path.
+    pub changed: Vec<ChangedFile>,
+
+    /// The number of files that are the same in both packages.
+    pub unchanged: usize,
+}
+
+impl PackageDiff {
+    /// Check if the packages have the same files.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Print this diff to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PackageDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in self.added.iter() {
+            writeln!(f, "+ `{}` ({} bytes)", file.path.display(), file.size)?;
+        }
+        for file in self.removed.iter() {
+            writeln!(f, "- `{}` ({} bytes)", file.path.display(), file.size)?;
+        }
+        for file in self.changed.iter() {
+            writeln!(
+                f,
+                "~ `{}` ({} -> {} bytes)",
+                file.path.display(),
+                file.old_size,
+                file.new_size
+            )?;
+        }
+        write!(
+            f,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// Compare two package dirs by file size and SHA-256.
+pub fn diff_packages(old_dir: &Path, new_dir: &Path) -> anyhow::Result<PackageDiff> {
+    let old_files = list_files(old_dir)?;
+    let mut new_files = list_files(new_dir)?;
+
+    let mut diff = PackageDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: 0,
+    };
+    for (path, old_size) in old_files {
+        let new_size = match new_files.remove(&path) {
+            Some(new_size) => new_size,
+            None => {
+                diff.removed.push(DiffFile {
+                    path,
+                    size: old_size,
+                });
+                continue;
+            }
+        };
+
+        let old_path = old_dir.join(&path);
+        let old_sha256 = sha256_file(&old_path)
+            .with_context(|| format!("failed to hash `{}`", old_path.display()))?;
+        let new_path = new_dir.join(&path);
+        let new_sha256 = sha256_file(&new_path)
+            .with_context(|| format!("failed to hash `{}`", new_path.display()))?;
+
+        if old_size == new_size && old_sha256 == new_sha256 {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push(ChangedFile {
+                path,
+                old_size,
+                new_size,
+                old_sha256,
+                new_sha256,
+            });
+        }
+    }
+    diff.added = new_files
+        .into_iter()
+        .map(|(path, size)| DiffFile { path, size })
+        .collect();
+
+    Ok(diff)
+}
+
+/// List the files in a package dir, with their sizes in bytes.
+///
+/// Paths are relative to the package top level.
+fn list_files(dir: &Path) -> anyhow::Result<BTreeMap<PathBuf, u64>> {
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry.with_context(|| format!("failed to read `{}`", dir.display()))?;
+        if !entry.file_type().is_file() || entry.file_name() == CACHE_FILE_NAME {
+            continue;
+        }
+
+        let relative_path = entry
+            .path()
+            .strip_prefix(dir)
+            .context("package file is not in the package dir")?;
+        let size = entry
+            .metadata()
+            .with_context(|| format!("failed to get metadata for `{}`", entry.path().display()))?
+            .len();
+        files.insert(relative_path.into(), size);
+    }
+
+    Ok(files)
+}
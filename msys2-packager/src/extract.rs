@@ -0,0 +1,190 @@
+use crate::pacman::InstalledPackage;
+use crate::pkg_config::get_environment_prefixes;
+use crate::pkg_config::is_pkg_config_file;
+use crate::pkg_config::relocate_pkg_config_file;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8Component;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use msys2::Msys2Environment;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Get the dir that the files of a package are extracted relative to.
+///
+/// This is the environment prefix for packages in an environment, like `ucrt64`,
+/// and the installation root for MSYS packages.
+pub fn get_package_root(package_name: &str) -> &'static str {
+    Msys2Environment::from_package_name(package_name)
+        .map(|environment| environment.get_prefix().trim_start_matches('/'))
+        .unwrap_or("")
+}
+
+/// Get the path of a package file relative to the package root, if it is selected by one of `paths`.
+///
+/// A path selects itself and everything under it.
+fn select_path(root: &str, file: &Utf8Path, paths: &[Utf8PathBuf]) -> Option<Utf8PathBuf> {
+    let relative_path = file.strip_prefix(root).ok()?;
+    paths
+        .iter()
+        .any(|path| relative_path.starts_with(path))
+        .then(|| relative_path.into())
+}
+
+/// Make sure every path selected at least one file.
+fn validate_selection(
+    package_name: &str,
+    paths: &[Utf8PathBuf],
+    extracted: &[Utf8PathBuf],
+) -> anyhow::Result<()> {
+    for path in paths.iter() {
+        ensure!(
+            extracted.iter().any(|file| file.starts_with(path)),
+            "`{path}` did not match any files in `{package_name}`"
+        );
+    }
+
+    Ok(())
+}
+
+/// Relocate the extracted pkg-config files of a package, so they work from `out_dir`.
+///
+/// See [`crate::pkg_config::relocate_pkg_config`].
+fn relocate_extracted_pkg_config(
+    msys2_installation_path: Option<&Utf8Path>,
+    root: &str,
+    out_dir: &Path,
+    extracted: &[Utf8PathBuf],
+) -> anyhow::Result<()> {
+    // MSYS packages do not have an environment to be relative to.
+    if root.is_empty() {
+        return Ok(());
+    }
+
+    let environment_prefixes = get_environment_prefixes(msys2_installation_path, root);
+    for path in extracted.iter().filter(|path| is_pkg_config_file(path)) {
+        relocate_pkg_config_file(&out_dir.join(path), &environment_prefixes)?;
+    }
+
+    Ok(())
+}
+
+/// Extract files from a package installed in an MSYS2 installation, by copying them out of the installation.
+///
+/// `paths` are relative to the package root, see [`get_package_root`].
+/// A path selects itself and everything under it.
+/// Files are written to `out_dir`, keeping their paths relative to the package root.
+/// Extracted pkg-config files are rewritten to be relative to where they are extracted,
+/// so builds can use them without an MSYS2 installation at the original path.
+///
+/// # Returns
+/// Returns the paths of the extracted files, relative to `out_dir`.
+pub fn extract_installed_package(
+    msys2_installation_path: &Utf8Path,
+    package: &InstalledPackage,
+    paths: &[Utf8PathBuf],
+    out_dir: &Path,
+) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let root = get_package_root(&package.name);
+
+    let mut extracted = Vec::new();
+    for file in package.load_files()? {
+        let relative_path = match select_path(root, &file, paths) {
+            Some(relative_path) => relative_path,
+            None => continue,
+        };
+
+        let src = msys2_installation_path.join(&file);
+        let dest = out_dir.join(&relative_path);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create parent dir at `{}`", parent.display())
+            })?;
+        }
+        std::fs::copy(&src, &dest)
+            .with_context(|| format!("failed to copy `{src}` to `{}`", dest.display()))?;
+
+        extracted.push(relative_path);
+    }
+
+    validate_selection(&package.name, paths, &extracted)?;
+    relocate_extracted_pkg_config(Some(msys2_installation_path), root, out_dir, &extracted)?;
+
+    Ok(extracted)
+}
+
+/// Extract files from a downloaded package archive, like `mingw-w64-ucrt-x86_64-gtk4-4.10.0-1-any.pkg.tar.zst`.
+///
+/// The archive file name must start with the package name, as it does for archives downloaded by pacman.
+/// Only zstd-compressed archives are supported.
+///
+/// `paths` are relative to the package root, see [`get_package_root`].
+/// A path selects itself and everything under it.
+/// Files are written to `out_dir`, keeping their paths relative to the package root.
+/// Extracted pkg-config files are rewritten to be relative to where they are extracted.
+///
+/// # Returns
+/// Returns the paths of the extracted files, relative to `out_dir`.
+pub fn extract_package_archive(
+    archive_path: &Path,
+    paths: &[Utf8PathBuf],
+    out_dir: &Path,
+) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let archive_name = archive_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("`{}` has an invalid file name", archive_path.display()))?;
+    ensure!(
+        archive_name.ends_with(".pkg.tar.zst"),
+        "`{archive_name}` is not a `.pkg.tar.zst` archive"
+    );
+    let root = get_package_root(archive_name);
+
+    let file = File::open(archive_path)
+        .with_context(|| format!("failed to open `{}`", archive_path.display()))?;
+    let decoder = ruzstd::StreamingDecoder::new(BufReader::new(file))
+        .context("failed to start zstd decoding")?;
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut extracted = Vec::new();
+    for entry in archive.entries().context("failed to read archive")? {
+        let mut entry = entry.context("failed to read archive entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let file = entry.path().context("failed to get archive entry path")?;
+        let file = Utf8PathBuf::try_from(file.into_owned())
+            .context("archive entry path is not unicode")?;
+        let relative_path = match select_path(root, &file, paths) {
+            Some(relative_path) => relative_path,
+            None => continue,
+        };
+        ensure!(
+            relative_path
+                .components()
+                .all(|component| matches!(component, Utf8Component::Normal(_))),
+            "archive entry path `{file}` is not a plain relative path"
+        );
+
+        let dest = out_dir.join(&relative_path);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create parent dir at `{}`", parent.display())
+            })?;
+        }
+        let mut dest_file = File::create(&dest)
+            .with_context(|| format!("failed to create `{}`", dest.display()))?;
+        std::io::copy(&mut entry, &mut dest_file)
+            .with_context(|| format!("failed to extract `{file}`"))?;
+
+        extracted.push(relative_path);
+    }
+
+    validate_selection(archive_name, paths, &extracted)?;
+    relocate_extracted_pkg_config(None, root, out_dir, &extracted)?;
+
+    Ok(extracted)
+}
@@ -0,0 +1,144 @@
+use crate::packager::FileFlags;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use msys2::Msys2Environment;
+
+/// A file to add to a package, parsed from the `--file` mini-language of the `msys2-packager` CLI.
+///
+/// The format is a `|`-separated list of `key=value` pairs, like `src=foo.exe|dest=foo.exe|flags=exe,upx,add_deps`.
+/// `dest` is required, while `src`, `flags`, and `when` are optional.
+/// `flags` is a `,`-separated list of `exe`, `upx`, `lib`, `add_deps`, and `strip`.
+/// `when` is a `,`-separated list of conditions like `env:ucrt64`, where the file is only added if any of them match.
+///
+/// `src` and `dest` may contain `{env}` and `{arch}`, which are replaced with the environment and its arch,
+/// like `ucrt64` and `x86_64`. See [`FileOption::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileOption {
+    /// The file source.
+    ///
+    /// If this is none, the file is looked up in the MSYS2 environment.
+    pub src: Option<Utf8PathBuf>,
+
+    /// The file destination, relative to the package top level.
+    pub dest: Utf8PathBuf,
+
+    /// The file flags
+    pub flags: FileFlags,
+
+    /// The environments this file is added for.
+    ///
+    /// If this is empty, the file is added for every environment.
+    pub when: Vec<Msys2Environment>,
+}
+
+impl FileOption {
+    /// Parse a list of files, one per line.
+    ///
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn parse_list(input: &str) -> anyhow::Result<Vec<Self>> {
+        input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                line.parse()
+                    .with_context(|| format!("invalid file on line {}", index + 1))
+            })
+            .collect()
+    }
+
+    /// Resolve this file for an environment.
+    ///
+    /// This replaces `{env}` and `{arch}` in `src` and `dest`, and clears `when`.
+    ///
+    /// # Returns
+    /// Returns None if `when` does not match the environment.
+    pub fn resolve(&self, environment: Msys2Environment) -> Option<Self> {
+        if !self.when.is_empty() && !self.when.contains(&environment) {
+            return None;
+        }
+
+        let substitute = |path: &Utf8PathBuf| -> Utf8PathBuf {
+            path.as_str()
+                .replace("{env}", environment.get_name())
+                .replace("{arch}", environment.get_arch().get_name())
+                .into()
+        };
+
+        Some(Self {
+            src: self.src.as_ref().map(substitute),
+            dest: substitute(&self.dest),
+            flags: self.flags,
+            when: Vec::new(),
+        })
+    }
+}
+
+impl std::str::FromStr for FileOption {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut src = None;
+        let mut dest = None;
+        let mut flags = FileFlags::empty();
+        let mut when = Vec::new();
+
+        for part in input.split('|') {
+            let (key, value) = part
+                .split_once('=')
+                .with_context(|| format!("missing key/value pair in `{}`", part))?;
+            let key = key.trim();
+            let value = value.trim();
+
+            match key {
+                "src" => {
+                    ensure!(src.is_none(), "two src elements detected");
+                    src = Some(value);
+                }
+                "dest" => {
+                    ensure!(dest.is_none(), "two dest elements detected");
+                    dest = Some(value);
+                }
+                "flags" => {
+                    ensure!(flags.is_empty(), "two flags elements detected");
+                    for flag in value.split(',') {
+                        flags |= FileFlags::from_name(flag)
+                            .with_context(|| format!("unknown flag `{flag}`"))?;
+                    }
+                }
+                "when" => {
+                    ensure!(when.is_empty(), "two when elements detected");
+                    for condition in value.split(',') {
+                        match condition.split_once(':') {
+                            Some(("env", environment)) => {
+                                let environment = environment.parse().with_context(|| {
+                                    format!("invalid environment in `{condition}`")
+                                })?;
+                                when.push(environment);
+                            }
+                            _ => {
+                                bail!("unknown condition `{condition}`");
+                            }
+                        }
+                    }
+                }
+                key => {
+                    bail!("unknown key `{key}`");
+                }
+            }
+        }
+
+        let src = src.map(|v| v.into());
+        let dest = dest.context("missing dest").map(|v| v.into())?;
+
+        Ok(Self {
+            src,
+            dest,
+            flags,
+            when,
+        })
+    }
+}
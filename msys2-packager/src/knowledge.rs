@@ -0,0 +1,296 @@
+use crate::os_version::WindowsVersion;
+#[cfg(feature = "process")]
+use crate::trust::TrustPolicy;
+#[cfg(feature = "process")]
+use crate::trust::SIGNATURE_EXTENSION;
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "process")]
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The newest knowledge base format that this version understands.
+pub const KNOWLEDGE_BASE_VERSION: u32 = 1;
+
+/// The env var that points at a knowledge base file, overriding the user and built-in ones.
+pub const KNOWLEDGE_BASE_ENV: &str = "MSYS2_PACKAGER_KNOWLEDGE_BASE";
+
+/// The file name of knowledge base files.
+pub const KNOWLEDGE_BASE_FILE_NAME: &str = "knowledge.json";
+
+/// Where to download the latest knowledge base from, which is published with each release.
+pub const KNOWLEDGE_BASE_URL: &str =
+    "https://github.com/nathaniel-daniel/gtk-packager-rs/releases/latest/download/knowledge.json";
+
+/// The knowledge base shipped with the crate.
+const BUILTIN_KNOWLEDGE_BASE: &str = include_str!("../knowledge.json");
+
+/// The knowledge base used by [`crate::util::is_system_dll`] and [`crate::util::is_api_set_dll`].
+static GLOBAL_KNOWLEDGE_BASE: OnceLock<KnowledgeBase> = OnceLock::new();
+
+/// What the packager knows about the dlls and functions that Windows provides.
+///
+/// A copy is built into the crate, which users can override with a newer one,
+/// as new Windows versions add system dlls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct KnowledgeBase {
+    /// The version of the format
+    pub version: u32,
+
+    /// The names of dlls that the OS provides, in lowercase, without the extension
+    pub system_dlls: BTreeSet<String>,
+
+    /// The prefixes of api set dll names, like `api-`
+    pub api_set_prefixes: Vec<String>,
+
+    /// The first Windows version with each function, keyed by dll name, in lowercase and without the extension, and then by function name.
+    ///
+    /// A function name of `*` applies to every function of the dll, for dlls that are newer than Windows 7.
+    #[serde(default)]
+    pub api_versions: BTreeMap<String, BTreeMap<String, WindowsVersion>>,
+}
+
+impl KnowledgeBase {
+    /// Get the knowledge base built into the crate.
+    pub fn builtin() -> Self {
+        Self::from_slice(BUILTIN_KNOWLEDGE_BASE.as_bytes())
+            .expect("the built-in knowledge base is invalid")
+    }
+
+    /// Parse a knowledge base from JSON.
+    pub fn from_slice(data: &[u8]) -> anyhow::Result<Self> {
+        let mut knowledge_base: Self =
+            serde_json::from_slice(data).context("failed to parse knowledge base")?;
+        ensure!(
+            knowledge_base.version <= KNOWLEDGE_BASE_VERSION,
+            "the knowledge base has version {}, but only versions up to {KNOWLEDGE_BASE_VERSION} are supported. Update msys2-packager",
+            knowledge_base.version
+        );
+
+        knowledge_base.system_dlls = knowledge_base
+            .system_dlls
+            .into_iter()
+            .map(|name| get_dll_stem(&name))
+            .collect();
+        knowledge_base.api_versions = knowledge_base
+            .api_versions
+            .into_iter()
+            .map(|(name, functions)| (get_dll_stem(&name), functions))
+            .collect();
+
+        Ok(knowledge_base)
+    }
+
+    /// Load a knowledge base from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        Self::from_slice(&data).with_context(|| format!("failed to load `{}`", path.display()))
+    }
+
+    /// Get the knowledge base used by [`crate::util::is_system_dll`] and [`crate::util::is_api_set_dll`].
+    ///
+    /// On first use, this is loaded from the file at [`KNOWLEDGE_BASE_ENV`] if it is set,
+    /// and falls back to [`KnowledgeBase::builtin`].
+    /// A file that fails to load is skipped with a warning.
+    pub fn global() -> &'static Self {
+        GLOBAL_KNOWLEDGE_BASE.get_or_init(|| Self::load_default(None))
+    }
+
+    /// Initialize the knowledge base returned by [`KnowledgeBase::global`] with the one in a packager profile dir.
+    ///
+    /// The file at [`KNOWLEDGE_BASE_ENV`] is still used first if it is set,
+    /// then the file at [`get_profile_path`] if it exists, falling back to [`KnowledgeBase::builtin`].
+    /// This does nothing if the global knowledge base was already set or used.
+    pub fn init_global(profile_dir: &Path) -> &'static Self {
+        GLOBAL_KNOWLEDGE_BASE.get_or_init(|| Self::load_default(Some(profile_dir)))
+    }
+
+    /// Load the knowledge base from the env var, then the profile dir if there is one, then the built-in one.
+    fn load_default(profile_dir: Option<&Path>) -> Self {
+        let path = std::env::var_os(KNOWLEDGE_BASE_ENV)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                profile_dir
+                    .map(get_profile_path)
+                    .filter(|path| path.exists())
+            });
+        let Some(path) = path else {
+            return Self::builtin();
+        };
+
+        match Self::load(&path) {
+            Ok(knowledge_base) => knowledge_base,
+            Err(error) => {
+                eprintln!("warning: using the built-in knowledge base: {error:#}");
+                Self::builtin()
+            }
+        }
+    }
+
+    /// Set the knowledge base returned by [`KnowledgeBase::global`].
+    ///
+    /// # Errors
+    /// Returns the knowledge base back if the global one was already set or used.
+    pub fn set_global(self) -> Result<(), Self> {
+        GLOBAL_KNOWLEDGE_BASE.set(self)
+    }
+
+    /// Check if a given dll is a system dll, as in one that is provided by the OS.
+    pub fn is_system_dll(&self, name: &str) -> bool {
+        self.system_dlls.contains(&get_dll_stem(name))
+    }
+
+    /// Get the first Windows version with a function of a dll, if it is known to be newer than Windows 7.
+    pub fn get_api_version(&self, dll: &str, function: &str) -> Option<WindowsVersion> {
+        let functions = self.api_versions.get(&get_dll_stem(dll))?;
+        functions
+            .get(function)
+            .or_else(|| functions.get("*"))
+            .copied()
+    }
+
+    /// Check if a given name is an api set dll.
+    ///
+    /// Names are in the format {prefix}{alphanumeric,'-'}{n}-{n}-{n}.dll where n is a number,
+    /// and prefix is one of [`KnowledgeBase::api_set_prefixes`].
+    ///
+    /// See <https://learn.microsoft.com/en-us/windows/win32/apiindex/windows-apisets>.
+    pub fn is_api_set_dll(&self, name: &str) -> bool {
+        let Some(name) = self
+            .api_set_prefixes
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix.as_str()))
+        else {
+            return false;
+        };
+        let name = name.trim_end_matches(".dll");
+
+        let name = match name.rsplit_once('l') {
+            Some((name, rest)) => {
+                let mut n_iter = rest.split('-').filter(|s| !s.is_empty());
+                for _ in 0..3 {
+                    let _n = match n_iter.next().map(|n| n.parse::<u32>()) {
+                        Some(Ok(n)) => n,
+                        None | Some(Err(_)) => {
+                            return false;
+                        }
+                    };
+                }
+
+                if n_iter.next().is_some() {
+                    return false;
+                }
+
+                name
+            }
+            None => return false,
+        };
+
+        name.chars().all(|c| c.is_alphanumeric() || c == '-')
+    }
+}
+
+/// Get the path of the knowledge base file in a packager profile dir, which `knowledge update` writes.
+pub fn get_profile_path(profile_dir: &Path) -> PathBuf {
+    profile_dir.join(KNOWLEDGE_BASE_FILE_NAME)
+}
+
+/// Download a knowledge base with curl, and write it to `dest` if it is trusted and valid.
+///
+/// The download must be allowed by `policy`, see [`TrustPolicy::check_file`], which checks it under the name [`KNOWLEDGE_BASE_FILE_NAME`].
+/// If the policy allows signed files, the detached signature is downloaded from `url` with [`SIGNATURE_EXTENSION`] appended,
+/// and checked with the keyring of the MSYS2 installation at `msys2_installation_path`.
+/// The file at `dest` is only replaced once the download has been checked.
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn download(
+    url: &str,
+    dest: &Path,
+    policy: &TrustPolicy,
+    msys2_installation_path: Option<&Path>,
+) -> anyhow::Result<KnowledgeBase> {
+    let parent = dest
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create `{}`", parent.display()))?;
+
+    // The policy pins files by name, so download to a temp dir under the real name.
+    let temp_dir = parent.join(format!(".knowledge.{}.tmp", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)
+        .with_context(|| format!("failed to create `{}`", temp_dir.display()))?;
+    let temp_path = temp_dir.join(KNOWLEDGE_BASE_FILE_NAME);
+
+    let result = (|| {
+        curl_download(url, &temp_path)?;
+        if policy.allow_signed && !policy.sha256.contains_key(KNOWLEDGE_BASE_FILE_NAME) {
+            let mut signature_path = temp_path.clone().into_os_string();
+            signature_path.push(".");
+            signature_path.push(SIGNATURE_EXTENSION);
+            curl_download(
+                &format!("{url}.{SIGNATURE_EXTENSION}"),
+                Path::new(&signature_path),
+            )?;
+        }
+        policy
+            .check_file(&temp_path, msys2_installation_path)
+            .context("the downloaded knowledge base is not trusted")?;
+
+        let knowledge_base = KnowledgeBase::load(&temp_path)?;
+        std::fs::rename(&temp_path, dest).with_context(|| {
+            format!(
+                "failed to rename `{}` to `{}`",
+                temp_path.display(),
+                dest.display()
+            )
+        })?;
+
+        Ok(knowledge_base)
+    })();
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    result
+}
+
+/// Download a url to a file with curl.
+#[cfg(feature = "process")]
+fn curl_download(url: &str, dest: &Path) -> anyhow::Result<()> {
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(dest)
+        .arg(url)
+        .output()
+        .context("failed to run `curl`")?;
+    ensure!(
+        output.status.success(),
+        "failed to download `{url}`: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    Ok(())
+}
+
+/// Get the lowercase name of a dll, without the `.dll` or `.drv` extension.
+fn get_dll_stem(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    name.trim_end_matches(".dll")
+        .trim_end_matches(".drv")
+        .to_string()
+}
@@ -1,4 +1,58 @@
+//! A library for bundling executables built against MSYS2 with the libraries and data they need to run.
+//!
+//! The main entry point is [`packager::Packager`].
+//! Files are added to a packager, which then resolves their dll dependencies from an MSYS2 environment
+//! and copies everything into an output directory.
+#![warn(missing_docs)]
+
+/// Dependency audit data embedded by `cargo auditable`
+pub mod auditable;
+/// The incremental packaging cache
+mod cache;
+/// Subcommands shared by the packager CLIs
+#[cfg(feature = "cli")]
+pub mod cli;
+/// Comparing package dirs
+pub mod diff;
+/// Extracting files from MSYS2 packages
+pub mod extract;
+/// The `--file` mini-language
+pub mod file_option;
+/// What the packager knows about Windows, like its system dlls
+pub mod knowledge;
+/// License attribution for packaged MSYS2 packages
+pub mod licenses;
+/// Lockfiles for reproducible packaging
+pub mod lock;
+/// Minimum Windows versions of packaged binaries
+pub mod os_version;
 /// The packager
 pub mod packager;
+/// The local pacman database
+pub mod pacman;
+/// Relocating pkg-config files
+pub mod pkg_config;
+/// Extensions to the packaging process
+pub mod plugin;
+/// License and advisory policies for packaged crates and MSYS2 packages
+pub mod policy;
+/// Machine-readable reports
+pub mod report;
+/// Dependency resolution
+pub mod resolver;
+/// Software bills of materials
+pub mod sbom;
+/// Code signing
+pub mod sign;
+/// Sharing dlls between packages
+pub mod store;
+/// Uploading debug symbols to symbol servers
+pub mod symbols;
+/// Trust policies for downloaded content
+pub mod trust;
 /// Util
 pub mod util;
+/// Verifying package dirs
+pub mod verify;
+/// Version and build info
+pub mod version;
@@ -0,0 +1,75 @@
+use crate::pacman::LocalDatabase;
+use crate::report::OwningPackage;
+use anyhow::Context;
+use camino::Utf8Path;
+use std::fmt::Write;
+
+/// The name of the license attribution document written to the top level of a package.
+pub const LICENSES_FILE_NAME: &str = "THIRD-PARTY-LICENSES.txt";
+
+/// The line that separates packages in the document.
+const SEPARATOR: &str =
+    "================================================================================";
+
+/// Generate a document with the licenses of the given MSYS2 packages.
+///
+/// For each package, this includes its declared licenses
+/// and the contents of every file it installed under a `share/licenses` dir,
+/// like `/ucrt64/share/licenses/gtk4/COPYING` or `/usr/share/licenses/bash/COPYING`.
+pub fn generate_license_attribution(
+    msys2_installation_path: &Utf8Path,
+    database: &LocalDatabase,
+    packages: &[OwningPackage],
+) -> anyhow::Result<String> {
+    let mut document = String::new();
+    writeln!(
+        document,
+        "This software includes the following third-party packages, distributed by MSYS2."
+    )?;
+
+    for package in packages.iter() {
+        let installed_package = database
+            .get(&package.name)
+            .with_context(|| format!("`{}` is missing from the pacman database", package.name))?;
+
+        writeln!(document)?;
+        writeln!(document, "{SEPARATOR}")?;
+        writeln!(document, "{} {}", package.name, package.version)?;
+        if !installed_package.licenses.is_empty() {
+            writeln!(
+                document,
+                "License: {}",
+                installed_package.licenses.join(", ")
+            )?;
+        }
+        writeln!(document, "{SEPARATOR}")?;
+
+        let license_files: Vec<_> = installed_package
+            .load_files()
+            .with_context(|| format!("failed to load files for `{}`", package.name))?
+            .into_iter()
+            .filter(|file| file.as_str().contains("share/licenses/"))
+            .collect();
+
+        if license_files.is_empty() {
+            writeln!(document)?;
+            writeln!(document, "This package does not include license files.")?;
+        }
+
+        for file in license_files.iter() {
+            let path = msys2_installation_path.join(file);
+            let contents =
+                std::fs::read(&path).with_context(|| format!("failed to read `{path}`"))?;
+
+            writeln!(document)?;
+            writeln!(document, "--- {file} ---")?;
+            writeln!(document)?;
+            document.push_str(&String::from_utf8_lossy(&contents));
+            if !document.ends_with('\n') {
+                document.push('\n');
+            }
+        }
+    }
+
+    Ok(document)
+}
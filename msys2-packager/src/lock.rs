@@ -0,0 +1,162 @@
+use crate::packager::FileFlags;
+use crate::packager::Plan;
+use crate::report::OwnershipReport;
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The current version of the lockfile format.
+///
+/// Lockfiles with a different version are rejected.
+pub(crate) const LOCK_VERSION: u32 = 1;
+
+/// A binary from an MSYS2 package, recorded in a [`Lockfile`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LockedFile {
+    /// The file destination, relative to the package top level.
+    pub dest: PathBuf,
+
+    /// The name of the MSYS2 package that installed the source file.
+    pub package: String,
+
+    /// The installed package version.
+    pub version: String,
+
+    /// The lowercase hex SHA-256 of the source file.
+    pub sha256: String,
+}
+
+/// A record of the MSYS2 binaries that were packaged, to make later packaging runs reproducible.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Lockfile {
+    /// The lockfile format version
+    pub version: u32,
+
+    /// Packaged binaries from MSYS2 packages, sorted by destination.
+    pub files: Vec<LockedFile>,
+}
+
+impl Lockfile {
+    /// Make a lockfile for a plan, using a report of which package each file came from.
+    ///
+    /// Only exes and dlls that came from an MSYS2 package are recorded.
+    /// Source files are hashed, so this reflects the MSYS2 installation and not any upx or signing.
+    pub fn new(plan: &Plan, ownership: &OwnershipReport) -> anyhow::Result<Self> {
+        let versions: BTreeMap<&str, &str> = ownership
+            .packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.version.as_str()))
+            .collect();
+
+        let mut files = Vec::new();
+        for (file, owned) in plan.files.iter().zip(ownership.files.iter()) {
+            if !file.flags.intersects(FileFlags::EXE | FileFlags::LIB) {
+                continue;
+            }
+            let package = match owned.package.as_deref() {
+                Some(package) => package,
+                None => continue,
+            };
+
+            let version = versions
+                .get(package)
+                .with_context(|| format!("missing the version of `{package}`"))?;
+            let sha256 = crate::sbom::sha256_file(&file.src)
+                .with_context(|| format!("failed to hash `{}`", file.src.display()))?;
+
+            files.push(LockedFile {
+                dest: file.dest.clone(),
+                package: package.into(),
+                version: version.to_string(),
+                sha256,
+            });
+        }
+        files.sort_by(|a, b| a.dest.cmp(&b.dest));
+
+        Ok(Self {
+            version: LOCK_VERSION,
+            files,
+        })
+    }
+
+    /// Load a lockfile.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let lockfile: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        ensure!(
+            lockfile.version == LOCK_VERSION,
+            "`{}` has unsupported version {}, expected {LOCK_VERSION}",
+            path.display(),
+            lockfile.version
+        );
+
+        Ok(lockfile)
+    }
+
+    /// Save this lockfile.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut data =
+            serde_json::to_string_pretty(self).context("failed to serialize lockfile")?;
+        data.push('\n');
+        std::fs::write(path, data).with_context(|| format!("failed to write `{}`", path.display()))
+    }
+
+    /// Get the differences between this lockfile and another one, made from the current MSYS2 installation.
+    ///
+    /// # Returns
+    /// Returns a human-readable description of each difference, which is empty if the lockfiles match.
+    pub fn diff(&self, actual: &Self) -> Vec<String> {
+        let locked: BTreeMap<&Path, &LockedFile> = self
+            .files
+            .iter()
+            .map(|file| (file.dest.as_path(), file))
+            .collect();
+        let actual: BTreeMap<&Path, &LockedFile> = actual
+            .files
+            .iter()
+            .map(|file| (file.dest.as_path(), file))
+            .collect();
+
+        let mut differences = Vec::new();
+        for (dest, locked_file) in locked.iter() {
+            match actual.get(dest) {
+                None => {
+                    differences.push(format!("`{}` would no longer be packaged", dest.display()))
+                }
+                Some(actual_file) if actual_file != locked_file => {
+                    differences.push(format!(
+                        "`{}` would change from {} {} ({}) to {} {} ({})",
+                        dest.display(),
+                        locked_file.package,
+                        locked_file.version,
+                        locked_file.sha256,
+                        actual_file.package,
+                        actual_file.version,
+                        actual_file.sha256
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        for (dest, actual_file) in actual.iter() {
+            if !locked.contains_key(dest) {
+                differences.push(format!(
+                    "`{}` would be newly packaged from {} {}",
+                    dest.display(),
+                    actual_file.package,
+                    actual_file.version
+                ));
+            }
+        }
+
+        differences
+    }
+}
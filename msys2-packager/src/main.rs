@@ -1,111 +1,572 @@
-use anyhow::bail;
+mod commands;
+
+/// The env var that sets the profile dir, in place of `--profile-dir`.
+const PROFILE_DIR_ENV: &str = "MSYS2_PACKAGER_PROFILE_DIR";
+
 use anyhow::ensure;
 use anyhow::Context;
 use camino::Utf8PathBuf;
-use msys2_packager::packager::FileFlags;
+use msys2::Msys2Environment;
+use msys2_packager::file_option::FileOption;
+use msys2_packager::knowledge::KnowledgeBase;
+use msys2_packager::lock::Lockfile;
+use msys2_packager::packager::CrtCheck;
+use msys2_packager::packager::OverwritePolicy;
 use msys2_packager::packager::Packager;
+use msys2_packager::packager::UnresolvedError;
+use msys2_packager::report::OutputFormat;
+use msys2_packager::report::PackageReport;
+use msys2_packager::resolver::ResolveOptions;
+use msys2_packager::sign::get_sign_options;
+use msys2_packager::store::DllStore;
+use msys2_packager::util::get_reproducible_timestamp;
+use msys2_packager::util::get_upx_options;
 use msys2_packager::util::locate_msys2_installation;
+use msys2_packager::util::UpxLevel;
+use msys2_packager::verify::verify_package_with_options;
+use msys2_packager::version::VersionInfo;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::path::PathBuf;
 
-#[derive(Debug)]
-struct FileOption {
-    src: Option<Utf8PathBuf>,
-    dest: Utf8PathBuf,
-    flags: FileFlags,
-}
-
-impl std::str::FromStr for FileOption {
-    type Err = anyhow::Error;
-
-    fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut src = None;
-        let mut dest = None;
-        let mut flags = FileFlags::empty();
-
-        for part in input.split('|') {
-            let (key, value) = part
-                .split_once('=')
-                .with_context(|| format!("missing key/value pair in `{}`", part))?;
-            let key = key.trim();
-            let value = value.trim();
-
-            match key {
-                "src" => {
-                    ensure!(src.is_none(), "two src elements detected");
-                    src = Some(value);
-                }
-                "dest" => {
-                    ensure!(dest.is_none(), "two dest elements detected");
-                    dest = Some(value);
-                }
-                "flags" => {
-                    ensure!(flags.is_empty(), "two flags elements detected");
-                    for flag in value.split(',') {
-                        match flag {
-                            "exe" => {
-                                flags |= FileFlags::EXE;
-                            }
-                            "upx" => {
-                                flags |= FileFlags::UPX;
-                            }
-                            "lib" => {
-                                flags |= FileFlags::LIB;
-                            }
-                            "add_deps" => {
-                                flags |= FileFlags::ADD_DEPS;
-                            }
-                            flag => {
-                                bail!("unknown flag `{flag}`");
-                            }
-                        }
-                    }
-                }
-                key => {
-                    bail!("unknown key `{key}`");
-                }
-            }
-        }
-
-        let src = src.map(|v| v.into());
-        let dest = dest.context("missing dest").map(|v| v.into())?;
-
-        Ok(Self { src, dest, flags })
-    }
-}
-
 #[derive(Debug, argh::FromArgs)]
 #[argh(description = "a tool to bundle MSYS2 executables")]
 struct Options {
+    #[argh(subcommand)]
+    subcommand: Option<Subcommand>,
+
     #[argh(option, long = "file", description = "files to add to the package")]
     files: Vec<FileOption>,
 
+    #[argh(
+        option,
+        description = "a file listing files to add to the package, one `--file` value per line, or `-` for stdin"
+    )]
+    files_from: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "a JSON manifest of files to add to the package, like the plan printed by `--dry-run --output-format json`, which fails on files that conflict with added ones. May be passed multiple times"
+    )]
+    from_manifest: Vec<PathBuf>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names, like `libicudata*.dll`, to never add automatically, even if packaged binaries import them"
+    )]
+    exclude: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to treat as system dlls provided by the OS, which are never packaged. Also applies to `list-deps` and `which`"
+    )]
+    system_dll: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of dll names to package from MSYS2 when imported, even if they are considered system dlls. Also applies to `list-deps` and `which`"
+    )]
+    bundle_dll: Vec<String>,
+
     #[argh(switch, description = "whether to upx the binary")]
     upx: bool,
 
-    #[argh(option, long = "out", short = 'o', description = "the output dir")]
-    out: PathBuf,
+    #[argh(option, description = "the path to the upx binary")]
+    upx_path: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "the upx compression level, `1` to `9`, `best`, or `ultra-brute`"
+    )]
+    upx_level: Option<UpxLevel>,
+
+    #[argh(switch, description = "do not pass `--lzma` to upx")]
+    no_lzma: bool,
+
+    #[argh(
+        option,
+        description = "a pattern of file names to never upx, like `libgio-*.dll`. May be passed multiple times"
+    )]
+    no_upx: Vec<String>,
+
+    #[argh(
+        switch,
+        description = "package files that upx fails on uncompressed, with a warning, instead of failing"
+    )]
+    upx_allow_failures: bool,
+
+    #[argh(
+        switch,
+        description = "strip symbols from files with the `strip` flag, before upx"
+    )]
+    strip: bool,
+
+    #[argh(
+        option,
+        description = "keep the debug info of stripped files as `.debug` files in this dir, outside the output dir. Requires `--strip`"
+    )]
+    debug_symbols_dir: Option<PathBuf>,
+
+    #[argh(
+        option,
+        short = 'j',
+        default = "1",
+        description = "the number of threads to use when copying and upx-ing files"
+    )]
+    jobs: usize,
+
+    #[argh(
+        switch,
+        description = "print the files that would be packaged, without packaging them"
+    )]
+    dry_run: bool,
+
+    #[argh(
+        switch,
+        description = "report which MSYS2 package each packaged file came from"
+    )]
+    report: bool,
+
+    #[argh(
+        switch,
+        description = "report the size of each packaged file and category of files, before and after upx"
+    )]
+    size_report: bool,
+
+    #[argh(
+        option,
+        description = "write the dll dependency graph to this file, as JSON if it ends in `.json` and as Graphviz DOT otherwise"
+    )]
+    emit_graph: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "a lockfile recording the MSYS2 package, version, and SHA-256 of each packaged exe and dll, written after packaging"
+    )]
+    lock_file: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "fail instead of updating `--lock-file` if the packaged MSYS2 binaries would differ from it"
+    )]
+    locked: bool,
+
+    #[argh(
+        switch,
+        description = "write the licenses of the MSYS2 packages that packaged files came from to THIRD-PARTY-LICENSES.txt"
+    )]
+    licenses: bool,
+
+    #[argh(
+        option,
+        description = "sign packaged exes and dlls with signtool using this .pfx certificate. The password is read from the SIGN_CERT_PASSWORD env var, or the file at the SIGN_CERT_PASSWORD_FILE env var, if set"
+    )]
+    sign_cert: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "the RFC 3161 timestamp server to use when signing"
+    )]
+    sign_timestamp_url: Option<String>,
+
+    #[argh(
+        option,
+        default = "CrtCheck::Warn",
+        description = "what to do when a packaged binary links against a different C runtime than the MSYS2 environment, `off`, `warn`, or `error`"
+    )]
+    crt_check: CrtCheck,
+
+    #[argh(
+        option,
+        default = "OverwritePolicy::Skip",
+        description = "what to do when a file already exists in the output dir, `error`, `skip`, `overwrite`, or `overwrite-if-different`. Files that are stripped, upx-ed, or signed never equal their source, so `overwrite-if-different` always replaces them"
+    )]
+    overwrite: OverwritePolicy,
+
+    #[argh(
+        switch,
+        description = "verify each copied file, and write a SHA256SUMS file to the output dir"
+    )]
+    verify_copies: bool,
+
+    #[argh(
+        option,
+        description = "hard-link dlls that are packaged unchanged from a content-addressed store in this dir, which can be shared between packages"
+    )]
+    dll_store: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "set the timestamps in the headers of packaged exes and dlls to `SOURCE_DATE_EPOCH`, or 0, for reproducible packages"
+    )]
+    deterministic: bool,
+
+    #[argh(
+        switch,
+        description = "do not always package the support dlls of the toolchain, like `libgcc_s_seh-1.dll`"
+    )]
+    no_toolchain_runtime: bool,
+
+    #[argh(
+        option,
+        description = "also package for this MSYS2 environment, like `mingw32` for a 32-bit plugin host. Each environment is packaged into a dir named after its arch, like `x64` or `x86`, in the output dir. May be passed multiple times"
+    )]
+    side_by_side: Vec<Msys2Environment>,
+
+    #[argh(
+        option,
+        default = "OutputFormat::Text",
+        description = "the format of the results printed to stdout, `text` or `json`"
+    )]
+    output_format: OutputFormat,
+
+    #[argh(
+        option,
+        description = "the dir for per-user data that may be shared between runs, like the knowledge base written by `knowledge update`. This can also be set with the MSYS2_PACKAGER_PROFILE_DIR env var"
+    )]
+    profile_dir: Option<PathBuf>,
+
+    #[argh(
+        option,
+        long = "out",
+        short = 'o',
+        description = "the output dir, required when packaging"
+    )]
+    out: Option<PathBuf>,
+
+    #[argh(switch, description = "print the version")]
+    version: bool,
+
+    #[argh(
+        switch,
+        description = "with `--version`, print build info like the features and crate versions"
+    )]
+    verbose: bool,
+}
+
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand)]
+enum Subcommand {
+    Extract(crate::commands::extract::Options),
+    Knowledge(msys2_packager::cli::knowledge::Options),
+    ListDeps(crate::commands::list_deps::Options),
+    Which(crate::commands::which::Options),
+}
+
+/// Get the system dll rules of the command line, to check packages with the rules they were made with.
+fn get_resolve_options(options: &Options) -> ResolveOptions {
+    let mut resolve_options = ResolveOptions::new();
+    for pattern in options.system_dll.iter() {
+        resolve_options.system(pattern.clone());
+    }
+    for pattern in options.bundle_dll.iter() {
+        resolve_options.bundle(pattern.clone());
+    }
+    resolve_options
+}
+
+/// Get the MSYS2 environment to use, falling back to the `MSYSTEM` env var.
+fn get_msys2_environment(
+    environment: Option<Msys2Environment>,
+) -> anyhow::Result<Msys2Environment> {
+    match environment {
+        Some(environment) => Ok(environment),
+        None => std::env::var("MSYSTEM")
+            .context("missing `MSYSTEM` env")?
+            .parse()
+            .context("invalid MSYSTEM var"),
+    }
+}
+
+/// Read a list of files for `--files-from`, where `-` is stdin.
+fn read_file_list(path: &Path) -> anyhow::Result<Vec<FileOption>> {
+    let input = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("failed to read stdin")?
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?
+    };
+
+    FileOption::parse_list(&input).with_context(|| format!("failed to parse `{}`", path.display()))
+}
+
+/// Get the version and build info of this binary.
+fn get_version_info() -> VersionInfo {
+    let mut info = VersionInfo::new(
+        env!("CARGO_BIN_NAME").into(),
+        env!("CARGO_PKG_VERSION").into(),
+    );
+    if cfg!(feature = "process") {
+        info.feature("process".into());
+    }
+    if cfg!(feature = "cli") {
+        info.feature("cli".into());
+    }
+    info
 }
 
 fn main() -> anyhow::Result<()> {
     let options: Options = argh::from_env();
 
+    if options.version {
+        get_version_info().print(options.output_format, options.verbose)?;
+        return Ok(());
+    }
+
+    let profile_dir = options
+        .profile_dir
+        .clone()
+        .or_else(|| std::env::var_os(PROFILE_DIR_ENV).map(PathBuf::from))
+        .filter(|profile_dir| !profile_dir.as_os_str().is_empty());
+    if let Some(profile_dir) = profile_dir.as_deref() {
+        KnowledgeBase::init_global(profile_dir);
+    }
+
+    let resolve_options = get_resolve_options(&options);
+    if let Some(subcommand) = options.subcommand {
+        match subcommand {
+            Subcommand::Extract(options) => {
+                crate::commands::extract::exec(options)?;
+            }
+            Subcommand::Knowledge(options) => {
+                let msys2_installation_path = locate_msys2_installation().ok();
+                msys2_packager::cli::knowledge::exec(
+                    options,
+                    profile_dir.as_deref(),
+                    msys2_installation_path
+                        .as_ref()
+                        .map(|path| path.as_std_path()),
+                )?;
+            }
+            Subcommand::ListDeps(options) => {
+                crate::commands::list_deps::exec(options, &resolve_options)?;
+            }
+            Subcommand::Which(options) => {
+                crate::commands::which::exec(options, &resolve_options)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let out = options.out.clone().context("missing `--out`")?;
     let msys2_installation_location = locate_msys2_installation()?;
-    let msys2_environment = std::env::var("MSYSTEM")
-        .context("missing `MSYSTEM` env")?
-        .parse()
-        .context("invalid MSYSTEM var")?;
-
-    let mut packager = Packager::new(msys2_installation_location, msys2_environment, options.out);
-    packager.upx(options.upx);
-    for file_option in options.files {
+    let msys2_environment = get_msys2_environment(None)?;
+    let mut files = options.files.clone();
+    if let Some(path) = options.files_from.as_deref() {
+        files.extend(read_file_list(path)?);
+    }
+
+    if options.side_by_side.is_empty() {
+        let report = package_tree(
+            &options,
+            &files,
+            msys2_installation_location,
+            msys2_environment,
+            out,
+            options.debug_symbols_dir.clone(),
+        );
+        let report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                // Tools reading the JSON report still need to know which dependencies are missing.
+                if let (OutputFormat::Json, Some(error)) = (
+                    options.output_format,
+                    error.downcast_ref::<UnresolvedError>(),
+                ) {
+                    error.report().print(OutputFormat::Json)?;
+                }
+                return Err(error);
+            }
+        };
+        report.print(options.output_format)?;
+        return Ok(());
+    }
+
+    ensure!(
+        options.lock_file.is_none() && options.emit_graph.is_none(),
+        "`--lock-file` and `--emit-graph` are not supported with `--side-by-side`"
+    );
+    let mut environments = vec![msys2_environment];
+    environments.extend(options.side_by_side.iter().copied());
+    let mut reports = BTreeMap::new();
+    for environment in environments {
+        let arch = environment.get_arch();
+        let tree = arch.get_windows_name();
+        ensure!(
+            !reports.contains_key(tree),
+            "more than one environment has the `{tree}` arch"
+        );
+
+        let tree_out = out.join(tree);
+        let report = package_tree(
+            &options,
+            &files,
+            msys2_installation_location.clone(),
+            environment,
+            tree_out.clone(),
+            options
+                .debug_symbols_dir
+                .as_ref()
+                .map(|debug_symbols_dir| debug_symbols_dir.join(tree)),
+        );
+        let report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                // Output the trees packaged so far, and the one with missing dependencies.
+                if let (OutputFormat::Json, Some(unresolved)) = (
+                    options.output_format,
+                    error.downcast_ref::<UnresolvedError>(),
+                ) {
+                    reports.insert(tree, unresolved.report().clone());
+                    let json = serde_json::to_string_pretty(&reports)?;
+                    println!("{json}");
+                }
+                return Err(error.context(format!("failed to package the `{tree}` tree")));
+            }
+        };
+
+        // Each tree must load on its own, as Windows only looks for dlls next to the exe.
+        if !options.dry_run {
+            let verify_report =
+                verify_package_with_options(&tree_out, Some(arch), &resolve_options)
+                    .with_context(|| format!("failed to verify the `{tree}` tree"))?;
+            ensure!(
+                verify_report.is_ok(),
+                "the `{tree}` tree does not load on its own:\n{verify_report}"
+            );
+        }
+
+        reports.insert(tree, report);
+    }
+
+    match options.output_format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&reports)?;
+            println!("{json}");
+        }
+        format => {
+            for (tree, report) in reports.iter() {
+                println!("{tree}:");
+                report.print(format)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Package the files for one MSYS2 environment into an output dir.
+fn package_tree(
+    options: &Options,
+    files: &[FileOption],
+    msys2_installation_location: Utf8PathBuf,
+    msys2_environment: Msys2Environment,
+    out: PathBuf,
+    debug_symbols_dir: Option<PathBuf>,
+) -> anyhow::Result<PackageReport> {
+    let mut packager = Packager::new(msys2_installation_location, msys2_environment, out);
+    packager
+        .upx(options.upx)
+        .upx_options(get_upx_options(
+            options.upx_path.clone(),
+            options.upx_level,
+            options.no_lzma,
+            options.upx_allow_failures,
+        ))
+        .strip(options.strip)
+        .debug_symbols_dir(debug_symbols_dir)
+        .jobs(options.jobs)
+        .licenses(options.licenses)
+        .crt_check(options.crt_check)
+        .overwrite(options.overwrite)
+        .verify_copies(options.verify_copies)
+        .dll_store(options.dll_store.clone().map(DllStore::new))
+        .normalize_pe_timestamps(
+            options
+                .deterministic
+                .then(get_reproducible_timestamp)
+                .transpose()?,
+        )
+        .toolchain_runtime(!options.no_toolchain_runtime)
+        .sign(get_sign_options(
+            options.sign_cert.clone(),
+            options.sign_timestamp_url.clone(),
+        )?);
+    if options.locked {
+        let path = options
+            .lock_file
+            .as_deref()
+            .context("`--locked` requires `--lock-file`")?;
+        packager.locked(Some(Lockfile::load(path)?));
+    }
+    for pattern in options.exclude.iter() {
+        packager.exclude(pattern.clone());
+    }
+    for pattern in options.no_upx.iter() {
+        packager.no_upx(pattern.clone());
+    }
+    for pattern in options.system_dll.iter() {
+        packager.system_dll(pattern.clone());
+    }
+    for pattern in options.bundle_dll.iter() {
+        packager.bundle_dll(pattern.clone());
+    }
+    for file_option in files {
+        let file_option = match file_option.resolve(msys2_environment) {
+            Some(file_option) => file_option,
+            None => continue,
+        };
         packager.add_file(
             file_option.src.map(|src| src.into()),
             file_option.dest.into(),
             file_option.flags,
         );
     }
+    for path in options.from_manifest.iter() {
+        packager.extend_from_manifest(path)?;
+    }
 
-    packager.package().context("failed to package")?;
+    let mut report = if options.dry_run {
+        packager.dry_run().context("failed to plan package")?
+    } else {
+        packager.package().context("failed to package")?
+    };
+    if options.report && report.ownership.is_none() {
+        report.ownership = Some(
+            packager
+                .package_report_for_plan(&report.plan)
+                .context("failed to report package ownership")?,
+        );
+    }
+    if options.size_report {
+        report.sizes = Some(
+            packager
+                .size_report_for_plan(&report.plan)
+                .context("failed to report package sizes")?,
+        );
+    }
+    if let Some(path) = options.lock_file.as_deref() {
+        if !options.locked && !options.dry_run {
+            packager
+                .lockfile_for_plan(&report.plan)?
+                .save(path)
+                .context("failed to save lockfile")?;
+        }
+    }
+    if let Some(path) = options.emit_graph.as_deref() {
+        write_dependency_graph(&packager, path)?;
+    }
 
-    Ok(())
+    Ok(report)
+}
+
+/// Write the dependency graph of a packager that has planned its files.
+fn write_dependency_graph(packager: &Packager, path: &Path) -> anyhow::Result<()> {
+    let graph = packager
+        .dependency_graph()
+        .context("no dependency graph was recorded")?;
+    graph
+        .write(path)
+        .context("failed to write the dependency graph")
 }
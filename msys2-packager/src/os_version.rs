@@ -0,0 +1,339 @@
+use crate::knowledge::KnowledgeBase;
+use crate::report::OutputFormat;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+/// Names of Windows releases, by the version they shipped with.
+const RELEASE_NAMES: &[(WindowsVersion, &str)] = &[
+    (WindowsVersion::new(6, 1, 7601), "Windows 7 SP1"),
+    (WindowsVersion::new(6, 2, 9200), "Windows 8"),
+    (WindowsVersion::new(6, 3, 9600), "Windows 8.1"),
+    (
+        WindowsVersion::new(10, 0, 10240),
+        "Windows 10 1507, LTSB 2015",
+    ),
+    (
+        WindowsVersion::new(10, 0, 14393),
+        "Windows 10 1607, LTSB 2016",
+    ),
+    (WindowsVersion::new(10, 0, 15063), "Windows 10 1703"),
+    (WindowsVersion::new(10, 0, 17134), "Windows 10 1803"),
+    (
+        WindowsVersion::new(10, 0, 17763),
+        "Windows 10 1809, LTSC 2019",
+    ),
+    (WindowsVersion::new(10, 0, 19041), "Windows 10 2004"),
+    (
+        WindowsVersion::new(10, 0, 19044),
+        "Windows 10 21H2, LTSC 2021",
+    ),
+    (WindowsVersion::new(10, 0, 22000), "Windows 11 21H2"),
+    (
+        WindowsVersion::new(10, 0, 26100),
+        "Windows 11 24H2, LTSC 2024",
+    ),
+];
+
+/// An error that may occur while parsing a [`WindowsVersion`] from a string.
+#[derive(Debug)]
+pub struct WindowsVersionFromStrError(String);
+
+impl std::fmt::Display for WindowsVersionFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is not a valid Windows version, like `10.0.17763`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for WindowsVersionFromStrError {}
+
+/// A Windows version, like `10.0.17763`.
+///
+/// Versions compare by major version, then minor version, then build number.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WindowsVersion {
+    /// The major version
+    pub major: u16,
+
+    /// The minor version
+    pub minor: u16,
+
+    /// The build number, which is 0 if unknown
+    pub build: u32,
+}
+
+impl WindowsVersion {
+    /// Make a new [`WindowsVersion`].
+    pub const fn new(major: u16, minor: u16, build: u32) -> Self {
+        Self {
+            major,
+            minor,
+            build,
+        }
+    }
+
+    /// Get the name of the Windows release with this version, like `Windows 10 1809, LTSC 2019`.
+    pub fn get_release_name(self) -> Option<&'static str> {
+        RELEASE_NAMES
+            .iter()
+            .find(|(version, _)| *version == self)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl std::str::FromStr for WindowsVersion {
+    type Err = WindowsVersionFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || WindowsVersionFromStrError(input.into());
+
+        let mut iter = input.split('.');
+        let major = iter.next().and_then(|major| major.parse().ok());
+        let minor = iter.next().and_then(|minor| minor.parse().ok());
+        let build = match iter.next() {
+            Some(build) => build.parse().ok(),
+            None => Some(0),
+        };
+        match (major, minor, build, iter.next()) {
+            (Some(major), Some(minor), Some(build), None) => Ok(Self::new(major, minor, build)),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl std::fmt::Display for WindowsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.build != 0 {
+            write!(f, ".{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for WindowsVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowsVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let input = String::deserialize(deserializer)?;
+        input.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// An imported function that needs a newer Windows version
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct VersionedImport {
+    /// The name of the imported dll
+    pub dll: String,
+
+    /// The name of the imported function
+    pub function: String,
+
+    /// The first Windows version with the function
+    pub version: WindowsVersion,
+}
+
+/// The Windows versions that a binary needs
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct BinaryOsVersion {
+    /// The binary, relative to the package top level
+    pub path: PathBuf,
+
+    /// The subsystem version in the PE header, which Windows refuses to load the binary below
+    pub declared: WindowsVersion,
+
+    /// The newest Windows version needed by an import, if any import is known to need one
+    pub required: Option<WindowsVersion>,
+
+    /// The imports that are known to need a Windows version, newest first
+    pub imports: Vec<VersionedImport>,
+}
+
+/// The estimated minimum Windows version of every binary in a package dir
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct OsVersionReport {
+    /// The minimum Windows version of the whole package, as the newest of the declared and required versions of its binaries
+    pub minimum: Option<WindowsVersion>,
+
+    /// The binaries, sorted by path
+    pub binaries: Vec<BinaryOsVersion>,
+}
+
+impl OsVersionReport {
+    /// Get the binaries that need a Windows version newer than `version`.
+    pub fn get_binaries_newer_than(
+        &self,
+        version: WindowsVersion,
+    ) -> impl Iterator<Item = &BinaryOsVersion> {
+        self.binaries.iter().filter(move |binary| {
+            binary.declared > version || binary.required.is_some_and(|required| required > version)
+        })
+    }
+
+    /// Print this report to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for OsVersionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for binary in self.binaries.iter() {
+            write!(
+                f,
+                "{}: declares {}",
+                binary.path.display(),
+                DisplayVersion(binary.declared)
+            )?;
+            if let Some(required) = binary.required {
+                write!(f, ", requires {}", DisplayVersion(required))?;
+            }
+            writeln!(f)?;
+            for import in binary.imports.iter() {
+                writeln!(
+                    f,
+                    "    {}!{}: {}",
+                    import.dll,
+                    import.function,
+                    DisplayVersion(import.version)
+                )?;
+            }
+        }
+
+        match self.minimum {
+            Some(minimum) => write!(f, "Minimum Windows version: {}", DisplayVersion(minimum)),
+            None => write!(f, "No binaries"),
+        }
+    }
+}
+
+/// Display a version with its release name, if it has one.
+struct DisplayVersion(WindowsVersion);
+
+impl std::fmt::Display for DisplayVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.get_release_name() {
+            Some(name) => write!(f, "{} ({name})", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// Estimate the minimum Windows version of every exe and dll in a package dir, from the functions they import.
+///
+/// Import versions come from the `api-versions` of the knowledge base,
+/// which only lists functions added after Windows 7, so this is a lower bound.
+/// Delay-loaded imports and functions loaded with `GetProcAddress` are not seen,
+/// as apps are expected to check for them at runtime.
+pub fn analyze_package_os_version(
+    package_dir: &Path,
+    knowledge_base: &KnowledgeBase,
+) -> anyhow::Result<OsVersionReport> {
+    let mut binaries = Vec::new();
+    for entry in WalkDir::new(package_dir).sort_by_file_name() {
+        let entry = entry.context("failed to read package dir entry")?;
+        let is_binary = entry.path().extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("exe") || extension.eq_ignore_ascii_case("dll")
+        });
+        if !entry.file_type().is_file() || !is_binary {
+            continue;
+        }
+
+        let relative_path = entry
+            .path()
+            .strip_prefix(package_dir)
+            .context("package file is not in the package dir")?
+            .to_path_buf();
+        let bytes = std::fs::read(entry.path())
+            .with_context(|| format!("failed to read `{}`", relative_path.display()))?;
+        let binary = analyze_os_version_from_bytes(relative_path.clone(), &bytes, knowledge_base)
+            .with_context(|| format!("failed to analyze `{}`", relative_path.display()))?;
+        binaries.push(binary);
+    }
+
+    let minimum = binaries
+        .iter()
+        .flat_map(|binary| std::iter::once(binary.declared).chain(binary.required))
+        .max();
+
+    Ok(OsVersionReport { minimum, binaries })
+}
+
+/// Estimate the minimum Windows version of an exe or dll, already loaded into memory.
+///
+/// `path` is only used to label the result.
+pub fn analyze_os_version_from_bytes(
+    path: PathBuf,
+    bytes: &[u8],
+    knowledge_base: &KnowledgeBase,
+) -> anyhow::Result<BinaryOsVersion> {
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
+    let windows_fields = pe
+        .header
+        .optional_header
+        .context("pe file has no optional header")?
+        .windows_fields;
+    let declared = WindowsVersion::new(
+        windows_fields.major_subsystem_version,
+        windows_fields.minor_subsystem_version,
+        0,
+    );
+
+    let mut imports: Vec<_> = pe
+        .imports
+        .iter()
+        .filter_map(|import| {
+            let version = knowledge_base.get_api_version(import.dll, &import.name)?;
+            Some(VersionedImport {
+                dll: import.dll.to_string(),
+                function: import.name.to_string(),
+                version,
+            })
+        })
+        .collect();
+    imports.sort_by(|a, b| {
+        b.version
+            .cmp(&a.version)
+            .then_with(|| a.dll.cmp(&b.dll))
+            .then_with(|| a.function.cmp(&b.function))
+    });
+
+    Ok(BinaryOsVersion {
+        path,
+        declared,
+        required: imports.first().map(|import| import.version),
+        imports,
+    })
+}
@@ -1,20 +1,82 @@
-use crate::util::get_dll_imports;
-use crate::util::is_api_set_dll;
-use crate::util::is_system_dll;
+use crate::cache::Cache;
+use crate::cache::CacheEntry;
+use crate::licenses::generate_license_attribution;
+use crate::licenses::LICENSES_FILE_NAME;
+use crate::lock::Lockfile;
+use crate::pacman::LocalDatabase;
+use crate::plugin::PackagerPlugin;
+use crate::report::FileExplanation;
+use crate::report::OwnedFile;
+use crate::report::OwnershipReport;
+use crate::report::OwningPackage;
+use crate::report::PackageReport;
+use crate::report::SizeReport;
+use crate::report::SizedFile;
+use crate::report::Timings;
+use crate::resolver::resolve_dependencies_with_options;
+use crate::resolver::DependencyGraph;
+use crate::resolver::Import;
+use crate::resolver::Msys2DependencySource;
+use crate::resolver::ResolveOptions;
+#[cfg(feature = "process")]
+use crate::sign::signtool_sign;
+use crate::sign::SignOptions;
+use crate::store::DllStore;
+use crate::util::get_toolchain_runtime_dlls;
+#[cfg(feature = "process")]
+use crate::util::locate_objcopy;
+#[cfg(feature = "process")]
+use crate::util::locate_strip;
+use crate::util::lookup_msys2_file;
+use crate::util::matches_dll_pattern;
+use crate::util::normalize_pe_timestamps;
+#[cfg(feature = "process")]
+use crate::util::objcopy_add_debuglink;
+#[cfg(feature = "process")]
+use crate::util::objcopy_only_keep_debug;
+#[cfg(feature = "process")]
+use crate::util::strip;
+#[cfg(feature = "process")]
 use crate::util::upx;
+#[cfg(feature = "process")]
+use crate::util::upx_test;
+use crate::util::CRuntime;
+use crate::util::PeNormalization;
+use crate::util::UnwindRuntime;
+use crate::util::UpxOptions;
 use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context;
 use camino::Utf8PathBuf;
 use msys2::Msys2Environment;
-use std::collections::HashSet;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::ffi::OsString;
+use std::io::BufRead;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Instant;
+use walkdir::WalkDir;
+
+pub use crate::cache::CACHE_FILE_NAME;
+
+/// The name of the file with the SHA-256 of each packaged file, written to the out dir when copies are verified.
+pub const CHECKSUMS_FILE_NAME: &str = "SHA256SUMS";
 
 bitflags::bitflags! {
     /// File data
+    ///
+    /// New flags may be added in the future.
     pub struct FileFlags: u32 {
         /// This file is a dynamic library.
         const LIB = 1 << 0;
@@ -27,17 +89,250 @@ bitflags::bitflags! {
 
         /// Whether to locate and add the binary dependencies of this file automatically.
         const ADD_DEPS = 1 << 3;
+
+        /// This can be stripped of symbols.
+        const STRIP = 1 << 4;
+    }
+}
+
+impl FileFlags {
+    /// Get the names of the set flags, as used by the `msys2-packager` CLI.
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::LIB, "lib"),
+            (Self::UPX, "upx"),
+            (Self::EXE, "exe"),
+            (Self::ADD_DEPS, "add_deps"),
+            (Self::STRIP, "strip"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .map(|(_, name)| name)
+        .collect()
+    }
+
+    /// Get a flag from its name, as used by the `msys2-packager` CLI, see [`FileFlags::names`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lib" => Some(Self::LIB),
+            "upx" => Some(Self::UPX),
+            "exe" => Some(Self::EXE),
+            "add_deps" => Some(Self::ADD_DEPS),
+            "strip" => Some(Self::STRIP),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for FileFlags {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.names().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileFlags {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        let mut flags = Self::empty();
+        for name in names.iter() {
+            flags |= Self::from_name(name)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown flag `{name}`")))?;
+        }
+        Ok(flags)
+    }
+}
+
+/// An error that may occur while parsing a [`FileReason`] from a string.
+#[derive(Debug)]
+pub struct FileReasonFromStrError(String);
+
+impl std::fmt::Display for FileReasonFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is not a valid file reason, pass `explicit`, `profile:<name>`, `dep-of:<file>`, or `theme:<name>`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for FileReasonFromStrError {}
+
+/// Why a file is in a package.
+///
+/// This is written as `explicit`, `profile:<name>`, `dep-of:<file>`, or `theme:<name>`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum FileReason {
+    /// The file was added by the user, like an app exe or an asset
+    #[default]
+    Explicit,
+
+    /// The file is part of a named set of support files, like `gio-modules`
+    Profile(String),
+
+    /// The file is a dll imported by another packaged file, which is its destination
+    DepOf(PathBuf),
+
+    /// The file is part of a named theme
+    Theme(String),
+}
+
+impl std::str::FromStr for FileReason {
+    type Err = FileReasonFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if input == "explicit" {
+            return Ok(Self::Explicit);
+        }
+
+        let (kind, value) = input
+            .split_once(':')
+            .filter(|(_, value)| !value.is_empty())
+            .ok_or_else(|| FileReasonFromStrError(input.into()))?;
+        match kind {
+            "profile" => Ok(Self::Profile(value.into())),
+            "dep-of" => Ok(Self::DepOf(value.into())),
+            "theme" => Ok(Self::Theme(value.into())),
+            _ => Err(FileReasonFromStrError(input.into())),
+        }
+    }
+}
+
+impl std::fmt::Display for FileReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Explicit => write!(f, "explicit"),
+            Self::Profile(name) => write!(f, "profile:{name}"),
+            // Destinations are written with `/`, so reasons read the same on every platform.
+            Self::DepOf(dest) => write!(f, "dep-of:{}", dest.to_string_lossy().replace('\\', "/")),
+            Self::Theme(name) => write!(f, "theme:{name}"),
+        }
+    }
+}
+
+impl Serialize for FileReason {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileReason {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// An error that may occur while parsing a [`CrtCheck`] from a string.
+#[derive(Debug)]
+pub struct CrtCheckFromStrError(String);
+
+impl std::fmt::Display for CrtCheckFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid C runtime check", self.0)
+    }
+}
+
+impl std::error::Error for CrtCheckFromStrError {}
+
+/// What to do when a packaged binary uses a different C runtime than the MSYS2 environment.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum CrtCheck {
+    /// Do not check C runtimes.
+    Off,
+
+    /// Print a warning for each mismatched binary.
+    #[default]
+    Warn,
+
+    /// Fail planning.
+    Error,
+}
+
+impl std::str::FromStr for CrtCheck {
+    type Err = CrtCheckFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(CrtCheckFromStrError(input.into())),
+        }
+    }
+}
+
+/// An error that may occur while parsing an [`OverwritePolicy`] from a string.
+#[derive(Debug)]
+pub struct OverwritePolicyFromStrError(String);
+
+impl std::fmt::Display for OverwritePolicyFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid overwrite policy", self.0)
+    }
+}
+
+impl std::error::Error for OverwritePolicyFromStrError {}
+
+/// What to do when a file already exists at a destination in the out dir.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum OverwritePolicy {
+    /// Fail packaging.
+    Error,
+
+    /// Keep the existing file.
+    #[default]
+    Skip,
+
+    /// Replace the existing file.
+    Overwrite,
+
+    /// Replace the existing file if its contents differ from the source.
+    ///
+    /// The existing file is compared to the source, not to what packaging would make of it.
+    /// Files that are stripped, upx-ed, signed, or get normalized PE timestamps are changed after they are copied,
+    /// so they never compare equal to their source, and are always replaced.
+    OverwriteIfDifferent,
+}
+
+impl std::str::FromStr for OverwritePolicy {
+    type Err = OverwritePolicyFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "error" => Ok(Self::Error),
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "overwrite-if-different" => Ok(Self::OverwriteIfDifferent),
+            _ => Err(OverwritePolicyFromStrError(input.into())),
+        }
     }
 }
 
 /// A file to be added to the project.
 #[derive(Debug)]
-struct File {
+pub struct File {
     /// The file source.
     ///
     /// If this is none,
-    // flags must have either the EXE or LIB attribute.
-    // Additionally, dest must be composed of a single component.
+    /// flags must have either the EXE or LIB attribute.
+    /// Additionally, dest must be composed of a single component.
     src: Option<PathBuf>,
 
     /// The file destination.
@@ -47,6 +342,208 @@ struct File {
 
     /// Flags that specify the type of file.
     flags: FileFlags,
+
+    /// Why the file is packaged.
+    reason: FileReason,
+
+    /// The manifest the file was read from, if it was added by [`Packager::extend_from_manifest`].
+    manifest: Option<PathBuf>,
+}
+
+impl File {
+    /// Get the file source.
+    ///
+    /// This is None if the file will be looked up in the MSYS2 environment and has not been resolved yet.
+    pub fn src(&self) -> Option<&Path> {
+        self.src.as_deref()
+    }
+
+    /// Get the file destination, relative to the package top level.
+    pub fn dest(&self) -> &Path {
+        &self.dest
+    }
+
+    /// Get the file flags.
+    pub fn flags(&self) -> FileFlags {
+        self.flags
+    }
+
+    /// Get why the file is packaged.
+    pub fn reason(&self) -> &FileReason {
+        &self.reason
+    }
+}
+
+/// The files of a manifest read by [`Packager::extend_from_manifest`].
+///
+/// This is the shape of a [`Plan`] and of the `files` of a JSON [`PackageReport`],
+/// so other fields of those, like sizes, are ignored.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    /// The files to add
+    files: Vec<ManifestFile>,
+}
+
+/// A file in a [`Manifest`]
+#[derive(Debug, Deserialize)]
+struct ManifestFile {
+    /// The file source, or None to look it up in the MSYS2 environment
+    #[serde(default)]
+    src: Option<PathBuf>,
+
+    /// The file destination, relative to the package top level
+    dest: PathBuf,
+
+    /// Flags that specify the type of file
+    #[serde(default = "FileFlags::empty")]
+    flags: FileFlags,
+
+    /// Why the file is packaged, or None to use the current [`Packager::reason`]
+    #[serde(default)]
+    reason: Option<FileReason>,
+}
+
+/// A file that will be copied into the package.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PlannedFile {
+    /// The file source.
+    pub src: PathBuf,
+
+    /// The file destination, relative to the package top level.
+    pub dest: PathBuf,
+
+    /// Flags that specify the type of file.
+    pub flags: FileFlags,
+
+    /// The size of the source, in bytes.
+    pub size: u64,
+
+    /// Why the file is packaged.
+    #[serde(default)]
+    pub reason: FileReason,
+}
+
+/// The error returned by [`Packager::package`] if dependencies could not be located.
+///
+/// This keeps the report of the plan, so callers can still output which dependencies are missing.
+#[derive(Debug)]
+pub struct UnresolvedError(Box<PackageReport>);
+
+impl UnresolvedError {
+    /// Get the report of the plan, which lists the dependencies that could not be located.
+    pub fn report(&self) -> &PackageReport {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for UnresolvedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "missing {}",
+            self.0
+                .plan
+                .unresolved
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnresolvedError {}
+
+/// The planned packaging operations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Plan {
+    /// The files that will be copied into the package, in the order they will be copied.
+    pub files: Vec<PlannedFile>,
+
+    /// Names of dependencies that could not be located.
+    ///
+    /// Packaging fails if this is not empty.
+    pub unresolved: Vec<String>,
+
+    /// The total size of all files, in bytes.
+    pub total_size: u64,
+}
+
+impl Plan {
+    /// Load a plan saved with [`Plan::save`], or a JSON [`PackageReport`].
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse `{}`", path.display()))
+    }
+
+    /// Save this plan as JSON, so what was packaged and why can be looked up after the run.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut data = serde_json::to_string_pretty(self)?;
+        data.push('\n');
+        std::fs::write(path, data).with_context(|| format!("failed to write `{}`", path.display()))
+    }
+
+    /// Remove the files under a dir of the package from this plan, like ones that were bundled into another file after packaging.
+    ///
+    /// `dir` is relative to the package top level.
+    ///
+    /// # Returns
+    /// Returns the number of files that were removed.
+    pub fn remove_dir(&mut self, dir: &Path) -> usize {
+        let num_files = self.files.len();
+        self.files.retain(|file| !file.dest.starts_with(dir));
+        self.total_size = self.files.iter().map(|file| file.size).sum();
+
+        num_files - self.files.len()
+    }
+
+    /// Explain why a file is in the plan.
+    ///
+    /// This follows [`FileReason::DepOf`] from the file to the file that brought in its chain of dependencies.
+    pub fn explain(&self, dest: &Path) -> FileExplanation {
+        let mut chain: Vec<&PlannedFile> = Vec::new();
+        let mut dest = dest.to_path_buf();
+        while let Some(file) = self.files.iter().find(|file| file.dest == dest) {
+            // Dependency cycles are possible between dlls.
+            if chain.iter().any(|seen| std::ptr::eq(*seen, file)) {
+                break;
+            }
+            chain.push(file);
+
+            match &file.reason {
+                FileReason::DepOf(importer) => dest = importer.clone(),
+                _ => break,
+            }
+        }
+
+        FileExplanation {
+            files: chain.into_iter().cloned().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in self.files.iter() {
+            writeln!(
+                f,
+                "`{}` -> `{}` ({:?}, {} bytes, {})",
+                file.src.display(),
+                file.dest.display(),
+                file.flags,
+                file.size,
+                file.reason
+            )?;
+        }
+        for name in self.unresolved.iter() {
+            writeln!(f, "missing `{name}`")?;
+        }
+        write!(f, "{} files, {} bytes", self.files.len(), self.total_size)
+    }
 }
 
 /// A packager for a GTK-rs project, backed by MSYS2.
@@ -58,7 +555,29 @@ pub struct Packager {
     files: Vec<File>,
 
     resolve_unknown_libraries: bool,
+    resolve_options: ResolveOptions,
     upx: bool,
+    upx_options: UpxOptions,
+    no_upx: Vec<String>,
+    strip: bool,
+    debug_symbols_dir: Option<PathBuf>,
+    jobs: usize,
+    incremental: bool,
+    check_unwind_runtime: bool,
+    crt_check: CrtCheck,
+    overwrite: OverwritePolicy,
+    verify_copies: bool,
+    checksums: bool,
+    dll_store: Option<DllStore>,
+    pe_timestamp: Option<u32>,
+    toolchain_runtime: bool,
+    licenses: bool,
+    sign: Option<SignOptions>,
+    plugins: Vec<Box<dyn PackagerPlugin>>,
+    locked: Option<Lockfile>,
+    reason: FileReason,
+
+    dependency_graph: Option<DependencyGraph>,
 }
 
 impl Packager {
@@ -75,28 +594,381 @@ impl Packager {
 
             files: Vec::with_capacity(256),
             resolve_unknown_libraries: true,
+            resolve_options: ResolveOptions::new(),
             upx: false,
+            upx_options: UpxOptions::new(),
+            no_upx: Vec::new(),
+            strip: false,
+            debug_symbols_dir: None,
+            jobs: 1,
+            incremental: false,
+            check_unwind_runtime: true,
+            crt_check: CrtCheck::Warn,
+            overwrite: OverwritePolicy::Skip,
+            verify_copies: false,
+            checksums: true,
+            dll_store: None,
+            pe_timestamp: None,
+            toolchain_runtime: true,
+            licenses: false,
+            sign: None,
+            plugins: Vec::new(),
+            locked: None,
+            reason: FileReason::Explicit,
+
+            dependency_graph: None,
         }
     }
 
     /// Add a file to be packaged.
+    ///
+    /// `dest` is relative to the package top level.
+    /// If `src` is None, the file is looked up by name in the `lib` and `bin` dirs of the MSYS2 environment.
+    /// In that case, `flags` must contain [`FileFlags::EXE`] or [`FileFlags::LIB`],
+    /// and `dest` must be a single file name.
+    ///
+    /// If multiple files share a `dest`, the first one added wins.
+    /// The file is recorded with the current [`Packager::reason`].
     pub fn add_file(&mut self, src: Option<PathBuf>, dest: PathBuf, flags: FileFlags) -> &mut Self {
-        self.files.push(File { src, dest, flags });
+        self.files.push(File {
+            src,
+            dest,
+            flags,
+            reason: self.reason.clone(),
+            manifest: None,
+        });
+        self
+    }
+
+    /// Set why the files added after this are packaged, which is recorded in the plan.
+    ///
+    /// This lets the code that adds a set of support files tag all of them at once.
+    /// Dependencies that are resolved automatically are always recorded as [`FileReason::DepOf`].
+    ///
+    /// Defaults to [`FileReason::Explicit`].
+    pub fn reason(&mut self, reason: FileReason) -> &mut Self {
+        self.reason = reason;
+        self
+    }
+
+    /// Add the files of a JSON manifest, like one emitted by another tool or an earlier packaging phase.
+    ///
+    /// The manifest is an object with a `files` array of `src`, `dest`, `flags`, and `reason`, like a [`Plan`] or a JSON [`PackageReport`].
+    /// `flags` are named like [`FileFlags::names`], and `src` may be left out to look the file up like [`Packager::add_file`] does.
+    /// Relative sources are resolved against the dir of the manifest.
+    ///
+    /// Files that were already added with the same source and flags are skipped.
+    /// Files without a `reason` get the current [`Packager::reason`].
+    ///
+    /// Files whose destination is taken by a file with a different source or flags fail [`Packager::plan`],
+    /// which checks the final list of files, including ones added after this and resolved dependencies.
+    ///
+    /// # Returns
+    /// Returns the number of added files.
+    ///
+    /// # Errors
+    /// Returns an error if a file is invalid, like one with an absolute destination.
+    /// No files are added if there is an error.
+    pub fn extend_from_manifest(&mut self, path: &Path) -> anyhow::Result<usize> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let manifest: Manifest = serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut files: Vec<File> = Vec::with_capacity(manifest.files.len());
+        for file in manifest.files {
+            let dest = &file.dest;
+            ensure!(
+                dest.is_relative()
+                    && dest
+                        .components()
+                        .all(|component| matches!(component, std::path::Component::Normal(_))),
+                "`{}` in `{}` is not a relative path inside the package",
+                dest.display(),
+                path.display()
+            );
+            if file.src.is_none() {
+                ensure!(
+                    file.flags.intersects(FileFlags::EXE | FileFlags::LIB)
+                        && dest.components().count() == 1,
+                    "`{}` in `{}` has no src, so it must be an exe or lib with a file name as its dest",
+                    dest.display(),
+                    path.display()
+                );
+            }
+            let src = file.src.map(|src| base_dir.join(src));
+
+            let is_duplicate = self.files.iter().chain(files.iter()).any(|added| {
+                added.dest == file.dest && added.src == src && added.flags == file.flags
+            });
+            if is_duplicate {
+                continue;
+            }
+            files.push(File {
+                src,
+                dest: file.dest,
+                flags: file.flags,
+                reason: file.reason.unwrap_or_else(|| self.reason.clone()),
+                manifest: Some(path.to_path_buf()),
+            });
+        }
+
+        let num_files = files.len();
+        self.files.extend(files);
+
+        Ok(num_files)
+    }
+
+    /// Get the files that were added so far.
+    ///
+    /// After planning or packaging, this includes the dependencies that were resolved.
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    /// Whether to resolve unknown libraries.
+    ///
+    /// Defaults to true.
+    pub fn resolve_unknown_libraries(&mut self, resolve_unknown_libraries: bool) -> &mut Self {
+        self.resolve_unknown_libraries = resolve_unknown_libraries;
+        self
+    }
+
+    /// Exclude dlls with names matching a glob pattern, like `libicudata*.dll`, from being added automatically.
+    ///
+    /// Matching dlls are not added when resolving unknown libraries, even if packaged binaries import them,
+    /// and their dependencies are not followed.
+    /// A warning listing excluded dlls is printed.
+    /// Files added with [`Packager::add_file`] are not affected.
+    pub fn exclude(&mut self, pattern: String) -> &mut Self {
+        self.resolve_options.exclude(pattern);
+        self
+    }
+
+    /// Treat dlls with names matching a glob pattern as system dlls, so they are never packaged automatically.
+    ///
+    /// This extends the built-in list of system dlls, see [`crate::util::is_system_dll`].
+    pub fn system_dll(&mut self, pattern: String) -> &mut Self {
+        self.resolve_options.system(pattern);
+        self
+    }
+
+    /// Package dlls with names matching a glob pattern when they are imported, even if they are considered system dlls.
+    ///
+    /// The dlls are located in the MSYS2 environment, like any other library.
+    pub fn bundle_dll(&mut self, pattern: String) -> &mut Self {
+        self.resolve_options.bundle(pattern);
+        self
+    }
+
+    /// Whether to use upx
+    ///
+    /// Packaging will fail if this is enabled without the `process` feature.
+    pub fn upx(&mut self, upx: bool) -> &mut Self {
+        self.upx = upx;
+        self
+    }
+
+    /// Set the options used to run upx, like the compression level.
+    pub fn upx_options(&mut self, upx_options: UpxOptions) -> &mut Self {
+        self.upx_options = upx_options;
+        self
+    }
+
+    /// Add a pattern of file names that are never upx-ed, like `libgio-*.dll`.
+    ///
+    /// Patterns use `*` and `?` wildcards, and match file names case-insensitively.
+    pub fn no_upx(&mut self, pattern: String) -> &mut Self {
+        self.no_upx.push(pattern);
+        self
+    }
+
+    /// Whether to strip symbols from exes and libraries with [`FileFlags::STRIP`], before they are upx-ed.
+    ///
+    /// The `strip` or `llvm-strip` of the MSYS2 environment is used, falling back to `strip` on the PATH.
+    /// Packaging will fail if this is enabled without the `process` feature.
+    ///
+    /// Defaults to false.
+    pub fn strip(&mut self, strip: bool) -> &mut Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Keep the debug info of stripped files in `.debug` files in a separate dir, like `debug-symbols`.
+    ///
+    /// Before a file is stripped, its debug info is copied to `<dir>/<dest>.debug` with `objcopy --only-keep-debug`,
+    /// and the stripped file gets a debug link to it, so crash dumps of stripped packages can still be symbolized.
+    /// The dir should be outside of the out dir, so the debug info is not shipped.
+    /// Packaging will fail if this is set without [`Packager::strip`].
+    ///
+    /// Defaults to None.
+    pub fn debug_symbols_dir(&mut self, debug_symbols_dir: Option<PathBuf>) -> &mut Self {
+        self.debug_symbols_dir = debug_symbols_dir;
+        self
+    }
+
+    /// The number of threads to use when copying and upx-ing files.
+    ///
+    /// Dependency resolution is always performed on the calling thread,
+    /// so the set of packaged files does not depend on this value.
+    /// A value of 0 is treated as 1.
+    ///
+    /// Defaults to 1.
+    pub fn jobs(&mut self, jobs: usize) -> &mut Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Whether to update an existing out dir incrementally.
+    ///
+    /// When enabled, a cache file describing the packaged files is kept in the out dir.
+    /// Files whose sources have not changed since the last run are not copied again,
+    /// and files that were packaged by the last run but are no longer part of the package are removed.
+    ///
+    /// Defaults to false.
+    pub fn incremental(&mut self, incremental: bool) -> &mut Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Whether to check that packaged binaries use the unwind runtime of the MSYS2 environment.
+    ///
+    /// Mixing unwind runtimes, like SEH and SJLJ, does not fail at load time,
+    /// but usually crashes once a panic or exception crosses between binaries.
+    ///
+    /// Defaults to true.
+    pub fn check_unwind_runtime(&mut self, check_unwind_runtime: bool) -> &mut Self {
+        self.check_unwind_runtime = check_unwind_runtime;
+        self
+    }
+
+    /// What to do when a packaged binary links against a different C runtime than the MSYS2 environment uses.
+    ///
+    /// Binaries using MSVCRT and UCRT can load into one process,
+    /// but passing allocations or `FILE` pointers between them corrupts memory.
+    ///
+    /// Defaults to [`CrtCheck::Warn`].
+    pub fn crt_check(&mut self, crt_check: CrtCheck) -> &mut Self {
+        self.crt_check = crt_check;
+        self
+    }
+
+    /// What to do when a file already exists at a destination in the out dir.
+    ///
+    /// This only applies to non-incremental packaging.
+    /// Incremental packaging owns the out dir, and replaces any file that is not up to date.
+    ///
+    /// Defaults to [`OverwritePolicy::Skip`].
+    pub fn overwrite(&mut self, overwrite: OverwritePolicy) -> &mut Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Whether to verify each copied file, and write the SHA-256 of each packaged file to [`CHECKSUMS_FILE_NAME`] in the out dir.
+    ///
+    /// Copies are checked against the hash of their source, which catches corruption or antivirus interference.
+    /// Upx-ed files are checked with `upx -t` instead, as upx changes their contents.
+    /// The checksums file is written last, so it describes the final files, see [`write_checksums`].
+    ///
+    /// Defaults to false.
+    pub fn verify_copies(&mut self, verify_copies: bool) -> &mut Self {
+        self.verify_copies = verify_copies;
+        self
+    }
+
+    /// Whether [`Packager::package`] writes the checksums file when verifying copies.
+    ///
+    /// Disable this if more files are written to the out dir after packaging,
+    /// and call [`write_checksums`] once they are, so the checksums describe the finished package.
+    ///
+    /// Defaults to true.
+    pub fn checksums(&mut self, checksums: bool) -> &mut Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Hard-link libraries from a store shared between packages, instead of copying them.
+    ///
+    /// Only libraries that are packaged unchanged are linked, so files that are upx-ed, stripped, or signed are still copied.
+    ///
+    /// Defaults to None.
+    pub fn dll_store(&mut self, dll_store: Option<DllStore>) -> &mut Self {
+        self.dll_store = dll_store;
+        self
+    }
+
+    /// Set the timestamps in the PE headers of packaged exes and libraries to a fixed value, for reproducible packages.
+    ///
+    /// Timestamps are set after stripping, and again after upx-ing, which copies the header of its input.
+    /// Signed files are left untouched with a warning, as changing them would break their signature.
+    /// Files signed by the packager are signed after their timestamps are set.
+    /// See [`normalize_pe_timestamps`] for which timestamps are set.
+    ///
+    /// Defaults to None, which leaves timestamps untouched.
+    pub fn normalize_pe_timestamps(&mut self, pe_timestamp: Option<u32>) -> &mut Self {
+        self.pe_timestamp = pe_timestamp;
+        self
+    }
+
+    /// Whether to always package the support dlls of the toolchain of the MSYS2 environment,
+    /// like `libgcc_s_seh-1.dll`, `libwinpthread-1.dll`, and `libstdc++-6.dll`.
+    ///
+    /// These can be loaded lazily, or through C dependencies, in ways that import scanning does not see.
+    /// Dlls that are not installed in the environment are skipped.
+    ///
+    /// Defaults to true.
+    pub fn toolchain_runtime(&mut self, toolchain_runtime: bool) -> &mut Self {
+        self.toolchain_runtime = toolchain_runtime;
+        self
+    }
+
+    /// Whether to write a document with the licenses of every MSYS2 package that packaged files came from.
+    ///
+    /// The document is written to [`LICENSES_FILE_NAME`] in the out dir,
+    /// and the report returned by [`Packager::package`] includes the package ownership.
+    ///
+    /// Defaults to false.
+    pub fn licenses(&mut self, licenses: bool) -> &mut Self {
+        self.licenses = licenses;
+        self
+    }
+
+    /// Sign packaged exes and libraries with signtool, after they are upx-ed.
+    ///
+    /// Packaging will fail if this is set without the `process` feature.
+    ///
+    /// Defaults to None.
+    pub fn sign(&mut self, sign: Option<SignOptions>) -> &mut Self {
+        self.sign = sign;
+        self
+    }
+
+    /// Add a plugin, which is called while planning.
+    ///
+    /// Plugins are called in the order they were added.
+    pub fn add_plugin(&mut self, plugin: Box<dyn PackagerPlugin>) -> &mut Self {
+        self.plugins.push(plugin);
         self
     }
 
-    /// Whether to resolve unknown libraries.
+    /// Require the MSYS2 binaries that would be packaged to match a lockfile.
     ///
-    /// Defaults to true.
-    pub fn resolve_unknown_libraries(&mut self, resolve_unknown_libraries: bool) -> &mut Self {
-        self.resolve_unknown_libraries = resolve_unknown_libraries;
+    /// If set, planning fails if any packaged exe or dll from an MSYS2 package
+    /// would come from a different package, package version, or have different contents.
+    /// See [`Packager::lockfile_for_plan`].
+    pub fn locked(&mut self, locked: Option<Lockfile>) -> &mut Self {
+        self.locked = locked;
         self
     }
 
-    /// Whether to use upx
-    pub fn upx(&mut self, upx: bool) -> &mut Self {
-        self.upx = upx;
-        self
+    /// Get the dll dependency graph, showing which binary pulled in which library.
+    ///
+    /// # Returns
+    /// Returns None if dependencies have not been resolved yet,
+    /// or if resolving unknown libraries is disabled.
+    pub fn dependency_graph(&self) -> Option<&DependencyGraph> {
+        self.dependency_graph.as_ref()
     }
 
     /// Get the MSYS2 environment path
@@ -105,54 +977,149 @@ impl Packager {
             .join(self.msys2_environment.get_prefix().trim_start_matches('/'))
     }
 
-    /// Lookup a library with the given packager settings.
-    ///
-    /// # Result
-    /// Returns an error if the library could not be found of if the lookup failed.
-    fn lookup_msys2_file(&self, name: &OsStr) -> anyhow::Result<Option<PathBuf>> {
-        const PATH_EXT: &[&str] = &["dll", "exe"];
+    /// Add the support dlls of the toolchain that are installed and were not added yet.
+    fn add_toolchain_runtime(&mut self) -> anyhow::Result<()> {
+        for name in get_toolchain_runtime_dlls(self.msys2_environment) {
+            if self.files.iter().any(|file| file.dest == Path::new(name))
+                || self.resolve_options.is_excluded(name)
+            {
+                continue;
+            }
 
-        let lookup_dir = self.get_msys2_environment_path();
+            let src = self
+                .lookup_msys2_file(OsStr::new(name))
+                .with_context(|| format!("failed to locate `{name}`"))?;
+            if let Some(src) = src {
+                self.files.push(File {
+                    src: Some(src),
+                    dest: name.into(),
+                    flags: FileFlags::LIB | FileFlags::UPX | FileFlags::STRIP | FileFlags::ADD_DEPS,
+                    reason: FileReason::Profile("toolchain-runtime".into()),
+                    manifest: None,
+                });
+            }
+        }
 
-        for path in ["lib", "bin"] {
-            let path = lookup_dir.join(path);
-            let path = path.join_os(name);
+        Ok(())
+    }
 
-            if path
-                .try_exists()
-                .context("failed to check if file exists")?
-            {
-                return Ok(Some(path));
-            }
+    /// Check that binaries only link against the C runtime used by the MSYS2 environment.
+    ///
+    /// Binaries are classified by the C runtime dlls they import.
+    /// Mismatches are handled according to [`Packager::crt_check`].
+    fn validate_c_runtimes<'a, I>(&self, imports: I) -> anyhow::Result<()>
+    where
+        I: Iterator<Item = &'a Import>,
+    {
+        if self.crt_check == CrtCheck::Off {
+            return Ok(());
+        }
+        let expected = match CRuntime::from_msys2_environment(self.msys2_environment) {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
 
-            for ext in PATH_EXT {
-                // Append .ext to path.
-                // Path cannot do this but OsString can.
-                let path = {
-                    let mut path = OsString::from(&path);
-                    path.push(".");
-                    path.push(ext);
+        let mut found: BTreeMap<&Path, BTreeSet<CRuntime>> = BTreeMap::new();
+        for import in imports {
+            if let Some(c_runtime) = CRuntime::from_dll_name(&import.name) {
+                found
+                    .entry(import.importer.as_path())
+                    .or_default()
+                    .insert(c_runtime);
+            }
+        }
 
-                    PathBuf::from(path)
-                };
+        let mut problems = Vec::new();
+        for (importer, c_runtimes) in found.iter() {
+            for c_runtime in c_runtimes
+                .iter()
+                .filter(|c_runtime| **c_runtime != expected)
+            {
+                problems.push(format!(
+                    "`{}` links against {}, but the `{:?}` environment uses {}",
+                    importer.display(),
+                    c_runtime.get_name(),
+                    self.msys2_environment,
+                    expected.get_name(),
+                ));
+            }
+        }
 
-                if path
-                    .try_exists()
-                    .context("failed to check if file exists")?
-                {
-                    return Ok(Some(path));
+        match self.crt_check {
+            CrtCheck::Error if !problems.is_empty() => {
+                bail!(
+                    "{}. Binaries using different C runtimes corrupt memory when they share allocations or files",
+                    problems.join("\n")
+                );
+            }
+            _ => {
+                for problem in problems {
+                    eprintln!("warning: {problem}");
                 }
             }
         }
 
-        Ok(None)
+        Ok(())
     }
 
-    // TODO: Consider adding multithreading option.
-    /// Try to package
-    pub fn package(&mut self) -> anyhow::Result<()> {
-        // Create base dir
-        std::fs::create_dir_all(&self.out_dir).context("failed to create out dir")?;
+    /// Ensure that the given dll, needed by `user`, does not provide an unwind runtime
+    /// that conflicts with the one used by the MSYS2 environment.
+    ///
+    /// This does nothing if unwind runtime checking is disabled.
+    fn validate_unwind_runtime(&self, name: &str, user: &Path) -> anyhow::Result<()> {
+        if !self.check_unwind_runtime {
+            return Ok(());
+        }
+
+        let found = match UnwindRuntime::from_dll_name(name) {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        let expected = match UnwindRuntime::from_msys2_environment(self.msys2_environment) {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+
+        ensure!(
+            found == expected,
+            "`{}` needs `{name}`, which provides the {} unwind runtime, \
+            but the `{:?}` environment uses the {} unwind runtime (`{}`). \
+            Binaries using different unwind runtimes cannot unwind through each other, \
+            so panics and exceptions would crash the packaged application at runtime. \
+            Rebuild `{}` with a toolchain that uses the {} unwind runtime, \
+            or pick a target that maps to a matching MSYS2 environment",
+            user.display(),
+            found.get_name(),
+            self.msys2_environment,
+            expected.get_name(),
+            expected.get_dll_name(),
+            user.display(),
+            expected.get_name(),
+        );
+
+        Ok(())
+    }
+
+    /// Lookup a library with the given packager settings.
+    ///
+    /// # Result
+    /// Returns an error if the library could not be found of if the lookup failed.
+    fn lookup_msys2_file(&self, name: &OsStr) -> anyhow::Result<Option<PathBuf>> {
+        lookup_msys2_file(&self.get_msys2_environment_path(), name)
+    }
+
+    /// Locate the sources of files that were added without one,
+    /// and add the dependencies of binaries if requested.
+    ///
+    /// Running this more than once is harmless,
+    /// as files that were already resolved are skipped.
+    ///
+    /// # Returns
+    /// Returns the names of dependencies that could not be located.
+    fn resolve_files(&mut self) -> anyhow::Result<Vec<String>> {
+        if self.toolchain_runtime {
+            self.add_toolchain_runtime()?;
+        }
 
         // Lookup missing
         for i in 0..self.files.len() {
@@ -185,69 +1152,118 @@ impl Packager {
         }
 
         if self.resolve_unknown_libraries {
-            let mut known_libraries = HashSet::<OsString>::new();
-            let mut unknown_libraries = HashSet::<OsString>::new();
-            let mut files_to_copy_offset = 0;
-            loop {
-                for file in self.files[files_to_copy_offset..].iter().filter(|file| {
+            let binaries: Vec<&Path> = self
+                .files
+                .iter()
+                .filter(|file| {
                     file.flags.contains(FileFlags::LIB) || file.flags.contains(FileFlags::EXE)
-                }) {
-                    let file_src = file.src.as_ref().unwrap_or_else(|| {
+                })
+                .map(|file| {
+                    file.src.as_deref().unwrap_or_else(|| {
                         panic!(
                             "`{}` should be resolved, but it is not",
                             file.dest.display()
                         )
-                    });
-                    let file_name = file_src.file_name().context("missing file name")?;
-                    known_libraries.insert(file_name.into());
-                    unknown_libraries.remove(file_name);
+                    })
+                })
+                .collect();
+            let source = Msys2DependencySource::new(self.get_msys2_environment_path());
+            let resolution =
+                resolve_dependencies_with_options(&source, &binaries, &self.resolve_options)?;
 
-                    for name in get_dll_imports(file_src)
-                        .with_context(|| {
-                            format!("failed to get bin deps for `{}`", file_src.display())
-                        })?
-                        .into_iter()
-                        .filter(|name| !is_system_dll(name))
-                    {
-                        if !known_libraries.contains(OsStr::new(&name)) {
-                            if is_api_set_dll(&name) {
-                                eprintln!("`{name}` is part of an api set, skipping...");
-                                known_libraries.insert(name.into());
-                            } else {
-                                unknown_libraries.insert(name.into());
-                            }
-                        }
-                    }
-                }
-                files_to_copy_offset = self.files.len().saturating_sub(1);
+            for import in resolution.imports.iter() {
+                self.validate_unwind_runtime(&import.name, &import.importer)?;
+            }
 
-                let has_unknown = !unknown_libraries.is_empty();
-                for library in unknown_libraries.drain() {
-                    let src = self
-                        .lookup_msys2_file(&library)
-                        .with_context(|| {
-                            format!("failed to locate `{}`", Path::new(&library).display())
-                        })?
-                        .with_context(|| format!("missing `{}`", Path::new(&library).display()))?;
-
-                    println!(
-                        "Adding new library `{}` from `{}`...",
-                        Path::new(&library).display(),
-                        src.display()
-                    );
-                    self.add_file(
-                        Some(src),
-                        library.into(),
-                        FileFlags::UPX | FileFlags::LIB | FileFlags::ADD_DEPS,
-                    );
-                }
+            self.validate_c_runtimes(
+                resolution
+                    .imports
+                    .iter()
+                    .chain(resolution.system_imports.iter()),
+            )?;
+
+            self.dependency_graph = Some(DependencyGraph::from_resolution(&resolution));
+
+            if !resolution.excluded.is_empty() {
+                eprintln!(
+                    "warning: skipped imported libraries that match an exclude pattern: {}",
+                    resolution
+                        .excluded
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+
+            for name in resolution.api_sets.iter() {
+                eprintln!("`{name}` is part of an api set, skipping...");
+            }
 
-                if !has_unknown {
-                    break;
+            // Imports name their importer by source, so map sources back to destinations.
+            let mut src_dests: HashMap<&Path, &Path> = HashMap::new();
+            for file in self.files.iter() {
+                if let Some(src) = file.src.as_deref() {
+                    src_dests.entry(src).or_insert(&file.dest);
                 }
             }
+            for library in resolution.libraries.iter() {
+                src_dests
+                    .entry(&library.path)
+                    .or_insert(Path::new(&library.name));
+            }
+            let mut reasons = Vec::with_capacity(resolution.libraries.len());
+            for library in resolution.libraries.iter() {
+                let importer = resolution
+                    .imports
+                    .iter()
+                    .find(|import| import.name.eq_ignore_ascii_case(&library.name))
+                    .map(|import| {
+                        src_dests
+                            .get(import.importer.as_path())
+                            .map(|dest| dest.to_path_buf())
+                            .or_else(|| import.importer.file_name().map(PathBuf::from))
+                            .unwrap_or_else(|| import.importer.clone())
+                    });
+                reasons.push(importer.map_or(FileReason::Explicit, FileReason::DepOf));
+            }
+
+            for (library, reason) in resolution.libraries.into_iter().zip(reasons) {
+                eprintln!(
+                    "Adding new library `{}` from `{}` ({reason})...",
+                    library.name,
+                    library.path.display()
+                );
+                self.files.push(File {
+                    src: Some(library.path),
+                    dest: library.name.into(),
+                    flags: FileFlags::UPX | FileFlags::STRIP | FileFlags::LIB | FileFlags::ADD_DEPS,
+                    reason,
+                    manifest: None,
+                });
+            }
+
+            return Ok(resolution.unresolved);
         }
 
+        Ok(Vec::new())
+    }
+
+    /// Plan out the packaging operations, without touching the out dir.
+    ///
+    /// This performs dependency resolution, so the plan contains every file that [`Packager::package`] would copy.
+    /// Files are listed in the order they will be copied,
+    /// and only the first file added for a given destination is included.
+    ///
+    /// Unlike [`Packager::package`], this does not fail if dependencies could not be located.
+    /// Instead, they are listed in the plan.
+    ///
+    /// Added plugins may modify and validate the plan, see [`PackagerPlugin`].
+    pub fn plan(&mut self) -> anyhow::Result<Plan> {
+        let unresolved = self.resolve_files()?;
+
+        let mut claimed_dests: HashMap<PathBuf, (&PathBuf, &File)> = HashMap::new();
+        let mut files = Vec::with_capacity(self.files.len());
         for file in self.files.iter() {
             ensure!(
                 file.dest.is_relative(),
@@ -267,37 +1283,714 @@ impl Packager {
                 "`{}` is being added from a system directory",
                 file_src.display()
             );
+
+            // Only the first file for a given destination is copied.
+            if let Some((claimed_src, claimed_file)) = claimed_dests.get(&file.dest) {
+                // Manifests come from other tools, so a conflict with one is likely a broken pipeline.
+                let conflicts = *claimed_src != file_src || claimed_file.flags != file.flags;
+                if conflicts {
+                    if let Some(manifest) =
+                        file.manifest.as_ref().or(claimed_file.manifest.as_ref())
+                    {
+                        let describe = |src: &Path, flags: FileFlags| {
+                            format!(
+                                "`{}` with flags `{}`",
+                                src.display(),
+                                flags.names().join(",")
+                            )
+                        };
+                        bail!(
+                            "`{}` from `{}` is {}, but it was already added from {}",
+                            file.dest.display(),
+                            manifest.display(),
+                            describe(file_src, file.flags),
+                            describe(claimed_src, claimed_file.flags)
+                        );
+                    }
+                }
+                if *claimed_src != file_src {
+                    eprintln!(
+                        "warning: `{}` and `{}` are both added as `{}`, only the first will be packaged",
+                        claimed_src.display(),
+                        file_src.display(),
+                        file.dest.display()
+                    );
+                }
+                continue;
+            }
+            claimed_dests.insert(file.dest.clone(), (file_src, file));
+
+            let size = std::fs::metadata(file_src)
+                .with_context(|| format!("failed to get metadata for `{}`", file_src.display()))?
+                .len();
+
+            files.push(PlannedFile {
+                src: file_src.clone(),
+                dest: file.dest.clone(),
+                flags: file.flags,
+                size,
+                reason: file.reason.clone(),
+            });
+        }
+
+        let mut plan = Plan {
+            files,
+            unresolved,
+            total_size: 0,
+        };
+        for plugin in self.plugins.iter() {
+            plugin
+                .modify_plan(&mut plan)
+                .with_context(|| format!("plugin `{}` failed to modify the plan", plugin.name()))?;
+        }
+        plan.total_size = plan.files.iter().map(|file| file.size).sum();
+        for plugin in self.plugins.iter() {
+            plugin
+                .validate(&plan)
+                .with_context(|| format!("plugin `{}` rejected the plan", plugin.name()))?;
+        }
+
+        if let Some(locked) = self.locked.as_ref() {
+            let actual = self.lockfile_for_plan(&plan)?;
+            let differences = locked.diff(&actual);
+            if !differences.is_empty() {
+                bail!(
+                    "the packaged MSYS2 binaries do not match the lockfile:\n  {}",
+                    differences.join("\n  ")
+                );
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Plan out the packaging operations, like [`Packager::plan`], but return a timed report.
+    pub fn dry_run(&mut self) -> anyhow::Result<PackageReport> {
+        let plan_start = Instant::now();
+        let plan = self.plan()?;
+        let plan_time = plan_start.elapsed();
+
+        Ok(PackageReport {
+            plan,
+            timings: Timings {
+                plan_secs: plan_time.as_secs_f64(),
+                copy_secs: None,
+            },
+            ownership: None,
+            sizes: None,
+        })
+    }
+
+    /// Plan out packaging, and report which MSYS2 package each packaged file came from.
+    ///
+    /// Nothing is written to the out dir.
+    pub fn package_report(&mut self) -> anyhow::Result<OwnershipReport> {
+        let plan = self.plan()?;
+        self.package_report_for_plan(&plan)
+    }
+
+    /// Report which MSYS2 package each file of an existing plan came from.
+    ///
+    /// This reads the local pacman database of the MSYS2 installation.
+    pub fn package_report_for_plan(&self, plan: &Plan) -> anyhow::Result<OwnershipReport> {
+        let database = LocalDatabase::load(&self.msys2_installation_path)
+            .context("failed to load the pacman database")?;
+        self.package_report_with_database(&database, plan)
+    }
+
+    /// Report which MSYS2 package each file of an existing plan came from, using an already loaded database.
+    fn package_report_with_database(
+        &self,
+        database: &LocalDatabase,
+        plan: &Plan,
+    ) -> anyhow::Result<OwnershipReport> {
+        let owners = database
+            .load_file_owners()
+            .context("failed to load package files")?;
+
+        let mut num_files = BTreeMap::new();
+        let mut files = Vec::with_capacity(plan.files.len());
+        for file in plan.files.iter() {
+            let package = file
+                .src
+                .strip_prefix(&self.msys2_installation_path)
+                .ok()
+                .and_then(|path| owners.get(path));
+            if let Some(package) = package {
+                *num_files.entry(package).or_insert(0) += 1;
+            }
+
+            files.push(OwnedFile {
+                dest: file.dest.clone(),
+                package: package.map(String::from),
+            });
+        }
+
+        let packages = num_files
+            .into_iter()
+            .map(|(name, num_files)| {
+                let version = database
+                    .get(name)
+                    .map(|package| package.version.clone())
+                    .with_context(|| format!("`{name}` is missing from the pacman database"))?;
+                Ok(OwningPackage {
+                    name: name.into(),
+                    version,
+                    num_files,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(OwnershipReport { files, packages })
+    }
+
+    /// Make a lockfile that records the MSYS2 package, version, and hash of each packaged exe and dll.
+    pub fn lockfile_for_plan(&self, plan: &Plan) -> anyhow::Result<Lockfile> {
+        let ownership = self.package_report_for_plan(plan)?;
+        Lockfile::new(plan, &ownership).context("failed to make lockfile")
+    }
+
+    /// Report the size of each file of an existing plan, by category.
+    ///
+    /// Packaged sizes are read from the out dir, for files that exist there.
+    pub fn size_report_for_plan(&self, plan: &Plan) -> anyhow::Result<SizeReport> {
+        let mut files = Vec::with_capacity(plan.files.len());
+        for file in plan.files.iter() {
             let dest = self.out_dir.join(&file.dest);
+            let packaged_size = match std::fs::metadata(&dest) {
+                Ok(metadata) => Some(metadata.len()),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+                Err(error) => {
+                    return Err(error).with_context(|| {
+                        format!("failed to get metadata for `{}`", dest.display())
+                    });
+                }
+            };
+
+            files.push(SizedFile {
+                dest: file.dest.clone(),
+                category: self.get_size_category(file).into(),
+                size: file.size,
+                packaged_size,
+            });
+        }
+
+        Ok(SizeReport::new(files))
+    }
+
+    /// Get the size report category of a planned file.
+    fn get_size_category(&self, file: &PlannedFile) -> &'static str {
+        if !file.src.starts_with(&self.msys2_installation_path) {
+            return "app";
+        }
+
+        let dest = file
+            .dest
+            .to_string_lossy()
+            .replace('\\', "/")
+            .to_ascii_lowercase();
+        let file_name = dest.rsplit('/').next().unwrap_or(&dest);
+        if dest.starts_with("share/icons/") {
+            "icons"
+        } else if dest.starts_with("share/locale/") {
+            "locales"
+        } else if dest.starts_with("share/themes/") {
+            "themes"
+        } else if dest.starts_with("lib/gstreamer-1.0/") || file_name.starts_with("libgst") {
+            "gstreamer"
+        } else if [
+            "libgtk",
+            "libgdk",
+            "libglib",
+            "libgio",
+            "libgobject",
+            "libgmodule",
+            "libpango",
+            "libcairo",
+            "libharfbuzz",
+            "libgraphene",
+            "libepoxy",
+        ]
+        .iter()
+        .any(|prefix| file_name.starts_with(prefix))
+            || dest.starts_with("lib/gtk-")
+        {
+            "gtk core"
+        } else if file.flags.contains(FileFlags::EXE) {
+            "executables"
+        } else if file.flags.contains(FileFlags::LIB) {
+            "libraries"
+        } else {
+            "data"
+        }
+    }
+
+    /// Try to package
+    ///
+    /// If dependencies could not be located, this fails with an [`UnresolvedError`].
+    pub fn package(&mut self) -> anyhow::Result<PackageReport> {
+        let plan_start = Instant::now();
+        let plan = self.plan()?;
+        let plan_time = plan_start.elapsed();
+
+        ensure!(
+            cfg!(feature = "process") || !self.upx,
+            "upx requires the `process` feature"
+        );
+        ensure!(
+            cfg!(feature = "process") || !self.strip,
+            "strip requires the `process` feature"
+        );
+        ensure!(
+            self.strip || self.debug_symbols_dir.is_none(),
+            "splitting debug info requires stripping"
+        );
+        ensure!(
+            cfg!(feature = "process") || self.sign.is_none(),
+            "signing requires the `process` feature"
+        );
+
+        if !plan.unresolved.is_empty() {
+            return Err(UnresolvedError(Box::new(PackageReport {
+                plan,
+                timings: Timings {
+                    plan_secs: plan_time.as_secs_f64(),
+                    copy_secs: None,
+                },
+                ownership: None,
+                sizes: None,
+            }))
+            .into());
+        }
+
+        let copy_start = Instant::now();
+
+        // Create base dir
+        std::fs::create_dir_all(&self.out_dir).context("failed to create out dir")?;
+        if let Some(dll_store) = self.dll_store.as_ref() {
+            dll_store
+                .register_package(&self.out_dir)
+                .context("failed to register the out dir with the dll store")?;
+        }
+
+        // Pick the files to copy.
+        let old_cache = if self.incremental {
+            Cache::load(&self.out_dir)
+        } else {
+            Cache::new()
+        };
+        let mut new_cache = Cache::new();
+        let mut files_to_copy = Vec::with_capacity(plan.files.len());
+        for file in plan.files.iter() {
+            let dest = self.out_dir.join(&file.dest);
+
+            if self.incremental {
+                // Only attempt a copy if the source changed since the last run.
+                let entry = CacheEntry::new(
+                    &file.src,
+                    self.should_strip(file.flags),
+                    self.should_strip(file.flags) && self.debug_symbols_dir.is_some(),
+                    self.should_upx(file).then_some(&self.upx_options),
+                    self.get_sign_cert(file.flags),
+                    self.get_pe_timestamp(file.flags),
+                )?;
+                let up_to_date = dest.exists() && old_cache.entries.get(&file.dest) == Some(&entry);
+                new_cache.entries.insert(file.dest.clone(), entry);
+
+                if !up_to_date {
+                    files_to_copy.push((file, dest));
+                }
+            } else if !dest.exists() {
+                files_to_copy.push((file, dest));
+            } else {
+                let should_copy = match self.overwrite {
+                    OverwritePolicy::Error => {
+                        bail!("`{}` already exists", dest.display());
+                    }
+                    OverwritePolicy::Skip => false,
+                    OverwritePolicy::Overwrite => true,
+                    OverwritePolicy::OverwriteIfDifferent => !files_are_equal(&file.src, &dest)
+                        .with_context(|| {
+                            format!(
+                                "failed to compare `{}` to `{}`",
+                                file.src.display(),
+                                dest.display()
+                            )
+                        })?,
+                };
+                if should_copy {
+                    files_to_copy.push((file, dest));
+                }
+            }
+        }
+
+        if self.incremental {
+            Cache::invalidate(&self.out_dir)?;
+
+            // Prune files from the last run that are no longer a part of this package.
+            for dest in old_cache
+                .entries
+                .keys()
+                .filter(|dest| !new_cache.entries.contains_key(*dest))
+            {
+                let path = self.out_dir.join(dest);
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {
+                        eprintln!("Removed stale file `{}`", dest.display());
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("failed to remove `{}`", path.display()));
+                    }
+                }
+            }
+        }
+
+        if self.jobs <= 1 || files_to_copy.len() <= 1 {
+            for (file, dest) in files_to_copy.iter() {
+                self.copy_file(file, dest)?;
+            }
+        } else {
+            let next_index = AtomicUsize::new(0);
+            let failed = AtomicBool::new(false);
+            let errors = Mutex::new(Vec::new());
+            let num_threads = self.jobs.min(files_to_copy.len());
 
-            // Only attempt a copy if the destination is empty.
-            // TODO: Consider emitting a warning if this would cause an overwrite for another file made by this packager.
-            if !dest.exists() {
-                // Try to create parent dir.
-                if let Some(parent) = dest.parent() {
-                    std::fs::create_dir_all(parent).with_context(|| {
-                        format!("failed to create parent dir at `{}`", parent.display())
-                    })?;
+            std::thread::scope(|scope| {
+                for _ in 0..num_threads {
+                    scope.spawn(|| {
+                        while !failed.load(Ordering::SeqCst) {
+                            let index = next_index.fetch_add(1, Ordering::SeqCst);
+                            let (file, dest) = match files_to_copy.get(index) {
+                                Some(entry) => entry,
+                                None => break,
+                            };
+
+                            if let Err(error) = self.copy_file(file, dest) {
+                                failed.store(true, Ordering::SeqCst);
+                                errors
+                                    .lock()
+                                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                                    .push((index, error));
+                            }
+                        }
+                    });
                 }
+            });
+
+            // Report the error for the earliest file, to keep errors stable between runs.
+            let errors = errors
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some((_, error)) = errors.into_iter().min_by_key(|(index, _)| *index) {
+                return Err(error);
+            }
+        }
+
+        // Sign everything that was copied in one signtool invocation, as it is slow to start.
+        // Files that were not copied were signed by a previous run, as signing is part of the cache entry.
+        #[cfg(feature = "process")]
+        if let Some(sign) = self.sign.as_ref() {
+            let to_sign: Vec<_> = files_to_copy
+                .iter()
+                .filter(|(file, _)| self.get_sign_cert(file.flags).is_some())
+                .map(|(_, dest)| dest.as_path())
+                .collect();
+            signtool_sign(&to_sign, sign).context("failed to sign files")?;
+        }
+
+        if self.incremental {
+            new_cache.save(&self.out_dir)?;
+        }
+
+        let ownership = if self.licenses {
+            let database = LocalDatabase::load(&self.msys2_installation_path)
+                .context("failed to load the pacman database")?;
+            let ownership = self.package_report_with_database(&database, &plan)?;
+            let document = generate_license_attribution(
+                &self.msys2_installation_path,
+                &database,
+                &ownership.packages,
+            )?;
+
+            let path = self.out_dir.join(LICENSES_FILE_NAME);
+            std::fs::write(&path, document)
+                .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+            Some(ownership)
+        } else {
+            None
+        };
+
+        if self.verify_copies && self.checksums {
+            write_checksums(&self.out_dir)?;
+        }
+
+        let copy_time = copy_start.elapsed();
+
+        Ok(PackageReport {
+            plan,
+            timings: Timings {
+                plan_secs: plan_time.as_secs_f64(),
+                copy_secs: Some(copy_time.as_secs_f64()),
+            },
+            ownership,
+            sizes: None,
+        })
+    }
 
-                // Perform copy
-                std::fs::copy(file_src, &dest).with_context(|| {
+    /// Whether a file will be upx-ed when it is copied.
+    fn should_upx(&self, file: &PlannedFile) -> bool {
+        let file_name = file
+            .dest
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy())
+            .unwrap_or_default();
+
+        self.upx
+            && file.flags.contains(FileFlags::UPX)
+            && (file.flags.contains(FileFlags::LIB) || file.flags.contains(FileFlags::EXE))
+            && !self
+                .no_upx
+                .iter()
+                .any(|pattern| matches_dll_pattern(pattern, &file_name))
+    }
+
+    /// Whether a file is packaged unchanged, without being stripped, upx-ed, signed, or having its timestamps set.
+    fn is_packaged_unchanged(&self, file: &PlannedFile) -> bool {
+        !self.should_upx(file)
+            && !self.should_strip(file.flags)
+            && self.get_sign_cert(file.flags).is_none()
+            && self.get_pe_timestamp(file.flags).is_none()
+    }
+
+    /// Get the timestamp that the PE headers of a file with the given flags will be set to, if they will be set.
+    fn get_pe_timestamp(&self, flags: FileFlags) -> Option<u32> {
+        self.pe_timestamp
+            .filter(|_| flags.contains(FileFlags::LIB) || flags.contains(FileFlags::EXE))
+    }
+
+    /// Set the timestamps in the PE headers of a copied file, if needed.
+    fn set_pe_timestamps(&self, file: &PlannedFile, dest: &Path) -> anyhow::Result<()> {
+        let Some(timestamp) = self.get_pe_timestamp(file.flags) else {
+            return Ok(());
+        };
+
+        let normalization = normalize_pe_timestamps(dest, timestamp)
+            .with_context(|| format!("failed to set the timestamps of `{}`", dest.display()))?;
+        if normalization == PeNormalization::Signed {
+            eprintln!(
+                "warning: `{}` is signed, so its timestamps were not set",
+                dest.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Whether a file with the given flags will be stripped when it is copied.
+    fn should_strip(&self, flags: FileFlags) -> bool {
+        self.strip
+            && flags.contains(FileFlags::STRIP)
+            && (flags.contains(FileFlags::LIB) || flags.contains(FileFlags::EXE))
+    }
+
+    /// Get the certificate that a file with the given flags will be signed with, if it will be signed.
+    fn get_sign_cert(&self, flags: FileFlags) -> Option<&Path> {
+        self.sign
+            .as_ref()
+            .filter(|_| flags.contains(FileFlags::LIB) || flags.contains(FileFlags::EXE))
+            .map(|sign| sign.cert())
+    }
+
+    /// Copy a single file to its destination, stripping and upx-ing it if needed.
+    fn copy_file(&self, file: &PlannedFile, dest: &Path) -> anyhow::Result<()> {
+        // Try to create parent dir.
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create parent dir at `{}`", parent.display())
+            })?;
+        }
+
+        // Remove the old file, as it may be a hard link into a dll store, which must not be written through.
+        match std::fs::remove_file(dest) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to remove `{}`", dest.display()));
+            }
+        }
+
+        if let Some(dll_store) = self.dll_store.as_ref() {
+            if self.is_packaged_unchanged(file) && file.flags.contains(FileFlags::LIB) {
+                dll_store.link(&file.src, dest).with_context(|| {
                     format!(
-                        "failed to copy `{}` to `{}`",
-                        file_src.display(),
-                        dest.display()
+                        "failed to link `{}` from the dll store at `{}`",
+                        dest.display(),
+                        dll_store.dir().display()
                     )
                 })?;
+                return Ok(());
+            }
+        }
+
+        // Hash the source before copying, so a source that changes during the copy is not missed.
+        let src_sha256 = if self.verify_copies {
+            let sha256 = crate::sbom::sha256_file(&file.src)
+                .with_context(|| format!("failed to hash `{}`", file.src.display()))?;
+            Some(sha256)
+        } else {
+            None
+        };
+
+        // Perform copy
+        std::fs::copy(&file.src, dest).with_context(|| {
+            format!(
+                "failed to copy `{}` to `{}`",
+                file.src.display(),
+                dest.display()
+            )
+        })?;
+
+        if let Some(src_sha256) = src_sha256 {
+            let dest_sha256 = crate::sbom::sha256_file(dest)
+                .with_context(|| format!("failed to hash `{}`", dest.display()))?;
+            ensure!(
+                src_sha256 == dest_sha256,
+                "the copy of `{}` at `{}` is corrupted, its SHA-256 is {dest_sha256} instead of {src_sha256}",
+                file.src.display(),
+                dest.display()
+            );
+        }
+
+        // Strip before upx, as strip cannot read upx-ed files.
+        #[cfg(feature = "process")]
+        if self.should_strip(file.flags) {
+            let environment_path = self.get_msys2_environment_path();
+
+            // Keep the debug info before it is stripped.
+            let debug_path = match self.debug_symbols_dir.as_ref() {
+                Some(debug_symbols_dir) => {
+                    let mut debug_path = debug_symbols_dir.join(&file.dest).into_os_string();
+                    debug_path.push(".debug");
+                    let debug_path = PathBuf::from(debug_path);
+                    if let Some(parent) = debug_path.parent() {
+                        std::fs::create_dir_all(parent).with_context(|| {
+                            format!("failed to create parent dir at `{}`", parent.display())
+                        })?;
+                    }
+
+                    let objcopy_path = locate_objcopy(&environment_path);
+                    objcopy_only_keep_debug(&objcopy_path, dest, &debug_path).with_context(
+                        || format!("failed to copy the debug info of `{}`", dest.display()),
+                    )?;
+                    Some((objcopy_path, debug_path))
+                }
+                None => None,
+            };
+
+            let strip_path = locate_strip(&environment_path);
+            strip(&strip_path, dest)
+                .with_context(|| format!("failed to strip `{}`", dest.display()))?;
+
+            if let Some((objcopy_path, debug_path)) = debug_path {
+                objcopy_add_debuglink(&objcopy_path, dest, &debug_path).with_context(|| {
+                    format!("failed to link `{}` to its debug info", dest.display())
+                })?;
+            }
+        }
+
+        self.set_pe_timestamps(file, dest)?;
 
-                // If this file is a library or exe and the user asked us to upx it, upx it.
-                if self.upx
-                    && file.flags.contains(FileFlags::UPX)
-                    && (file.flags.contains(FileFlags::LIB) || file.flags.contains(FileFlags::EXE))
-                {
-                    upx(&dest).with_context(|| format!("failed to upx `{}`", dest.display()))?;
+        // If this file is a library or exe and the user asked us to upx it, upx it.
+        #[cfg(feature = "process")]
+        if self.should_upx(file) {
+            match upx(dest, &self.upx_options) {
+                Ok(()) => {
+                    if self.verify_copies {
+                        upx_test(dest, &self.upx_options).with_context(|| {
+                            format!("failed to verify upx-ed `{}`", dest.display())
+                        })?;
+                    }
+                }
+                // upx leaves the file untouched when it fails, so it can be packaged uncompressed.
+                Err(error) if self.upx_options.get_allow_failures() => {
+                    eprintln!(
+                        "warning: failed to upx `{}`, packaging it uncompressed: {error:#}",
+                        dest.display()
+                    );
+                }
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("failed to upx `{}`", dest.display()));
                 }
             }
+
+            // upx keeps the timestamps of its input, but set them again in case its stub has others.
+            self.set_pe_timestamps(file, dest)?;
         }
 
         Ok(())
     }
 }
+
+/// Write the SHA-256 of every file in a dir to [`CHECKSUMS_FILE_NAME`] in it.
+///
+/// The dir is walked as it is, so this describes files written after packaging too.
+/// The checksums file and the incremental cache, see [`CACHE_FILE_NAME`], are skipped.
+/// The format matches `sha256sum`, so the package can be checked with `sha256sum -c`.
+pub fn write_checksums(out_dir: &Path) -> anyhow::Result<()> {
+    let mut checksums = String::new();
+    for entry in WalkDir::new(out_dir).sort_by_file_name() {
+        let entry = entry.context("failed to read dir entry")?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative_path = entry
+            .path()
+            .strip_prefix(out_dir)
+            .context("dir entry is not in the out dir")?;
+        if relative_path == Path::new(CHECKSUMS_FILE_NAME)
+            || relative_path == Path::new(CACHE_FILE_NAME)
+        {
+            continue;
+        }
+
+        let sha256 = crate::sbom::sha256_file(entry.path())
+            .with_context(|| format!("failed to hash `{}`", entry.path().display()))?;
+        let dest = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        checksums.push_str(&format!("{sha256} *{dest}\n"));
+    }
+
+    let path = out_dir.join(CHECKSUMS_FILE_NAME);
+    std::fs::write(&path, checksums)
+        .with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// Check if two files have the same contents.
+fn files_are_equal(a: &Path, b: &Path) -> anyhow::Result<bool> {
+    if std::fs::metadata(a)?.len() != std::fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut a = std::io::BufReader::new(std::fs::File::open(a)?);
+    let mut b = std::io::BufReader::new(std::fs::File::open(b)?);
+    loop {
+        let a_buffer = a.fill_buf()?;
+        let b_buffer = b.fill_buf()?;
+        let len = a_buffer.len().min(b_buffer.len());
+        if len == 0 {
+            return Ok(a_buffer.is_empty() && b_buffer.is_empty());
+        }
+        if a_buffer[..len] != b_buffer[..len] {
+            return Ok(false);
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
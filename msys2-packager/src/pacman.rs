@@ -0,0 +1,229 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The path to the local database, relative to the MSYS2 installation.
+const LOCAL_DATABASE_PATH: &str = "var/lib/pacman/local";
+
+/// A package that is installed in an MSYS2 installation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct InstalledPackage {
+    /// The package name, like `mingw-w64-ucrt-x86_64-gtk4`.
+    pub name: String,
+
+    /// The package version, like `4.10.0-1`.
+    pub version: String,
+
+    /// The names of things this package provides, in addition to its name.
+    ///
+    /// Version constraints are stripped.
+    pub provides: Vec<String>,
+
+    /// The declared licenses, like `spdx:LGPL-2.1-or-later`.
+    pub licenses: Vec<String>,
+
+    /// The names of the packages this package depends on.
+    ///
+    /// Version constraints are stripped.
+    pub depends: Vec<String>,
+
+    /// The dir of this package in the local database.
+    path: Utf8PathBuf,
+}
+
+impl InstalledPackage {
+    /// Parse a package from the contents of its `desc` file.
+    ///
+    /// `path` is the dir of the package in the local database.
+    fn from_desc(desc: &str, path: Utf8PathBuf) -> anyhow::Result<Self> {
+        let sections = parse_sections(desc);
+        let get_single = |key: &str| {
+            sections
+                .get(key)
+                .and_then(|values| values.first())
+                .map(|value| value.to_string())
+                .with_context(|| format!("missing `%{key}%`"))
+        };
+
+        let name = get_single("NAME")?;
+        let version = get_single("VERSION")?;
+        let get_names = |key: &str| -> Vec<String> {
+            sections
+                .get(key)
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|value| {
+                            let end = value.find(['=', '<', '>']).unwrap_or(value.len());
+                            value[..end].to_string()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let provides = get_names("PROVIDES");
+        let depends = get_names("DEPENDS");
+        let licenses = sections
+            .get("LICENSE")
+            .map(|values| values.iter().map(|value| value.to_string()).collect())
+            .unwrap_or_default();
+
+        Ok(Self {
+            name,
+            version,
+            provides,
+            licenses,
+            depends,
+            path,
+        })
+    }
+
+    /// Load the paths of the files this package installed.
+    ///
+    /// Paths are relative to the MSYS2 installation, like `ucrt64/bin/libgtk-4-1.dll`.
+    /// Dirs are skipped.
+    pub fn load_files(&self) -> anyhow::Result<Vec<Utf8PathBuf>> {
+        let files_path = self.path.join("files");
+        let files = std::fs::read_to_string(&files_path)
+            .with_context(|| format!("failed to read `{files_path}`"))?;
+
+        Ok(parse_sections(&files)
+            .get("FILES")
+            .map(|files| {
+                files
+                    .iter()
+                    .filter(|file| !file.ends_with('/'))
+                    .map(Utf8PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+/// The database of packages installed in an MSYS2 installation.
+///
+/// This reads the files pacman keeps on disk, so pacman does not need to be run.
+#[derive(Debug)]
+pub struct LocalDatabase {
+    /// Installed packages, by name
+    packages: BTreeMap<String, InstalledPackage>,
+}
+
+impl LocalDatabase {
+    /// Load the local database of an MSYS2 installation, like `C:/msys64`.
+    pub fn load(msys2_installation_path: &Utf8Path) -> anyhow::Result<Self> {
+        let path = msys2_installation_path.join(LOCAL_DATABASE_PATH);
+
+        let mut packages = BTreeMap::new();
+        for entry in path
+            .read_dir_utf8()
+            .with_context(|| format!("failed to read `{path}`"))?
+        {
+            let entry = entry.context("failed to get dir entry")?;
+
+            // The database also contains a version file, which we skip.
+            let file_type = entry.file_type().context("failed to get file type")?;
+            if !file_type.is_dir() {
+                continue;
+            }
+
+            let desc_path: Utf8PathBuf = entry.path().join("desc");
+            let desc = std::fs::read_to_string(&desc_path)
+                .with_context(|| format!("failed to read `{desc_path}`"))?;
+            let package = InstalledPackage::from_desc(&desc, entry.path().into())
+                .with_context(|| format!("failed to parse `{desc_path}`"))?;
+
+            packages.insert(package.name.clone(), package);
+        }
+
+        Ok(Self { packages })
+    }
+
+    /// Get an installed package by name.
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.get(name)
+    }
+
+    /// Check if a package with the given name is installed, or if an installed package provides it.
+    pub fn is_installed(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+            || self
+                .packages
+                .values()
+                .any(|package| package.provides.iter().any(|provides| provides == name))
+    }
+
+    /// Iterate over installed packages, sorted by name.
+    pub fn packages(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.packages.values()
+    }
+
+    /// Load the files of every installed package, to look up which package owns a file.
+    pub fn load_file_owners(&self) -> anyhow::Result<FileOwners> {
+        let mut owners = HashMap::new();
+        for package in self.packages.values() {
+            let files = package
+                .load_files()
+                .with_context(|| format!("failed to load files for `{}`", package.name))?;
+            for file in files {
+                owners.insert(file.as_str().to_ascii_lowercase(), package.name.clone());
+            }
+        }
+
+        Ok(FileOwners { owners })
+    }
+}
+
+/// A map of files to the packages that installed them.
+#[derive(Debug)]
+pub struct FileOwners {
+    /// Package names, keyed by lowercase paths relative to the installation, with `/` separators
+    owners: HashMap<String, String>,
+}
+
+impl FileOwners {
+    /// Get the name of the package that owns a file.
+    ///
+    /// `path` is relative to the MSYS2 installation, like `ucrt64/bin/libgtk-4-1.dll`.
+    /// Matching is case-insensitive, like Windows paths.
+    ///
+    /// # Returns
+    /// Returns None if no package owns the file.
+    pub fn get(&self, path: &Path) -> Option<&str> {
+        let key = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_ascii_lowercase())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.owners.get(&key).map(|name| name.as_str())
+    }
+}
+
+/// Parse the `%KEY%` sections of a pacman database file.
+///
+/// Each section is a `%KEY%` line, followed by one value per line, ending in an empty line.
+fn parse_sections(text: &str) -> BTreeMap<&str, Vec<&str>> {
+    let mut sections = BTreeMap::new();
+    let mut values: Option<&mut Vec<&str>> = None;
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+
+        if let Some(key) = line
+            .strip_prefix('%')
+            .and_then(|line| line.strip_suffix('%'))
+        {
+            values = Some(sections.entry(key).or_default());
+        } else if line.is_empty() {
+            values = None;
+        } else if let Some(values) = values.as_mut() {
+            values.push(line);
+        }
+    }
+
+    sections
+}
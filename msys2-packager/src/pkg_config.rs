@@ -0,0 +1,228 @@
+use anyhow::Context;
+use camino::Utf8Path;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The dirs of pkg-config files, relative to an MSYS2 environment or a copy of one.
+pub const PKG_CONFIG_DIRS: &[&str] = &["lib/pkgconfig", "share/pkgconfig"];
+
+/// The `prefix` of relocated pkg-config files, which pkgconf expands to the dir two levels above the file.
+pub const RELOCATED_PREFIX: &str = "${pcfiledir}/../..";
+
+/// A pkg-config file whose prefix does not point at an existing dir
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PkgConfigProblem {
+    /// The pkg-config file
+    pub path: PathBuf,
+
+    /// The `prefix` variable of the file, as written
+    pub prefix: String,
+
+    /// Where pkgconf would find the prefix, after applying the sysroot and `${pcfiledir}`
+    pub resolved: PathBuf,
+}
+
+impl std::fmt::Display for PkgConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` has the prefix `{}`, but `{}` does not exist",
+            self.path.display(),
+            self.prefix,
+            self.resolved.display()
+        )
+    }
+}
+
+/// Check if a file, relative to an MSYS2 environment or a copy of one, is a pkg-config file.
+pub fn is_pkg_config_file(path: &Utf8Path) -> bool {
+    path.extension() == Some("pc")
+        && path
+            .parent()
+            .is_some_and(|parent| PKG_CONFIG_DIRS.iter().any(|dir| parent == *dir))
+}
+
+/// Get the absolute paths that pkg-config files of an environment may use for it.
+///
+/// `root` is the environment prefix without the leading `/`, like `ucrt64`, see [`crate::extract::get_package_root`].
+/// Files use the prefix as seen from MSYS2, like `/ucrt64`,
+/// but some packages write the Windows path of the installation they were built in, like `C:/msys64/ucrt64`.
+/// The Windows path goes first, as it ends with the MSYS2 one.
+pub fn get_environment_prefixes(
+    msys2_installation_path: Option<&Utf8Path>,
+    root: &str,
+) -> Vec<String> {
+    let mut prefixes = Vec::with_capacity(2);
+    if let Some(msys2_installation_path) = msys2_installation_path {
+        prefixes.push(
+            msys2_installation_path
+                .join(root)
+                .as_str()
+                .replace('\\', "/"),
+        );
+    }
+    prefixes.push(format!("/{root}"));
+
+    prefixes
+}
+
+/// Rewrite a pkg-config file so that its prefix is relative to the file.
+///
+/// The `prefix` variable becomes [`RELOCATED_PREFIX`], so a copy of an environment works wherever it is moved.
+/// Other absolute paths into the environment, like `/ucrt64/include`, are rewritten to use `${prefix}`.
+/// `environment_prefixes` are the absolute paths of the environment, without trailing slashes,
+/// see [`get_environment_prefixes`].
+pub fn relocate_pkg_config(contents: &str, environment_prefixes: &[String]) -> String {
+    let mut relocated = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        if line.starts_with("prefix=") {
+            relocated.push_str("prefix=");
+            relocated.push_str(RELOCATED_PREFIX);
+            if line.ends_with("\r\n") {
+                relocated.push_str("\r\n");
+            } else if line.ends_with('\n') {
+                relocated.push('\n');
+            }
+            continue;
+        }
+
+        let mut line = line.to_string();
+        for environment_prefix in environment_prefixes.iter() {
+            line = replace_path_prefix(&line, environment_prefix, "${prefix}");
+        }
+        relocated.push_str(&line);
+    }
+
+    relocated
+}
+
+/// Relocate a pkg-config file in place, see [`relocate_pkg_config`].
+///
+/// # Returns
+/// Returns true if the file changed.
+pub fn relocate_pkg_config_file(
+    path: &Path,
+    environment_prefixes: &[String],
+) -> anyhow::Result<bool> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let relocated = relocate_pkg_config(&contents, environment_prefixes);
+    if relocated == contents {
+        return Ok(false);
+    }
+
+    std::fs::write(path, relocated)
+        .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    Ok(true)
+}
+
+/// Get the `prefix` variable of a pkg-config file.
+pub fn get_prefix(contents: &str) -> Option<&str> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("prefix="))
+        .map(|prefix| prefix.trim())
+}
+
+/// Check that the prefix of every pkg-config file in an environment, or a copy of one, exists.
+///
+/// This resolves prefixes the way pkgconf does: `${pcfiledir}` expands to the dir of the file,
+/// and `sysroot`, which is `PKG_CONFIG_SYSROOT_DIR`, is prepended to prefixes that start with `/`.
+/// Files that pass build against the environment they are in, wherever it was moved to.
+pub fn check_pkg_config_dirs(
+    environment_path: &Path,
+    sysroot: Option<&Path>,
+) -> anyhow::Result<Vec<PkgConfigProblem>> {
+    let mut problems = Vec::new();
+    for dir in PKG_CONFIG_DIRS {
+        let dir = environment_path.join(dir);
+        let dir_entries = match dir.read_dir() {
+            Ok(dir_entries) => dir_entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read `{}`", dir.display()));
+            }
+        };
+
+        let mut paths = Vec::new();
+        for dir_entry in dir_entries {
+            let path = dir_entry
+                .with_context(|| format!("failed to read `{}`", dir.display()))?
+                .path();
+            if path.extension().is_some_and(|extension| extension == "pc") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            let Some(prefix) = get_prefix(&contents) else {
+                continue;
+            };
+            let resolved = resolve_prefix(prefix, &dir, sysroot);
+            if !resolved.is_dir() {
+                problems.push(PkgConfigProblem {
+                    path,
+                    prefix: prefix.to_string(),
+                    resolved,
+                });
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Resolve the `prefix` of a pkg-config file in `pc_file_dir` to a path.
+fn resolve_prefix(prefix: &str, pc_file_dir: &Path, sysroot: Option<&Path>) -> PathBuf {
+    if let Some(rest) = prefix.strip_prefix("${pcfiledir}") {
+        let mut resolved = pc_file_dir.to_path_buf();
+        for component in rest
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty())
+        {
+            resolved.push(component);
+        }
+        return resolved;
+    }
+
+    match (prefix.strip_prefix('/'), sysroot) {
+        (Some(rest), Some(sysroot)) => sysroot.join(rest),
+        _ => PathBuf::from(prefix),
+    }
+}
+
+/// Replace occurrences of the path `from` in `input` with `to`.
+///
+/// Occurrences are only replaced if they end at a path boundary,
+/// so `/ucrt64` does not match the start of `/ucrt64-extra`.
+fn replace_path_prefix(input: &str, from: &str, to: &str) -> String {
+    if from.is_empty() {
+        return input.to_string();
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(index) = rest.find(from) {
+        let after = &rest[index + from.len()..];
+        let at_boundary = after
+            .chars()
+            .next()
+            .is_none_or(|c| matches!(c, '/' | '\\' | ' ' | '"' | '\'' | '\r' | '\n'));
+
+        output.push_str(&rest[..index]);
+        if at_boundary {
+            output.push_str(to);
+        } else {
+            output.push_str(from);
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+
+    output
+}
@@ -0,0 +1,29 @@
+use crate::packager::Plan;
+
+/// An extension to the packaging process.
+///
+/// Plugins are registered on a [`Packager`](crate::packager::Packager) with
+/// [`Packager::add_plugin`](crate::packager::Packager::add_plugin),
+/// usually by a tool that depends on this crate and enables them with its own cargo features.
+/// Every hook has a default implementation that does nothing, so plugins only implement the hooks they need.
+///
+/// Hooks are called in the order plugins were added.
+pub trait PackagerPlugin: Send + Sync {
+    /// Get the name of this plugin, for error messages.
+    fn name(&self) -> &str;
+
+    /// Modify the plan before it is validated and carried out.
+    ///
+    /// Files may be added, removed, or retargeted.
+    /// The total size of the plan is recomputed after every plugin has run.
+    fn modify_plan(&self, _plan: &mut Plan) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Check the plan after every plugin has modified it.
+    ///
+    /// Returning an error aborts planning, and so packaging.
+    fn validate(&self, _plan: &Plan) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
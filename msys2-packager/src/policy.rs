@@ -0,0 +1,546 @@
+use crate::report::OutputFormat;
+use anyhow::bail;
+use anyhow::Context;
+use msys2::Msys2Environment;
+use serde::Deserialize;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// The prefix of SPDX license expressions in the pacman database.
+const SPDX_PREFIX: &str = "spdx:";
+
+/// What the crates and MSYS2 packages of an app may be, checked before packaging.
+///
+/// Package names match crates by name, and MSYS2 packages by full name, like `mingw-w64-ucrt-x86_64-openssl`,
+/// or by name without the environment prefix, like `openssl`.
+/// License ids are compared case-insensitively.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct Policy {
+    /// The licenses that are allowed, or empty to allow every license that is not denied.
+    ///
+    /// Components without a declared license fail if this is not empty.
+    #[serde(default)]
+    pub allow_licenses: BTreeSet<String>,
+
+    /// The licenses that are not allowed
+    #[serde(default)]
+    pub deny_licenses: BTreeSet<String>,
+
+    /// Packages whose licenses are not checked, like ones with a separate agreement
+    #[serde(default)]
+    pub license_exceptions: BTreeSet<String>,
+
+    /// Packages that must not be used at all
+    #[serde(default)]
+    pub deny_packages: BTreeSet<String>,
+
+    /// Known vulnerable versions of packages
+    #[serde(default)]
+    pub advisories: Vec<Advisory>,
+
+    /// Whether to also run `cargo deny check advisories` over the crates, which checks them against the RustSec database
+    #[serde(default)]
+    pub cargo_deny: bool,
+}
+
+/// A known vulnerability in a package
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct Advisory {
+    /// The id of the advisory, like a CVE or RUSTSEC id
+    pub id: String,
+
+    /// The affected package
+    pub package: String,
+
+    /// The first version with a fix, or None if every version is affected.
+    ///
+    /// Versions are compared like pacman does, which also works for semver versions without pre-releases.
+    #[serde(default)]
+    pub fixed: Option<String>,
+}
+
+/// The kind of a [`PolicyComponent`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PolicyComponentKind {
+    /// A Rust crate
+    Crate,
+
+    /// An MSYS2 package
+    Msys2Package,
+}
+
+impl std::fmt::Display for PolicyComponentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Crate => write!(f, "crate"),
+            Self::Msys2Package => write!(f, "MSYS2 package"),
+        }
+    }
+}
+
+/// A crate or MSYS2 package to check against a [`Policy`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PolicyComponent {
+    /// The kind
+    pub kind: PolicyComponentKind,
+
+    /// The name
+    pub name: String,
+
+    /// The version
+    pub version: String,
+
+    /// The declared license, as an SPDX expression, if one is declared
+    pub license: Option<String>,
+}
+
+impl PolicyComponent {
+    /// Make a new [`PolicyComponent`] without a license.
+    pub fn new(kind: PolicyComponentKind, name: String, version: String) -> Self {
+        Self {
+            kind,
+            name,
+            version,
+            license: None,
+        }
+    }
+
+    /// Set the license, as an SPDX expression.
+    pub fn license(&mut self, license: String) -> &mut Self {
+        self.license = Some(license);
+        self
+    }
+
+    /// Set the license from the licenses of an MSYS2 package in the pacman database, like `spdx:LGPL-2.1-or-later`.
+    ///
+    /// Packages with several licenses are under all of them.
+    pub fn pacman_licenses(&mut self, licenses: &[String]) -> &mut Self {
+        if licenses.is_empty() {
+            return self;
+        }
+
+        let license = licenses
+            .iter()
+            .map(|license| format!("({})", license.strip_prefix(SPDX_PREFIX).unwrap_or(license)))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        self.license(license)
+    }
+
+    /// Check if a policy entry names this component.
+    fn is_named(&self, name: &str) -> bool {
+        if self.name == name {
+            return true;
+        }
+
+        self.kind == PolicyComponentKind::Msys2Package
+            && Msys2Environment::from_package_name(&self.name)
+                .and_then(|environment| self.name.strip_prefix(environment.get_package_prefix()))
+                .is_some_and(|base_name| base_name == name)
+    }
+}
+
+/// A component that breaks a [`Policy`]
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct PolicyViolation {
+    /// The kind of the component
+    pub kind: PolicyComponentKind,
+
+    /// The name of the component
+    pub name: String,
+
+    /// The version of the component
+    pub version: String,
+
+    /// Why the component breaks the policy
+    pub reason: String,
+}
+
+/// The results of checking components against a [`Policy`]
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct PolicyReport {
+    /// The number of components that were checked
+    pub num_components: usize,
+
+    /// The components that break the policy
+    pub violations: Vec<PolicyViolation>,
+}
+
+impl PolicyReport {
+    /// Check if no component breaks the policy.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Print this report to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PolicyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for violation in self.violations.iter() {
+            writeln!(
+                f,
+                "{} `{}` {}: {}",
+                violation.kind, violation.name, violation.version, violation.reason
+            )?;
+        }
+        write!(
+            f,
+            "Checked {} components, {} violations",
+            self.num_components,
+            self.violations.len()
+        )
+    }
+}
+
+impl Policy {
+    /// Load a policy from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let policy: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+
+        Ok(policy)
+    }
+
+    /// Check components against this policy.
+    pub fn check(&self, components: &[PolicyComponent]) -> PolicyReport {
+        let mut violations = Vec::new();
+        for component in components.iter() {
+            let mut violate = |reason: String| {
+                violations.push(PolicyViolation {
+                    kind: component.kind,
+                    name: component.name.clone(),
+                    version: component.version.clone(),
+                    reason,
+                });
+            };
+
+            if self
+                .deny_packages
+                .iter()
+                .any(|name| component.is_named(name))
+            {
+                violate("the package is denied".into());
+            }
+
+            for advisory in self.advisories.iter() {
+                if !component.is_named(&advisory.package) {
+                    continue;
+                }
+                match advisory.fixed.as_deref() {
+                    Some(fixed) if compare_versions(&component.version, fixed).is_lt() => {
+                        violate(format!("affected by {}, fixed in {fixed}", advisory.id));
+                    }
+                    Some(_) => {}
+                    None => {
+                        violate(format!("affected by {}, which has no fix", advisory.id));
+                    }
+                }
+            }
+
+            if self
+                .license_exceptions
+                .iter()
+                .any(|name| component.is_named(name))
+            {
+                continue;
+            }
+            match component.license.as_deref() {
+                Some(license) => match self.is_license_allowed(license) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        violate(format!("the license `{license}` is not allowed"));
+                    }
+                    Err(error) => {
+                        violate(format!("the license `{license}` is invalid: {error}"));
+                    }
+                },
+                None if !self.allow_licenses.is_empty() => {
+                    violate("no license is declared".into());
+                }
+                None => {}
+            }
+        }
+
+        PolicyReport {
+            num_components: components.len(),
+            violations,
+        }
+    }
+
+    /// Check if an SPDX license expression is allowed by this policy.
+    ///
+    /// `OR` expressions are allowed if any side is, and `AND` expressions if both sides are.
+    /// Exceptions, like `WITH GCC-exception-3.1`, are allowed if their license is.
+    ///
+    /// # Errors
+    /// Returns an error if the expression is invalid.
+    pub fn is_license_allowed(&self, expression: &str) -> anyhow::Result<bool> {
+        let tokens = tokenize_license(expression);
+        let mut parser = LicenseParser {
+            policy: self,
+            tokens: &tokens,
+            index: 0,
+        };
+        let allowed = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.index) {
+            bail!("unexpected `{token}`");
+        }
+
+        Ok(allowed)
+    }
+
+    /// Check if a single license id is allowed by this policy.
+    ///
+    /// A trailing `+`, which means "or later", is ignored.
+    fn is_license_id_allowed(&self, id: &str) -> bool {
+        let id = id.trim_end_matches('+');
+        let contains =
+            |ids: &BTreeSet<String>| ids.iter().any(|other| other.eq_ignore_ascii_case(id));
+
+        !contains(&self.deny_licenses)
+            && (self.allow_licenses.is_empty() || contains(&self.allow_licenses))
+    }
+}
+
+/// Split an SPDX license expression into parens and words.
+///
+/// Older crates separate licenses with `/`, which means `OR`.
+fn tokenize_license(expression: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in expression.char_indices() {
+        let is_separator = c.is_whitespace() || matches!(c, '(' | ')' | '/');
+        if is_separator {
+            if let Some(start) = start.take() {
+                tokens.push(&expression[start..index]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&expression[index..index + c.len_utf8()]);
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&expression[start..]);
+    }
+
+    tokens
+}
+
+/// A recursive descent parser that evaluates SPDX license expressions against a policy.
+struct LicenseParser<'a> {
+    /// The policy to evaluate against
+    policy: &'a Policy,
+
+    /// The tokens of the expression
+    tokens: &'a [&'a str],
+
+    /// The index of the next token
+    index: usize,
+}
+
+impl LicenseParser<'_> {
+    /// Get the next token if it is a given operator, ignoring case.
+    fn eat_operator(&mut self, operators: &[&str]) -> bool {
+        let is_operator = self.tokens.get(self.index).is_some_and(|token| {
+            operators
+                .iter()
+                .any(|operator| token.eq_ignore_ascii_case(operator))
+        });
+        if is_operator {
+            self.index += 1;
+        }
+        is_operator
+    }
+
+    /// Parse `<and> (OR <and>)*`.
+    fn parse_or(&mut self) -> anyhow::Result<bool> {
+        let mut allowed = self.parse_and()?;
+        while self.eat_operator(&["OR", "/"]) {
+            // Both sides are parsed, so invalid expressions are always errors.
+            let other = self.parse_and()?;
+            allowed = allowed || other;
+        }
+        Ok(allowed)
+    }
+
+    /// Parse `<atom> (AND <atom>)*`.
+    fn parse_and(&mut self) -> anyhow::Result<bool> {
+        let mut allowed = self.parse_atom()?;
+        while self.eat_operator(&["AND"]) {
+            let other = self.parse_atom()?;
+            allowed = allowed && other;
+        }
+        Ok(allowed)
+    }
+
+    /// Parse `( <or> )` or `<id> [WITH <exception>]`.
+    fn parse_atom(&mut self) -> anyhow::Result<bool> {
+        let token = *self
+            .tokens
+            .get(self.index)
+            .context("unexpected end of expression")?;
+        self.index += 1;
+
+        match token {
+            "(" => {
+                let allowed = self.parse_or()?;
+                if self.tokens.get(self.index) != Some(&")") {
+                    bail!("missing `)`");
+                }
+                self.index += 1;
+                Ok(allowed)
+            }
+            ")" | "/" => bail!("unexpected `{token}`"),
+            _ if ["AND", "OR", "WITH"]
+                .iter()
+                .any(|operator| token.eq_ignore_ascii_case(operator)) =>
+            {
+                bail!("unexpected `{token}`")
+            }
+            _ => {
+                if self.eat_operator(&["WITH"]) {
+                    self.tokens
+                        .get(self.index)
+                        .context("missing exception after `WITH`")?;
+                    self.index += 1;
+                }
+                Ok(self.policy.is_license_id_allowed(token))
+            }
+        }
+    }
+}
+
+/// Compare two package versions like pacman's `vercmp`, like `1:2.0-1` and `1.10-2`.
+///
+/// Versions are `[epoch:]version[-release]`, where the epoch is compared first,
+/// then the version and release, segment by segment.
+/// Numeric segments compare as numbers and are newer than alphabetic ones,
+/// and a trailing alphabetic segment is a pre-release, so `1.0rc1` is older than `1.0`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a_version, a_release) = split_version(a);
+    let (b_epoch, b_version, b_release) = split_version(b);
+
+    compare_segments(a_epoch, b_epoch)
+        .then_with(|| compare_segments(a_version, b_version))
+        .then_with(|| match (a_release, b_release) {
+            (Some(a_release), Some(b_release)) => compare_segments(a_release, b_release),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split a version into its epoch, version, and release.
+fn split_version(version: &str) -> (&str, &str, Option<&str>) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.bytes().all(|b| b.is_ascii_digit()) => (epoch, rest),
+        _ => ("0", version),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compare two versions segment by segment, like `rpmvercmp`.
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+    loop {
+        let is_separator = |b: &u8| !b.is_ascii_alphanumeric();
+        a = &a[a.iter().position(|b| !is_separator(b)).unwrap_or(a.len())..];
+        b = &b[b.iter().position(|b| !is_separator(b)).unwrap_or(b.len())..];
+        let (Some(a_first), Some(b_first)) = (a.first(), b.first()) else {
+            break;
+        };
+
+        let is_numeric = a_first.is_ascii_digit();
+        if is_numeric != b_first.is_ascii_digit() {
+            // Numeric segments are newer than alphabetic ones.
+            return if is_numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let take = |s: &[u8]| {
+            s.iter()
+                .position(|c| {
+                    if is_numeric {
+                        !c.is_ascii_digit()
+                    } else {
+                        !c.is_ascii_alphabetic()
+                    }
+                })
+                .unwrap_or(s.len())
+        };
+        let a_len = take(a);
+        let b_len = take(b);
+        let (a_segment, a_rest) = a.split_at(a_len);
+        let (b_segment, b_rest) = b.split_at(b_len);
+
+        let ordering = if is_numeric {
+            let a_segment = trim_leading_zeros(a_segment);
+            let b_segment = trim_leading_zeros(b_segment);
+            a_segment
+                .len()
+                .cmp(&b_segment.len())
+                .then_with(|| a_segment.cmp(b_segment))
+        } else {
+            a_segment.cmp(b_segment)
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+
+        a = a_rest;
+        b = b_rest;
+    }
+
+    // A version with more segments is newer, unless the extra segment is alphabetic, like a pre-release.
+    match (a.first(), b.first()) {
+        (None, None) => Ordering::Equal,
+        (Some(a_first), None) if a_first.is_ascii_alphabetic() => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(b_first)) if b_first.is_ascii_alphabetic() => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), Some(_)) => Ordering::Equal,
+    }
+}
+
+/// Remove the leading zeros of a numeric version segment.
+fn trim_leading_zeros(segment: &[u8]) -> &[u8] {
+    let start = segment
+        .iter()
+        .position(|c| *c != b'0')
+        .unwrap_or(segment.len());
+    &segment[start..]
+}
@@ -0,0 +1,361 @@
+use crate::packager::Plan;
+use crate::packager::PlannedFile;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// An error that may occur while parsing an [`OutputFormat`] from a string.
+#[derive(Debug)]
+pub struct OutputFormatFromStrError(String);
+
+impl std::fmt::Display for OutputFormatFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid output format", self.0)
+    }
+}
+
+impl std::error::Error for OutputFormatFromStrError {}
+
+/// The format of the results printed by the CLIs
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+
+    /// A single JSON object, for other tools to consume
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = OutputFormatFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(OutputFormatFromStrError(input.into())),
+        }
+    }
+}
+
+/// How long the phases of a packaging run took.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct Timings {
+    /// The time spent resolving and planning, in seconds.
+    pub plan_secs: f64,
+
+    /// The time spent copying files, in seconds.
+    ///
+    /// This is None if nothing was copied, like for a dry run.
+    pub copy_secs: Option<f64>,
+}
+
+/// A packaged file and the MSYS2 package it came from.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct OwnedFile {
+    /// The file destination, relative to the package top level.
+    pub dest: PathBuf,
+
+    /// The name of the MSYS2 package that installed the source file.
+    ///
+    /// This is None for files that do not come from an MSYS2 package, like the user's binaries.
+    pub package: Option<String>,
+}
+
+/// An MSYS2 package that packaged files came from.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct OwningPackage {
+    /// The package name, like `mingw-w64-ucrt-x86_64-gtk4`.
+    pub name: String,
+
+    /// The installed package version.
+    pub version: String,
+
+    /// The number of packaged files that came from this package.
+    pub num_files: usize,
+}
+
+/// The MSYS2 packages that packaged files came from.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct OwnershipReport {
+    /// Every packaged file, in plan order.
+    pub files: Vec<OwnedFile>,
+
+    /// Packages that own at least one packaged file, sorted by name.
+    pub packages: Vec<OwningPackage>,
+}
+
+impl std::fmt::Display for OwnershipReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in self.files.iter() {
+            writeln!(
+                f,
+                "`{}` <- {}",
+                file.dest.display(),
+                file.package.as_deref().unwrap_or("(no package)")
+            )?;
+        }
+        for package in self.packages.iter() {
+            writeln!(
+                f,
+                "{} {} ({} files)",
+                package.name, package.version, package.num_files
+            )?;
+        }
+        write!(f, "{} packages", self.packages.len())
+    }
+}
+
+/// A packaged file and its size.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct SizedFile {
+    /// The file destination, relative to the package top level.
+    pub dest: PathBuf,
+
+    /// The category of the file, like `gstreamer` or `icons`.
+    pub category: String,
+
+    /// The size of the source, in bytes.
+    pub size: u64,
+
+    /// The size in the out dir, after upx, in bytes.
+    ///
+    /// This is None if the file was not packaged yet.
+    pub packaged_size: Option<u64>,
+}
+
+/// The total size of a category of packaged files.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct SizeCategory {
+    /// The category name, like `gstreamer` or `icons`.
+    pub name: String,
+
+    /// The number of files in this category.
+    pub num_files: usize,
+
+    /// The total size of the sources, in bytes.
+    pub size: u64,
+
+    /// The total size in the out dir, after upx, in bytes.
+    ///
+    /// This is None if any file in the category was not packaged yet.
+    pub packaged_size: Option<u64>,
+}
+
+/// A breakdown of the size of a package.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct SizeReport {
+    /// Every packaged file, largest first.
+    pub files: Vec<SizedFile>,
+
+    /// The file categories, largest first.
+    pub categories: Vec<SizeCategory>,
+}
+
+impl SizeReport {
+    /// Make a size report from a list of files, sorting files and computing category totals.
+    ///
+    /// Sorting uses the packaged size, falling back to the source size for files that were not packaged yet.
+    pub fn new(mut files: Vec<SizedFile>) -> Self {
+        files.sort_by(|a, b| {
+            let a_size = a.packaged_size.unwrap_or(a.size);
+            let b_size = b.packaged_size.unwrap_or(b.size);
+            b_size.cmp(&a_size).then_with(|| a.dest.cmp(&b.dest))
+        });
+
+        let mut categories: Vec<SizeCategory> = Vec::new();
+        for file in files.iter() {
+            let category = match categories
+                .iter_mut()
+                .find(|category| category.name == file.category)
+            {
+                Some(category) => category,
+                None => {
+                    categories.push(SizeCategory {
+                        name: file.category.clone(),
+                        num_files: 0,
+                        size: 0,
+                        packaged_size: Some(0),
+                    });
+                    categories.last_mut().expect("a category was just pushed")
+                }
+            };
+            category.num_files += 1;
+            category.size += file.size;
+            category.packaged_size = category
+                .packaged_size
+                .zip(file.packaged_size)
+                .map(|(total, size)| total + size);
+        }
+        categories.sort_by(|a, b| {
+            let a_size = a.packaged_size.unwrap_or(a.size);
+            let b_size = b.packaged_size.unwrap_or(b.size);
+            b_size.cmp(&a_size).then_with(|| a.name.cmp(&b.name))
+        });
+
+        Self { files, categories }
+    }
+}
+
+impl SizeReport {
+    /// Print this report to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_sizes = |size: u64, packaged_size: Option<u64>| match packaged_size {
+            Some(packaged_size) => format!("{size} -> {packaged_size} bytes"),
+            None => format!("{size} bytes"),
+        };
+
+        writeln!(f, "Files:")?;
+        for file in self.files.iter() {
+            writeln!(
+                f,
+                "  `{}` ({}): {}",
+                file.dest.display(),
+                file.category,
+                format_sizes(file.size, file.packaged_size)
+            )?;
+        }
+        write!(f, "Categories:")?;
+        for category in self.categories.iter() {
+            write!(
+                f,
+                "\n  {} ({} files): {}",
+                category.name,
+                category.num_files,
+                format_sizes(category.size, category.packaged_size)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a file is in a package, see [`Plan::explain`].
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct FileExplanation {
+    /// The file, followed by the files that depend on it, in order.
+    ///
+    /// This is empty if the file is not in the package.
+    pub files: Vec<PlannedFile>,
+}
+
+impl FileExplanation {
+    /// Print this explanation to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                print!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for FileExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, file) in self.files.iter().enumerate() {
+            if i != 0 {
+                write!(f, "  needed by ")?;
+            }
+            writeln!(f, "`{}` ({})", file.dest.display(), file.reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A summary of a packaging run.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct PackageReport {
+    /// The plan that was carried out
+    #[serde(flatten)]
+    pub plan: Plan,
+
+    /// Phase timings
+    pub timings: Timings,
+
+    /// The MSYS2 packages that packaged files came from.
+    ///
+    /// This is only filled in when requested, as it is expensive to compute.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<OwnershipReport>,
+
+    /// A breakdown of the package size.
+    ///
+    /// This is only filled in when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<SizeReport>,
+}
+
+impl PackageReport {
+    /// Print this report to stdout in the given format.
+    ///
+    /// In the text format, dry runs list the full plan,
+    /// while real runs only print a summary as the packager already logs what it does.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => match self.timings.copy_secs {
+                Some(copy_secs) => {
+                    if let Some(ownership) = self.ownership.as_ref() {
+                        println!("{ownership}");
+                    }
+                    if let Some(sizes) = self.sizes.as_ref() {
+                        println!("{sizes}");
+                    }
+                    println!(
+                        "Packaged {} files ({} bytes) in {:.2}s",
+                        self.plan.files.len(),
+                        self.plan.total_size,
+                        self.timings.plan_secs + copy_secs
+                    );
+                }
+                None => {
+                    if let Some(ownership) = self.ownership.as_ref() {
+                        println!("{ownership}");
+                    }
+                    if let Some(sizes) = self.sizes.as_ref() {
+                        println!("{sizes}");
+                    }
+                    println!("{}", self.plan);
+                }
+            },
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
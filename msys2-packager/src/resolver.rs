@@ -0,0 +1,641 @@
+use crate::report::OutputFormat;
+use crate::util::get_dll_imports;
+use crate::util::get_dll_imports_from_bytes;
+use crate::util::is_api_set_dll;
+use crate::util::is_system_dll;
+use crate::util::lookup_msys2_file;
+use crate::util::matches_dll_pattern;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A source of information about binaries, used to resolve their dependencies.
+pub trait DependencySource {
+    /// Get the names of the dlls imported by the binary at the given path.
+    fn get_imports(&self, path: &Path) -> anyhow::Result<Vec<String>>;
+
+    /// Locate a library by name.
+    ///
+    /// # Returns
+    /// Returns None if the library does not exist.
+    fn lookup(&self, name: &str) -> anyhow::Result<Option<PathBuf>>;
+}
+
+/// A [`DependencySource`] backed by an MSYS2 environment on disk.
+#[derive(Debug)]
+pub struct Msys2DependencySource {
+    environment_path: Utf8PathBuf,
+}
+
+impl Msys2DependencySource {
+    /// Make a new [`Msys2DependencySource`] from the path to an MSYS2 environment, like `C:/msys64/ucrt64`.
+    pub fn new(environment_path: Utf8PathBuf) -> Self {
+        Self { environment_path }
+    }
+}
+
+impl DependencySource for Msys2DependencySource {
+    fn get_imports(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        get_dll_imports(path)
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        lookup_msys2_file(&self.environment_path, OsStr::new(name))
+    }
+}
+
+/// A [`DependencySource`] backed by a single dir on disk, like a package dir.
+///
+/// Libraries are only located directly inside the dir, like Windows does for the dir of an exe.
+#[derive(Debug)]
+pub struct DirDependencySource {
+    dir: PathBuf,
+}
+
+impl DirDependencySource {
+    /// Make a new [`DirDependencySource`] from the path to a dir.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl DependencySource for DirDependencySource {
+    fn get_imports(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        get_dll_imports(path)
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        let path = self.dir.join(name);
+        Ok(path.is_file().then_some(path))
+    }
+}
+
+/// A [`DependencySource`] backed by import tables held in memory.
+///
+/// This does not touch the filesystem,
+/// so it can be used to plan packages from uploaded binaries or manifests, like in a browser.
+/// Libraries are located by matching their file names case-insensitively, like Windows does.
+///
+/// This (de)serializes as a map of binary paths to the names of the dlls they import.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MemoryDependencySource {
+    binaries: BTreeMap<PathBuf, Vec<String>>,
+}
+
+impl MemoryDependencySource {
+    /// Make a new, empty [`MemoryDependencySource`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a binary with the given imports.
+    ///
+    /// Binaries can be located by their file names.
+    /// If a binary was already added at the same path, its imports are replaced.
+    pub fn add_binary(&mut self, path: PathBuf, imports: Vec<String>) -> &mut Self {
+        self.binaries.insert(path, imports);
+        self
+    }
+
+    /// Add a binary, reading its imports from the contents of a PE file.
+    pub fn add_binary_from_bytes(
+        &mut self,
+        path: PathBuf,
+        bytes: &[u8],
+    ) -> anyhow::Result<&mut Self> {
+        let imports = get_dll_imports_from_bytes(bytes)
+            .with_context(|| format!("failed to get imports for `{}`", path.display()))?;
+        Ok(self.add_binary(path, imports))
+    }
+}
+
+impl DependencySource for MemoryDependencySource {
+    fn get_imports(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.binaries
+            .get(path)
+            .cloned()
+            .with_context(|| format!("`{}` was not added", path.display()))
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        Ok(self
+            .binaries
+            .keys()
+            .find(|path| {
+                path.file_name()
+                    .is_some_and(|file_name| file_name.eq_ignore_ascii_case(name))
+            })
+            .cloned())
+    }
+}
+
+/// A library that was located while resolving dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResolvedLibrary {
+    /// The name of the library, as it appears in import tables.
+    pub name: String,
+
+    /// The location of the library.
+    pub path: PathBuf,
+}
+
+/// A dll import of a binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Import {
+    /// The binary with the import.
+    pub importer: PathBuf,
+
+    /// The name of the imported dll.
+    pub name: String,
+}
+
+/// The result of resolving dependencies.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct Resolution {
+    /// Libraries that were located, in the order they were resolved.
+    pub libraries: Vec<ResolvedLibrary>,
+
+    /// Names of api set dlls that were encountered.
+    ///
+    /// These are provided by the OS and are never packaged.
+    pub api_sets: Vec<String>,
+
+    /// Names of libraries that could not be located.
+    pub unresolved: Vec<String>,
+
+    /// Every non-system import that was encountered, in the order it was encountered.
+    pub imports: Vec<Import>,
+
+    /// Every system import that was encountered, in the order it was encountered.
+    ///
+    /// These are provided by the OS and are never packaged.
+    pub system_imports: Vec<Import>,
+
+    /// Names of libraries that were not located, as they matched an exclude pattern.
+    pub excluded: Vec<String>,
+}
+
+/// Options that change how dependencies are resolved.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ResolveOptions {
+    /// Glob patterns of dll names, like `libicudata*.dll`, that are never located or followed.
+    ///
+    /// See [`matches_dll_pattern`].
+    pub exclude: Vec<String>,
+
+    /// Glob patterns of dll names to treat as system dlls, in addition to those known by [`is_system_dll`].
+    ///
+    /// System dlls are provided by the OS, so they are never located or followed.
+    pub system: Vec<String>,
+
+    /// Glob patterns of dll names to locate and follow, even if [`is_system_dll`] considers them system dlls.
+    ///
+    /// This takes precedence over [`ResolveOptions::system`].
+    pub bundle: Vec<String>,
+}
+
+impl ResolveOptions {
+    /// Make new, default [`ResolveOptions`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a glob pattern of dll names to exclude.
+    pub fn exclude(&mut self, pattern: String) -> &mut Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    /// Treat dlls with names matching a glob pattern as system dlls.
+    pub fn system(&mut self, pattern: String) -> &mut Self {
+        self.system.push(pattern);
+        self
+    }
+
+    /// Locate and follow dlls with names matching a glob pattern, even if they are considered system dlls.
+    pub fn bundle(&mut self, pattern: String) -> &mut Self {
+        self.bundle.push(pattern);
+        self
+    }
+
+    /// Check if a dll is a system dll, taking the system and bundle patterns into account.
+    pub fn is_system_dll(&self, name: &str) -> bool {
+        let matches_any = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| matches_dll_pattern(pattern, name))
+        };
+
+        !matches_any(&self.bundle) && (is_system_dll(name) || matches_any(&self.system))
+    }
+
+    /// Check if a dll name matches an exclude pattern.
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| matches_dll_pattern(pattern, name))
+    }
+}
+
+/// An edge of a [`DependencyGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct DependencyEdge {
+    /// The file name of the binary with the import.
+    pub importer: String,
+
+    /// The name of the imported dll.
+    pub name: String,
+}
+
+/// The graph of which binary imports which dll, from resolving dependencies.
+///
+/// System dlls are left out.
+#[derive(Debug, Clone, Default, Serialize)]
+#[non_exhaustive]
+pub struct DependencyGraph {
+    /// Every import, in the order it was encountered.
+    pub edges: Vec<DependencyEdge>,
+
+    /// Names of imported dlls that could not be located.
+    pub unresolved: Vec<String>,
+}
+
+impl DependencyGraph {
+    /// Make a graph from the result of resolving dependencies.
+    pub fn from_resolution(resolution: &Resolution) -> Self {
+        let edges = resolution
+            .imports
+            .iter()
+            .map(|import| DependencyEdge {
+                importer: import
+                    .importer
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| import.importer.display().to_string()),
+                name: import.name.clone(),
+            })
+            .collect();
+
+        Self {
+            edges,
+            unresolved: resolution.unresolved.clone(),
+        }
+    }
+
+    /// Write this graph to a file.
+    ///
+    /// The graph is written as JSON if the path ends in `.json`, and as DOT otherwise.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let data = if is_json {
+            serde_json::to_string_pretty(self)?
+        } else {
+            self.to_dot()
+        };
+
+        std::fs::write(path, data).with_context(|| format!("failed to write `{}`", path.display()))
+    }
+
+    /// Render this graph in the Graphviz DOT language.
+    ///
+    /// Unresolved dlls are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut dot = String::from("digraph dependencies {\n");
+        for name in self.unresolved.iter() {
+            dot.push_str(&format!("    {} [color=red];\n", quote(name)));
+        }
+        for edge in self.edges.iter() {
+            dot.push_str(&format!(
+                "    {} -> {};\n",
+                quote(&edge.importer),
+                quote(&edge.name)
+            ));
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+/// Resolve the dependencies of the given binaries.
+///
+/// Binaries are scanned breadth-first, in the order they are given,
+/// so the result only depends on the inputs and not on things like hash iteration order.
+/// The given binaries are considered to satisfy imports of their file names.
+/// System dlls are not resolved, though imports of them are recorded.
+pub fn resolve_dependencies<S>(source: &S, binaries: &[&Path]) -> anyhow::Result<Resolution>
+where
+    S: DependencySource + ?Sized,
+{
+    resolve_dependencies_with_options(source, binaries, &ResolveOptions::default())
+}
+
+/// Resolve the dependencies of the given binaries, like [`resolve_dependencies`], with options.
+pub fn resolve_dependencies_with_options<S>(
+    source: &S,
+    binaries: &[&Path],
+    options: &ResolveOptions,
+) -> anyhow::Result<Resolution>
+where
+    S: DependencySource + ?Sized,
+{
+    let mut resolution = Resolution::default();
+    let mut known_libraries = HashSet::new();
+    let mut queue = VecDeque::with_capacity(binaries.len());
+
+    for binary in binaries.iter() {
+        let file_name = binary
+            .file_name()
+            .with_context(|| format!("`{}` is missing a file name", binary.display()))?;
+        known_libraries.insert(file_name.to_string_lossy().into_owned());
+        queue.push_back(binary.to_path_buf());
+    }
+
+    while let Some(path) = queue.pop_front() {
+        let imports = source
+            .get_imports(&path)
+            .with_context(|| format!("failed to get bin deps for `{}`", path.display()))?;
+
+        for name in imports {
+            if options.is_system_dll(&name) {
+                resolution.system_imports.push(Import {
+                    importer: path.clone(),
+                    name,
+                });
+                continue;
+            }
+
+            resolution.imports.push(Import {
+                importer: path.clone(),
+                name: name.clone(),
+            });
+
+            if !known_libraries.insert(name.clone()) {
+                continue;
+            }
+
+            if is_api_set_dll(&name) {
+                resolution.api_sets.push(name);
+                continue;
+            }
+
+            if options.is_excluded(&name) {
+                resolution.excluded.push(name);
+                continue;
+            }
+
+            match source
+                .lookup(&name)
+                .with_context(|| format!("failed to locate `{name}`"))?
+            {
+                Some(library_path) => {
+                    queue.push_back(library_path.clone());
+                    resolution.libraries.push(ResolvedLibrary {
+                        name,
+                        path: library_path,
+                    });
+                }
+                None => {
+                    resolution.unresolved.push(name);
+                }
+            }
+        }
+    }
+
+    Ok(resolution)
+}
+
+/// Where an import in an [`ImportTree`] was located.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ImportStatus {
+    /// The binary itself, at the root of the tree.
+    Root,
+
+    /// Located next to the binary, like in a package dir.
+    Bundled,
+
+    /// Located in the MSYS2 environment.
+    Msys2,
+
+    /// A system dll, provided by the OS.
+    System,
+
+    /// An api set dll, provided by the OS.
+    ApiSet,
+
+    /// Not located anywhere.
+    Missing,
+}
+
+impl ImportStatus {
+    /// Get the name of this status.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::Root => "root",
+            Self::Bundled => "bundled",
+            Self::Msys2 => "msys2",
+            Self::System => "system",
+            Self::ApiSet => "api-set",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// A binary and the tree of dlls it imports.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ImportTree {
+    /// The name of the binary, as it appears in import tables.
+    pub name: String,
+
+    /// The location of the binary, if it was located.
+    pub path: Option<PathBuf>,
+
+    /// Where the binary was located.
+    pub status: ImportStatus,
+
+    /// Whether this binary already appeared earlier in the tree.
+    ///
+    /// The imports of repeated binaries are only listed the first time they appear.
+    pub repeated: bool,
+
+    /// The imports of this binary.
+    pub imports: Vec<ImportTree>,
+}
+
+impl ImportTree {
+    /// Print this tree to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write this tree and its imports, indented by `depth`.
+    fn fmt_depth(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{:indent$}{}", "", self.name, indent = depth * 2)?;
+        if self.status != ImportStatus::Root {
+            write!(f, " [{}]", self.status.get_name())?;
+        }
+        if let Some(path) = self.path.as_ref() {
+            write!(f, " `{}`", path.display())?;
+        }
+        if self.repeated {
+            write!(f, " (see above)")?;
+        }
+
+        for import in self.imports.iter() {
+            writeln!(f)?;
+            import.fmt_depth(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ImportTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_depth(f, 0)
+    }
+}
+
+/// Build the recursive import tree of a binary.
+///
+/// Imports are first looked up in `bundle`, if given, and then in `source`.
+/// Unlike [`resolve_dependencies`], system dlls are included in the tree, though their imports are not.
+pub fn build_import_tree<B, S>(
+    bundle: Option<&B>,
+    source: &S,
+    binary: &Path,
+) -> anyhow::Result<ImportTree>
+where
+    B: DependencySource + ?Sized,
+    S: DependencySource + ?Sized,
+{
+    build_import_tree_with_options(bundle, source, binary, &ResolveOptions::default())
+}
+
+/// Build the recursive import tree of a binary, like [`build_import_tree`], with options.
+///
+/// Only the system dll rules of `options` are used, so excluded dlls are still looked up.
+pub fn build_import_tree_with_options<B, S>(
+    bundle: Option<&B>,
+    source: &S,
+    binary: &Path,
+    options: &ResolveOptions,
+) -> anyhow::Result<ImportTree>
+where
+    B: DependencySource + ?Sized,
+    S: DependencySource + ?Sized,
+{
+    let name = binary
+        .file_name()
+        .with_context(|| format!("`{}` is missing a file name", binary.display()))?
+        .to_string_lossy()
+        .into_owned();
+
+    let mut seen = HashSet::new();
+    seen.insert(name.to_ascii_lowercase());
+
+    let mut tree = ImportTree {
+        name,
+        path: Some(binary.to_path_buf()),
+        status: ImportStatus::Root,
+        repeated: false,
+        imports: Vec::new(),
+    };
+    add_import_tree_imports(bundle, source, options, &mut tree, &mut seen)?;
+
+    Ok(tree)
+}
+
+/// Fill in the imports of a located node of an import tree, recursively.
+///
+/// `seen` holds the lowercase names of binaries whose imports were already listed.
+fn add_import_tree_imports<B, S>(
+    bundle: Option<&B>,
+    source: &S,
+    options: &ResolveOptions,
+    tree: &mut ImportTree,
+    seen: &mut HashSet<String>,
+) -> anyhow::Result<()>
+where
+    B: DependencySource + ?Sized,
+    S: DependencySource + ?Sized,
+{
+    let path = match tree.path.as_ref() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let imports = match tree.status {
+        ImportStatus::Bundled | ImportStatus::Root => bundle
+            .map(|bundle| bundle.get_imports(path))
+            .unwrap_or_else(|| source.get_imports(path)),
+        _ => source.get_imports(path),
+    }
+    .with_context(|| format!("failed to get bin deps for `{}`", path.display()))?;
+
+    for name in imports {
+        let (status, path) = if options.is_system_dll(&name) {
+            (ImportStatus::System, None)
+        } else if is_api_set_dll(&name) {
+            (ImportStatus::ApiSet, None)
+        } else if let Some(path) = bundle
+            .map(|bundle| bundle.lookup(&name))
+            .transpose()
+            .with_context(|| format!("failed to locate `{name}`"))?
+            .flatten()
+        {
+            (ImportStatus::Bundled, Some(path))
+        } else if let Some(path) = source
+            .lookup(&name)
+            .with_context(|| format!("failed to locate `{name}`"))?
+        {
+            (ImportStatus::Msys2, Some(path))
+        } else {
+            (ImportStatus::Missing, None)
+        };
+
+        let repeated = path.is_some() && !seen.insert(name.to_ascii_lowercase());
+        let mut import = ImportTree {
+            name,
+            path,
+            status,
+            repeated,
+            imports: Vec::new(),
+        };
+        if !repeated {
+            add_import_tree_imports(bundle, source, options, &mut import, seen)?;
+        }
+        tree.imports.push(import);
+    }
+
+    Ok(())
+}
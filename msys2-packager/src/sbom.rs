@@ -0,0 +1,442 @@
+use crate::pacman::LocalDatabase;
+use crate::report::OwnershipReport;
+use anyhow::Context;
+use camino::Utf8Path;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// An error that may occur while parsing an [`SbomFormat`] from a string.
+#[derive(Debug)]
+pub struct SbomFormatFromStrError(String);
+
+impl std::fmt::Display for SbomFormatFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid SBOM format", self.0)
+    }
+}
+
+impl std::error::Error for SbomFormatFromStrError {}
+
+/// The format of a software bill of materials
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum SbomFormat {
+    /// CycloneDX 1.5, as JSON
+    CycloneDx,
+
+    /// SPDX 2.3, as JSON
+    Spdx,
+}
+
+impl SbomFormat {
+    /// Get the conventional file name for an SBOM in this format.
+    pub fn get_file_name(self) -> &'static str {
+        match self {
+            Self::CycloneDx => "sbom.cdx.json",
+            Self::Spdx => "sbom.spdx.json",
+        }
+    }
+}
+
+impl std::str::FromStr for SbomFormat {
+    type Err = SbomFormatFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "cyclonedx" => Ok(Self::CycloneDx),
+            "spdx" => Ok(Self::Spdx),
+            _ => Err(SbomFormatFromStrError(input.into())),
+        }
+    }
+}
+
+/// A file in a package, and its hash.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SbomFile {
+    /// The path of the file, relative to the package top level.
+    pub path: PathBuf,
+
+    /// The lowercase hex SHA-256 of the packaged file.
+    pub sha256: String,
+}
+
+impl SbomFile {
+    /// Hash a packaged file.
+    ///
+    /// `path` is relative to `out_dir`.
+    pub fn new(out_dir: &Path, path: PathBuf) -> anyhow::Result<Self> {
+        let full_path = out_dir.join(&path);
+        let sha256 = sha256_file(&full_path)
+            .with_context(|| format!("failed to hash `{}`", full_path.display()))?;
+
+        Ok(Self { path, sha256 })
+    }
+}
+
+/// A component of a package, like a crate or an MSYS2 package.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SbomComponent {
+    /// The component name.
+    pub name: String,
+
+    /// The component version.
+    pub version: String,
+
+    /// The package URL, like `pkg:cargo/anyhow@1.0.69`.
+    pub purl: Option<String>,
+
+    /// Declared licenses.
+    ///
+    /// Entries prefixed by `spdx:`, like in MSYS2 packages, or set through [`SbomComponent::spdx_license`] are SPDX expressions.
+    pub licenses: Vec<String>,
+
+    /// Packaged files that came from this component.
+    pub files: Vec<SbomFile>,
+}
+
+impl SbomComponent {
+    /// Make a new [`SbomComponent`].
+    pub fn new(name: String, version: String) -> Self {
+        Self {
+            name,
+            version,
+            purl: None,
+            licenses: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Set the package URL.
+    pub fn purl(&mut self, purl: String) -> &mut Self {
+        self.purl = Some(purl);
+        self
+    }
+
+    /// Add a license that is a valid SPDX expression.
+    pub fn spdx_license(&mut self, license: &str) -> &mut Self {
+        self.licenses.push(format!("spdx:{license}"));
+        self
+    }
+
+    /// Add a packaged file.
+    pub fn file(&mut self, file: SbomFile) -> &mut Self {
+        self.files.push(file);
+        self
+    }
+
+    /// Get the declared license as an SPDX expression.
+    ///
+    /// # Returns
+    /// Returns None if there are no licenses, or if any license is not an SPDX expression.
+    fn get_spdx_license(&self) -> Option<String> {
+        let licenses = self
+            .licenses
+            .iter()
+            .map(|license| license.strip_prefix("spdx:"))
+            .collect::<Option<Vec<_>>>()?;
+        match licenses.as_slice() {
+            [] => None,
+            [license] => Some(license.to_string()),
+            licenses => Some(
+                licenses
+                    .iter()
+                    .map(|license| format!("({license})"))
+                    .collect::<Vec<_>>()
+                    .join(" AND "),
+            ),
+        }
+    }
+}
+
+/// A software bill of materials for a package.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Sbom {
+    /// The component being packaged, like the Rust binary.
+    pub main_component: SbomComponent,
+
+    /// Everything else that went into the package.
+    pub components: Vec<SbomComponent>,
+}
+
+impl Sbom {
+    /// Make a new [`Sbom`] for the given main component.
+    pub fn new(main_component: SbomComponent) -> Self {
+        Self {
+            main_component,
+            components: Vec::new(),
+        }
+    }
+
+    /// Add a component.
+    pub fn component(&mut self, component: SbomComponent) -> &mut Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Add a component for every MSYS2 package that packaged files came from, with the hashes of those files.
+    ///
+    /// Packaged files that did not come from an MSYS2 package are added to the main component.
+    pub fn add_packaged_files(
+        &mut self,
+        msys2_installation_path: &Utf8Path,
+        out_dir: &Path,
+        ownership: &OwnershipReport,
+    ) -> anyhow::Result<&mut Self> {
+        let database = LocalDatabase::load(msys2_installation_path)
+            .context("failed to load the pacman database")?;
+
+        let mut components = BTreeMap::new();
+        for package in ownership.packages.iter() {
+            let mut component = SbomComponent::new(package.name.clone(), package.version.clone());
+            component.purl(format!(
+                "pkg:alpm/msys2/{}@{}",
+                package.name, package.version
+            ));
+            if let Some(installed_package) = database.get(&package.name) {
+                component.licenses = installed_package.licenses.clone();
+            }
+            components.insert(package.name.as_str(), component);
+        }
+
+        for file in ownership.files.iter() {
+            let sbom_file = SbomFile::new(out_dir, file.dest.clone())?;
+            let component = match file
+                .package
+                .as_deref()
+                .and_then(|package| components.get_mut(package))
+            {
+                Some(component) => component,
+                None => &mut self.main_component,
+            };
+            component.file(sbom_file);
+        }
+
+        self.components.extend(components.into_values());
+
+        Ok(self)
+    }
+
+    /// Serialize this SBOM as JSON, in the given format.
+    pub fn to_json(&self, format: SbomFormat) -> anyhow::Result<String> {
+        let value = match format {
+            SbomFormat::CycloneDx => self.to_cyclonedx(),
+            SbomFormat::Spdx => self.to_spdx(),
+        };
+
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+
+    /// Make a CycloneDX 1.5 document.
+    fn to_cyclonedx(&self) -> Value {
+        let cyclonedx_component = |component: &SbomComponent, component_type: &str| {
+            let mut value = json!({
+                "type": component_type,
+                "bom-ref": component.purl.clone().unwrap_or_else(|| component.name.clone()),
+                "name": component.name,
+                "version": component.version,
+            });
+            if let Some(purl) = component.purl.as_ref() {
+                value["purl"] = json!(purl);
+            }
+            if !component.licenses.is_empty() {
+                value["licenses"] = match component.get_spdx_license() {
+                    Some(expression) => json!([{ "expression": expression }]),
+                    None => component
+                        .licenses
+                        .iter()
+                        .map(|license| json!({ "license": { "name": license } }))
+                        .collect(),
+                };
+            }
+            if !component.files.is_empty() {
+                value["components"] = component
+                    .files
+                    .iter()
+                    .map(|file| {
+                        json!({
+                            "type": "file",
+                            "name": file.path.to_string_lossy().replace('\\', "/"),
+                            "hashes": [{ "alg": "SHA-256", "content": file.sha256 }],
+                        })
+                    })
+                    .collect();
+            }
+            value
+        };
+
+        let components: Vec<_> = self
+            .components
+            .iter()
+            .map(|component| cyclonedx_component(component, "library"))
+            .collect();
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": format_timestamp(SystemTime::now()),
+                "tools": [{ "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }],
+                "component": cyclonedx_component(&self.main_component, "application"),
+            },
+            "components": components,
+        })
+    }
+
+    /// Make an SPDX 2.3 document.
+    fn to_spdx(&self) -> Value {
+        let mut packages = Vec::new();
+        let mut files = Vec::new();
+        let mut relationships = Vec::new();
+        let mut namespace_hasher = Sha256::new();
+
+        let all_components = std::iter::once(&self.main_component).chain(self.components.iter());
+        for (package_index, component) in all_components.enumerate() {
+            let package_id = format!("SPDXRef-Package-{package_index}");
+            let license = component
+                .get_spdx_license()
+                .unwrap_or_else(|| "NOASSERTION".into());
+
+            let mut package = json!({
+                "SPDXID": package_id,
+                "name": component.name,
+                "versionInfo": component.version,
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": license,
+                "copyrightText": "NOASSERTION",
+            });
+            if let Some(purl) = component.purl.as_ref() {
+                package["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+            packages.push(package);
+
+            relationships.push(if package_index == 0 {
+                json!({
+                    "spdxElementId": "SPDXRef-DOCUMENT",
+                    "relationshipType": "DESCRIBES",
+                    "relatedSpdxElement": package_id,
+                })
+            } else {
+                json!({
+                    "spdxElementId": "SPDXRef-Package-0",
+                    "relationshipType": "CONTAINS",
+                    "relatedSpdxElement": package_id,
+                })
+            });
+
+            for file in component.files.iter() {
+                let file_id = format!("SPDXRef-File-{}", files.len());
+                let file_name = file.path.to_string_lossy().replace('\\', "/");
+                namespace_hasher.update(file_name.as_bytes());
+                namespace_hasher.update(file.sha256.as_bytes());
+
+                files.push(json!({
+                    "SPDXID": file_id,
+                    "fileName": format!("./{file_name}"),
+                    "checksums": [{ "algorithm": "SHA256", "checksumValue": file.sha256 }],
+                    "licenseConcluded": "NOASSERTION",
+                    "copyrightText": "NOASSERTION",
+                }));
+                relationships.push(json!({
+                    "spdxElementId": package_id,
+                    "relationshipType": "CONTAINS",
+                    "relatedSpdxElement": file_id,
+                }));
+            }
+        }
+
+        let name = format!(
+            "{}-{}",
+            self.main_component.name, self.main_component.version
+        );
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            // The namespace must be unique per document, so we derive it from the packaged files.
+            "documentNamespace": format!(
+                "https://spdx.org/spdxdocs/{name}-{}",
+                to_hex(&namespace_hasher.finalize())
+            ),
+            "creationInfo": {
+                "created": format_timestamp(SystemTime::now()),
+                "creators": [format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+            "files": files,
+            "relationships": relationships,
+        })
+    }
+}
+
+/// Get the lowercase hex SHA-256 of a file.
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Encode bytes as lowercase hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{byte:02x}").expect("writing to a string cannot fail");
+    }
+    hex
+}
+
+/// Format a time as an RFC 3339 UTC timestamp, like `2023-02-25T12:30:00Z`.
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+
+    // Convert days since the epoch to a civil date.
+    // See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
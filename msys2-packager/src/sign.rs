@@ -0,0 +1,242 @@
+#[cfg(feature = "process")]
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+#[cfg(feature = "process")]
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "process")]
+use std::process::Command;
+#[cfg(feature = "process")]
+use std::process::Stdio;
+
+/// The env var with the password of the certificate file.
+pub const SIGN_CERT_PASSWORD_ENV: &str = "SIGN_CERT_PASSWORD";
+
+/// The env var with the path to a file that holds the password of the certificate file, used if [`SIGN_CERT_PASSWORD_ENV`] is not set.
+pub const SIGN_CERT_PASSWORD_FILE_ENV: &str = "SIGN_CERT_PASSWORD_FILE";
+
+/// The max length of the file paths passed to one signtool run.
+///
+/// Windows limits command lines to 32767 characters, and this leaves room for the other args.
+#[cfg(feature = "process")]
+const MAX_SIGNTOOL_PATHS_LEN: usize = 28 * 1024;
+
+/// Signs files with a password-protected certificate, reading the password from the environment instead of the command line.
+///
+/// The file paths are read from stdin, one per line.
+#[cfg(feature = "process")]
+const POWERSHELL_SIGN_SCRIPT: &str = "$ErrorActionPreference = 'Stop'; \
+$cert = New-Object System.Security.Cryptography.X509Certificates.X509Certificate2($env:MSYS2_PACKAGER_SIGN_CERT, $env:MSYS2_PACKAGER_SIGN_PASSWORD); \
+$params = @{ Certificate = $cert; HashAlgorithm = 'SHA256' }; \
+if ($env:MSYS2_PACKAGER_SIGN_TIMESTAMP_URL) { $params.TimestampServer = $env:MSYS2_PACKAGER_SIGN_TIMESTAMP_URL }; \
+foreach ($path in $input) { \
+if (-not $path) { continue }; \
+$signature = Set-AuthenticodeSignature -FilePath $path @params; \
+if ($signature.Status -eq 'NotSigned') { throw \"failed to sign ${path}: $($signature.StatusMessage)\" } \
+}";
+
+/// Options for signing packaged binaries with signtool.
+#[derive(Clone)]
+pub struct SignOptions {
+    cert: PathBuf,
+    password: Option<String>,
+    timestamp_url: Option<String>,
+}
+
+impl SignOptions {
+    /// Make new [`SignOptions`] that sign with the given `.pfx` certificate file.
+    pub fn new(cert: PathBuf) -> Self {
+        Self {
+            cert,
+            password: None,
+            timestamp_url: None,
+        }
+    }
+
+    /// Set the password of the certificate file.
+    pub fn password(&mut self, password: Option<String>) -> &mut Self {
+        self.password = password;
+        self
+    }
+
+    /// Set the URL of the RFC 3161 timestamp server to use.
+    ///
+    /// Without a timestamp, signatures become invalid once the certificate expires.
+    pub fn timestamp_url(&mut self, timestamp_url: Option<String>) -> &mut Self {
+        self.timestamp_url = timestamp_url;
+        self
+    }
+
+    /// Get the path to the certificate file.
+    pub fn cert(&self) -> &Path {
+        &self.cert
+    }
+}
+
+/// Make [`SignOptions`] from CLI options, reading the password from the environment.
+///
+/// The password is read from [`SIGN_CERT_PASSWORD_ENV`], or the file at [`SIGN_CERT_PASSWORD_FILE_ENV`], if either is set.
+///
+/// # Returns
+/// Returns None if there is no certificate.
+pub fn get_sign_options(
+    cert: Option<PathBuf>,
+    timestamp_url: Option<String>,
+) -> anyhow::Result<Option<SignOptions>> {
+    let cert = match cert {
+        Some(cert) => cert,
+        None => {
+            ensure!(
+                timestamp_url.is_none(),
+                "`--sign-timestamp-url` requires `--sign-cert`"
+            );
+            return Ok(None);
+        }
+    };
+
+    let password = match std::env::var(SIGN_CERT_PASSWORD_ENV) {
+        Ok(password) => Some(password),
+        Err(_) => {
+            match std::env::var_os(SIGN_CERT_PASSWORD_FILE_ENV).filter(|path| !path.is_empty()) {
+                Some(path) => {
+                    let path = PathBuf::from(path);
+                    let password = std::fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read `{}`", path.display()))?;
+                    Some(password.trim_end_matches(['\r', '\n']).to_string())
+                }
+                None => None,
+            }
+        }
+    };
+
+    let mut sign = SignOptions::new(cert);
+    sign.password(password).timestamp_url(timestamp_url);
+    Ok(Some(sign))
+}
+
+impl std::fmt::Debug for SignOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignOptions")
+            .field("cert", &self.cert)
+            // Keep the password out of logs.
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("timestamp_url", &self.timestamp_url)
+            .finish()
+    }
+}
+
+/// Sign files with signtool, using SHA-256 file digests.
+///
+/// Files are signed in batches, to keep each command line under the Windows limit.
+/// signtool only takes certificate passwords on the command line, where other processes can read them,
+/// so certificates with a password are signed with PowerShell's `Set-AuthenticodeSignature` instead,
+/// which gets the password from its environment.
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn signtool_sign(paths: &[&Path], options: &SignOptions) -> anyhow::Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(password) = options.password.as_deref() {
+        return powershell_sign(paths, options, password);
+    }
+
+    let mut start = 0;
+    while start < paths.len() {
+        // Always take one path, so a path that is too long on its own still gets an error from signtool.
+        let mut end = start + 1;
+        let mut len = paths[start].as_os_str().len();
+        while let Some(path) = paths.get(end) {
+            // Each path may be quoted, and is separated by a space.
+            len += path.as_os_str().len() + 3;
+            if len > MAX_SIGNTOOL_PATHS_LEN {
+                break;
+            }
+            end += 1;
+        }
+
+        run_signtool(&paths[start..end], options)?;
+        start = end;
+    }
+
+    Ok(())
+}
+
+/// Run signtool once on a batch of files.
+#[cfg(feature = "process")]
+fn run_signtool(paths: &[&Path], options: &SignOptions) -> anyhow::Result<()> {
+    let mut command = Command::new("signtool");
+    command
+        .arg("sign")
+        .arg("/fd")
+        .arg("SHA256")
+        .arg("/f")
+        .arg(&options.cert);
+    if let Some(timestamp_url) = options.timestamp_url.as_ref() {
+        command
+            .arg("/tr")
+            .arg(timestamp_url)
+            .arg("/td")
+            .arg("SHA256");
+    }
+    command.args(paths);
+
+    let output = command.output().context("failed to run signtool")?;
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("signtool exit code was non-zero, `{}{}`", stdout, stderr);
+    }
+
+    Ok(())
+}
+
+/// Sign files with a password-protected certificate through PowerShell, see [`POWERSHELL_SIGN_SCRIPT`].
+///
+/// The password is passed in the environment and the paths on stdin, so neither is on the command line.
+#[cfg(feature = "process")]
+fn powershell_sign(paths: &[&Path], options: &SignOptions, password: &str) -> anyhow::Result<()> {
+    let mut command = Command::new("powershell");
+    command
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            POWERSHELL_SIGN_SCRIPT,
+        ])
+        .env("MSYS2_PACKAGER_SIGN_CERT", &options.cert)
+        .env("MSYS2_PACKAGER_SIGN_PASSWORD", password)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    match options.timestamp_url.as_ref() {
+        Some(timestamp_url) => command.env("MSYS2_PACKAGER_SIGN_TIMESTAMP_URL", timestamp_url),
+        None => command.env_remove("MSYS2_PACKAGER_SIGN_TIMESTAMP_URL"),
+    };
+
+    let mut child = command.spawn().context("failed to run powershell")?;
+    {
+        let mut stdin = child.stdin.take().context("missing powershell stdin")?;
+        for path in paths {
+            let path = path
+                .to_str()
+                .with_context(|| format!("`{}` is not unicode", path.display()))?;
+            writeln!(stdin, "{path}").context("failed to write to powershell stdin")?;
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for powershell")?;
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to sign with powershell, `{}{}`", stdout, stderr);
+    }
+
+    Ok(())
+}
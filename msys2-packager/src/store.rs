@@ -0,0 +1,367 @@
+use crate::report::OutputFormat;
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// The name of the dir in a store that records the out dirs that link from it.
+const PACKAGES_DIR_NAME: &str = "packages";
+
+/// A content-addressed store of dlls, shared between the packages on a machine.
+///
+/// Files are stored by their SHA-256 and hard-linked into packages,
+/// so packages that share dlls only keep one copy on disk.
+/// Stored files must never be modified in place, so only files that are packaged unchanged should be linked.
+#[derive(Debug, Clone)]
+pub struct DllStore {
+    dir: PathBuf,
+}
+
+impl DllStore {
+    /// Make a new [`DllStore`] in the given dir.
+    ///
+    /// The dir is created when the first file is stored.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Get the dir of this store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Place a file at `dest` by hard-linking it from the store, adding it to the store first if needed.
+    ///
+    /// Files are hashed again after they are added, so a corrupted copy never enters the store.
+    /// If a hard link cannot be made, like when the store is on a different volume, the file is copied instead.
+    /// `dest` must not exist.
+    ///
+    /// # Returns
+    /// Returns true if `dest` was hard-linked.
+    pub fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<bool> {
+        let sha256 = crate::sbom::sha256_file(src)
+            .with_context(|| format!("failed to hash `{}`", src.display()))?;
+        let stored_path = self.dir.join(&sha256[..2]).join(&sha256);
+
+        if stored_path.exists() {
+            // Record the use for garbage collection.
+            // This is best-effort, as Windows will not open a dll that a running app loaded for writing.
+            let _ = std::fs::File::options()
+                .write(true)
+                .open(&stored_path)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+        } else {
+            let parent = stored_path.parent().context("stored path has no parent")?;
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+
+            // Copy to a temp file first, so other packagers never see a partial file.
+            let temp_path = parent.join(format!("{sha256}.{}.tmp", std::process::id()));
+            std::fs::copy(src, &temp_path).with_context(|| {
+                format!(
+                    "failed to copy `{}` to `{}`",
+                    src.display(),
+                    temp_path.display()
+                )
+            })?;
+            let stored_sha256 = crate::sbom::sha256_file(&temp_path)
+                .with_context(|| format!("failed to hash `{}`", temp_path.display()))?;
+            if stored_sha256 != sha256 {
+                let _ = std::fs::remove_file(&temp_path);
+            }
+            ensure!(
+                stored_sha256 == sha256,
+                "the copy of `{}` in the dll store is corrupted",
+                src.display()
+            );
+            std::fs::rename(&temp_path, &stored_path).with_context(|| {
+                format!(
+                    "failed to rename `{}` to `{}`",
+                    temp_path.display(),
+                    stored_path.display()
+                )
+            })?;
+        }
+
+        if std::fs::hard_link(&stored_path, dest).is_ok() {
+            return Ok(true);
+        }
+        std::fs::copy(&stored_path, dest).with_context(|| {
+            format!(
+                "failed to copy `{}` to `{}`",
+                stored_path.display(),
+                dest.display()
+            )
+        })?;
+
+        Ok(false)
+    }
+
+    /// Record that an out dir links files from this store, so [`DllStore::gc`] keeps them.
+    pub fn register_package(&self, out_dir: &Path) -> anyhow::Result<()> {
+        let out_dir = out_dir
+            .canonicalize()
+            .with_context(|| format!("failed to canonicalize `{}`", out_dir.display()))?;
+        let id = crate::sbom::to_hex(&Sha256::digest(out_dir.as_os_str().as_encoded_bytes()));
+
+        let packages_dir = self.dir.join(PACKAGES_DIR_NAME);
+        std::fs::create_dir_all(&packages_dir)
+            .with_context(|| format!("failed to create `{}`", packages_dir.display()))?;
+        let path = packages_dir.join(format!("{id}.json"));
+        let data = serde_json::to_vec_pretty(&PackageRecord { out_dir })?;
+        std::fs::write(&path, data)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Remove stored files that no package links, to keep the store under a size.
+    ///
+    /// A stored file is in use if a registered out dir that still exists has a file with the same contents.
+    /// Files in use are never removed, and registrations of out dirs that no longer exist are dropped.
+    /// Of the rest, files that were not linked for `unused_for` are removed,
+    /// and then the least recently linked files are removed until the store is at most `max_size` bytes.
+    ///
+    /// This should not run while packaging into the store.
+    pub fn gc(
+        &self,
+        max_size: Option<u64>,
+        unused_for: Option<Duration>,
+        dry_run: bool,
+    ) -> anyhow::Result<GcReport> {
+        let mut report = GcReport {
+            dry_run,
+            ..GcReport::default()
+        };
+
+        // Find the out dirs that still link from the store.
+        let mut out_dirs = Vec::new();
+        let packages_dir = self.dir.join(PACKAGES_DIR_NAME);
+        for dir_entry in read_dir_if_exists(&packages_dir)? {
+            let path = dir_entry.path();
+            let record = std::fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<PackageRecord>(&data).ok());
+            match record {
+                Some(record) if record.out_dir.is_dir() => {
+                    out_dirs.push(record.out_dir);
+                }
+                _ => {
+                    if !dry_run {
+                        std::fs::remove_file(&path)
+                            .with_context(|| format!("failed to remove `{}`", path.display()))?;
+                    }
+                    report.num_dropped_packages += 1;
+                }
+            }
+        }
+        report.num_packages = out_dirs.len();
+
+        // Collect the stored files.
+        let mut entries = Vec::new();
+        for dir_entry in read_dir_if_exists(&self.dir)? {
+            if dir_entry.file_name() == PACKAGES_DIR_NAME || !dir_entry.file_type()?.is_dir() {
+                continue;
+            }
+            for dir_entry in read_dir_if_exists(&dir_entry.path())? {
+                let metadata = dir_entry.metadata()?;
+                let file_name = dir_entry.file_name();
+                let Some(sha256) = file_name.to_str() else {
+                    continue;
+                };
+                // Skip temp files, which may belong to a running packager.
+                if !metadata.is_file() || sha256.ends_with(".tmp") {
+                    continue;
+                }
+                entries.push(StoredFile {
+                    path: dir_entry.path(),
+                    sha256: sha256.into(),
+                    size: metadata.len(),
+                    last_used: metadata.modified()?,
+                });
+            }
+        }
+
+        // Hash the files of out dirs that could be stored files, going by size.
+        let sizes: HashSet<u64> = entries.iter().map(|entry| entry.size).collect();
+        let mut used = HashSet::new();
+        for out_dir in out_dirs.iter() {
+            for dir_entry in walkdir::WalkDir::new(out_dir) {
+                let dir_entry = dir_entry?;
+                if !dir_entry.file_type().is_file() || !sizes.contains(&dir_entry.metadata()?.len())
+                {
+                    continue;
+                }
+                let sha256 = crate::sbom::sha256_file(dir_entry.path())
+                    .with_context(|| format!("failed to hash `{}`", dir_entry.path().display()))?;
+                used.insert(sha256);
+            }
+        }
+
+        let mut size: u64 = entries.iter().map(|entry| entry.size).sum();
+        let (in_use, mut unused): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|entry| used.contains(&entry.sha256));
+        report.num_in_use = in_use.len();
+        report.in_use_size = in_use.iter().map(|entry| entry.size).sum();
+
+        // Remove the least recently used files first.
+        unused.sort_by_key(|entry| entry.last_used);
+        let now = SystemTime::now();
+        for entry in unused {
+            let age = now.duration_since(entry.last_used).unwrap_or_default();
+            let too_old = unused_for.is_some_and(|unused_for| age >= unused_for);
+            let too_big = max_size.is_some_and(|max_size| size > max_size);
+            if !too_old && !too_big {
+                report.num_kept += 1;
+                report.kept_size += entry.size;
+                continue;
+            }
+
+            if !dry_run {
+                std::fs::remove_file(&entry.path)
+                    .with_context(|| format!("failed to remove `{}`", entry.path.display()))?;
+                // Remove the prefix dir if it is now empty, which fails otherwise.
+                if let Some(parent) = entry.path.parent() {
+                    let _ = std::fs::remove_dir(parent);
+                }
+            }
+            size -= entry.size;
+            report.num_removed += 1;
+            report.removed_size += entry.size;
+        }
+        report.size = size;
+
+        Ok(report)
+    }
+}
+
+/// A record of an out dir that links from a store
+#[derive(Debug, Serialize, Deserialize)]
+struct PackageRecord {
+    /// The canonical path to the out dir
+    out_dir: PathBuf,
+}
+
+/// A file in a store
+#[derive(Debug)]
+struct StoredFile {
+    /// The path to the file
+    path: PathBuf,
+
+    /// The SHA-256 of the file, which is its name
+    sha256: String,
+
+    /// The size of the file, in bytes
+    size: u64,
+
+    /// When the file was last linked into a package
+    last_used: SystemTime,
+}
+
+/// The results of garbage-collecting a [`DllStore`]
+#[derive(Debug, Default, Clone, Serialize)]
+#[non_exhaustive]
+pub struct GcReport {
+    /// Whether nothing was actually removed
+    pub dry_run: bool,
+
+    /// The number of registered out dirs that still exist
+    pub num_packages: usize,
+
+    /// The number of registrations that were dropped, as their out dirs no longer exist
+    pub num_dropped_packages: usize,
+
+    /// The number of stored files that packages link
+    pub num_in_use: usize,
+
+    /// The total size of the stored files that packages link, in bytes
+    pub in_use_size: u64,
+
+    /// The number of unused stored files that were kept
+    pub num_kept: usize,
+
+    /// The total size of the unused stored files that were kept, in bytes
+    pub kept_size: u64,
+
+    /// The number of stored files that were removed
+    pub num_removed: usize,
+
+    /// The total size of the stored files that were removed, in bytes
+    pub removed_size: u64,
+
+    /// The size of the store after collection, in bytes
+    pub size: u64,
+}
+
+impl GcReport {
+    /// Print this report to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for GcReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Packages: {} ({} dropped)",
+            self.num_packages, self.num_dropped_packages
+        )?;
+        writeln!(
+            f,
+            "In use: {} files ({} bytes)",
+            self.num_in_use, self.in_use_size
+        )?;
+        writeln!(
+            f,
+            "Unused: {} files ({} bytes)",
+            self.num_kept, self.kept_size
+        )?;
+        let removed = if self.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        writeln!(
+            f,
+            "{removed}: {} files ({} bytes)",
+            self.num_removed, self.removed_size
+        )?;
+        write!(f, "Store size: {} bytes", self.size)
+    }
+}
+
+/// Read a dir, treating a missing dir as empty.
+fn read_dir_if_exists(path: &Path) -> anyhow::Result<Vec<std::fs::DirEntry>> {
+    let dir_entries = match std::fs::read_dir(path) {
+        Ok(dir_entries) => dir_entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read `{}`", path.display()));
+        }
+    };
+
+    dir_entries
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read `{}`", path.display()))
+}
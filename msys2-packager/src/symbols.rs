@@ -0,0 +1,257 @@
+#[cfg(feature = "process")]
+use anyhow::ensure;
+use anyhow::Context;
+#[cfg(feature = "process")]
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "process")]
+use std::process::Command;
+#[cfg(feature = "process")]
+use std::process::Stdio;
+use walkdir::WalkDir;
+
+/// The extension of split debug info files, see [`crate::packager::Packager::debug_symbols_dir`].
+pub const DEBUG_EXTENSION: &str = "debug";
+
+/// The env var with a bearer token for [`SymbolServer::Http`] uploads.
+pub const SYMBOL_SERVER_TOKEN_ENV: &str = "MSYS2_PACKAGER_SYMBOL_SERVER_TOKEN";
+
+/// An error that may occur while parsing a [`SymbolServer`] from a string.
+#[derive(Debug)]
+pub struct SymbolServerFromStrError(String);
+
+impl std::fmt::Display for SymbolServerFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is not a valid symbol server, pass an `http://` or `https://` url or `sentry:<org>/<project>`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for SymbolServerFromStrError {}
+
+/// Where to upload debug symbols
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum SymbolServer {
+    /// A server that accepts HTTP PUTs in the symstore layout, like `{url}/{file name}/{build id}/{file name}`
+    Http(String),
+
+    /// A Sentry project, uploaded to with `sentry-cli`
+    Sentry {
+        /// The organization slug
+        org: String,
+
+        /// The project slug
+        project: String,
+    },
+}
+
+impl std::str::FromStr for SymbolServer {
+    type Err = SymbolServerFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if input.starts_with("http://") || input.starts_with("https://") {
+            return Ok(Self::Http(input.trim_end_matches('/').into()));
+        }
+
+        input
+            .strip_prefix("sentry:")
+            .and_then(|rest| rest.split_once('/'))
+            .filter(|(org, project)| {
+                !org.is_empty() && !project.is_empty() && !project.contains('/')
+            })
+            .map(|(org, project)| Self::Sentry {
+                org: org.into(),
+                project: project.into(),
+            })
+            .ok_or_else(|| SymbolServerFromStrError(input.into()))
+    }
+}
+
+impl std::fmt::Display for SymbolServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(url) => write!(f, "{url}"),
+            Self::Sentry { org, project } => write!(f, "sentry:{org}/{project}"),
+        }
+    }
+}
+
+/// A file to upload to a symbol server
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SymbolFile {
+    /// The file
+    pub path: PathBuf,
+
+    /// The path of the file on the server, like `app.exe/5F3E1A2B1c000/app.exe`
+    pub key: String,
+}
+
+/// Get the build id of an exe or dll, which symbol servers key its files by.
+///
+/// This is the GUID and age of the CodeView record, which the linker writes with `--build-id`, like symstore uses for pdbs.
+/// Binaries without one use the timestamp and image size from their headers, like symstore uses for binaries.
+pub fn get_build_id(bytes: &[u8]) -> anyhow::Result<String> {
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
+    if let Some(codeview) = pe
+        .debug_data
+        .as_ref()
+        .and_then(|debug_data| debug_data.codeview_pdb70_debug_info.as_ref())
+    {
+        let signature = codeview.signature;
+        // The first three fields of the GUID are little-endian.
+        let data1 = u32::from_le_bytes([signature[0], signature[1], signature[2], signature[3]]);
+        let data2 = u16::from_le_bytes([signature[4], signature[5]]);
+        let data3 = u16::from_le_bytes([signature[6], signature[7]]);
+        let mut build_id = format!("{data1:08X}{data2:04X}{data3:04X}");
+        for byte in signature[8..].iter() {
+            build_id.push_str(&format!("{byte:02X}"));
+        }
+        build_id.push_str(&format!("{:x}", codeview.age));
+
+        return Ok(build_id);
+    }
+
+    let size_of_image = pe
+        .header
+        .optional_header
+        .context("missing optional header")?
+        .windows_fields
+        .size_of_image;
+    Ok(format!(
+        "{:08X}{size_of_image:x}",
+        pe.header.coff_header.time_date_stamp
+    ))
+}
+
+/// Get the files to upload for the split debug info in a debug symbols dir.
+///
+/// Each `.debug` file is uploaded along with the packaged binary it was split from,
+/// both keyed by the build id of the binary, see [`get_build_id`].
+/// `debug_symbols_dir` mirrors the layout of `package_dir`.
+pub fn collect_symbol_files(
+    package_dir: &Path,
+    debug_symbols_dir: &Path,
+) -> anyhow::Result<Vec<SymbolFile>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(debug_symbols_dir).sort_by_file_name() {
+        let entry =
+            entry.with_context(|| format!("failed to read `{}`", debug_symbols_dir.display()))?;
+        let debug_path = entry.path();
+        if !entry.file_type().is_file()
+            || debug_path
+                .extension()
+                .is_none_or(|extension| extension != DEBUG_EXTENSION)
+        {
+            continue;
+        }
+
+        let relative_path = debug_path
+            .strip_prefix(debug_symbols_dir)
+            .context("debug file is not in the debug symbols dir")?
+            .with_extension("");
+        let binary_path = package_dir.join(&relative_path);
+        if !binary_path.is_file() {
+            // The binary is no longer packaged.
+            continue;
+        }
+        let bytes = std::fs::read(&binary_path)
+            .with_context(|| format!("failed to read `{}`", binary_path.display()))?;
+        let build_id = get_build_id(&bytes).with_context(|| {
+            format!("failed to get the build id of `{}`", binary_path.display())
+        })?;
+
+        for path in [binary_path, debug_path.to_path_buf()] {
+            let file_name = path
+                .file_name()
+                .context("missing file name")?
+                .to_str()
+                .with_context(|| format!("`{}` is not utf8", path.display()))?
+                .to_string();
+            files.push(SymbolFile {
+                path,
+                key: format!("{file_name}/{build_id}/{file_name}"),
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Upload the split debug info in a debug symbols dir, and the binaries it was split from, to a symbol server.
+///
+/// HTTP uploads are made with curl, and send the token in [`SYMBOL_SERVER_TOKEN_ENV`], if it is set.
+/// Sentry uploads are made with `sentry-cli`, which reads its own auth token.
+/// This requires the `process` feature.
+///
+/// # Returns
+/// Returns the number of uploaded files.
+#[cfg(feature = "process")]
+pub fn upload_symbols(
+    server: &SymbolServer,
+    package_dir: &Path,
+    debug_symbols_dir: &Path,
+) -> anyhow::Result<usize> {
+    let files = collect_symbol_files(package_dir, debug_symbols_dir)?;
+    if files.is_empty() {
+        return Ok(0);
+    }
+
+    match server {
+        SymbolServer::Http(url) => {
+            let token = std::env::var(SYMBOL_SERVER_TOKEN_ENV)
+                .ok()
+                .filter(|token| !token.is_empty());
+            for file in files.iter() {
+                let file_url = format!("{url}/{}", file.key);
+                let mut command = Command::new("curl");
+                command
+                    .args(["--fail", "--silent", "--show-error", "--upload-file"])
+                    .arg(&file.path)
+                    .arg(&file_url)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                // Pass the token through stdin, so it does not show up in the process list.
+                if token.is_some() {
+                    command.args(["--header", "@-"]).stdin(Stdio::piped());
+                }
+                let mut child = command.spawn().context("failed to run `curl`")?;
+                if let Some(token) = token.as_deref() {
+                    let mut stdin = child.stdin.take().context("missing curl stdin")?;
+                    writeln!(stdin, "Authorization: Bearer {token}")
+                        .context("failed to write to curl stdin")?;
+                }
+                let output = child
+                    .wait_with_output()
+                    .context("failed to wait for `curl`")?;
+                ensure!(
+                    output.status.success(),
+                    "failed to upload `{}` to `{file_url}`: {}",
+                    file.path.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+        SymbolServer::Sentry { org, project } => {
+            let output = Command::new("sentry-cli")
+                .args(["debug-files", "upload", "--org", org, "--project", project])
+                .args(files.iter().map(|file| &file.path))
+                .output()
+                .context(
+                    "failed to run `sentry-cli`, install it from https://docs.sentry.io/cli/",
+                )?;
+            ensure!(
+                output.status.success(),
+                "failed to upload debug files to Sentry: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+
+    Ok(files.len())
+}
@@ -0,0 +1,190 @@
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+#[cfg(feature = "process")]
+use std::process::Command;
+
+/// The extension of the detached signatures that MSYS2 repos publish next to package archives.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// What downloaded content, like package archives, is trusted.
+///
+/// The policy fails closed: content that is neither pinned nor allowed by a signature is rejected.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct TrustPolicy {
+    /// Pinned lowercase hex SHA-256s, keyed by file name.
+    ///
+    /// Pinned files must match their pin, even if they are signed.
+    #[serde(default)]
+    pub sha256: BTreeMap<String, String>,
+
+    /// Whether files without a pin are trusted if they have a valid signature from the MSYS2 pacman keyring.
+    #[serde(default)]
+    pub allow_signed: bool,
+}
+
+impl TrustPolicy {
+    /// Load a policy from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let policy: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        for (name, sha256) in policy.sha256.iter() {
+            ensure_sha256(name, sha256)?;
+        }
+
+        Ok(policy)
+    }
+
+    /// Pin the SHA-256 of a file name, replacing an earlier pin.
+    ///
+    /// # Errors
+    /// Returns an error if `sha256` is not a lowercase hex SHA-256.
+    pub fn pin(&mut self, name: &str, sha256: &str) -> anyhow::Result<&mut Self> {
+        ensure_sha256(name, sha256)?;
+        self.sha256.insert(name.into(), sha256.into());
+
+        Ok(self)
+    }
+
+    /// Check that a downloaded file is trusted by this policy.
+    ///
+    /// Signatures are read from the file path with [`SIGNATURE_EXTENSION`] appended,
+    /// and checked with the gpg and pacman keyring of the MSYS2 installation at `msys2_installation_path`,
+    /// which is only needed if the file is not pinned.
+    ///
+    /// # Errors
+    /// Returns an error if the file is not trusted, or if trust could not be checked.
+    pub fn check_file(
+        &self,
+        path: &Path,
+        msys2_installation_path: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("`{}` has an invalid file name", path.display()))?;
+
+        if let Some(pinned) = self.sha256.get(name) {
+            let sha256 = crate::sbom::sha256_file(path)
+                .with_context(|| format!("failed to hash `{}`", path.display()))?;
+            ensure!(
+                &sha256 == pinned,
+                "the SHA-256 of `{name}` is `{sha256}`, but the trust policy pins `{pinned}`"
+            );
+            return Ok(());
+        }
+
+        if self.allow_signed {
+            let msys2_installation_path = msys2_installation_path
+                .context("verifying signatures requires an MSYS2 installation")?;
+            return verify_signature(path, msys2_installation_path).with_context(|| {
+                format!("`{name}` is not pinned, and its signature is not valid")
+            });
+        }
+
+        bail!("`{name}` is not pinned by the trust policy, and the trust policy does not allow signed files")
+    }
+}
+
+/// Check that a pin is a lowercase hex SHA-256.
+fn ensure_sha256(name: &str, sha256: &str) -> anyhow::Result<()> {
+    ensure!(
+        sha256.len() == 64 && sha256.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')),
+        "the pin for `{name}` is not a lowercase hex SHA-256"
+    );
+
+    Ok(())
+}
+
+/// Verify the detached signature of a file with the pacman keyring of an MSYS2 installation.
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+fn verify_signature(path: &Path, msys2_installation_path: &Path) -> anyhow::Result<()> {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".");
+    signature_path.push(SIGNATURE_EXTENSION);
+    let signature_path = Path::new(&signature_path);
+    ensure!(
+        signature_path.is_file(),
+        "`{}` does not exist",
+        signature_path.display()
+    );
+
+    let gpg = msys2_installation_path.join("usr/bin/gpg.exe");
+    let output = Command::new(&gpg)
+        .arg("--homedir")
+        .arg(msys2_installation_path.join("etc/pacman.d/gnupg"))
+        .args([
+            "--batch",
+            "--no-auto-key-retrieve",
+            "--status-fd",
+            "1",
+            "--verify",
+        ])
+        .arg(signature_path)
+        .arg(path)
+        .output()
+        .with_context(|| format!("failed to run `{}`", gpg.display()))?;
+    ensure!(
+        output.status.success(),
+        "gpg rejected the signature: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // gpg exits successfully for good signatures from expired, revoked, or untrusted keys, so check what it reported.
+    check_gpg_status(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Check the `--status-fd` output of `gpg --verify`, like pacman does.
+///
+/// The signature must be valid, from a key that is fully or ultimately trusted by the keyring,
+/// and neither the signature nor the key may be expired or revoked.
+#[cfg(feature = "process")]
+fn check_gpg_status(status: &str) -> anyhow::Result<()> {
+    let mut valid = false;
+    let mut trusted = false;
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "VALIDSIG" => valid = true,
+            "TRUST_FULLY" | "TRUST_ULTIMATE" => trusted = true,
+            "REVKEYSIG" => bail!("the signing key is revoked"),
+            "EXPKEYSIG" => bail!("the signing key is expired"),
+            "EXPSIG" => bail!("the signature is expired"),
+            "BADSIG" => bail!("the signature is bad"),
+            "ERRSIG" => {
+                bail!("the signature could not be checked, the key may be missing from the keyring")
+            }
+            "TRUST_UNDEFINED" | "TRUST_NEVER" | "TRUST_MARGINAL" => {
+                bail!("the signing key is not trusted by the keyring (`{keyword}`)")
+            }
+            _ => {}
+        }
+    }
+    ensure!(valid, "gpg did not report a valid signature");
+    ensure!(
+        trusted,
+        "gpg did not report how much the signing key is trusted"
+    );
+
+    Ok(())
+}
+
+/// Verify the detached signature of a file with the pacman keyring of an MSYS2 installation.
+///
+/// Without the `process` feature, gpg cannot run, so this always fails.
+#[cfg(not(feature = "process"))]
+fn verify_signature(_path: &Path, _msys2_installation_path: &Path) -> anyhow::Result<()> {
+    bail!("verifying signatures requires the `process` feature")
+}
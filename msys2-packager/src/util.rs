@@ -1,20 +1,191 @@
+use crate::knowledge::KnowledgeBase;
+#[cfg(feature = "process")]
 use anyhow::bail;
 use anyhow::Context;
+use camino::Utf8Path;
+#[cfg(feature = "process")]
 use camino::Utf8PathBuf;
+use msys2::Msys2Arch;
 use msys2::Msys2Environment;
+use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "process")]
 use std::process::Command;
 
+/// An error that may occur while parsing an [`UpxLevel`] from a string.
+#[derive(Debug)]
+pub struct UpxLevelFromStrError(String);
+
+impl std::fmt::Display for UpxLevelFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid upx compression level", self.0)
+    }
+}
+
+impl std::error::Error for UpxLevelFromStrError {}
+
+/// A upx compression level.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum UpxLevel {
+    /// A level from 1, the fastest, to 9, the smallest.
+    Level(u8),
+
+    /// `--best`, which is smaller and slower than 9.
+    Best,
+
+    /// `--ultra-brute`, which tries every compression method and is very slow.
+    UltraBrute,
+}
+
+impl UpxLevel {
+    /// Get the upx arg for this level.
+    pub fn get_arg(self) -> String {
+        match self {
+            Self::Level(level) => format!("-{level}"),
+            Self::Best => "--best".into(),
+            Self::UltraBrute => "--ultra-brute".into(),
+        }
+    }
+}
+
+impl std::str::FromStr for UpxLevel {
+    type Err = UpxLevelFromStrError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "best" => Ok(Self::Best),
+            "ultra-brute" => Ok(Self::UltraBrute),
+            _ => match input.parse() {
+                Ok(level @ 1..=9) => Ok(Self::Level(level)),
+                _ => Err(UpxLevelFromStrError(input.into())),
+            },
+        }
+    }
+}
+
+/// Options for running upx.
+#[derive(Debug, Clone)]
+pub struct UpxOptions {
+    path: PathBuf,
+    level: Option<UpxLevel>,
+    lzma: bool,
+    allow_failures: bool,
+}
+
+impl UpxOptions {
+    /// Make new [`UpxOptions`], which run `upx` from the PATH with `--lzma` and upx's default level.
+    pub fn new() -> Self {
+        Self {
+            path: PathBuf::from("upx"),
+            level: None,
+            lzma: true,
+            allow_failures: false,
+        }
+    }
+
+    /// Set the path to the upx binary.
+    ///
+    /// Defaults to `upx`, which is looked up on the PATH.
+    pub fn path(&mut self, path: PathBuf) -> &mut Self {
+        self.path = path;
+        self
+    }
+
+    /// Set the compression level.
+    ///
+    /// Defaults to None, which uses upx's default.
+    pub fn level(&mut self, level: Option<UpxLevel>) -> &mut Self {
+        self.level = level;
+        self
+    }
+
+    /// Whether to use LZMA compression, which is smaller but slower to decompress.
+    ///
+    /// Defaults to true.
+    pub fn lzma(&mut self, lzma: bool) -> &mut Self {
+        self.lzma = lzma;
+        self
+    }
+
+    /// Whether a file that upx fails on is packaged uncompressed with a warning, instead of failing packaging.
+    ///
+    /// upx refuses some files, like ones with unusual sections, that are otherwise fine to package.
+    ///
+    /// Defaults to false.
+    pub fn allow_failures(&mut self, allow_failures: bool) -> &mut Self {
+        self.allow_failures = allow_failures;
+        self
+    }
+
+    /// Get whether upx failures are downgraded to warnings.
+    pub fn get_allow_failures(&self) -> bool {
+        self.allow_failures
+    }
+
+    /// Get the path to the upx binary.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the compression level, if one is set.
+    pub fn get_level(&self) -> Option<UpxLevel> {
+        self.level
+    }
+
+    /// Get whether LZMA compression is used.
+    pub fn get_lzma(&self) -> bool {
+        self.lzma
+    }
+}
+
+impl Default for UpxOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Make [`UpxOptions`] from the upx options of a command line.
+///
+/// `path` overrides the upx binary, and `no_lzma` turns off LZMA compression.
+pub fn get_upx_options(
+    path: Option<PathBuf>,
+    level: Option<UpxLevel>,
+    no_lzma: bool,
+    allow_failures: bool,
+) -> UpxOptions {
+    let mut upx_options = UpxOptions::new();
+    if let Some(path) = path {
+        upx_options.path(path);
+    }
+    upx_options
+        .level(level)
+        .lzma(!no_lzma)
+        .allow_failures(allow_failures);
+    upx_options
+}
+
 /// Upx a file.
-pub fn upx<P>(path: P) -> anyhow::Result<()>
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn upx<P>(path: P, options: &UpxOptions) -> anyhow::Result<()>
 where
     P: AsRef<Path>,
 {
-    let output = Command::new("upx")
-        .arg(path.as_ref())
-        .arg("--lzma")
+    let mut command = Command::new(&options.path);
+    command.arg(path.as_ref());
+    if options.lzma {
+        command.arg("--lzma");
+    }
+    if let Some(level) = options.level {
+        command.arg(level.get_arg());
+    }
+    let output = command
         .output()
-        .context("failed to run upx")?;
+        .with_context(|| format!("failed to run `{}`", options.path.display()))?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         bail!("upx exit code was non-zero, `{}`", stderr);
@@ -23,11 +194,207 @@ where
     Ok(())
 }
 
+/// The names of strip tools in an MSYS2 environment, in the order they are tried.
+///
+/// Clang environments may only have `llvm-strip`.
+const STRIP_FILE_NAMES: &[&str] = &["strip.exe", "llvm-strip.exe"];
+
+/// The names of objcopy tools in an MSYS2 environment, in the order they are tried.
+///
+/// Clang environments may only have `llvm-objcopy`.
+const OBJCOPY_FILE_NAMES: &[&str] = &["objcopy.exe", "llvm-objcopy.exe"];
+
+/// Strip the symbols from an exe or dll, with `strip --strip-unneeded`.
+///
+/// Only symbols that are not needed for relocation are removed, so this is safe for dlls.
+/// `strip_path` is the strip tool to use, see [`locate_strip`].
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn strip<P>(strip_path: &Path, path: P) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let output = Command::new(strip_path)
+        .arg("--strip-unneeded")
+        .arg(path.as_ref())
+        .output()
+        .with_context(|| format!("failed to run `{}`", strip_path.display()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("strip exit code was non-zero, `{}`", stderr);
+    }
+
+    Ok(())
+}
+
+/// Locate the strip tool of an MSYS2 environment, like `C:/msys64/ucrt64`.
+///
+/// `strip` is tried before `llvm-strip`.
+///
+/// # Returns
+/// Returns `strip`, to be looked up on the PATH, if the environment has no strip tool.
+pub fn locate_strip(environment_path: &Utf8Path) -> PathBuf {
+    locate_environment_tool(environment_path, STRIP_FILE_NAMES, "strip")
+}
+
+/// Locate the objcopy tool of an MSYS2 environment, like `C:/msys64/ucrt64`.
+///
+/// `objcopy` is tried before `llvm-objcopy`.
+///
+/// # Returns
+/// Returns `objcopy`, to be looked up on the PATH, if the environment has no objcopy tool.
+pub fn locate_objcopy(environment_path: &Utf8Path) -> PathBuf {
+    locate_environment_tool(environment_path, OBJCOPY_FILE_NAMES, "objcopy")
+}
+
+/// Locate the first tool with one of the given file names in the `bin` dir of an MSYS2 environment.
+///
+/// # Returns
+/// Returns `fallback`, to be looked up on the PATH, if none exist.
+fn locate_environment_tool(environment_path: &Utf8Path, names: &[&str], fallback: &str) -> PathBuf {
+    names
+        .iter()
+        .map(|name| environment_path.join("bin").join(name).into_std_path_buf())
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(fallback))
+}
+
+/// Copy the debug info of an exe or dll to a separate file, with `objcopy --only-keep-debug`.
+///
+/// `objcopy_path` is the objcopy tool to use, see [`locate_objcopy`].
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn objcopy_only_keep_debug(
+    objcopy_path: &Path,
+    path: &Path,
+    debug_path: &Path,
+) -> anyhow::Result<()> {
+    run_objcopy(
+        Command::new(objcopy_path)
+            .arg("--only-keep-debug")
+            .arg(path)
+            .arg(debug_path),
+        objcopy_path,
+    )
+}
+
+/// Link an exe or dll to its separate debug info file, with `objcopy --add-gnu-debuglink`.
+///
+/// Debuggers look for the debug info file by its file name and checksum,
+/// so it may be moved as long as it is not renamed or changed.
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn objcopy_add_debuglink(
+    objcopy_path: &Path,
+    path: &Path,
+    debug_path: &Path,
+) -> anyhow::Result<()> {
+    let mut debuglink = OsString::from("--add-gnu-debuglink=");
+    debuglink.push(debug_path);
+
+    run_objcopy(
+        Command::new(objcopy_path).arg(debuglink).arg(path),
+        objcopy_path,
+    )
+}
+
+/// Run an objcopy command, failing if it exits with a non-zero code.
+#[cfg(feature = "process")]
+fn run_objcopy(command: &mut Command, objcopy_path: &Path) -> anyhow::Result<()> {
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run `{}`", objcopy_path.display()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("objcopy exit code was non-zero, `{}`", stderr);
+    }
+
+    Ok(())
+}
+
+/// Test the integrity of a file that was upx-ed, with `upx -t`.
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
+pub fn upx_test<P>(path: P, options: &UpxOptions) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let output = Command::new(&options.path)
+        .arg("-t")
+        .arg(path.as_ref())
+        .output()
+        .with_context(|| format!("failed to run `{}`", options.path.display()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("upx integrity test failed, `{}`", stderr);
+    }
+
+    Ok(())
+}
+
+/// Get the paths that [`lookup_msys2_file`] tries, in the order it tries them.
+///
+/// The `lib` and `bin` dirs are searched, in that order.
+/// In each dir, `name` is tried as-is, then with `.dll` and `.exe` extensions.
+pub fn get_msys2_lookup_paths(environment_path: &Utf8Path, name: &OsStr) -> Vec<PathBuf> {
+    const PATH_EXT: &[&str] = &["dll", "exe"];
+
+    let mut paths = Vec::with_capacity(2 * (PATH_EXT.len() + 1));
+    for path in ["lib", "bin"] {
+        let path = environment_path.join(path);
+        let path = path.join_os(name);
+        paths.push(path.clone());
+
+        for ext in PATH_EXT {
+            // Append .ext to path.
+            // Path cannot do this but OsString can.
+            let mut path_with_ext = OsString::from(&path);
+            path_with_ext.push(".");
+            path_with_ext.push(ext);
+
+            paths.push(PathBuf::from(path_with_ext));
+        }
+    }
+
+    paths
+}
+
+/// Lookup a file in an MSYS2 environment, like `C:/msys64/ucrt64`.
+///
+/// The `lib` and `bin` dirs are searched, in that order.
+/// If `name` does not exist as-is, `.dll` and `.exe` extensions are tried.
+/// See [`get_msys2_lookup_paths`] for the exact paths that are tried.
+///
+/// # Result
+/// Returns None if the file could not be found.
+pub fn lookup_msys2_file(
+    environment_path: &Utf8Path,
+    name: &OsStr,
+) -> anyhow::Result<Option<PathBuf>> {
+    for path in get_msys2_lookup_paths(environment_path, name) {
+        if path
+            .try_exists()
+            .context("failed to check if file exists")?
+        {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
 /// Locate a MSYS2 installation.
 ///
 /// # Returns
 /// Returns a [`Utf8PathBuf`].
 /// This is because MSYS2 requires an ASCII installation path.
+///
+/// This requires the `process` feature.
+#[cfg(feature = "process")]
 pub fn locate_msys2_installation() -> anyhow::Result<Utf8PathBuf> {
     let mut command = Command::new("cmd");
     let mut output = command
@@ -40,14 +407,14 @@ pub fn locate_msys2_installation() -> anyhow::Result<Utf8PathBuf> {
         output.stderr.pop();
         output.stderr.pop();
     }
-    if output.stderr.ends_with(&[b'\n']) {
+    if output.stderr.ends_with(b"\n") {
         output.stderr.pop();
     }
     if output.stdout.ends_with(b"\r\n") {
         output.stdout.pop();
         output.stdout.pop();
     }
-    if output.stdout.ends_with(&[b'\n']) {
+    if output.stdout.ends_with(b"\n") {
         output.stdout.pop();
     }
 
@@ -64,59 +431,157 @@ pub fn locate_msys2_installation() -> anyhow::Result<Utf8PathBuf> {
 }
 
 /// Check if a given dll is a system dll, as in one that is provided by the OS.
+///
+/// The system dlls come from [`KnowledgeBase::global`].
 pub fn is_system_dll(name: &str) -> bool {
-    let name = name.to_ascii_lowercase();
-    let name = name.trim_end_matches(".dll").trim_end_matches(".drv");
-
-    matches!(
-        name,
-        "kernel32"
-            | "ole32"
-            | "oleaut32"
-            | "mfplat"
-            | "user32"
-            | "mf"
-            | "mfreadwrite"
-            | "bcrypt"
-            | "advapi32"
-            | "shell32"
-            | "dnsapi"
-            | "gdi32"
-            | "imm32"
-            | "comdlg32"
-            | "opengl32"
-            | "shlwapi"
-            | "comctl32"
-            | "winspool"
-            | "version"
-            | "cfgmgr32"
-            | "kernelbase"
-            | "usp10"
-            | "msvfw32"
-            | "msimg32"
-            | "winmm"
-            | "rpcrt4"
-            | "userenv"
-            | "hid"
-            | "wsock32"
-            | "ntdll"
-            | "d3d11"
-            | "msvcrt"
-            | "gdiplus"
-            | "avicap32"
-            | "crypt32"
-            | "setupapi"
-            | "iphlpapi"
-            | "ws2_32"
-            | "win32u"
-            | "ncrypt"
-            | "dwmapi"
-            | "dxgi"
-            | "uxtheme"
-            | "mpr"
-            | "netapi32"
-            | "dwrite"
-    )
+    KnowledgeBase::global().is_system_dll(name)
+}
+
+/// The result of normalizing the timestamps of a PE file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PeNormalization {
+    /// The timestamps were set, or already had the given value.
+    Normalized,
+
+    /// The file is signed, so it was left untouched, as changing it would break the signature.
+    Signed,
+}
+
+/// Set the timestamps of a PE file, already loaded into memory, to a fixed value, for reproducible output.
+///
+/// This sets the COFF header, export directory, and debug directory timestamps,
+/// and updates the optional header checksum if the file has one.
+/// Resource directory timestamps are left alone, as windres always writes 0.
+pub fn normalize_pe_timestamps_in_bytes(
+    bytes: &mut [u8],
+    timestamp: u32,
+) -> anyhow::Result<PeNormalization> {
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
+    let optional_header = pe
+        .header
+        .optional_header
+        .context("pe file has no optional header")?;
+    let data_directories = &optional_header.data_directories;
+    if data_directories
+        .get_certificate_table()
+        .is_some_and(|table| table.size != 0)
+    {
+        return Ok(PeNormalization::Signed);
+    }
+
+    // Find the offsets of every timestamp first, as parsing borrows the bytes.
+    let pe_offset = pe.header.dos_header.pe_pointer as usize;
+    // After the signature, machine, and number of sections.
+    let mut timestamp_offsets = vec![pe_offset + 8];
+    // After the signature and COFF header, and then the fields before the checksum, which are the same size in PE32 and PE32+.
+    let checksum_offset = pe_offset + 24 + 64;
+
+    // Map a range of rvas to a file offset, if the whole range is in the raw data of one section.
+    // The raw data is padded to the file alignment, and anything past it is zero-filled when loaded and not in the file.
+    let rva_to_offset = |rva: u32, len: u32| {
+        pe.sections.iter().find_map(|section| {
+            let size = match section.virtual_size {
+                0 => section.size_of_raw_data,
+                virtual_size => virtual_size.min(section.size_of_raw_data),
+            };
+            let relative = rva.checked_sub(section.virtual_address)?;
+            (relative.checked_add(len)? <= size)
+                .then(|| section.pointer_to_raw_data.checked_add(relative))
+                .flatten()
+                .map(|offset| offset as usize)
+        })
+    };
+    if let Some(table) = data_directories.get_export_table() {
+        if table.size != 0 {
+            // Only the fields up to and including the timestamp need to be in the file.
+            let offset = rva_to_offset(table.virtual_address, 8)
+                .context("export directory is outside of every section")?;
+            // After the characteristics.
+            timestamp_offsets.push(offset + 4);
+        }
+    }
+    if let Some(table) = data_directories.get_debug_table() {
+        const DEBUG_DIRECTORY_SIZE: usize = 28;
+
+        if table.size != 0 {
+            let offset = rva_to_offset(table.virtual_address, table.size)
+                .context("debug directory is outside of every section")?;
+            for i in 0..(table.size as usize / DEBUG_DIRECTORY_SIZE) {
+                // After the characteristics.
+                timestamp_offsets.push(offset + i * DEBUG_DIRECTORY_SIZE + 4);
+            }
+        }
+    }
+
+    for offset in timestamp_offsets {
+        let field = bytes
+            .get_mut(offset..offset + 4)
+            .context("timestamp is outside of the file")?;
+        field.copy_from_slice(&timestamp.to_le_bytes());
+    }
+
+    let checksum_field = bytes
+        .get(checksum_offset..checksum_offset + 4)
+        .context("checksum is outside of the file")?;
+    if checksum_field != [0; 4] {
+        let checksum = get_pe_checksum(bytes, checksum_offset);
+        bytes[checksum_offset..checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    Ok(PeNormalization::Normalized)
+}
+
+/// Set the timestamps of the PE file at the given path to a fixed value, for reproducible output.
+///
+/// See [`normalize_pe_timestamps_in_bytes`].
+pub fn normalize_pe_timestamps<P>(path: P, timestamp: u32) -> anyhow::Result<PeNormalization>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut bytes = std::fs::read(path).context("failed to read file")?;
+    let original = bytes.clone();
+    let normalization = normalize_pe_timestamps_in_bytes(&mut bytes, timestamp)?;
+    if bytes != original {
+        std::fs::write(path, bytes).context("failed to write file")?;
+    }
+
+    Ok(normalization)
+}
+
+/// Get the timestamp to set in reproducible outputs, from the `SOURCE_DATE_EPOCH` env var.
+///
+/// # Returns
+/// Returns 0 if the env var is not set.
+pub fn get_reproducible_timestamp() -> anyhow::Result<u32> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(timestamp) => timestamp.trim().parse().with_context(|| {
+            format!("`SOURCE_DATE_EPOCH` is `{timestamp}`, which is not a 32-bit unix timestamp")
+        }),
+        Err(std::env::VarError::NotPresent) => Ok(0),
+        Err(e) => Err(e).context("invalid `SOURCE_DATE_EPOCH` env var"),
+    }
+}
+
+/// Compute the checksum of a PE file, like `CheckSumMappedFile`.
+///
+/// `checksum_offset` is the offset of the checksum field, which is skipped.
+fn get_pe_checksum(bytes: &[u8], checksum_offset: usize) -> u32 {
+    let mut sum: u32 = 0;
+    for (i, word) in bytes.chunks(2).enumerate() {
+        let offset = i * 2;
+        if offset + 2 > checksum_offset && offset < checksum_offset + 4 {
+            continue;
+        }
+
+        let word = u16::from_le_bytes([word[0], word.get(1).copied().unwrap_or(0)]);
+        sum += u32::from(word);
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum = (sum & 0xFFFF) + (sum >> 16);
+
+    sum.wrapping_add(bytes.len() as u32)
 }
 
 /// Get dll imports for the given library or executable.
@@ -126,55 +591,80 @@ where
 {
     let path = path.as_ref();
     let bytes = std::fs::read(path).context("failed to read file")?;
-    let pe = goblin::pe::PE::parse(&bytes).context("failed to parse pe file")?;
+    get_dll_imports_from_bytes(&bytes)
+}
+
+/// Get dll imports for the given library or executable, already loaded into memory.
+pub fn get_dll_imports_from_bytes(bytes: &[u8]) -> anyhow::Result<Vec<String>> {
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
 
     Ok(pe.libraries.iter().map(|name| name.to_string()).collect())
 }
 
+/// Get the arch an exe or dll, already loaded into memory, was built for.
+///
+/// # Returns
+/// Returns None if the arch is not one that MSYS2 supports.
+pub fn get_pe_arch_from_bytes(bytes: &[u8]) -> anyhow::Result<Option<Msys2Arch>> {
+    use goblin::pe::header::COFF_MACHINE_ARM64;
+    use goblin::pe::header::COFF_MACHINE_X86;
+    use goblin::pe::header::COFF_MACHINE_X86_64;
+
+    let pe = goblin::pe::PE::parse(bytes).context("failed to parse pe file")?;
+    let arch = match pe.header.coff_header.machine {
+        COFF_MACHINE_X86_64 => Some(Msys2Arch::X86_64),
+        COFF_MACHINE_X86 => Some(Msys2Arch::I686),
+        COFF_MACHINE_ARM64 => Some(Msys2Arch::AArch64),
+        _ => None,
+    };
+
+    Ok(arch)
+}
+
 /// Check if a given name is an api set dll.
 ///
 /// Names are in the format {api-,ext-}{alphanumeric,'-'}{n}-{n}-{n}.dll where n is a number.
+/// The prefixes come from [`KnowledgeBase::global`].
 ///
-/// See https://learn.microsoft.com/en-us/windows/win32/apiindex/windows-apisets.
+/// See <https://learn.microsoft.com/en-us/windows/win32/apiindex/windows-apisets>.
 pub fn is_api_set_dll(name: &str) -> bool {
-    // api- exists on all Windows versions, ext- does not.
-    if let Some(name) = name
-        .strip_prefix("api-")
-        .or_else(|| name.strip_prefix("ext-"))
-    {
-        let name = name.trim_end_matches(".dll");
-
-        let name = match name.rsplit_once('l') {
-            Some((name, rest)) => {
-                let mut n_iter = rest.split('-').filter(|s| !s.is_empty());
-                for _ in 0..3 {
-                    let _n = match n_iter.next().map(|n| n.parse::<u32>()) {
-                        Some(Ok(n)) => n,
-                        None | Some(Err(_)) => {
-                            return false;
-                        }
-                    };
-                }
+    KnowledgeBase::global().is_api_set_dll(name)
+}
 
-                if n_iter.next().is_some() {
-                    return false;
-                }
+/// Check if a dll name matches a glob pattern, like `libicudata*.dll`.
+///
+/// `*` matches any run of characters and `?` matches one character.
+/// Matching is case-insensitive, like Windows file names.
+pub fn matches_dll_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
 
-                name
+    let mut pattern_index = 0;
+    let mut name_index = 0;
+    // Where to resume if the current match fails: the index after the last `*`, and the name index it is matched up to.
+    let mut backtrack = None;
+    while name_index < name.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                pattern_index += 1;
+                backtrack = Some((pattern_index, name_index));
             }
-            None => return false,
-        };
-
-        for c in name.chars() {
-            if !c.is_alphanumeric() && c != '-' {
-                return false;
+            Some(c) if *c == '?' || *c == name[name_index] => {
+                pattern_index += 1;
+                name_index += 1;
             }
+            _ => match backtrack.as_mut() {
+                Some((star_pattern_index, star_name_index)) => {
+                    *star_name_index += 1;
+                    pattern_index = *star_pattern_index;
+                    name_index = *star_name_index;
+                }
+                None => return false,
+            },
         }
-
-        true
-    } else {
-        false
     }
+
+    pattern[pattern_index..].iter().all(|c| *c == '*')
 }
 
 /// Convert a target triple into an MSYS2 environment.
@@ -227,3 +717,160 @@ pub fn target_triple_to_msys2_environment(triple: &str) -> Option<Msys2Environme
         _ => None,
     }
 }
+
+/// Get the support dlls of the toolchain of an MSYS2 environment.
+///
+/// Binaries built by the toolchain may load these lazily, or through C dependencies,
+/// in ways that import scanning does not see.
+///
+/// # Returns
+/// Returns an empty slice if the environment is not known to this library.
+pub fn get_toolchain_runtime_dlls(environment: Msys2Environment) -> &'static [&'static str] {
+    match environment {
+        Msys2Environment::Mingw64 | Msys2Environment::Ucrt64 => &[
+            "libgcc_s_seh-1.dll",
+            "libwinpthread-1.dll",
+            "libstdc++-6.dll",
+        ],
+        Msys2Environment::Mingw32 => &[
+            "libgcc_s_dw2-1.dll",
+            "libwinpthread-1.dll",
+            "libstdc++-6.dll",
+        ],
+        Msys2Environment::Clang64 | Msys2Environment::Clang32 | Msys2Environment::ClangArm64 => {
+            &["libunwind.dll", "libwinpthread-1.dll", "libc++.dll"]
+        }
+        _ => &[],
+    }
+}
+
+/// A runtime used to unwind the stack, for things like panics and C++ exceptions.
+///
+/// Binaries using different unwind runtimes cannot safely be mixed in one process.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum UnwindRuntime {
+    /// Structured exception handling based unwinding, used by x86_64 GCC.
+    Seh,
+
+    /// DWARF-2 based unwinding, used by i686 GCC.
+    Dwarf2,
+
+    /// setjmp/longjmp based unwinding.
+    ///
+    /// No MSYS2 environment uses this,
+    /// but it can appear in binaries built by other MinGW distributions.
+    Sjlj,
+
+    /// LLVM's libunwind, used by Clang.
+    LibUnwind,
+}
+
+impl UnwindRuntime {
+    /// Get the unwind runtime that the toolchain of an MSYS2 environment uses.
+    ///
+    /// # Returns
+    /// Returns None if the environment is not known to this library.
+    pub fn from_msys2_environment(environment: Msys2Environment) -> Option<Self> {
+        match environment {
+            Msys2Environment::Msys | Msys2Environment::Mingw64 | Msys2Environment::Ucrt64 => {
+                Some(Self::Seh)
+            }
+            Msys2Environment::Mingw32 => Some(Self::Dwarf2),
+            Msys2Environment::Clang64
+            | Msys2Environment::Clang32
+            | Msys2Environment::ClangArm64 => Some(Self::LibUnwind),
+            _ => None,
+        }
+    }
+
+    /// Get the unwind runtime provided by the dll with the given name.
+    ///
+    /// # Returns
+    /// Returns None if the dll is not an unwind runtime.
+    pub fn from_dll_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "libgcc_s_seh-1.dll" => Some(Self::Seh),
+            "libgcc_s_dw2-1.dll" => Some(Self::Dwarf2),
+            "libgcc_s_sjlj-1.dll" => Some(Self::Sjlj),
+            "libunwind.dll" => Some(Self::LibUnwind),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the dll that provides this runtime.
+    pub fn get_dll_name(self) -> &'static str {
+        match self {
+            Self::Seh => "libgcc_s_seh-1.dll",
+            Self::Dwarf2 => "libgcc_s_dw2-1.dll",
+            Self::Sjlj => "libgcc_s_sjlj-1.dll",
+            Self::LibUnwind => "libunwind.dll",
+        }
+    }
+
+    /// Get a human-readable name for this runtime.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::Seh => "SEH",
+            Self::Dwarf2 => "DWARF-2",
+            Self::Sjlj => "SJLJ",
+            Self::LibUnwind => "libunwind",
+        }
+    }
+}
+
+/// A C runtime that binaries link against.
+///
+/// Each C runtime has its own heap and stdio state,
+/// so passing allocations or `FILE` pointers between binaries using different C runtimes corrupts memory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum CRuntime {
+    /// The legacy `msvcrt.dll`, used by the MINGW environments.
+    Msvcrt,
+
+    /// The Universal C Runtime, used by the UCRT and CLANG environments.
+    Ucrt,
+}
+
+impl CRuntime {
+    /// Get the C runtime that the toolchain of an MSYS2 environment uses.
+    ///
+    /// # Returns
+    /// Returns None if the environment does not use a Windows C runtime, like MSYS.
+    pub fn from_msys2_environment(environment: Msys2Environment) -> Option<Self> {
+        match environment {
+            Msys2Environment::Mingw64 | Msys2Environment::Mingw32 => Some(Self::Msvcrt),
+            Msys2Environment::Ucrt64
+            | Msys2Environment::Clang64
+            | Msys2Environment::Clang32
+            | Msys2Environment::ClangArm64 => Some(Self::Ucrt),
+            _ => None,
+        }
+    }
+
+    /// Get the C runtime provided by the dll with the given name.
+    ///
+    /// The UCRT is usually imported through `api-ms-win-crt-*` api set dlls.
+    ///
+    /// # Returns
+    /// Returns None if the dll is not a C runtime.
+    pub fn from_dll_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "msvcrt.dll" {
+            Some(Self::Msvcrt)
+        } else if name == "ucrtbase.dll" || name.starts_with("api-ms-win-crt-") {
+            Some(Self::Ucrt)
+        } else {
+            None
+        }
+    }
+
+    /// Get a human-readable name for this runtime.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::Msvcrt => "MSVCRT",
+            Self::Ucrt => "UCRT",
+        }
+    }
+}
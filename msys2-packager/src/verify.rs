@@ -0,0 +1,199 @@
+use crate::report::OutputFormat;
+use crate::resolver::ResolveOptions;
+use crate::util::get_dll_imports_from_bytes;
+use crate::util::get_pe_arch_from_bytes;
+use crate::util::is_api_set_dll;
+use anyhow::Context;
+use msys2::Msys2Arch;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+/// An import that no file in a package satisfies.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct UnresolvedImport {
+    /// The binary with the import, relative to the package top level.
+    pub importer: PathBuf,
+
+    /// The name of the imported dll.
+    pub name: String,
+}
+
+/// A binary built for a different arch than its package.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct MismatchedArch {
+    /// The binary, relative to the package top level.
+    pub path: PathBuf,
+
+    /// The arch of the binary, or None if it is not one that MSYS2 supports.
+    pub arch: Option<&'static str>,
+}
+
+/// The result of verifying a package dir.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct VerifyReport {
+    /// The number of exes and dlls whose imports were checked.
+    pub binaries: usize,
+
+    /// Imports that are not satisfied, sorted by importer.
+    pub unresolved: Vec<UnresolvedImport>,
+
+    /// Binaries built for a different arch than the package, sorted by path.
+    ///
+    /// This is only checked if the package has an expected arch.
+    pub mismatched_arch: Vec<MismatchedArch>,
+}
+
+impl VerifyReport {
+    /// Check if every import was satisfied, and every binary has the expected arch.
+    pub fn is_ok(&self) -> bool {
+        self.unresolved.is_empty() && self.mismatched_arch.is_empty()
+    }
+
+    /// Print this report to stdout in the given format.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for import in self.unresolved.iter() {
+            writeln!(
+                f,
+                "`{}` imports missing `{}`",
+                import.importer.display(),
+                import.name
+            )?;
+        }
+        for binary in self.mismatched_arch.iter() {
+            writeln!(
+                f,
+                "`{}` is built for `{}`",
+                binary.path.display(),
+                binary.arch.unwrap_or("an unknown arch")
+            )?;
+        }
+        write!(
+            f,
+            "Checked {} binaries, {} unresolved imports",
+            self.binaries,
+            self.unresolved.len()
+        )
+    }
+}
+
+/// Verify that every exe and dll in a package dir can load.
+///
+/// An import is satisfied by a file in the package top level, where Windows looks next to the exe,
+/// by a file in the same dir as the importer, where libraries loaded by path look,
+/// or by a system or api set dll.
+/// Matching is case-insensitive, like Windows.
+pub fn verify_package(package_dir: &Path) -> anyhow::Result<VerifyReport> {
+    verify_package_for_arch(package_dir, None)
+}
+
+/// Verify that every exe and dll in a package dir can load, and is built for the given arch.
+///
+/// This is like [`verify_package`], but also checks the arch if one is given,
+/// like for packages that keep a dir per arch for plugin hosts of a different bitness.
+pub fn verify_package_for_arch(
+    package_dir: &Path,
+    arch: Option<Msys2Arch>,
+) -> anyhow::Result<VerifyReport> {
+    verify_package_with_options(package_dir, arch, &ResolveOptions::default())
+}
+
+/// Verify a package dir, like [`verify_package_for_arch`], with the system dll rules of `options`.
+///
+/// Packages made with extra system or bundled dlls must be verified with the same rules,
+/// or the imports of those dlls are reported wrongly.
+pub fn verify_package_with_options(
+    package_dir: &Path,
+    arch: Option<Msys2Arch>,
+    options: &ResolveOptions,
+) -> anyhow::Result<VerifyReport> {
+    let mut files = HashSet::new();
+    let mut binaries = Vec::new();
+    for entry in WalkDir::new(package_dir).sort_by_file_name() {
+        let entry = entry.context("failed to read package dir entry")?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative_path = entry
+            .path()
+            .strip_prefix(package_dir)
+            .context("package file is not in the package dir")?
+            .to_path_buf();
+        files.insert(normalize_path(&relative_path));
+
+        let is_binary = relative_path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("exe") || extension.eq_ignore_ascii_case("dll")
+        });
+        if is_binary {
+            binaries.push(relative_path);
+        }
+    }
+
+    let mut unresolved = Vec::new();
+    let mut mismatched_arch = Vec::new();
+    for binary in binaries.iter() {
+        let bytes = std::fs::read(package_dir.join(binary))
+            .with_context(|| format!("failed to read `{}`", binary.display()))?;
+        let imports = get_dll_imports_from_bytes(&bytes)
+            .with_context(|| format!("failed to get imports for `{}`", binary.display()))?;
+        if let Some(arch) = arch {
+            let binary_arch = get_pe_arch_from_bytes(&bytes)
+                .with_context(|| format!("failed to get the arch of `{}`", binary.display()))?;
+            if binary_arch != Some(arch) {
+                mismatched_arch.push(MismatchedArch {
+                    path: binary.clone(),
+                    arch: binary_arch.map(Msys2Arch::get_name),
+                });
+            }
+        }
+        let parent = binary.parent().unwrap_or_else(|| Path::new(""));
+        for name in imports {
+            let satisfied = options.is_system_dll(&name)
+                || is_api_set_dll(&name)
+                || files.contains(&normalize_path(Path::new(&name)))
+                || files.contains(&normalize_path(&parent.join(&name)));
+            if !satisfied {
+                unresolved.push(UnresolvedImport {
+                    importer: binary.clone(),
+                    name,
+                });
+            }
+        }
+    }
+
+    Ok(VerifyReport {
+        binaries: binaries.len(),
+        unresolved,
+        mismatched_arch,
+    })
+}
+
+/// Normalize a relative path for comparison, like Windows does.
+fn normalize_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy().to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("/")
+}
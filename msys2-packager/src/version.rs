@@ -0,0 +1,138 @@
+use crate::report::OutputFormat;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// The version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Get the versions of the file formats this crate reads and writes, by name.
+pub fn get_schema_versions() -> BTreeMap<&'static str, u32> {
+    BTreeMap::from([
+        ("cache", crate::cache::CACHE_VERSION),
+        ("lock", crate::lock::LOCK_VERSION),
+    ])
+}
+
+/// Version and build info for a binary, for `--version --verbose`.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct VersionInfo {
+    /// The binary name
+    pub name: String,
+
+    /// The binary version
+    pub version: String,
+
+    /// The git commit the binary was built from, if known
+    pub git_hash: Option<String>,
+
+    /// When the binary was built, as an RFC 3339 UTC timestamp, if known.
+    ///
+    /// Binaries built from git may use the commit time, so this stays the same across rebuilds.
+    pub build_date: Option<String>,
+
+    /// The enabled cargo features
+    pub features: Vec<String>,
+
+    /// The versions of the library crates the binary was built with, by name
+    pub crates: BTreeMap<String, String>,
+
+    /// The versions of the file formats the binary reads and writes, by name
+    pub schemas: BTreeMap<String, u32>,
+}
+
+impl VersionInfo {
+    /// Make a new [`VersionInfo`] for a binary.
+    ///
+    /// The versions of `msys2` and `msys2-packager`, and the schema versions, are filled in.
+    pub fn new(name: String, version: String) -> Self {
+        Self {
+            name,
+            version,
+            git_hash: None,
+            build_date: None,
+            features: Vec::new(),
+            crates: BTreeMap::from([
+                ("msys2".into(), msys2::VERSION.into()),
+                ("msys2-packager".into(), VERSION.into()),
+            ]),
+            schemas: get_schema_versions()
+                .into_iter()
+                .map(|(name, version)| (name.into(), version))
+                .collect(),
+        }
+    }
+
+    /// Set the git commit the binary was built from.
+    pub fn git_hash(&mut self, git_hash: Option<String>) -> &mut Self {
+        self.git_hash = git_hash;
+        self
+    }
+
+    /// Set when the binary was built, in seconds since the Unix epoch.
+    pub fn build_timestamp(&mut self, secs: Option<u64>) -> &mut Self {
+        self.build_date = secs.map(|secs| {
+            crate::sbom::format_timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        });
+        self
+    }
+
+    /// Add an enabled cargo feature.
+    pub fn feature(&mut self, feature: String) -> &mut Self {
+        self.features.push(feature);
+        self
+    }
+
+    /// Print this to stdout.
+    ///
+    /// If `verbose` is false, only the name and version are printed in text mode.
+    pub fn print(&self, format: OutputFormat, verbose: bool) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Text if !verbose => {
+                println!("{} {}", self.name, self.version);
+            }
+            OutputFormat::Text => {
+                println!("{self}");
+            }
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(self)?;
+                println!("{json}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}", self.name, self.version)?;
+        writeln!(
+            f,
+            "git hash: {}",
+            self.git_hash.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(
+            f,
+            "build date: {}",
+            self.build_date.as_deref().unwrap_or("unknown")
+        )?;
+        if self.features.is_empty() {
+            writeln!(f, "features: (none)")?;
+        } else {
+            writeln!(f, "features: {}", self.features.join(", "))?;
+        }
+        writeln!(f, "crates:")?;
+        for (name, version) in self.crates.iter() {
+            writeln!(f, "  {name} {version}")?;
+        }
+        write!(f, "schemas:")?;
+        for (name, version) in self.schemas.iter() {
+            write!(f, "\n  {name} v{version}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+//! Package a stand-in for `examples/hello-gtk4` against a mocked MSYS2 installation,
+//! and compare the results against golden files.
+//!
+//! This runs without MSYS2. `msys2-gtk-packager/tests/hello_gtk4.rs` packages the real app.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an intended change.
+
+use msys2::Msys2Environment;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use msys2_packager::packager::UnresolvedError;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+/// The env var that makes tests rewrite golden files instead of comparing against them.
+const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+
+/// A file in the mocked MSYS2 installation.
+enum MockFile {
+    /// A PE binary with the given dll imports
+    Pe(&'static [&'static str]),
+
+    /// A data file with the given contents
+    Data(&'static str),
+}
+
+/// A package in the mocked MSYS2 installation
+struct MockPackage {
+    /// The package name
+    name: &'static str,
+
+    /// The package version
+    version: &'static str,
+
+    /// Files, relative to the installation
+    files: &'static [(&'static str, MockFile)],
+}
+
+/// The packages of a small UCRT64 GTK4 installation.
+///
+/// The import graphs are trimmed down versions of the real ones.
+const PACKAGES: &[MockPackage] = &[
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-gtk4",
+        version: "4.14.4-1",
+        files: &[
+            (
+                "ucrt64/bin/libgtk-4-1.dll",
+                MockFile::Pe(&[
+                    "libglib-2.0-0.dll",
+                    "libgobject-2.0-0.dll",
+                    "libgio-2.0-0.dll",
+                    "libcairo-2.dll",
+                    "libintl-8.dll",
+                    "KERNEL32.dll",
+                    "api-ms-win-crt-runtime-l1-1-0.dll",
+                ]),
+            ),
+            (
+                "ucrt64/lib/gtk-4.0/4.0.0/media/libmedia-gstreamer.dll",
+                MockFile::Pe(&["libgtk-4-1.dll", "libgstreamer-1.0-0.dll"]),
+            ),
+        ],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-glib2",
+        version: "2.80.2-1",
+        files: &[
+            (
+                "ucrt64/bin/libglib-2.0-0.dll",
+                MockFile::Pe(&[
+                    "libintl-8.dll",
+                    "KERNEL32.dll",
+                    "api-ms-win-crt-string-l1-1-0.dll",
+                ]),
+            ),
+            (
+                "ucrt64/bin/libgobject-2.0-0.dll",
+                MockFile::Pe(&["libglib-2.0-0.dll", "libffi-8.dll", "KERNEL32.dll"]),
+            ),
+            (
+                "ucrt64/bin/libgio-2.0-0.dll",
+                MockFile::Pe(&["libglib-2.0-0.dll", "libgobject-2.0-0.dll", "SHELL32.dll"]),
+            ),
+            (
+                "ucrt64/bin/gdbus.exe",
+                MockFile::Pe(&["libgio-2.0-0.dll", "libglib-2.0-0.dll", "KERNEL32.dll"]),
+            ),
+            (
+                "ucrt64/share/glib-2.0/schemas/gschemas.compiled",
+                MockFile::Data("GVariant schemas"),
+            ),
+        ],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-cairo",
+        version: "1.18.0-3",
+        files: &[(
+            "ucrt64/bin/libcairo-2.dll",
+            MockFile::Pe(&["libpixman-1-0.dll", "GDI32.dll"]),
+        )],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-pixman",
+        version: "0.43.4-1",
+        files: &[(
+            "ucrt64/bin/libpixman-1-0.dll",
+            MockFile::Pe(&["KERNEL32.dll"]),
+        )],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-gettext-runtime",
+        version: "0.22.5-2",
+        files: &[(
+            "ucrt64/bin/libintl-8.dll",
+            MockFile::Pe(&["libiconv-2.dll", "KERNEL32.dll"]),
+        )],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-libiconv",
+        version: "1.17-4",
+        files: &[("ucrt64/bin/libiconv-2.dll", MockFile::Pe(&["KERNEL32.dll"]))],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-libffi",
+        version: "3.4.6-1",
+        files: &[("ucrt64/bin/libffi-8.dll", MockFile::Pe(&["KERNEL32.dll"]))],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-gstreamer",
+        version: "1.24.5-1",
+        files: &[(
+            "ucrt64/bin/libgstreamer-1.0-0.dll",
+            MockFile::Pe(&["libglib-2.0-0.dll", "libgobject-2.0-0.dll"]),
+        )],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-gcc-libs",
+        version: "14.1.0-3",
+        files: &[
+            (
+                "ucrt64/bin/libgcc_s_seh-1.dll",
+                MockFile::Pe(&["libwinpthread-1.dll", "KERNEL32.dll"]),
+            ),
+            (
+                "ucrt64/bin/libstdc++-6.dll",
+                MockFile::Pe(&["libgcc_s_seh-1.dll", "libwinpthread-1.dll"]),
+            ),
+        ],
+    },
+    MockPackage {
+        name: "mingw-w64-ucrt-x86_64-libwinpthread-git",
+        version: "12.0.0.r32.gf3e3e7d1c-1",
+        files: &[(
+            "ucrt64/bin/libwinpthread-1.dll",
+            MockFile::Pe(&["KERNEL32.dll"]),
+        )],
+    },
+];
+
+/// The stylesheet of the example, packaged as an asset.
+///
+/// This is a copy of `examples/hello-gtk4/assets/hello.css`, so this crate does not reach outside its dir.
+const EXAMPLE_CSS: &str = "label {\n    font-size: 24px;\n}\n";
+
+/// The imports of the stand-in for the `hello-gtk4` exe.
+const APP_IMPORTS: &[&str] = &[
+    "libgtk-4-1.dll",
+    "libglib-2.0-0.dll",
+    "libgobject-2.0-0.dll",
+    "libgcc_s_seh-1.dll",
+    "KERNEL32.dll",
+    "api-ms-win-crt-heap-l1-1-0.dll",
+];
+
+/// Make a minimal PE32+ image that imports the given dlls, one ordinal each.
+fn make_pe(imports: &[&str], is_dll: bool) -> Vec<u8> {
+    const PE_OFFSET: usize = 0x40;
+    const OPTIONAL_HEADER_SIZE: usize = 240;
+    const HEADERS_SIZE: usize = 0x200;
+    const SECTION_RVA: u32 = 0x1000;
+    const DESCRIPTOR_SIZE: usize = 20;
+
+    // The import section: descriptors, then one lookup table per dll, then names.
+    let descriptors_size = (imports.len() + 1) * DESCRIPTOR_SIZE;
+    let tables_offset = descriptors_size;
+    let names_offset = tables_offset + imports.len() * 16;
+    let mut section = vec![0; names_offset];
+    for (i, name) in imports.iter().enumerate() {
+        let table_rva = SECTION_RVA + (tables_offset + i * 16) as u32;
+        let name_rva = SECTION_RVA + (section.len()) as u32;
+        section.extend_from_slice(name.as_bytes());
+        section.push(0);
+
+        let descriptor = i * DESCRIPTOR_SIZE;
+        section[descriptor..descriptor + 4].copy_from_slice(&table_rva.to_le_bytes());
+        section[descriptor + 12..descriptor + 16].copy_from_slice(&name_rva.to_le_bytes());
+        section[descriptor + 16..descriptor + 20].copy_from_slice(&table_rva.to_le_bytes());
+
+        // Import ordinal 1, then a null entry.
+        let table = tables_offset + i * 16;
+        section[table..table + 8].copy_from_slice(&(1u64 | (1 << 63)).to_le_bytes());
+    }
+    let virtual_size = section.len() as u32;
+    section.resize(section.len().next_multiple_of(0x200), 0);
+
+    let mut pe = vec![0; HEADERS_SIZE];
+    pe[0..2].copy_from_slice(b"MZ");
+    pe[0x3C..0x40].copy_from_slice(&(PE_OFFSET as u32).to_le_bytes());
+
+    let mut headers = Vec::new();
+    headers.extend_from_slice(b"PE\0\0");
+
+    // COFF header
+    let characteristics: u16 = if is_dll { 0x2022 } else { 0x0022 };
+    headers.extend_from_slice(&0x8664u16.to_le_bytes());
+    headers.extend_from_slice(&1u16.to_le_bytes());
+    headers.extend_from_slice(&[0; 12]);
+    headers.extend_from_slice(&(OPTIONAL_HEADER_SIZE as u16).to_le_bytes());
+    headers.extend_from_slice(&characteristics.to_le_bytes());
+
+    // Optional header
+    let optional_header_start = headers.len();
+    let image_size = SECTION_RVA + (virtual_size.next_multiple_of(0x1000));
+    headers.extend_from_slice(&0x20Bu16.to_le_bytes());
+    headers.extend_from_slice(&[0; 2]);
+    headers.extend_from_slice(&[0; 4 * 5]);
+    headers.extend_from_slice(&0x1_4000_0000u64.to_le_bytes());
+    headers.extend_from_slice(&0x1000u32.to_le_bytes());
+    headers.extend_from_slice(&0x200u32.to_le_bytes());
+    headers.extend_from_slice(&[0; 2 * 4]);
+    headers.extend_from_slice(&6u16.to_le_bytes());
+    headers.extend_from_slice(&0u16.to_le_bytes());
+    headers.extend_from_slice(&0u32.to_le_bytes());
+    headers.extend_from_slice(&image_size.to_le_bytes());
+    headers.extend_from_slice(&(HEADERS_SIZE as u32).to_le_bytes());
+    headers.extend_from_slice(&0u32.to_le_bytes());
+    headers.extend_from_slice(&3u16.to_le_bytes());
+    headers.extend_from_slice(&0u16.to_le_bytes());
+    headers.extend_from_slice(&[0; 8 * 4]);
+    headers.extend_from_slice(&0u32.to_le_bytes());
+    headers.extend_from_slice(&16u32.to_le_bytes());
+    for i in 0..16 {
+        let (rva, size) = if i == 1 {
+            (SECTION_RVA, descriptors_size as u32)
+        } else {
+            (0, 0)
+        };
+        headers.extend_from_slice(&rva.to_le_bytes());
+        headers.extend_from_slice(&size.to_le_bytes());
+    }
+    assert_eq!(
+        headers.len() - optional_header_start,
+        OPTIONAL_HEADER_SIZE,
+        "bad optional header size"
+    );
+
+    // Section header
+    headers.extend_from_slice(b".idata\0\0");
+    headers.extend_from_slice(&virtual_size.to_le_bytes());
+    headers.extend_from_slice(&SECTION_RVA.to_le_bytes());
+    headers.extend_from_slice(&(section.len() as u32).to_le_bytes());
+    headers.extend_from_slice(&(HEADERS_SIZE as u32).to_le_bytes());
+    headers.extend_from_slice(&[0; 12]);
+    headers.extend_from_slice(&0xC000_0040u32.to_le_bytes());
+
+    pe[PE_OFFSET..PE_OFFSET + headers.len()].copy_from_slice(&headers);
+    pe.extend_from_slice(&section);
+
+    pe
+}
+
+/// Write the mocked MSYS2 installation, including its pacman database.
+fn write_installation(root: &Path) {
+    for package in PACKAGES {
+        let mut file_list = String::from("%FILES%\n");
+        for (path, file) in package.files {
+            let full_path = root.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            let data = match file {
+                MockFile::Pe(imports) => make_pe(imports, path.ends_with(".dll")),
+                MockFile::Data(data) => data.as_bytes().to_vec(),
+            };
+            std::fs::write(&full_path, data).unwrap();
+            writeln!(file_list, "{path}").unwrap();
+        }
+
+        let database_dir = root
+            .join("var/lib/pacman/local")
+            .join(format!("{}-{}", package.name, package.version));
+        std::fs::create_dir_all(&database_dir).unwrap();
+        std::fs::write(
+            database_dir.join("desc"),
+            format!(
+                "%NAME%\n{}\n\n%VERSION%\n{}\n\n%LICENSE%\nspdx:MIT\n\n",
+                package.name, package.version
+            ),
+        )
+        .unwrap();
+        std::fs::write(database_dir.join("files"), file_list).unwrap();
+    }
+}
+
+/// Make a fresh, empty temp dir for a test.
+fn make_test_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("msys2-packager-test-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Format a path relative to a base, with `/` separators.
+fn relative(path: &Path, base: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Compare output against a golden file, or rewrite the golden file if requested.
+fn check_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read `{}`: {e}", path.display()));
+    let expected = expected.replace("\r\n", "\n");
+    assert!(
+        expected == actual,
+        "`{}` does not match, rerun with `{UPDATE_GOLDEN_ENV}=1` if this is intended\n--- expected\n{expected}\n--- actual\n{actual}",
+        path.display()
+    );
+}
+
+#[test]
+fn hello_gtk4() {
+    let dir = make_test_dir("hello-gtk4");
+    let installation = dir.join("msys64");
+    write_installation(&installation);
+
+    let app = dir.join("app/hello-gtk4.exe");
+    std::fs::create_dir_all(app.parent().unwrap()).unwrap();
+    std::fs::write(&app, make_pe(APP_IMPORTS, false)).unwrap();
+    let css = dir.join("app/hello.css");
+    std::fs::write(&css, EXAMPLE_CSS).unwrap();
+
+    let out = dir.join("out");
+    let mut packager = Packager::new(
+        installation.to_str().unwrap().into(),
+        Msys2Environment::Ucrt64,
+        out.clone(),
+    );
+    packager
+        .add_file(
+            Some(app),
+            "hello-gtk4.exe".into(),
+            FileFlags::EXE | FileFlags::ADD_DEPS,
+        )
+        .add_file(
+            None,
+            "gdbus.exe".into(),
+            FileFlags::EXE | FileFlags::ADD_DEPS,
+        )
+        .add_file(
+            Some(installation.join("ucrt64/lib/gtk-4.0/4.0.0/media/libmedia-gstreamer.dll")),
+            "lib/gtk-4.0/4.0.0/media/libmedia-gstreamer.dll".into(),
+            FileFlags::LIB | FileFlags::ADD_DEPS,
+        )
+        .add_file(
+            Some(installation.join("ucrt64/share/glib-2.0/schemas/gschemas.compiled")),
+            "share/glib-2.0/schemas/gschemas.compiled".into(),
+            FileFlags::empty(),
+        )
+        .add_file(
+            Some(css),
+            "share/hello-gtk4/hello.css".into(),
+            FileFlags::empty(),
+        );
+    let report = packager.package().expect("failed to package");
+    let ownership = packager
+        .package_report_for_plan(&report.plan)
+        .expect("failed to report ownership");
+
+    let mut plan = String::new();
+    for file in report.plan.files.iter() {
+        writeln!(
+            plan,
+            "{} <- {} {:?}",
+            relative(&file.dest, Path::new("")),
+            relative(&file.src, &dir),
+            file.flags.names()
+        )
+        .unwrap();
+    }
+    for name in report.plan.unresolved.iter() {
+        writeln!(plan, "missing {name}").unwrap();
+    }
+    check_golden("hello-gtk4.plan.txt", &plan);
+
+    let mut layout = String::new();
+    let mut paths: Vec<_> = WalkDir::new(&out)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| relative(entry.path(), &out))
+        .collect();
+    paths.sort();
+    for path in paths {
+        writeln!(layout, "{path}").unwrap();
+    }
+    check_golden("hello-gtk4.layout.txt", &layout);
+
+    check_golden("hello-gtk4.ownership.txt", &format!("{ownership}\n"));
+
+    let graph = packager
+        .dependency_graph()
+        .expect("missing dependency graph");
+    check_golden("hello-gtk4.deps.dot", &graph.to_dot());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Set the COFF header timestamp of a PE image made by [`make_pe`].
+fn set_pe_timestamp(pe: &mut [u8], timestamp: u32) {
+    // After the `PE\0\0` signature, machine, and number of sections.
+    pe[0x40 + 8..0x40 + 12].copy_from_slice(&timestamp.to_le_bytes());
+}
+
+/// Read every file of a dir, keyed by its path relative to the dir.
+fn read_dir_files(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    WalkDir::new(dir)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            (
+                relative(entry.path(), dir),
+                std::fs::read(entry.path()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn hello_gtk4_is_reproducible() {
+    const TIMESTAMP: u32 = 1_700_000_000;
+
+    let dir = make_test_dir("hello-gtk4-reproducible");
+    let installation = dir.join("msys64");
+    write_installation(&installation);
+    let app = dir.join("app/hello-gtk4.exe");
+    std::fs::create_dir_all(app.parent().unwrap()).unwrap();
+
+    // Package two builds of the app, which only differ in their build timestamps.
+    let mut packages = Vec::new();
+    for (i, build_timestamp) in [0x1234_5678, 0x2345_6789].into_iter().enumerate() {
+        let mut pe = make_pe(APP_IMPORTS, false);
+        set_pe_timestamp(&mut pe, build_timestamp);
+        std::fs::write(&app, pe).unwrap();
+
+        let out = dir.join(format!("out-{i}"));
+        let mut packager = Packager::new(
+            installation.to_str().unwrap().into(),
+            Msys2Environment::Ucrt64,
+            out.clone(),
+        );
+        packager.normalize_pe_timestamps(Some(TIMESTAMP)).add_file(
+            Some(app.clone()),
+            "hello-gtk4.exe".into(),
+            FileFlags::EXE | FileFlags::ADD_DEPS,
+        );
+        packager.package().expect("failed to package");
+
+        packages.push(read_dir_files(&out));
+    }
+
+    let paths: Vec<_> = packages[0].keys().collect();
+    assert!(
+        paths.contains(&&"libgtk-4-1.dll".to_string()),
+        "the dependencies were not packaged: {paths:?}"
+    );
+    assert_eq!(paths, packages[1].keys().collect::<Vec<_>>());
+    for (path, data) in packages[0].iter() {
+        assert!(data == &packages[1][path], "`{path}` differs between runs");
+    }
+    let exe = &packages[0]["hello-gtk4.exe"];
+    assert_eq!(exe[0x40 + 8..0x40 + 12], TIMESTAMP.to_le_bytes());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn missing_dependency_keeps_the_report() {
+    let dir = make_test_dir("hello-gtk4-missing");
+    let installation = dir.join("msys64");
+    write_installation(&installation);
+    let app = dir.join("app/hello-gtk4.exe");
+    std::fs::create_dir_all(app.parent().unwrap()).unwrap();
+    let mut imports = APP_IMPORTS.to_vec();
+    imports.push("libmissing-1.dll");
+    std::fs::write(&app, make_pe(&imports, false)).unwrap();
+
+    let out = dir.join("out");
+    let mut packager = Packager::new(
+        installation.to_str().unwrap().into(),
+        Msys2Environment::Ucrt64,
+        out.clone(),
+    );
+    packager.add_file(
+        Some(app),
+        "hello-gtk4.exe".into(),
+        FileFlags::EXE | FileFlags::ADD_DEPS,
+    );
+    let error = packager.package().unwrap_err();
+    assert_eq!(error.to_string(), "missing `libmissing-1.dll`");
+
+    let report = error
+        .downcast_ref::<UnresolvedError>()
+        .expect("the error is not an `UnresolvedError`")
+        .report();
+    assert_eq!(report.plan.unresolved, ["libmissing-1.dll"]);
+    assert!(report
+        .plan
+        .files
+        .iter()
+        .any(|file| file.dest == Path::new("libgtk-4-1.dll")));
+    assert!(!out.exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,137 @@
+//! Add files from manifests with `Packager::extend_from_manifest`,
+//! and check that conflicting destinations fail planning.
+
+use msys2::Msys2Environment;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use std::path::Path;
+use std::path::PathBuf;
+
+fn make_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "msys2-packager-manifest-test-{name}-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Make a packager for a test dir, which only packages the files it is given.
+fn make_packager(dir: &Path) -> Packager {
+    let mut packager = Packager::new(
+        dir.join("msys64").to_str().unwrap().into(),
+        Msys2Environment::Ucrt64,
+        dir.join("out"),
+    );
+    packager
+        .resolve_unknown_libraries(false)
+        .toolchain_runtime(false);
+    packager
+}
+
+/// Write the data files `a.txt` and `b.txt`, and a manifest with the given files.
+fn write_manifest(dir: &Path, files: &str) -> PathBuf {
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+    std::fs::write(dir.join("b.txt"), "b").unwrap();
+    let path = dir.join("manifest.json");
+    std::fs::write(&path, format!(r#"{{ "files": [{files}] }}"#)).unwrap();
+    path
+}
+
+#[test]
+fn same_source_is_skipped() {
+    let dir = make_test_dir("same-source");
+    let manifest = write_manifest(
+        &dir,
+        r#"{ "src": "a.txt", "dest": "share/a.txt" }, { "src": "b.txt", "dest": "share/b.txt" }"#,
+    );
+
+    let mut packager = make_packager(&dir);
+    packager.add_file(
+        Some(dir.join("a.txt")),
+        "share/a.txt".into(),
+        FileFlags::empty(),
+    );
+    assert_eq!(packager.extend_from_manifest(&manifest).unwrap(), 1);
+    // The same manifest again only has files that were already added.
+    assert_eq!(packager.extend_from_manifest(&manifest).unwrap(), 0);
+
+    let plan = packager.plan().unwrap();
+    let dests: Vec<_> = plan.files.iter().map(|file| file.dest.clone()).collect();
+    assert_eq!(
+        dests,
+        [PathBuf::from("share/a.txt"), PathBuf::from("share/b.txt")]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn different_source_fails_planning() {
+    let dir = make_test_dir("different-source");
+    let manifest = write_manifest(&dir, r#"{ "src": "b.txt", "dest": "share/a.txt" }"#);
+
+    let mut packager = make_packager(&dir);
+    packager.extend_from_manifest(&manifest).unwrap();
+    // The conflict is found even though this file is added after the manifest.
+    packager.add_file(
+        Some(dir.join("a.txt")),
+        "share/a.txt".into(),
+        FileFlags::empty(),
+    );
+
+    let error = packager.plan().unwrap_err().to_string();
+    assert!(error.contains("share/a.txt"), "{error}");
+    assert!(error.contains("manifest.json"), "{error}");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn different_flags_fail_planning() {
+    let dir = make_test_dir("different-flags");
+    let manifest = write_manifest(
+        &dir,
+        r#"{ "src": "a.txt", "dest": "share/a.txt", "flags": ["upx"] }"#,
+    );
+
+    let mut packager = make_packager(&dir);
+    packager.add_file(
+        Some(dir.join("a.txt")),
+        "share/a.txt".into(),
+        FileFlags::empty(),
+    );
+    assert_eq!(packager.extend_from_manifest(&manifest).unwrap(), 1);
+
+    let error = packager.plan().unwrap_err().to_string();
+    assert!(error.contains("`upx`"), "{error}");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn invalid_destination_is_rejected() {
+    let dir = make_test_dir("invalid-destination");
+    let mut packager = make_packager(&dir);
+
+    for dest in ["../a.txt", "share/../../a.txt", "/a.txt"] {
+        let manifest = write_manifest(
+            &dir,
+            &format!(
+                r#"{{ "src": "a.txt", "dest": "share/b.txt" }}, {{ "src": "a.txt", "dest": "{dest}" }}"#
+            ),
+        );
+        let error = packager.extend_from_manifest(&manifest).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("is not a relative path inside the package"),
+            "{error}"
+        );
+    }
+    // No files of a rejected manifest are added.
+    assert!(packager.files().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,118 @@
+//! Package into an out dir that already has the packaged files, with each `OverwritePolicy`.
+
+use msys2::Msys2Environment;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::OverwritePolicy;
+use msys2_packager::packager::Packager;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+fn make_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "msys2-packager-overwrite-test-{name}-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Package `same.txt` and `different.txt` into an out dir that already has both,
+/// where only `same.txt` matches its source.
+///
+/// The existing files get an old modification time, so replaced files can be told apart from kept ones.
+///
+/// # Returns
+/// Returns the out dir, or the error of packaging.
+fn package_over_existing(name: &str, overwrite: OverwritePolicy) -> anyhow::Result<PathBuf> {
+    let dir = make_test_dir(name);
+    let out = dir.join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    let old_time = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+    for (name, src, existing) in [
+        ("same.txt", "same", "same"),
+        ("different.txt", "new", "old"),
+    ] {
+        std::fs::write(dir.join(name), src).unwrap();
+        std::fs::write(out.join(name), existing).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(out.join(name))
+            .unwrap()
+            .set_modified(old_time)
+            .unwrap();
+    }
+
+    let mut packager = Packager::new(
+        dir.join("msys64").to_str().unwrap().into(),
+        Msys2Environment::Ucrt64,
+        out.clone(),
+    );
+    packager
+        .resolve_unknown_libraries(false)
+        .toolchain_runtime(false)
+        .overwrite(overwrite);
+    for name in ["same.txt", "different.txt"] {
+        packager.add_file(Some(dir.join(name)), name.into(), FileFlags::empty());
+    }
+    packager.package()?;
+
+    Ok(out)
+}
+
+/// Check if a file in an out dir was replaced by packaging.
+fn was_replaced(out: &Path, name: &str) -> bool {
+    let modified = std::fs::metadata(out.join(name))
+        .unwrap()
+        .modified()
+        .unwrap();
+    modified.elapsed().unwrap() < Duration::from_secs(60 * 60)
+}
+
+#[test]
+fn error() {
+    let error = package_over_existing("error", OverwritePolicy::Error).unwrap_err();
+    assert!(error.to_string().contains("already exists"), "{error}");
+}
+
+#[test]
+fn skip() {
+    let out = package_over_existing("skip", OverwritePolicy::Skip).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(out.join("different.txt")).unwrap(),
+        "old"
+    );
+    assert!(!was_replaced(&out, "same.txt"));
+    assert!(!was_replaced(&out, "different.txt"));
+    let _ = std::fs::remove_dir_all(out.parent().unwrap());
+}
+
+#[test]
+fn overwrite() {
+    let out = package_over_existing("overwrite", OverwritePolicy::Overwrite).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(out.join("different.txt")).unwrap(),
+        "new"
+    );
+    assert!(was_replaced(&out, "same.txt"));
+    assert!(was_replaced(&out, "different.txt"));
+    let _ = std::fs::remove_dir_all(out.parent().unwrap());
+}
+
+#[test]
+fn overwrite_if_different() {
+    let out = package_over_existing(
+        "overwrite-if-different",
+        OverwritePolicy::OverwriteIfDifferent,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(out.join("different.txt")).unwrap(),
+        "new"
+    );
+    assert!(!was_replaced(&out, "same.txt"));
+    assert!(was_replaced(&out, "different.txt"));
+    let _ = std::fs::remove_dir_all(out.parent().unwrap());
+}
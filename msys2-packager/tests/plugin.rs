@@ -0,0 +1,195 @@
+//! Add plugins with `Packager::add_plugin`, and check that their hooks are called while planning.
+
+use msys2::Msys2Environment;
+use msys2_packager::packager::FileFlags;
+use msys2_packager::packager::Packager;
+use msys2_packager::packager::Plan;
+use msys2_packager::plugin::PackagerPlugin;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+fn make_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "msys2-packager-plugin-test-{name}-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Make a packager for a test dir, with the data files `a.txt` and `bb.txt` added to `share`.
+fn make_packager(dir: &Path) -> Packager {
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+    std::fs::write(dir.join("bb.txt"), "bb").unwrap();
+
+    let mut packager = Packager::new(
+        dir.join("msys64").to_str().unwrap().into(),
+        Msys2Environment::Ucrt64,
+        dir.join("out"),
+    );
+    packager
+        .resolve_unknown_libraries(false)
+        .toolchain_runtime(false)
+        .add_file(
+            Some(dir.join("a.txt")),
+            "share/a.txt".into(),
+            FileFlags::empty(),
+        )
+        .add_file(
+            Some(dir.join("bb.txt")),
+            "share/bb.txt".into(),
+            FileFlags::empty(),
+        );
+    packager
+}
+
+/// A plugin that moves files from `share` to `data`, and records the hooks it is called with.
+struct MovePlugin {
+    /// The name of this plugin
+    name: &'static str,
+
+    /// The hooks called on every plugin, in order, as `{name}:{hook}`
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl PackagerPlugin for MovePlugin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn modify_plan(&self, plan: &mut Plan) -> anyhow::Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{}:modify_plan", self.name));
+        for file in plan.files.iter_mut() {
+            if let Ok(path) = file.dest.strip_prefix("share") {
+                file.dest = Path::new("data").join(path);
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self, plan: &Plan) -> anyhow::Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{}:validate", self.name));
+        // Every plugin modifies the plan before any validates it.
+        assert!(plan.files.iter().all(|file| file.dest.starts_with("data")));
+        Ok(())
+    }
+}
+
+/// A plugin that drops `bb.txt`, and rejects plans bigger than a byte.
+struct LimitPlugin;
+
+impl PackagerPlugin for LimitPlugin {
+    fn name(&self) -> &str {
+        "limit"
+    }
+
+    fn modify_plan(&self, plan: &mut Plan) -> anyhow::Result<()> {
+        plan.files
+            .retain(|file| file.dest.file_name().unwrap() != "bb.txt");
+        Ok(())
+    }
+
+    fn validate(&self, plan: &Plan) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            plan.total_size <= 1,
+            "the plan is {} bytes",
+            plan.total_size
+        );
+        Ok(())
+    }
+}
+
+/// A plugin that adds a data file to `share`.
+struct AddPlugin(PathBuf);
+
+impl PackagerPlugin for AddPlugin {
+    fn name(&self) -> &str {
+        "add"
+    }
+
+    fn modify_plan(&self, plan: &mut Plan) -> anyhow::Result<()> {
+        let mut file = plan.files[0].clone();
+        file.dest = Path::new("share").join(self.0.file_name().unwrap());
+        file.size = std::fs::metadata(&self.0)?.len();
+        file.src = self.0.clone();
+        plan.files.push(file);
+        Ok(())
+    }
+}
+
+#[test]
+fn plugins_modify_and_validate_the_plan() {
+    let dir = make_test_dir("modify");
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let mut packager = make_packager(&dir);
+    packager
+        .add_plugin(Box::new(MovePlugin {
+            name: "first",
+            calls: calls.clone(),
+        }))
+        .add_plugin(Box::new(MovePlugin {
+            name: "second",
+            calls: calls.clone(),
+        }));
+
+    let plan = packager.plan().unwrap();
+    let dests: Vec<_> = plan.files.iter().map(|file| file.dest.clone()).collect();
+    assert_eq!(
+        dests,
+        [PathBuf::from("data/a.txt"), PathBuf::from("data/bb.txt")]
+    );
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            "first:modify_plan",
+            "second:modify_plan",
+            "first:validate",
+            "second:validate"
+        ]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn total_size_is_recomputed_before_validation() {
+    let dir = make_test_dir("total-size");
+
+    let mut packager = make_packager(&dir);
+    packager.add_plugin(Box::new(LimitPlugin));
+    let plan = packager.plan().unwrap();
+    assert_eq!(plan.files.len(), 1);
+    assert_eq!(plan.total_size, 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rejected_plan_fails_packaging() {
+    let dir = make_test_dir("rejected");
+
+    let mut packager = make_packager(&dir);
+    packager.add_plugin(Box::new(LimitPlugin));
+    // This adds back a file bigger than the limit after the limit plugin dropped `bb.txt`.
+    packager.add_plugin(Box::new(AddPlugin(dir.join("bb.txt"))));
+
+    let error = format!("{:#}", packager.package().unwrap_err());
+    assert!(
+        error.contains("plugin `limit` rejected the plan"),
+        "{error}"
+    );
+    assert!(error.contains("the plan is 3 bytes"), "{error}");
+    assert!(!dir.join("out/share/a.txt").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -1,3 +1,9 @@
+//! Types describing MSYS2 environments.
+#![warn(missing_docs)]
+
+/// The version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// An error that may occur while parsing a [`Msys2Environment`] from a string.
 #[derive(Debug)]
 pub struct Msys2EnvironmentFromStrError(String);
@@ -11,7 +17,10 @@ impl std::fmt::Display for Msys2EnvironmentFromStrError {
 impl std::error::Error for Msys2EnvironmentFromStrError {}
 
 /// Possible MSYS2 environments
+///
+/// MSYS2 adds and retires environments over time, so this is non-exhaustive.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub enum Msys2Environment {
     /// This is not recommended,
     /// as it requires the user to have a MSYS2 installation.
@@ -24,11 +33,15 @@ pub enum Msys2Environment {
     /// This is the recommended target for x86_64.
     Ucrt64,
 
+    /// The Clang toolchain for x86_64, using UCRT.
     Clang64,
 
     /// This is the recommended target for i686
     Mingw32,
 
+    /// The Clang toolchain for i686, using UCRT.
+    ///
+    /// Note that MSYS2 has stopped providing packages for this environment.
     Clang32,
 
     /// This is the recommended target for aarch64.
@@ -36,6 +49,21 @@ pub enum Msys2Environment {
 }
 
 impl Msys2Environment {
+    /// Get the lowercase name, like `ucrt64`.
+    ///
+    /// This is the `MSYSTEM` value, lowercased, and parses back into the same environment.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::Msys => "msys",
+            Self::Mingw64 => "mingw64",
+            Self::Ucrt64 => "ucrt64",
+            Self::Clang64 => "clang64",
+            Self::Mingw32 => "mingw32",
+            Self::Clang32 => "clang32",
+            Self::ClangArm64 => "clangarm64",
+        }
+    }
+
     /// Get the path prefix.
     ///
     /// Note that this is an absolute path.
@@ -51,6 +79,55 @@ impl Msys2Environment {
         }
     }
 
+    /// Get the prefix of package names in this environment, like `mingw-w64-ucrt-x86_64-`.
+    ///
+    /// This is empty for [`Msys2Environment::Msys`], as its packages are not prefixed.
+    pub fn get_package_prefix(self) -> &'static str {
+        match self {
+            Self::Msys => "",
+            Self::Mingw64 => "mingw-w64-x86_64-",
+            Self::Ucrt64 => "mingw-w64-ucrt-x86_64-",
+            Self::Clang64 => "mingw-w64-clang-x86_64-",
+            Self::Mingw32 => "mingw-w64-i686-",
+            Self::Clang32 => "mingw-w64-clang-i686-",
+            Self::ClangArm64 => "mingw-w64-clang-aarch64-",
+        }
+    }
+
+    /// Get the environment that a package belongs to from its name, like `mingw-w64-ucrt-x86_64-gtk4`.
+    ///
+    /// # Returns
+    /// Returns None if the name does not have an environment prefix, like [`Msys2Environment::Msys`] packages.
+    pub fn from_package_name(name: &str) -> Option<Self> {
+        [
+            Self::Mingw64,
+            Self::Ucrt64,
+            Self::Clang64,
+            Self::Mingw32,
+            Self::Clang32,
+            Self::ClangArm64,
+        ]
+        .into_iter()
+        .find(|environment| name.starts_with(environment.get_package_prefix()))
+    }
+
+    /// Get the Rust target triple that builds for this environment, like `aarch64-pc-windows-gnullvm` for `clangarm64`.
+    ///
+    /// Rust has no separate target for UCRT with the GNU toolchain, so `ucrt64` uses the `-gnu` target, like `mingw64`.
+    ///
+    /// # Returns
+    /// Returns None if Rust has no target for the environment, like [`Msys2Environment::Msys`].
+    pub fn get_target_triple(self) -> Option<&'static str> {
+        match self {
+            Self::Msys => None,
+            Self::Mingw64 | Self::Ucrt64 => Some("x86_64-pc-windows-gnu"),
+            Self::Clang64 => Some("x86_64-pc-windows-gnullvm"),
+            Self::Mingw32 => Some("i686-pc-windows-gnu"),
+            Self::Clang32 => Some("i686-pc-windows-gnullvm"),
+            Self::ClangArm64 => Some("aarch64-pc-windows-gnullvm"),
+        }
+    }
+
     /// Get the arch of the environment.
     pub fn get_arch(self) -> Msys2Arch {
         match self {
@@ -82,8 +159,34 @@ impl std::str::FromStr for Msys2Environment {
 
 /// The architecture of an MSYS2 environment
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub enum Msys2Arch {
+    /// 64-bit x86
     X86_64,
+
+    /// 32-bit x86
     I686,
+
+    /// 64-bit ARM
     AArch64,
 }
+
+impl Msys2Arch {
+    /// Get the name, as used in package names, like `x86_64`.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64",
+            Self::I686 => "i686",
+            Self::AArch64 => "aarch64",
+        }
+    }
+
+    /// Get the name Windows uses, like `x64`.
+    pub fn get_windows_name(self) -> &'static str {
+        match self {
+            Self::X86_64 => "x64",
+            Self::I686 => "x86",
+            Self::AArch64 => "arm64",
+        }
+    }
+}